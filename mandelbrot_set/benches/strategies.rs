@@ -0,0 +1,43 @@
+//! Compare the wall-clock time of the rendering strategies on the classic
+//! full-set view, where the large black interior makes some rows far more
+//! expensive than others. Run with `cargo bench`.
+
+use std::time::{Duration, Instant};
+use num::Complex;
+use mandelbrot_set::{default_threads, RenderParams, Strategy};
+
+const RUNS: u32 = 3;
+
+fn time(strategy: Strategy, params: &RenderParams, threads: usize) -> Duration
+{
+    let mut pixels = vec![0; params.buffer_len()];
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        strategy.render(&mut pixels, params, threads);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let params = RenderParams {
+        limit: 1000,
+        ..RenderParams::new((1200, 900), Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 })
+    };
+    let threads = default_threads();
+
+    println!("{}x{} pixels, {} iterations, {} threads", params.region.bounds.0, params.region.bounds.1,
+             params.limit, threads);
+    let sequential = time(Strategy::Sequential, &params, threads);
+    for strategy in [Strategy::Sequential, Strategy::Bands, Strategy::Rayon, Strategy::Dynamic, Strategy::Tiles(256)] {
+        let elapsed = time(strategy, &params, threads);
+        println!("{:>12}: {:>8.1} ms  ({:.2}x)", format!("{:?}", strategy), elapsed.as_secs_f64() * 1e3,
+                 sequential.as_secs_f64() / elapsed.as_secs_f64());
+    }
+
+    for (name, unchecked) in [("no cardioid", RenderParams { interior_check: false, ..params.clone() }),
+                              ("no cycles", RenderParams { periodicity_epsilon: 0.0, ..params.clone() })] {
+        let elapsed = time(Strategy::Sequential, &unchecked, threads);
+        println!("{:>12}: {:>8.1} ms  ({:.2}x)", name, elapsed.as_secs_f64() * 1e3,
+                 sequential.as_secs_f64() / elapsed.as_secs_f64());
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::{Path, PathBuf};
+use num::Complex;
+use crate::{ImageFormat, MandelError, Region, RenderParams, Strategy, write_image_with_metadata};
+use crate::metadata::render_metadata;
+use crate::renderer::Renderer;
+
+
+/// The fewest digits in a frame number, so that `frame_%04d.png` patterns
+/// match short sequences too.
+pub const FRAME_NUMBER_DIGITS: usize = 4;
+
+
+/// A zoom animation: `frames` images, each `zoom_per_frame` times deeper than
+/// the one before, closing in on `target`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Zoom {
+    pub target: Complex<f64>,
+    pub frames: usize,
+    pub zoom_per_frame: f64,
+}
+
+impl Zoom {
+    /// The corners of frame `index`, given the corners of frame 0.
+    ///
+    /// Every corner moves towards `target` by the same factor, so the target
+    /// stays on the same pixel in every frame while everything around it
+    /// grows: a view centered on the target stays centered, and an
+    /// off-center one dives into the target where it first appeared.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::animation::Zoom;
+    ///
+    /// let zoom = Zoom { target: Complex { re: -1.0, im: 0.0 }, frames: 3, zoom_per_frame: 2.0 };
+    /// let start = (Complex { re: -3.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    /// let (upper_left, lower_right) = zoom.frame_bounds(start.0, start.1, 2);
+    /// assert_eq!(upper_left, Complex { re: -1.5, im: 0.25 });
+    /// assert_eq!(lower_right, Complex { re: -0.5, im: -0.25 });
+    /// ```
+    pub fn frame_bounds(&self, upper_left: Complex<f64>, lower_right: Complex<f64>, index: usize)
+        -> (Complex<f64>, Complex<f64>)
+    {
+        let scale = self.zoom_per_frame.powi(index as i32);
+        (self.target + (upper_left - self.target) / scale, self.target + (lower_right - self.target) / scale)
+    }
+
+    /// Where frame `index` goes in `dir`: `frame_0000.png`, `frame_0001.png`
+    /// and so on, with as many more digits as the last frame number needs.
+    pub fn frame_filename(&self, dir: &Path, index: usize) -> PathBuf
+    {
+        let digits = self.frames.saturating_sub(1).to_string().len().max(FRAME_NUMBER_DIGITS);
+        dir.join(format!("frame_{:0digits$}.png", index, digits = digits))
+    }
+}
+
+/// Render every frame of `zoom`, starting from the region in `params`, into
+/// `dir` on `threads` threads, creating `dir` if need be. Every frame is
+/// rendered into the same buffer on the same pool of threads. `on_frame` is
+/// called with the name of each file once it is written.
+pub fn run_animation(dir: &Path, params: &RenderParams, zoom: &Zoom, threads: usize, mut on_frame: impl FnMut(&Path))
+    -> Result<(), MandelError>
+{
+    std::fs::create_dir_all(dir)?;
+    let region = &params.region;
+    let mut renderer = Renderer::new(threads);
+    for index in 0..zoom.frames {
+        let (upper_left, lower_right) = zoom.frame_bounds(region.upper_left, region.lower_right, index);
+        let frame = RenderParams { region: Region::new(region.bounds, upper_left, lower_right), ..params.clone() };
+        let filename = zoom.frame_filename(dir, index);
+        renderer.render_into(&frame, Strategy::Rayon);
+        write_image_with_metadata(&filename.to_string_lossy(), &renderer.buffer, region.bounds,
+                                  frame.color.color_type(), ImageFormat::Png, &render_metadata(&frame))?;
+        on_frame(&filename);
+    }
+    Ok(())
+}
+
+
+#[test]
+fn test_frame_bounds_keep_a_centered_target_centered() {
+    let zoom = Zoom { target: Complex { re: -0.75, im: 0.1 }, frames: 10, zoom_per_frame: 1.5 };
+    let (upper_left, lower_right) = (Complex { re: -2.75, im: 1.6 }, Complex { re: 1.25, im: -1.4 });
+    for index in 0..zoom.frames {
+        let (ul, lr) = zoom.frame_bounds(upper_left, lower_right, index);
+        let center = (ul + lr) / 2.0;
+        assert!((center - zoom.target).norm() < 1e-12, "frame {}: {}", index, center);
+        let width = (lr.re - ul.re) * 1.5f64.powi(index as i32);
+        assert!((width - 4.0).abs() < 1e-12, "frame {}: {}", index, width);
+    }
+    assert_eq!(zoom.frame_bounds(upper_left, lower_right, 0), (upper_left, lower_right));
+}
+
+#[test]
+fn test_frame_filename() {
+    let zoom = Zoom { target: Complex { re: 0.0, im: 0.0 }, frames: 12, zoom_per_frame: 2.0 };
+    assert_eq!(zoom.frame_filename(Path::new("out"), 0), Path::new("out/frame_0000.png"));
+    assert_eq!(zoom.frame_filename(Path::new("out"), 11), Path::new("out/frame_0011.png"));
+
+    let long = Zoom { frames: 12345, ..zoom };
+    assert_eq!(long.frame_filename(Path::new("out"), 7), Path::new("out/frame_00007.png"));
+}
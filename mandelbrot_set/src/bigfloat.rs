@@ -0,0 +1,336 @@
+//! Binary floating point numbers with as many bits of mantissa as a render
+//! asks for, on top of `num`'s big integers. Every operation rounds its
+//! exact result to the nearest number of the wanted precision, ties to
+//! even, as IEEE 754 does; at 53 bits they therefore give the same answers
+//! as `f64` wherever `f64` neither overflows nor underflows.
+
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use num::{BigInt, BigUint, Integer, ToPrimitive, Zero};
+use num::bigint::Sign;
+
+
+/// The largest power of ten, either way, that `parse_decimal` accepts.
+pub const MAX_DECIMAL_EXPONENT: i64 = 100_000;
+
+
+/// The number `mantissa * 2^exponent`, where `mantissa` holds at most
+/// `precision` bits. Operations on two numbers round to the larger of
+/// their precisions.
+#[derive(Clone, Debug)]
+pub struct BigFloat {
+    mantissa: BigInt,
+    exponent: i64,
+    precision: u64,
+}
+
+impl BigFloat {
+    pub fn zero(precision: u64) -> BigFloat
+    {
+        BigFloat { mantissa: BigInt::zero(), exponent: 0, precision }
+    }
+
+    /// `x` rounded to `precision` bits, which is exact from 53 bits up.
+    /// Panics if `x` is infinite or NaN.
+    pub fn from_f64(x: f64, precision: u64) -> BigFloat
+    {
+        assert!(x.is_finite(), "{} has no BigFloat", x);
+        let bits = x.to_bits();
+        let (biased, fraction) = ((bits >> 52) & 0x7ff, bits & ((1 << 52) - 1));
+        let (mantissa, exponent) = match biased {
+            0 => (fraction, -1074),
+            _ => (fraction | 1 << 52, biased as i64 - 1075),
+        };
+        BigFloat::rounded(x.is_sign_negative(), BigUint::from(mantissa), exponent, false, precision)
+    }
+
+    /// `n` rounded to `precision` bits.
+    pub fn from_usize(n: usize, precision: u64) -> BigFloat
+    {
+        BigFloat::rounded(false, BigUint::from(n), 0, false, precision)
+    }
+
+    /// Parse a decimal number as `parse_decimal` reads it, rounding it once,
+    /// straight to `precision` bits, rather than through `f64`.
+    pub fn parse(s: &str, precision: u64) -> Option<BigFloat>
+    {
+        let (negative, digits, exponent) = parse_decimal(s)?;
+        let ten = BigUint::from(10u32);
+        Some(if exponent >= 0 {
+            BigFloat::rounded(negative, digits * ten.pow(exponent as u32), 0, false, precision)
+        } else {
+            BigFloat::ratio(negative, digits, ten.pow(exponent.unsigned_abs() as u32), 0, precision)
+        })
+    }
+
+    pub fn precision(&self) -> u64
+    {
+        self.precision
+    }
+
+    /// The `mantissa` and `exponent` of `mantissa * 2^exponent`.
+    pub(crate) fn parts(&self) -> (&BigInt, i64)
+    {
+        (&self.mantissa, self.exponent)
+    }
+
+    pub fn is_zero(&self) -> bool
+    {
+        self.mantissa.is_zero()
+    }
+
+    pub fn abs(&self) -> BigFloat
+    {
+        BigFloat { mantissa: BigInt::from_biguint(Sign::Plus, self.mantissa.magnitude().clone()), ..self.clone() }
+    }
+
+    /// The nearest `f64`. Numbers beyond the range of `f64` come out
+    /// infinite, and those below it are rounded twice on the way to zero.
+    pub fn to_f64(&self) -> f64
+    {
+        let rounded = BigFloat::rounded(self.mantissa.sign() == Sign::Minus, self.mantissa.magnitude().clone(),
+                                        self.exponent, false, 53);
+        // Within 53 bits the mantissa converts exactly, and scaling by
+        // powers of two is exact until the result leaves the normal range.
+        let mut value = rounded.mantissa.to_f64().unwrap();
+        let mut exponent = rounded.exponent;
+        while exponent != 0 && value != 0.0 && value.is_finite() {
+            let step = exponent.clamp(-1000, 1000);
+            value *= 2f64.powi(step as i32);
+            exponent -= step;
+        }
+        value
+    }
+
+    /// The number `magnitude * 2^exponent`, negated if `negative`, rounded
+    /// to `precision` bits. `sticky` says that the exact value lies a little
+    /// above `magnitude`, below its lowest bit, which only matters if
+    /// `magnitude` has more than `precision + 1` bits.
+    fn rounded(negative: bool, mut magnitude: BigUint, mut exponent: i64, sticky: bool, precision: u64) -> BigFloat
+    {
+        if magnitude.is_zero() {
+            return BigFloat::zero(precision);
+        }
+        let bits = magnitude.bits();
+        if bits > precision {
+            let shift = bits - precision;
+            let half = magnitude.bit(shift - 1);
+            let rest = sticky || magnitude.trailing_zeros().unwrap() < shift - 1;
+            magnitude >>= shift;
+            exponent += shift as i64;
+            if half && (rest || magnitude.bit(0)) {
+                magnitude += 1u32;
+                if magnitude.bits() > precision {
+                    magnitude >>= 1;
+                    exponent += 1;
+                }
+            }
+        }
+        let sign = if negative { Sign::Minus } else { Sign::Plus };
+        BigFloat { mantissa: BigInt::from_biguint(sign, magnitude), exponent, precision }
+    }
+
+    /// `numerator / denominator * 2^exponent`, negated if `negative`,
+    /// rounded to `precision` bits.
+    fn ratio(negative: bool, numerator: BigUint, denominator: BigUint, exponent: i64, precision: u64) -> BigFloat
+    {
+        // Enough bits of quotient that the remainder only breaks ties.
+        let shift = (precision + 2 + denominator.bits()).saturating_sub(numerator.bits());
+        let (quotient, remainder) = (numerator << shift).div_rem(&denominator);
+        BigFloat::rounded(negative, quotient, exponent - shift as i64, !remainder.is_zero(), precision)
+    }
+
+    /// One past the position of the highest bit: `|self|` is below
+    /// `2^top()`.
+    fn top(&self) -> i64
+    {
+        self.exponent + self.mantissa.bits() as i64
+    }
+}
+
+/// Split a decimal number, optionally signed and with an exponent such as
+/// `1.5e-3`, into its sign, its digits as an integer, and the power of ten
+/// to scale them by. `None` unless `s` is such a number, whitespace around
+/// it aside, with an exponent no larger than `MAX_DECIMAL_EXPONENT`.
+pub fn parse_decimal(s: &str) -> Option<(bool, BigUint, i64)>
+{
+    let s = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (number, exponent) = match s.find(['e', 'E']) {
+        Some(index) => (&s[..index], s[index + 1..].parse::<i64>().ok()?),
+        None => (s, 0),
+    };
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let digits = || whole.chars().chain(fraction.chars());
+    if whole.len() + fraction.len() == 0 || !digits().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let exponent = exponent.checked_sub(fraction.len() as i64)?;
+    if exponent.abs() > MAX_DECIMAL_EXPONENT {
+        return None;
+    }
+    let mantissa = digits().fold(BigUint::zero(), |value, digit| value * 10u32 + digit.to_digit(10).unwrap());
+    Some((negative, mantissa, exponent))
+}
+
+impl Add for &BigFloat {
+    type Output = BigFloat;
+
+    fn add(self, other: &BigFloat) -> BigFloat
+    {
+        let precision = self.precision.max(other.precision);
+        let (big, small) = match (self.is_zero(), other.is_zero()) {
+            (_, true) | (true, _) => {
+                let only = if self.is_zero() { other } else { self };
+                let negative = only.mantissa.sign() == Sign::Minus;
+                return BigFloat::rounded(negative, only.mantissa.magnitude().clone(), only.exponent, false, precision);
+            }
+            _ if self.top() >= other.top() => (self, other),
+            _ => (other, self),
+        };
+        // A `small` too small to reach the rounding of the sum is worth no
+        // more than a single low bit there, which keeps the shifts short.
+        let floor = big.top() - precision as i64 - 2;
+        let (addend, exponent) = if small.top() <= floor {
+            (BigInt::from_biguint(small.mantissa.sign(), BigUint::from(1u32)), floor - 1)
+        } else {
+            (small.mantissa.clone(), small.exponent)
+        };
+        let lowest = big.exponent.min(exponent);
+        let sum = (&big.mantissa << (big.exponent - lowest) as u64) + (addend << (exponent - lowest) as u64);
+        BigFloat::rounded(sum.sign() == Sign::Minus, sum.magnitude().clone(), lowest, false, precision)
+    }
+}
+
+impl Neg for &BigFloat {
+    type Output = BigFloat;
+
+    fn neg(self) -> BigFloat
+    {
+        BigFloat { mantissa: -&self.mantissa, ..self.clone() }
+    }
+}
+
+impl Sub for &BigFloat {
+    type Output = BigFloat;
+
+    fn sub(self, other: &BigFloat) -> BigFloat
+    {
+        self + &-other
+    }
+}
+
+impl Mul for &BigFloat {
+    type Output = BigFloat;
+
+    fn mul(self, other: &BigFloat) -> BigFloat
+    {
+        let product = &self.mantissa * &other.mantissa;
+        BigFloat::rounded(product.sign() == Sign::Minus, product.magnitude().clone(), self.exponent + other.exponent,
+                          false, self.precision.max(other.precision))
+    }
+}
+
+impl Div for &BigFloat {
+    type Output = BigFloat;
+
+    /// Panics if `other` is zero.
+    fn div(self, other: &BigFloat) -> BigFloat
+    {
+        assert!(!other.is_zero(), "division by zero");
+        let negative = (self.mantissa.sign() == Sign::Minus) != (other.mantissa.sign() == Sign::Minus);
+        BigFloat::ratio(negative, self.mantissa.magnitude().clone(), other.mantissa.magnitude().clone(),
+                        self.exponent - other.exponent, self.precision.max(other.precision))
+    }
+}
+
+impl PartialEq for BigFloat {
+    /// Equal in value, whatever the precisions.
+    fn eq(&self, other: &BigFloat) -> bool
+    {
+        self.cmp_value(other) == Ordering::Equal
+    }
+}
+
+impl PartialOrd for BigFloat {
+    fn partial_cmp(&self, other: &BigFloat) -> Option<Ordering>
+    {
+        Some(self.cmp_value(other))
+    }
+}
+
+impl BigFloat {
+    /// How `self` compares with `other` in value, exactly.
+    fn cmp_value(&self, other: &BigFloat) -> Ordering
+    {
+        let (sign, other_sign) = (self.mantissa.sign(), other.mantissa.sign());
+        if sign != other_sign || sign == Sign::NoSign {
+            return sign.cmp(&other_sign);
+        }
+        let lowest = self.exponent.min(other.exponent);
+        (&self.mantissa << (self.exponent - lowest) as u64).cmp(&(&other.mantissa << (other.exponent - lowest) as u64))
+    }
+}
+
+
+#[test]
+fn test_f64_round_trip() {
+    for x in [0.0, 1.0, -2.5, 0.1, 1e-300, -1e300, f64::MIN_POSITIVE, 5e-324, f64::MAX] {
+        assert_eq!(BigFloat::from_f64(x, 53).to_f64(), x);
+        assert_eq!(BigFloat::from_f64(x, 200).to_f64(), x);
+    }
+    // 0.1 is 1.1001100110011...b times 2^-4; kept to 8 bits it rounds up to
+    // 1.1001101b, or 205 / 2048.
+    assert_eq!(BigFloat::from_f64(0.1, 8).to_f64(), 205.0 / 2048.0);
+    assert_eq!(BigFloat::from_usize(255, 4).to_f64(), 256.0);
+}
+
+#[test]
+fn test_operations_match_f64_at_53_bits() {
+    // Values whose sums and products all round, including the cancellation
+    // of nearly equal numbers and ties.
+    let values = [0.1, 0.2, 0.3, -0.7, 1.0 / 3.0, 2.0f64.sqrt(), -1e-20, 1e20, 1.0 + f64::EPSILON, 3.0, 0.75,
+                  2.0f64.powi(53) + 2.0, 0.5];
+    for &a in &values {
+        for &b in &values {
+            let (big_a, big_b) = (BigFloat::from_f64(a, 53), BigFloat::from_f64(b, 53));
+            assert_eq!((&big_a + &big_b).to_f64(), a + b, "{} + {}", a, b);
+            assert_eq!((&big_a - &big_b).to_f64(), a - b, "{} - {}", a, b);
+            assert_eq!((&big_a * &big_b).to_f64(), a * b, "{} * {}", a, b);
+            assert_eq!((&big_a / &big_b).to_f64(), a / b, "{} / {}", a, b);
+            assert_eq!(big_a.partial_cmp(&big_b), a.partial_cmp(&b), "{} vs {}", a, b);
+        }
+    }
+    // A tie rounds to even: 2^53 + 1 is halfway between 2^53 and 2^53 + 2.
+    let big = BigFloat::from_f64(2.0f64.powi(53), 53);
+    assert_eq!((&big + &BigFloat::from_f64(1.0, 53)).to_f64(), 2.0f64.powi(53));
+    assert_eq!((&big + &BigFloat::from_f64(3.0, 53)).to_f64(), 2.0f64.powi(53) + 4.0);
+    // Anything below the last bit kept breaks a tie upwards.
+    let tie = BigUint::from(257u32);
+    assert_eq!(BigFloat::rounded(false, tie.clone(), 0, false, 8).to_f64(), 256.0);
+    assert_eq!(BigFloat::rounded(false, tie, 0, true, 8).to_f64(), 258.0);
+    assert_eq!((&big + &BigFloat::from_f64(1.0 + 2.0f64.powi(-40), 60)).precision(), 60);
+    assert!(BigFloat::zero(53) < BigFloat::from_f64(0.25, 53) && BigFloat::from_f64(-0.25, 53) < BigFloat::zero(53));
+    assert_eq!((&BigFloat::zero(53) + &BigFloat::from_f64(0.25, 53)).to_f64(), 0.25);
+}
+
+#[test]
+fn test_parse() {
+    for s in ["0.1", "-2.5e-3", "+7", " 1e10 ", "123.456", "5e-324", "1.7976931348623157e308", ".5", "2."] {
+        assert_eq!(BigFloat::parse(s, 53).unwrap().to_f64(), s.trim().parse::<f64>().unwrap(), "{}", s);
+    }
+    // Just above the tie between 1 and the next f64, 1 + 2^-53, which
+    // rounding to 60 bits first would have landed on and then rounded down.
+    let text = "1.000000000000000111022302462515654043";
+    assert_eq!(BigFloat::parse(text, 53).unwrap().to_f64(), 1.0 + f64::EPSILON);
+    assert_eq!(BigFloat::parse(text, 60).unwrap().to_f64(), 1.0);
+    // More bits tell apart what f64 cannot.
+    let (a, b) = (BigFloat::parse("0.1000000000000000000001", 128).unwrap(), BigFloat::parse("0.1", 128).unwrap());
+    assert!(a > b && (&a - &b).to_f64() > 9e-23 && (&a - &b).to_f64() < 1.1e-22);
+    for s in ["", "-", "1e", "1.2.3", "abc", "1e999999", "0x10", "inf"] {
+        assert!(BigFloat::parse(s, 53).is_none(), "{}", s);
+    }
+}
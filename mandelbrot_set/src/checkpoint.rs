@@ -0,0 +1,469 @@
+//! Checkpoints of long renders, so that a render cut short can be resumed
+//! without computing again the bands it had finished.
+//!
+//! A checkpoint starts with `MAGIC`, then the format `VERSION`, width,
+//! height, rows per band, bytes per pixel and iteration limit as
+//! little-endian `u32`s, then the squared bailout and the real and
+//! imaginary parts of the upper left and lower right corners as
+//! little-endian `f64`s, then a little-endian `u64` digest of all the
+//! render parameters. After this header comes one byte per band, 1 once
+//! the band is finished and 0 until then, and after those the pixels of the
+//! whole image row by row, as the strategy renders them and before any
+//! coloring that needs the whole image. A band's pixels are written before
+//! its byte is set, so a band cut short is left unfinished, not half saved.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use num::{BigInt, Complex};
+use crate::{ColorMode, Coloring, Fractal, MandelError, RenderParams, Trap, color_counts, correct_gamma, counts, slope};
+use crate::bigfloat::BigFloat;
+use crate::progress::Progress;
+use crate::strategy::Strategy;
+
+
+/// The first bytes of every checkpoint.
+pub const MAGIC: &[u8; 8] = b"MANDCKPT";
+/// The version of the format `Checkpoint::create` writes, and the only one
+/// `Checkpoint::open` accepts.
+pub const VERSION: u32 = 1;
+/// The size of the header in front of the bands' bytes.
+pub const HEADER_LEN: usize = 8 + 6 * 4 + 5 * 8 + 8;
+/// How many rows `--checkpoint` saves at a time.
+pub const CHECKPOINT_BAND_ROWS: usize = 64;
+
+
+/// A checkpoint file open for a render, and which of its bands are done.
+#[derive(Debug)]
+pub struct Checkpoint {
+    file: File,
+    band_rows: usize,
+    row_len: usize,
+    height: usize,
+    done: Vec<bool>,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint for `params` at `path`, with every band of
+    /// `band_rows` rows still to render. Whatever `path` held is replaced.
+    pub fn create(path: &Path, params: &RenderParams, band_rows: usize) -> Result<Checkpoint, MandelError>
+    {
+        assert!(band_rows > 0);
+        let header = header(params, band_rows)?;
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(true).open(path)?;
+        let mut checkpoint = Checkpoint::new(file, params, band_rows);
+        checkpoint.file.write_all(&header)?;
+        checkpoint.file.write_all(&vec![0; checkpoint.done.len()])?;
+        checkpoint.file.set_len(checkpoint.len())?;
+        Ok(checkpoint)
+    }
+
+    /// Open the checkpoint at `path` to carry on rendering `params`. Fails
+    /// with `MandelError::Parse` if it is not a checkpoint, is of another
+    /// `VERSION`, is cut short, or was started for other parameters.
+    pub fn open(path: &Path, params: &RenderParams) -> Result<Checkpoint, MandelError>
+    {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let truncated = |error: io::Error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => MandelError::Parse(format!("checkpoint {} is cut short", path.display())),
+            _ => MandelError::Io(error),
+        };
+        let mut found = [0; HEADER_LEN];
+        file.read_exact(&mut found[..12]).map_err(truncated)?;
+        if &found[..8] != MAGIC {
+            return Err(MandelError::Parse(format!("{} is not a checkpoint", path.display())));
+        }
+        let word = |index: usize| u32::from_le_bytes(found[8 + index * 4..12 + index * 4].try_into().unwrap());
+        if word(0) != VERSION {
+            return Err(MandelError::Parse(format!("checkpoint version {} is not supported; expected {}", word(0),
+                                                  VERSION)));
+        }
+        file.read_exact(&mut found[12..]).map_err(truncated)?;
+        let band_rows = u32::from_le_bytes(found[20..24].try_into().unwrap()) as usize;
+        if band_rows == 0 || found[..] != header(params, band_rows)?[..] {
+            return Err(MandelError::Parse(format!("checkpoint {} was saved by a render with other parameters; \
+                                                   resume it with the flags it was started with", path.display())));
+        }
+        let mut checkpoint = Checkpoint::new(file, params, band_rows);
+        let mut done = vec![0; checkpoint.done.len()];
+        checkpoint.file.read_exact(&mut done).map_err(truncated)?;
+        if checkpoint.file.metadata()?.len() != checkpoint.len() {
+            return Err(MandelError::Parse(format!("checkpoint {} is cut short", path.display())));
+        }
+        checkpoint.done = done.iter().map(|&flag| flag == 1).collect();
+        Ok(checkpoint)
+    }
+
+    /// A checkpoint of `params` in `file` with no band done yet.
+    fn new(file: File, params: &RenderParams, band_rows: usize) -> Checkpoint
+    {
+        let (width, height) = params.region.bounds;
+        let row_len = width * band_params(params).color.bytes_per_pixel();
+        Checkpoint { file, band_rows, row_len, height, done: vec![false; height.div_ceil(band_rows)] }
+    }
+
+    /// How many bands the checkpoint holds, and how many of them are done.
+    pub fn progress(&self) -> (usize, usize)
+    {
+        (self.done.iter().filter(|&&done| done).count(), self.done.len())
+    }
+
+    /// The rows of the image in band `band`.
+    fn rows(&self, band: usize) -> Range<usize>
+    {
+        band * self.band_rows..((band + 1) * self.band_rows).min(self.height)
+    }
+
+    /// Where in the file the pixels start.
+    fn data_offset(&self) -> u64
+    {
+        (HEADER_LEN + self.done.len()) as u64
+    }
+
+    /// The length of the whole file.
+    fn len(&self) -> u64
+    {
+        self.data_offset() + (self.height * self.row_len) as u64
+    }
+
+    /// Read the saved pixels of band `band` into `pixels`.
+    fn read_band(&mut self, band: usize, pixels: &mut [u8]) -> Result<(), MandelError>
+    {
+        self.file.seek(SeekFrom::Start(self.data_offset() + (self.rows(band).start * self.row_len) as u64))?;
+        self.file.read_exact(pixels)?;
+        Ok(())
+    }
+
+    /// Save `pixels` as band `band`, and only then mark it done.
+    fn save_band(&mut self, band: usize, pixels: &[u8]) -> Result<(), MandelError>
+    {
+        self.file.seek(SeekFrom::Start(self.data_offset() + (self.rows(band).start * self.row_len) as u64))?;
+        self.file.write_all(pixels)?;
+        self.file.sync_data()?;
+        self.file.seek(SeekFrom::Start((HEADER_LEN + band) as u64))?;
+        self.file.write_all(&[1])?;
+        self.file.sync_data()?;
+        self.done[band] = true;
+        Ok(())
+    }
+}
+
+/// The parameters the bands of a checkpoint are rendered with: `params`
+/// themselves, or their escape counts for `Coloring::Histogram`, which can
+/// only color them once every band is done.
+fn band_params(params: &RenderParams) -> RenderParams
+{
+    match params.coloring {
+        Coloring::Histogram => RenderParams { color: ColorMode::Counts, ..params.clone() },
+        _ => params.clone(),
+    }
+}
+
+/// The header of a checkpoint of `params` in bands of `band_rows` rows.
+/// Fails with `MandelError::Dimensions` if the image, its bands or the
+/// iteration limit do not fit the header's `u32`s.
+fn header(params: &RenderParams, band_rows: usize) -> Result<Vec<u8>, MandelError>
+{
+    let region = &params.region;
+    let (width, height) = region.bounds;
+    if [width, height, band_rows, params.limit].iter().any(|&field| field > u32::MAX as usize) {
+        return Err(MandelError::Dimensions(format!("checkpoints hold at most {} pixels on a side, rows per band and \
+                                                    iterations, got {}x{}, {} and {}", u32::MAX, width, height,
+                                                   band_rows, params.limit)));
+    }
+    let mut header = MAGIC.to_vec();
+    for field in [VERSION as usize, width, height, band_rows, band_params(params).color.bytes_per_pixel(),
+                  params.limit] {
+        header.extend((field as u32).to_le_bytes());
+    }
+    for field in [params.bailout, region.upper_left.re, region.upper_left.im, region.lower_right.re,
+                  region.lower_right.im] {
+        header.extend(field.to_le_bytes());
+    }
+    header.extend(digest(params).to_le_bytes());
+    Ok(header)
+}
+
+/// The bytes `digest` hashes: numbers little-endian, big integers as their
+/// length and then their bytes, and each choice between variants as its
+/// name and then what it holds.
+struct Fields(Vec<u8>);
+
+impl Fields {
+    fn number(&mut self, n: u64)
+    {
+        self.0.extend(n.to_le_bytes());
+    }
+
+    fn float(&mut self, x: f64)
+    {
+        self.0.extend(x.to_le_bytes());
+    }
+
+    fn complex(&mut self, c: Complex<f64>)
+    {
+        self.float(c.re);
+        self.float(c.im);
+    }
+
+    fn name(&mut self, name: &str)
+    {
+        self.number(name.len() as u64);
+        self.0.extend(name.as_bytes());
+    }
+
+    fn big(&mut self, n: &BigInt)
+    {
+        let bytes = n.to_signed_bytes_le();
+        self.number(bytes.len() as u64);
+        self.0.extend(bytes);
+    }
+
+    fn big_complex(&mut self, c: &Complex<BigFloat>)
+    {
+        for part in [&c.re, &c.im] {
+            let (mantissa, exponent) = part.parts();
+            self.big(mantissa);
+            self.number(exponent as u64);
+            self.number(part.precision());
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash of every render parameter, each laid out in turn
+/// by `Fields`, which unlike `std`'s hashers or a `Debug` string stays the
+/// same from one build to the next.
+fn digest(params: &RenderParams) -> u64
+{
+    let mut fields = Fields(Vec::new());
+    let region = &params.region;
+    for field in [region.bounds.0, region.bounds.1, params.limit, params.antialias] {
+        fields.number(field as u64);
+    }
+    fields.complex(region.upper_left);
+    fields.complex(region.lower_right);
+    for field in [params.bailout, params.gamma, params.periodicity_epsilon, params.distance_scale,
+                  params.stripe_density, params.display_gamma] {
+        fields.float(field);
+    }
+    for flag in [params.smooth, params.log_scale, params.invert, params.interior_check, params.mirror,
+                 params.light_angle.is_some(), params.perturbation.is_some(), params.precision.is_some()] {
+        fields.number(flag as u64);
+    }
+    fields.name(params.fractal.name());
+    fields.number(params.fractal.power() as u64);
+    match params.fractal {
+        Fractal::Julia(c) => fields.complex(c),
+        Fractal::Newton(epsilon) => fields.float(epsilon),
+        Fractal::Mandelbrot | Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) => {}
+    }
+    match &params.color {
+        ColorMode::Gray => fields.name("gray"),
+        ColorMode::Gray16 => fields.name("gray16"),
+        ColorMode::Rgb(palette) => {
+            fields.name("rgb");
+            let (stops, interior) = palette.colors();
+            fields.number(stops.len() as u64);
+            for color in stops.iter().chain([&interior]) {
+                fields.0.extend(color);
+            }
+        }
+        ColorMode::Counts => fields.name("counts"),
+        ColorMode::Values => fields.name("values"),
+    }
+    fields.name(match params.coloring {
+        Coloring::Linear => "linear",
+        Coloring::Histogram => "histogram",
+        Coloring::Distance => "distance",
+        Coloring::Trap => "trap",
+        Coloring::Binary => "binary",
+        Coloring::BinaryShaded => "binary-shaded",
+        Coloring::Stripe => "stripe",
+    });
+    match params.trap {
+        Trap::Point(point) => {
+            fields.name("point");
+            fields.complex(point);
+        }
+        Trap::Line(from, to) => {
+            fields.name("line");
+            fields.complex(from);
+            fields.complex(to);
+        }
+        Trap::Cross(point) => {
+            fields.name("cross");
+            fields.complex(point);
+        }
+        Trap::Circle(center, radius) => {
+            fields.name("circle");
+            fields.complex(center);
+            fields.float(radius);
+        }
+    }
+    if let Some(angle) = params.light_angle {
+        fields.float(angle);
+    }
+    if let Some(perturbation) = &params.perturbation {
+        fields.big(&perturbation.center.re);
+        fields.big(&perturbation.center.im);
+        fields.number(perturbation.bits);
+        fields.float(perturbation.span.0);
+        fields.float(perturbation.span.1);
+    }
+    if let Some(precision) = &params.precision {
+        fields.number(precision.bits);
+        fields.big_complex(&precision.upper_left);
+        fields.big_complex(&precision.lower_right);
+        fields.number(precision.julia_c.is_some() as u64);
+        if let Some(c) = &precision.julia_c {
+            fields.big_complex(c);
+        }
+    }
+    fields.0.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// Render the image described by `params` as `render_image_with_progress`
+/// does, one band of `checkpoint` at a time with `strategy`, saving each to
+/// `checkpoint` as it is done. Bands the checkpoint already holds are read
+/// back instead of rendered, and count neither into the work of any thread
+/// nor as new progress. Panics unless `strategy.renders_bands()`.
+pub fn render_image_with_checkpoint(params: &RenderParams, strategy: Strategy, threads: usize, progress: &Progress,
+                                    checkpoint: &mut Checkpoint) -> Result<(Vec<u8>, Vec<usize>), MandelError>
+{
+    assert!(strategy.renders_bands());
+    let band_params = band_params(params);
+    let (width, row_len) = (params.region.bounds.0, checkpoint.row_len);
+    let mut pixels = vec![0; band_params.buffer_len()];
+    let mut work_per_thread: Vec<usize> = Vec::new();
+    for band in 0..checkpoint.done.len() {
+        let rows = checkpoint.rows(band);
+        let pixels = &mut pixels[rows.start * row_len..rows.end * row_len];
+        if checkpoint.done[band] {
+            checkpoint.read_band(band, pixels)?;
+            progress.add(width * rows.len());
+            continue;
+        }
+        let work = strategy.render_band(pixels, &band_params, rows.start, threads, progress);
+        work_per_thread.resize(work_per_thread.len().max(work.len()), 0);
+        for (total, units) in work_per_thread.iter_mut().zip(work) {
+            *total += units;
+        }
+        checkpoint.save_band(band, pixels)?;
+    }
+    if params.coloring == Coloring::Histogram {
+        let counts: Vec<Option<usize>> = counts::decode_counts(&pixels).into_iter().map(counts::decode_count).collect();
+        pixels = color_counts(&counts, params);
+    }
+    slope::shade_slopes(&mut pixels, params, strategy, threads);
+    correct_gamma(&mut pixels, params);
+    Ok((pixels, work_per_thread))
+}
+
+
+#[cfg(test)]
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("mandelbrot_checkpoint_{}_{}", name, std::process::id()))
+}
+
+#[cfg(test)]
+fn sample() -> RenderParams {
+    use num::Complex;
+
+    RenderParams::new((12, 10), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+}
+
+#[test]
+fn test_resumed_render_matches() {
+    use crate::palette::Palette;
+    use crate::render_image;
+
+    let path = temp_path("resume");
+    let fire = ColorMode::Rgb(Palette::named("fire").unwrap());
+    for params in [sample(), RenderParams { coloring: Coloring::Histogram, color: fire, ..sample() }] {
+        let (expected, _) = render_image(&params, Strategy::Sequential, 1);
+        let mut checkpoint = Checkpoint::create(&path, &params, 3).unwrap();
+        assert_eq!(checkpoint.progress(), (0, 4));
+        let (pixels, work) = render_image_with_checkpoint(&params, Strategy::Bands, 2, &Progress::hidden(),
+                                                          &mut checkpoint).unwrap();
+        assert_eq!((pixels, work.iter().sum::<usize>()), (expected.clone(), 10));
+
+        // Cut short after the second band: the third and fourth were never
+        // marked done, and whatever they held is rendered again.
+        let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(HEADER_LEN as u64 + 2)).unwrap();
+        file.write_all(&[0, 0]).unwrap();
+        file.seek(SeekFrom::Start(HEADER_LEN as u64 + 4 + 6 * checkpoint.row_len as u64)).unwrap();
+        file.write_all(&vec![7; 4 * checkpoint.row_len]).unwrap();
+        drop(file);
+        let mut checkpoint = Checkpoint::open(&path, &params).unwrap();
+        assert_eq!(checkpoint.progress(), (2, 4));
+        let progress = Progress::new(12 * 10, false);
+        let (pixels, work) = render_image_with_checkpoint(&params, Strategy::Sequential, 1, &progress,
+                                                          &mut checkpoint).unwrap();
+        assert_eq!((pixels, work, progress.done()), (expected, vec![4], 120));
+        assert_eq!(checkpoint.progress(), (4, 4));
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_checkpoint_errors() {
+    use num::Complex;
+
+    let path = temp_path("errors");
+    let params = sample();
+    Checkpoint::create(&path, &params, 4).unwrap();
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(bytes.len(), HEADER_LEN + 3 + 12 * 10);
+    assert_eq!((&bytes[..8], &bytes[8..12]), (&MAGIC[..], &VERSION.to_le_bytes()[..]));
+
+    let opened = |params: &RenderParams| match Checkpoint::open(&path, params) {
+        Err(MandelError::Parse(message)) => message,
+        other => panic!("expected a parse error, got {:?}", other),
+    };
+    let zoomed = RenderParams::new((12, 10), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for other in [zoomed, RenderParams { limit: 100, ..sample() }, RenderParams { invert: true, ..sample() }] {
+        assert!(opened(&other).contains("other parameters"), "{:?}", other);
+    }
+    let rewrite = |bytes: &[u8]| std::fs::write(&path, bytes).unwrap();
+    rewrite(&bytes[..bytes.len() - 1]);
+    assert!(opened(&params).contains("cut short"));
+    rewrite(&bytes[..20]);
+    assert!(opened(&params).contains("cut short"));
+    rewrite(&[b"MANDITER", &bytes[8..]].concat());
+    assert!(opened(&params).contains("not a checkpoint"));
+    rewrite(&[&bytes[..8], &(VERSION + 1).to_le_bytes()[..], &bytes[12..]].concat());
+    assert!(opened(&params).contains("version 2"));
+    std::fs::remove_file(&path).unwrap();
+
+    let wide = RenderParams::new((u32::MAX as usize + 1, 1), Complex { re: -2.0, im: 1.0 },
+                                 Complex { re: 1.0, im: -1.0 });
+    for params in [wide, RenderParams { limit: u32::MAX as usize + 1, ..sample() }] {
+        assert!(matches!(Checkpoint::create(&path, &params, 4), Err(MandelError::Dimensions(_))));
+        assert!(!path.exists());
+    }
+}
+
+#[test]
+fn test_digest_covers_the_parameters() {
+    use num::Complex;
+    use crate::palette::Palette;
+
+    // Pinned, so that a change to the layout of `Fields` or to the defaults
+    // of `RenderParams` shows up here rather than as checkpoints that no
+    // longer resume.
+    assert_eq!(digest(&sample()), 0x4f60_2d5b_5d6b_d096);
+    let fire = Palette::named("fire").unwrap();
+    let others = [RenderParams { trap: Trap::Circle(Complex { re: 0.0, im: 0.0 }, 0.5), ..sample() },
+                  RenderParams { color: ColorMode::Rgb(fire.clone()), ..sample() },
+                  RenderParams { color: ColorMode::Rgb(fire.with_interior([255, 255, 255])), ..sample() },
+                  RenderParams { fractal: Fractal::Multibrot(3), ..sample() },
+                  RenderParams { fractal: Fractal::Julia(Complex { re: -0.8, im: 0.156 }), ..sample() },
+                  RenderParams { light_angle: Some(45.0), ..sample() },
+                  RenderParams { coloring: Coloring::Stripe, ..sample() }];
+    let mut digests: Vec<u64> = others.iter().chain([&sample()]).map(digest).collect();
+    digests.sort();
+    digests.dedup();
+    assert_eq!(digests.len(), others.len() + 1);
+}
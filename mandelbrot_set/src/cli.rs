@@ -0,0 +1,3163 @@
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use num::Complex;
+use mandelbrot_set::{AUTO_LIMIT_MAX, ColorMode, Coloring, DISTANCE_FALLOFF, Fractal, DISPLAY_GAMMA,
+                     LIMIT_OF_ITERATION, LIMIT_TO_CALL_IT_OFF_TO_INFINITY, ImageFormat, MAX_POWER, MandelError,
+                     NEWTON_EPSILON, PALETTE_NAMES,
+                     PERIODICITY_EPSILON, Palette, Region, AspectPolicy, CornerSwap, RenderParams, SMOOTH_BAILOUT,
+                     STREAM_BAND_ROWS, STRIPE_DENSITY, Strategy, TRAP_REACH, Trap, WIDTH_AT_ZOOM_ONE,
+                     auto_limit, default_threads, fit_viewport, viewport_from_center};
+use mandelbrot_set::animation::Zoom;
+use mandelbrot_set::deepzoom::{DZI_OVERLAP, DZI_TILE_SIZE, DeepZoom};
+use mandelbrot_set::bigfloat::BigFloat;
+use mandelbrot_set::checkpoint::CHECKPOINT_BAND_ROWS;
+use mandelbrot_set::perturbation::Perturbation;
+use mandelbrot_set::precision::{MAX_PRECISION, Precision};
+use mandelbrot_set::output::{DEFAULT_JPEG_QUALITY, EXR_UNAVAILABLE, STDOUT};
+use mandelbrot_set::palette::parse_rgb;
+use mandelbrot_set::poster::Grid;
+use mandelbrot_set::pyramid::{MAP_TILE_SIZE, MAX_ZOOM_LEVEL, Pyramid};
+use mandelbrot_set::raw::RawFormat;
+use mandelbrot_set::slope::LIGHT_ELEVATION;
+use mandelbrot_set::tile::{DEFAULT_TILE_SIZE, Tile};
+
+
+const CMD_ARG_COMPLEX_NUMBER_SEPARATOR: char = ',';
+
+
+pub struct Options {
+    pub filename: String,
+    pub format: ImageFormat,
+    pub params: RenderParams,
+    pub strategy: Strategy,
+    pub threads: usize,
+    pub verbosity: Verbosity,
+    pub quiet: bool,
+    /// Where to save the escape counts as well, if anywhere.
+    pub dump: Option<String>,
+    /// Where to write the bare escape counts for other tools, and how.
+    pub raw: Option<(String, RawFormat)>,
+    /// Where to save each band as it is finished, if anywhere.
+    pub checkpoint: Option<String>,
+    /// Whether to carry on from the bands `checkpoint` holds already.
+    pub resume: bool,
+    /// Whether to write the PNG image band by band as it is rendered.
+    pub stream: bool,
+    /// How many rows each band of a `stream` holds.
+    pub strip_rows: usize,
+    /// The grid of separate files to cut the image into, if any.
+    pub grid: Option<Grid>,
+    /// The only part of the image to render and write, if not all of it.
+    pub roi: Option<Tile>,
+    /// Whether to print what the render would cost instead of running it.
+    pub estimate: bool,
+    /// Whether to write coarse previews to `filename` before the full image.
+    pub progressive: bool,
+    /// How to tile the image when `filename` is a Deep Zoom descriptor, whose
+    /// tiles are in `format`.
+    pub deep_zoom: Option<DeepZoom>,
+    /// The seed `--random-palette` picked for itself, when it was given none.
+    pub palette_seed: Option<u64>,
+    /// Whether `--auto-iter` picked `params.limit` from the width of the view.
+    pub auto_iter: bool,
+    /// Which coordinates of the corners were given the wrong way round, and
+    /// swapped.
+    pub swapped: CornerSwap,
+    /// What to do with a view stretched to the shape of the image.
+    pub aspect: AspectPolicy,
+    /// Whether to print statistics of the escape counts after rendering.
+    pub stats: bool,
+}
+
+/// How much to report on standard error about a render, besides warnings
+/// and errors, which are always shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    #[default]
+    Normal,
+    /// `-v`: the view, the threads, how long each step took and how much
+    /// work each thread did.
+    Info,
+    /// `-vv`: also how the rows were split up and every render parameter.
+    Debug,
+}
+
+impl Verbosity {
+    /// One level more, up to `Debug`.
+    fn raised(self) -> Verbosity
+    {
+        match self {
+            Verbosity::Normal => Verbosity::Info,
+            Verbosity::Info | Verbosity::Debug => Verbosity::Debug,
+        }
+    }
+}
+
+/// The `animate` subcommand: the options of the first frame, and how to
+/// zoom from there.
+pub struct Animation {
+    pub options: Options,
+    pub out_dir: PathBuf,
+    pub zoom: Zoom,
+}
+
+/// The `recolor` subcommand: the dump to read, and how to color it. The
+/// region, limit and bailout in `options` are placeholders for those stored
+/// in the dump.
+pub struct Recolor {
+    pub input: String,
+    pub options: Options,
+}
+
+/// The `batch` subcommand: a file of render jobs, one per line, and the
+/// number of threads the parallel ones share.
+pub struct Batch {
+    pub input: String,
+    pub threads: usize,
+    pub quiet: bool,
+}
+
+/// The `bench` subcommand: the view to render, sequentially and then with
+/// `options.strategy` on `options.threads` threads, each the best of `runs`
+/// renders. `options.filename` is a placeholder; nothing is written.
+pub struct Bench {
+    pub options: Options,
+    pub runs: usize,
+}
+
+/// A line of input to `explore`: magnify the view `zoom` times about
+/// `pixel`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Click {
+    pub pixel: (usize, usize),
+    pub zoom: f64,
+}
+
+/// The `tiles` subcommand: the view to cut into a slippy-map pyramid, and
+/// where to put it. The size and filename in `options` are placeholders.
+pub struct MapTiles {
+    pub options: Options,
+    pub out_dir: PathBuf,
+    pub pyramid: Pyramid,
+}
+
+pub enum Command {
+    Help,
+    Render(Options),
+    Animate(Animation),
+    Recolor(Recolor),
+    Bench(Bench),
+    Batch(Batch),
+    Tiles(MapTiles),
+    /// Render the view, then zoom wherever the user points on standard
+    /// input and render again, until they quit.
+    Explore(Options),
+    /// Print the text chunks of the PNG file with this name.
+    ReadMetadata(String),
+    /// Print a command line rendering the image this PNG file holds again.
+    Describe(String),
+}
+
+
+/// The flags, and the few things a command line asks for by a flag and its
+/// value, that rule others out: each with what it does that does so, and
+/// what it cannot be combined with. The checks at the end of `parse_flags`
+/// and the notes in `usage` both read it, naming each pair only once.
+const CONFLICTS: [(&str, &str, &[&str]); 19] = [
+    ("EXR output", "holds escape values",
+     &["--palette", "--depth 16", "--gamma", "--log-scale", "--invert", "--display-gamma", "--coloring histogram",
+       "--coloring binary", "--coloring binary-shaded", "--aa", "--light-angle"]),
+    ("--dump", "stores integer counts",
+     &["--smooth", "--aa", "--coloring distance", "--coloring trap", "--coloring binary", "--coloring binary-shaded",
+       "--coloring stripe"]),
+    ("--output-raw", "stores integer counts",
+     &["--smooth", "--aa", "--coloring distance", "--coloring trap", "--coloring binary", "--coloring binary-shaded",
+       "--coloring stripe"]),
+    ("--coloring histogram", "works on integer counts", &["--smooth", "--aa"]),
+    ("--coloring distance", "replaces the smooth count", &["--smooth"]),
+    ("--coloring trap", "replaces the smooth count", &["--smooth"]),
+    ("--coloring binary", "colors by integer counts", &["--smooth"]),
+    ("--coloring binary-shaded", "colors by integer counts", &["--smooth"]),
+    ("--coloring stripe", "replaces the smooth count", &["--smooth"]),
+    ("--fractal newton", "converges rather than escaping",
+     &["--smooth", "--dump", "--output-raw", "--coloring histogram", "--coloring binary", "--coloring binary-shaded",
+       "--coloring stripe", "--light-angle"]),
+    ("--deep", "renders the Mandelbrot set colored by escape count",
+     &["--coloring distance", "--coloring trap", "--coloring binary", "--coloring binary-shaded", "--coloring stripe",
+       "--precision", "--estimate"]),
+    ("--precision", "renders the Mandelbrot or Julia set colored by escape count",
+     &["--coloring distance", "--coloring trap", "--coloring binary", "--coloring binary-shaded", "--coloring stripe",
+       "--estimate"]),
+    ("--format dzi", "writes a descriptor and a directory of tiles",
+     &["--stream", "--grid", "--roi", "--output -", "--depth 16"]),
+    ("--stream", "writes the rows as they are rendered",
+     &["--dump", "--output-raw", "--coloring histogram", "--light-angle"]),
+    ("--grid", "writes separate files",
+     &["--stream", "--dump", "--output-raw", "--coloring histogram", "--light-angle", "--output -", "--strategy"]),
+    ("--roi", "renders part of the image on its own",
+     &["--grid", "--stream", "--dump", "--output-raw", "--coloring histogram", "--light-angle", "--strategy",
+       "--estimate"]),
+    ("--progressive", "overwrites --output with each pass",
+     &["--stream", "--grid", "--roi", "--estimate", "--output -"]),
+    ("--stats", "needs every integer count of the whole image",
+     &["--smooth", "--aa", "--fractal newton", "--coloring distance", "--coloring trap", "--coloring binary",
+       "--coloring binary-shaded", "--coloring stripe", "--stream", "--grid", "--roi", "--estimate"]),
+    ("--checkpoint", "saves the image band by band",
+     &["--stream", "--grid", "--roi", "--estimate", "--progressive", "--dump", "--output-raw", "--stats",
+       "--light-angle"]),
+];
+
+/// Everything `CONFLICTS` rules `name` out with: its own entry's list, then
+/// the entries that list it.
+fn conflicts_with(name: &str) -> Vec<&'static str>
+{
+    let own = CONFLICTS.iter().filter(|(first, _, _)| *first == name).flat_map(|(_, _, others)| others.iter().copied());
+    let listed = CONFLICTS.iter().filter(|(_, _, others)| others.contains(&name)).map(|(first, _, _)| *first);
+    own.chain(listed).collect()
+}
+
+/// `(not with A, B or C)` for what `name` cannot be combined with, wrapped to
+/// the descriptions of `usage`, which start in column 26 and end by 79.
+fn conflict_note(name: &str) -> String
+{
+    let list = match conflicts_with(name).as_slice() {
+        [] => String::new(),
+        [only] => only.to_string(),
+        [rest @ .., last] => format!("{} or {}", rest.join(", "), last),
+    };
+    let mut lines = vec![];
+    let mut line = String::new();
+    for word in format!("(not with {})", list).split(' ') {
+        if !line.is_empty() && line.len() + 1 + word.len() > 54 {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+    lines.push(line);
+    lines.join(&format!("\n{:25}", ""))
+}
+
+/// Refuse the first pair of `CONFLICTS` of which `given` holds both.
+fn check_conflicts(given: &[&str]) -> Result<(), MandelError>
+{
+    for (name, reason, others) in &CONFLICTS {
+        if !given.contains(name) {
+            continue;
+        }
+        if let Some(other) = others.iter().find(|other| given.contains(other)) {
+            return Err(MandelError::Usage(format!("{} {} and cannot be combined with {}", name, reason, other)));
+        }
+    }
+    Ok(())
+}
+
+pub fn usage(program: &str) -> String
+{
+    format!("\
+Usage: {program} --output FILE --size WxH --upper-left RE,IM --lower-right RE,IM [OPTIONS]
+       {program} --output FILE --size WxH --center RE,IM --zoom Z|--width W [OPTIONS]
+       {program} animate --center RE,IM --frames N --zoom-per-frame F --out-dir DIR --size WxH [OPTIONS]
+       {program} recolor DUMP --output FILE [OPTIONS]
+       {program} tiles --upper-left RE,IM --lower-right RE,IM --max-zoom N --out-dir DIR [OPTIONS]
+       {program} explore --output FILE --size WxH --upper-left RE,IM --lower-right RE,IM [OPTIONS]
+       {program} batch FILE [--threads N] [--quiet]
+       {program} bench --size WxH --upper-left RE,IM --lower-right RE,IM [--threads N] [OPTIONS]
+       {program} --read-metadata FILE
+       {program} --describe FILE
+
+Options:
+  --output FILE          image file to write; the extension picks the format:
+                         .png, .ppm, .pgm, .bmp, .tif/.tiff, .jpg/.jpeg,
+                         .exr or .dzi; - writes to standard output, as PGM, or
+                         PPM for palette output, unless --format says otherwise
+                         (e.g. - --format png | convert - out.webp); all
+                         messages go to standard error, and standard output
+                         must not be a terminal
+  --format NAME          write this format whatever --output is called: png,
+                         ppm, pgm, bmp, tiff, jpeg or exr. EXR, in a build
+                         with the exr feature, holds each pixel's escape value
+                         as a 32-bit float, the smooth count with --smooth, and
+                         NaN inside the set
+                         {exr_conflicts}
+                         dzi writes a Deep Zoom Image for OpenSeadragon: the
+                         XML descriptor to --output and every level of tiles,
+                         each downsampled from the one above, to
+                         NAME_files/LEVEL/COLUMN_ROW.png beside it
+                         {dzi_conflicts}
+  --tile-format NAME     png (default) or jpeg, for the tiles of --format dzi
+  --overlap N            pixels each Deep Zoom tile shares with its
+                         neighbours (default {dzi_overlap})
+  --quality Q            JPEG quality from 1 to 100 (default {quality})
+  --dump FILE            also save every pixel's escape count, with the size,
+                         corners, limit and bailout, to FILE (e.g. view.iter)
+                         for coloring again later
+                         {dump_conflicts}
+  --output-raw FILE      also write every pixel's escape count, for analysis
+                         in other tools, to FILE: view.csv holds one line
+                         per row with -1 for points that never escape, and
+                         view.bin (or .raw) the width and height then each
+                         count as little-endian u32s, 4294967295 for points
+                         that never escape
+                         {raw_conflicts}
+  --stream               write PNG output {rows} rows at a time as they are
+                         rendered, so the whole image is never in memory;
+                         needs --strategy sequential or bands
+                         {stream_conflicts}
+  --strip-rows N         rows per band of --stream, which it implies; at most
+                         N times the width in pixels is held at once. The
+                         file is the same byte for byte whatever N is
+  --tile-rows N          same as --strip-rows N
+  --grid CxR             cut the image into C columns by R rows of separate
+                         files, e.g. out.png into out_r0_c0.png to
+                         out_r2_c3.png for 4x3, each recording its own part
+                         of the plane
+                         {grid_conflicts}
+  --roi X0,Y0,X1,Y1      render and write only the pixels from X0,Y0 up to
+                         but not including X1,Y1 of the --size image, each
+                         with the color it has in the whole image, e.g. to
+                         try out colors on part of a large view
+                         {roi_conflicts}
+  --stats                after rendering, print the fewest, most and mean
+                         iterations of the pixels that escaped, and the
+                         share of the image inside the set; a most equal to
+                         --max-iter says the limit may be too low
+                         {stats_conflicts}
+  --checkpoint FILE      render {checkpoint_rows} rows at a time, saving each
+                         band to FILE as it is finished, so that a render cut
+                         short can be resumed; needs --strategy sequential or
+                         bands. FILE may be deleted once the image is written
+                         {checkpoint_conflicts}
+  --resume FILE          carry on the render saved to FILE by --checkpoint,
+                         rendering only the bands it had not finished, and
+                         keep saving to FILE; give the other flags as they
+                         were given with --checkpoint
+  --estimate             sample the view coarsely and print roughly how many
+                         iterations and how long the render would take,
+                         without rendering it or writing --output, which it
+                         does not need
+  --progressive          write the image at 1/8 and then 1/4 of its size,
+                         enlarged, before the full render, each pass
+                         overwriting --output, for a preview that shows
+                         almost at once; the last pass is the same image a
+                         plain render makes
+                         {progressive_conflicts}
+  --size WxH             image size in pixels
+  --upper-left RE,IM     complex coordinate of the upper left corner
+  --lower-right RE,IM    complex coordinate of the lower right corner
+  --preserve-aspect      fit a region with square pixels to --size around
+                         --center; implied by --center, --zoom and --width
+  --strict               refuse corners given the wrong way round, the upper
+                         left one right of or below the lower right one,
+                         instead of swapping them with a warning
+  --square-pixels        widen or heighten the region given by the corners,
+                         about its middle, until its pixels are square, so
+                         that circles stay round; short for --aspect pad
+  --aspect POLICY        what to do with a region not the shape of --size,
+                         which stretches it: ignore it (the default), warn
+                         by how much, pad the shorter side or crop the
+                         longer one about its middle to square pixels
+  --center RE,IM         complex coordinate of the middle of the image, with
+                         --zoom or --width instead of the two corners
+  --zoom Z               magnification; at zoom 1 the image is {zoom_width}
+                         units wide
+  --width W              the span of the real axis across the image, instead
+                         of --zoom
+  --deep                 render by perturbation: each pixel follows its
+                         difference from a reference orbit iterated at full
+                         precision from --center, which may have any number
+                         of digits. For zooms past about 1e13, where f64
+                         coordinates break the image into blocks; needs
+                         --center, and renders the Mandelbrot set colored by
+                         escape count
+                         {deep_conflicts}
+  --precision N          place each point and iterate its orbit with N bits
+                         of mantissa (2 to {max_precision}), reading the
+                         corners, --center and --julia-c digit for digit; 53
+                         matches the default f64 render. Very slow; renders the
+                         Mandelbrot or Julia set colored by escape count
+                         {precision_conflicts}
+  --strategy NAME        how to split the work between threads: sequential
+                         (default), bands (one band of rows per thread),
+                         rayon (rows scheduled by work stealing), dynamic
+                         (threads claim rows from a shared counter) or tiles
+                         (square tiles rendered by a pool of threads)
+  --tile-size N          side length of the tiles for --strategy tiles
+                         (default {tile_size}), or of the Deep Zoom tiles with
+                         --format dzi (default {dzi_tile_size})
+  --parallel             same as --strategy rayon
+  --mode seq|par         same as --strategy sequential or --strategy rayon
+  --threads N            number of threads for the parallel strategies, at
+                         least 1 (default: one per available core)
+  --color gray|color     grayscale (default) or palette output
+  --coloring NAME        linear (default) spreads shades in proportion to the
+                         escape count; histogram equalizes them over the image
+                         so every shade is used about equally
+                         {histogram_conflicts}
+                         distance darkens points by how close they are to the
+                         set, which brings out its thin filaments (mandelbrot
+                         at power 2 and julia only)
+                         {distance_conflicts}
+                         trap shades every point, inside the set too, by how
+                         close its orbit comes to the --trap shape, from the
+                         first shade where it touches to the last {trap_reach}
+                         or more away; orbit-trap is the same as trap
+                         {trap_conflicts}
+                         binary draws the rings of binary decomposition,
+                         white where Im z is at least 0 as the orbit escapes
+                         and black where it is negative
+                         {binary_conflicts}
+                         binary-shaded lays the rings over the linear shades
+                         instead, darkening the negative side
+                         {binary_shaded_conflicts}
+                         stripe shades by the average over the orbit of
+                         0.5 + 0.5 sin(--stripe-density * arg z), which draws
+                         stripes flowing around the set; stripe-average is the
+                         same
+                         {stripe_conflicts}
+  --trap SHAPE           the orbit trap for --coloring trap: point:RE,IM
+                         (default point:0,0), cross:RE,IM for the horizontal
+                         and vertical lines through RE,IM, line:RE,IM:RE,IM
+                         through two points, or circle:RE,IM:R
+  --distance-scale PIXELS
+                         how far from the set, in pixels, --coloring distance
+                         brightens to the first shade; lower values sharpen
+                         the contrast (default {distance_scale})
+  --stripe-density S     how many stripes --coloring stripe draws for each turn
+                         of z around the origin (default {stripe_density})
+  --light-angle DEGREES  light the image as a raised surface, its height the
+                         smooth escape count, or the distance with
+                         --coloring distance, from DEGREES counterclockwise
+                         from the right (e.g. 45 for the upper right) and
+                         {light_elevation} degrees up, for an embossed look;
+                         renders the heights in a second pass
+                         {light_angle_conflicts}
+  --gamma G              raise each escaping pixel's position along the shades
+                         to the power 1/G, for G > 0; above 1 shifts shades
+                         towards the slow end of the range, below 1 towards
+                         the fast end (useful range 0.2 to 5, default 1)
+  --display-gamma G      encode the finished image for a display of gamma G,
+                         raising each channel, as a fraction of full
+                         brightness, to the power 1/G, for G > 0; unlike
+                         --gamma this works on the colors themselves, and
+                         {display_gamma} lifts the midtones that a linear
+                         mapping leaves too dark (default 1)
+  --gamma-correct        the same as --display-gamma {display_gamma}
+  --log-scale            spread shades by the logarithm of the escape count
+  --invert               draw grayscale output the other way round: the set
+                         white, fast-escaping points dark
+  --depth 8|16           bits per pixel of grayscale output (default 8); 16
+                         needs a .png, .pgm or .tif/.tiff file, or -
+  --palette NAME|FILE    color output with a built-in palette, one of
+                         {palettes}, or one read
+                         from FILE as --palette-file does
+                         (e.g. ./sunset.gradient)
+  --palette-file PATH    color output with a palette read from a Fractint .map
+                         file (R G B per line) or a gradient file (r,g,b per
+                         line), interpolated across the escape counts
+  --random-palette [SEED]
+                         color output with a gradient through a few random
+                         colors, the same for the same SEED; without one a
+                         seed is picked and printed
+  --interior-color R,G,B paint points that never escape this color instead of
+                         the palette's black (e.g. 255,255,255)
+  --fractal NAME         mandelbrot (default), burning-ship (|Re z| and |Im z|
+                         squared), tricorn (conj(z) squared), julia, or newton:
+                         Newton's method for z^3 - 1, each pixel colored by
+                         the root it converges to and darker the more
+                         iterations that took (a --max-iter of 30 or so
+                         shows the shading best), black if it never does;
+                         palettes do not apply
+                         {newton_conflicts}
+  --newton-epsilon E     how close to a root --fractal newton must come to
+                         count as converged (default {newton_epsilon})
+  --julia-c RE,IM        the constant c of the Julia set, e.g. -0.8,0.156
+  --power D              draw the Multibrot set z = z^D + c instead, for D from
+                         2 (the Mandelbrot set, default) to {max_power}
+  --max-iter N           iteration limit, at least 1 (default {limit});
+                         --iterations is accepted as well
+  --auto-iter            pick the iteration limit from the width of the view,
+                         50 * log10(4 / width)^1.5 but at least {limit}, and
+                         print it; --max-iter still wins
+  --auto-iter-max N      the highest limit --auto-iter picks (default
+                         {auto_limit_max})
+  --bailout R2           squared escape radius, at least 4 since smaller values
+                         misclassify interior points (default {bailout}, or
+                         {smooth_bailout} with --smooth, --coloring distance or
+                         stripe, or --light-angle)
+  --escape-radius R      the same as --bailout R*R, for R of at least 2; radii
+                         of 256 and more make --smooth colors smoother still
+  --smooth               continuous coloring without banding
+  --aa N                 anti-alias by averaging an N by N grid of samples in
+                         every pixel; takes N*N times as long (default 1)
+                         {aa_conflicts}
+  --no-interior-check    iterate points of the main cardioid and period-2 bulb
+                         instead of recognizing them at once; for benchmarks
+  --no-mirror            render both halves of a view symmetric about the real
+                         axis instead of copying rows across it, as the
+                         sequential and rayon strategies otherwise do; for
+                         benchmarks
+  --periodicity-epsilon E
+                         treat an orbit as a never-escaping cycle once it comes
+                         back within E of an earlier value; 0 turns the check
+                         off (default {epsilon})
+  --no-periodicity       the same as --periodicity-epsilon 0; for benchmarks
+  -v, --verbose          report the view, the threads, how long rendering and
+                         writing took, how many rows each thread rendered,
+                         how many points the interior check skipped, and how
+                         many orbits the periodicity check cut short
+  -vv                    also report how the rows were split between the
+                         threads, and every render parameter (-v -v does the
+                         same)
+  --quiet                do not show progress; it is shown on stderr when that
+                         is a terminal
+  --help                 print this message
+
+Animation:
+  animate renders a zoom into --center as DIR/frame_0000.png, frame_0001.png
+  and so on, on --threads threads, taking the other options above except
+  --output, --preserve-aspect, --strategy, --roi, --stats, --checkpoint and
+  --resume.
+  --center RE,IM         the point to zoom into; it keeps its place on screen
+  --frames N             number of frames, at least 1
+  --zoom-per-frame F     magnification from one frame to the next, e.g. 1.05
+  --out-dir DIR          directory for the frames, created if missing
+  --zoom Z               magnification of the first frame, centered on
+                         --center (default 1), or its --width; or give its
+                         corners with --upper-left and --lower-right instead
+
+Recoloring:
+  recolor colors the escape counts saved with --dump into a new image without
+  computing them again, taking the output and coloring options above:
+  --output, --format, --quality, --color, --palette, --palette-file,
+  --interior-color, --coloring linear|histogram, --gamma, --log-scale,
+  --invert, --display-gamma, --gamma-correct and --depth.
+
+Map tiles:
+  tiles renders the view as {map_tile}x{map_tile} PNG tiles in
+  DIR/{{z}}/{{x}}/{{y}}.png for slippy-map viewers such as Leaflet, zoom
+  level z covering the view with 2^z by 2^z tiles. It takes the options
+  above except --output, --format, --quality, --size, --dump, --output-raw,
+  --stream, --strip-rows, --tile-rows, --grid, --roi, --progressive,
+  --stats, --checkpoint, --resume, --strategy, --light-angle and --coloring
+  histogram; --preserve-aspect fits a square view.
+  --max-zoom N           the deepest zoom level, from 0 to {max_zoom}
+  --out-dir DIR          directory for the levels, created if missing
+  --iterations-per-level N
+                         raise --max-iter by N on each level (default 0)
+  --skip-empty           leave out tiles that are all the interior color
+
+Exploring:
+  explore renders the view to --output, taking the options above except
+  --estimate, --roi, --checkpoint, --resume and --output -, then reads what
+  to look at next from standard input, one line at a time:
+    X Y ZOOM             center the view on pixel X,Y of the image and
+                         magnify it ZOOM times (below 1 zooms out), and
+                         render it again to the same file
+    q                    quit, as does the end of the input
+
+Benchmarking:
+  bench renders the view sequentially and then in parallel, and prints how
+  long each took and the speedup, taking the options above except --output,
+  --format, --quality, --dump, --output-raw, --stream, --strip-rows,
+  --tile-rows, --grid, --roi, --progressive, --stats, --checkpoint and
+  --resume.
+  --strategy NAME        the parallel strategy to time (default bands)
+  --runs N               render each way N times and keep the fastest
+                         (default 1)
+
+Batches:
+  batch renders every job in FILE, one per line in the positional form
+  below without the program name, e.g.
+    out.png 800x600 -2,1.2 1,-1.2 1
+  and prints how long each took. Parallel jobs share one pool of --threads
+  threads. Blank lines and lines starting with # are ignored; a line that
+  does not parse is skipped with a warning, and the rest still rendered.
+
+Example:
+  {program} --output mandel.png --size 1000x750 --upper-left -1.20,0.35 --lower-right -1,0.20 --parallel
+
+Complex numbers, RE,IM above, may also be written RE+IMi or RE-IMi (e.g.
+-0.75+0.1i), or as a bare real RE or a bare imaginary IMi.
+
+PNG output records the fractal, size, corners and iteration limit it was
+rendered with in text chunks; --read-metadata prints those of FILE, and
+--describe prints a command line that renders FILE again.
+
+The older positional form is still accepted, optionally after --legacy:
+  {program} [--legacy] FILE PIXELS UPPERLEFT LOWERRIGHT <SEQUENTIAL:0|PARALLEL:1> [gray|color [LIMIT]]
+
+The Burning Ship's largest ship, at --max-iter 500 or so:
+  {program} --output ship.png --size 1000x900 --upper-left -1.8,0.01 --lower-right -1.7,-0.08 --fractal burning-ship
+The imaginary axis points up here, as for the Mandelbrot set, so the ship
+comes out upside down from how it is usually shown; flip the image (e.g.
+convert ship.png -flip ship.png) for masts pointing up.
+
+A zoom of 200 frames, put together with ffmpeg:
+  {program} animate --center -0.743643,0.131825 --frames 200 --zoom-per-frame 1.05 --out-dir zoom --size 640x480
+  ffmpeg -i zoom/frame_%04d.png zoom.mp4",
+        program = program, limit = LIMIT_OF_ITERATION, bailout = LIMIT_TO_CALL_IT_OFF_TO_INFINITY,
+        smooth_bailout = SMOOTH_BAILOUT, epsilon = PERIODICITY_EPSILON,
+        palettes = PALETTE_NAMES.join(", "), quality = DEFAULT_JPEG_QUALITY, tile_size = DEFAULT_TILE_SIZE,
+        dzi_tile_size = DZI_TILE_SIZE, dzi_overlap = DZI_OVERLAP, trap_reach = TRAP_REACH,
+        distance_scale = DISTANCE_FALLOFF, stripe_density = STRIPE_DENSITY, display_gamma = DISPLAY_GAMMA,
+        light_elevation = LIGHT_ELEVATION, zoom_width = WIDTH_AT_ZOOM_ONE,
+        rows = STREAM_BAND_ROWS, map_tile = MAP_TILE_SIZE, max_zoom = MAX_ZOOM_LEVEL, max_power = MAX_POWER,
+        max_precision = MAX_PRECISION, auto_limit_max = AUTO_LIMIT_MAX, checkpoint_rows = CHECKPOINT_BAND_ROWS,
+        newton_epsilon = NEWTON_EPSILON,
+        exr_conflicts = conflict_note("EXR output"), dzi_conflicts = conflict_note("--format dzi"),
+        dump_conflicts = conflict_note("--dump"), raw_conflicts = conflict_note("--output-raw"),
+        stream_conflicts = conflict_note("--stream"), grid_conflicts = conflict_note("--grid"),
+        roi_conflicts = conflict_note("--roi"), stats_conflicts = conflict_note("--stats"),
+        checkpoint_conflicts = conflict_note("--checkpoint"), progressive_conflicts = conflict_note("--progressive"),
+        deep_conflicts = conflict_note("--deep"), precision_conflicts = conflict_note("--precision"),
+        histogram_conflicts = conflict_note("--coloring histogram"),
+        distance_conflicts = conflict_note("--coloring distance"), trap_conflicts = conflict_note("--coloring trap"),
+        binary_conflicts = conflict_note("--coloring binary"),
+        binary_shaded_conflicts = conflict_note("--coloring binary-shaded"),
+        stripe_conflicts = conflict_note("--coloring stripe"), light_angle_conflicts = conflict_note("--light-angle"),
+        newton_conflicts = conflict_note("--fractal newton"), aa_conflicts = conflict_note("--aa"))
+}
+
+
+fn parse_bool(s: &str) -> Result<bool, String> {
+    match s {
+        "0" => Ok(false),
+        "1" => Ok(true),
+        _ => Err(format!("expected 0 or 1 for render mode, got '{}'", s)),
+    }
+}
+
+fn parse_color_mode(s: &str) -> Result<ColorMode, String> {
+    match s {
+        "gray" => Ok(ColorMode::Gray),
+        "color" => Ok(ColorMode::Rgb(Palette::default())),
+        _ => Err(format!("expected gray or color for color mode, got '{}'", s)),
+    }
+}
+
+fn parse_aspect(s: &str) -> Result<AspectPolicy, MandelError> {
+    match s {
+        "ignore" => Ok(AspectPolicy::Ignore),
+        "warn" => Ok(AspectPolicy::Warn),
+        "pad" => Ok(AspectPolicy::Pad),
+        "crop" => Ok(AspectPolicy::Crop),
+        _ => Err(MandelError::Parse(format!("expected ignore, warn, pad or crop, got '{}'", s))),
+    }
+}
+
+fn parse_coloring(s: &str) -> Result<Coloring, MandelError> {
+    match s {
+        "linear" => Ok(Coloring::Linear),
+        "histogram" => Ok(Coloring::Histogram),
+        "distance" => Ok(Coloring::Distance),
+        "trap" | "orbit-trap" => Ok(Coloring::Trap),
+        "binary" => Ok(Coloring::Binary),
+        "binary-shaded" => Ok(Coloring::BinaryShaded),
+        "stripe" | "stripe-average" => Ok(Coloring::Stripe),
+        _ => Err(MandelError::Parse(format!("expected linear, histogram, distance, trap, binary, binary-shaded or \
+                                             stripe, got '{}'", s))),
+    }
+}
+
+/// Parse an orbit trap: `point:RE,IM`, `cross:RE,IM`, `line:RE,IM:RE,IM`
+/// through two points, or `circle:RE,IM:R`.
+fn parse_trap(s: &str) -> Result<Trap, MandelError>
+{
+    let error = || MandelError::Parse(format!("expected point:RE,IM, cross:RE,IM, line:RE,IM:RE,IM or circle:RE,IM:R, \
+                                               got '{}'", s));
+    let parts: Vec<&str> = s.split(':').collect();
+    let point = |part: &str| parse_complex(part).ok_or_else(error);
+    match parts[..] {
+        ["point", at] => Ok(Trap::Point(point(at)?)),
+        ["cross", at] => Ok(Trap::Cross(point(at)?)),
+        ["line", from, to] => {
+            let (from, to) = (point(from)?, point(to)?);
+            if from == to {
+                return Err(MandelError::Parse(format!("a line trap needs two different points, got '{}'", s)));
+            }
+            Ok(Trap::Line(from, to))
+        }
+        ["circle", center, radius] => match f64::from_str(radius.trim()) {
+            Ok(radius) if radius > 0.0 && radius.is_finite() => Ok(Trap::Circle(point(center)?, radius)),
+            _ => Err(error()),
+        },
+        _ => Err(error()),
+    }
+}
+
+fn parse_distance_scale(s: &str) -> Result<f64, MandelError> {
+    match f64::from_str(s) {
+        Ok(scale) if scale > 0.0 && scale.is_finite() => Ok(scale),
+        _ => Err(MandelError::Parse(format!("expected a number of pixels greater than 0, got '{}'", s))),
+    }
+}
+
+fn parse_stripe_density(s: &str) -> Result<f64, MandelError> {
+    match s.parse::<f64>() {
+        Ok(density) if density > 0.0 && density.is_finite() => Ok(density),
+        _ => Err(MandelError::Parse(format!("expected a positive number of stripes, got '{}'", s))),
+    }
+}
+
+fn parse_light_angle(s: &str) -> Result<f64, MandelError> {
+    match f64::from_str(s) {
+        Ok(degrees) if degrees.is_finite() => Ok(degrees),
+        _ => Err(MandelError::Parse(format!("expected an angle in degrees, got '{}'", s))),
+    }
+}
+
+fn parse_gamma(s: &str) -> Result<f64, MandelError> {
+    match f64::from_str(s) {
+        Ok(gamma) if gamma > 0.0 && gamma.is_finite() => Ok(gamma),
+        _ => Err(MandelError::Parse(format!("expected a gamma greater than 0, got '{}'", s))),
+    }
+}
+
+fn parse_depth(s: &str) -> Result<u8, MandelError> {
+    match s {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err(MandelError::Parse(format!("expected 8 or 16, got '{}'", s))),
+    }
+}
+
+/// A seed for `--random-palette`, any whole number from 0 to 2^64 - 1.
+fn parse_seed(s: &str) -> Result<u64, MandelError> {
+    s.parse().map_err(|_| MandelError::Parse(format!("expected a seed from 0 to {}, got '{}'", u64::MAX, s)))
+}
+
+/// A seed for `--random-palette` given none: the time, to the nanosecond.
+fn fresh_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+}
+
+/// A built-in palette by name, or else, if there is a file at `s`, the
+/// palette in it, as `--palette-file` reads it.
+fn parse_palette(s: &str) -> Result<Palette, MandelError>
+{
+    if let Some(palette) = Palette::named(s) {
+        return Ok(palette);
+    }
+    if Path::new(s).exists() {
+        return Palette::from_file(Path::new(s));
+    }
+    Err(MandelError::Parse(format!("expected one of {} or a palette file, got '{}'", PALETTE_NAMES.join(", "), s)))
+}
+
+fn parse_strategy(s: &str) -> Result<Strategy, MandelError>
+{
+    match s {
+        "sequential" => Ok(Strategy::Sequential),
+        "bands" => Ok(Strategy::Bands),
+        "rayon" => Ok(Strategy::Rayon),
+        "dynamic" => Ok(Strategy::Dynamic),
+        "tiles" => Ok(Strategy::Tiles(DEFAULT_TILE_SIZE)),
+        _ => Err(MandelError::Parse(format!("expected sequential, bands, rayon, dynamic or tiles, got '{}'", s))),
+    }
+}
+
+fn parse_mode(s: &str) -> Result<Strategy, MandelError>
+{
+    match s {
+        "seq" => Ok(Strategy::Sequential),
+        "par" => Ok(Strategy::Rayon),
+        _ => Err(MandelError::Parse(format!("expected seq or par, got '{}'", s))),
+    }
+}
+
+/// `s` without the whitespace around it and without one pair of matching
+/// double or single quotes around that, which some shells and scripts pass
+/// through, so that `'"800x600"'` gives `800x600`.
+fn unquote(s: &str) -> &str
+{
+    let s = s.trim();
+    for quote in ['"', '\''] {
+        if let Some(inner) = s.strip_prefix(quote).and_then(|rest| rest.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    s
+}
+
+/// Parse `s` as two values on either side of `separator`, each with any
+/// whitespace around it ignored, so that " 45 , 50 " gives `(45, 50)`. The
+/// whole of `s` may be in quotes, as `unquote` takes them off.
+fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)>
+{
+    let s = unquote(s);
+    match s.find(separator) {
+        None => None,
+        Some(index) => {
+            match (T::from_str(s[..index].trim()), T::from_str(s[index + 1..].trim())) {
+                (Ok(l), Ok(r)) => Some((l, r)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Split a complex number written as `RE,IM`, `RE+IMi`, `RE-IMi`, a bare
+/// real `RE` or a bare imaginary `IMi` into the text of its two parts, the
+/// sign of the imaginary part included, and `"0"` for a part left out.
+fn complex_parts(s: &str) -> Option<(&str, &str)>
+{
+    let s = unquote(s);
+    if let Some(parts) = s.split_once(CMD_ARG_COMPLEX_NUMBER_SEPARATOR) {
+        return Some(parts);
+    }
+    let body = match s.strip_suffix('i') {
+        None => return Some((s, "0")),
+        Some(body) => body,
+    };
+    // The sign between the parts is the last one that neither leads the
+    // real part nor belongs to an exponent, as in 1e-3+2e-4i.
+    let between = body.char_indices().rev()
+        .find(|&(index, c)| (c == '+' || c == '-') && index > 0 && !body[..index].ends_with(['e', 'E']));
+    Some(match between {
+        Some((index, _)) => (&body[..index], &body[index..]),
+        None => ("0", body),
+    })
+}
+
+fn parse_complex(s: &str) -> Option<Complex<f64>>
+{
+    let (re, im) = complex_parts(s)?;
+    match (f64::from_str(re.trim()), f64::from_str(im.trim())) {
+        (Ok(re), Ok(im)) => Some(Complex { re, im }),
+        _ => None,
+    }
+}
+
+fn parse_bounds(s: &str) -> Result<(usize, usize), MandelError>
+{
+    match parse_pair::<usize>(s, 'x') {
+        None => Err(MandelError::Parse(format!("error parsing image dimensions '{}'", s))),
+        Some((width, height)) if width == 0 || height == 0 =>
+            Err(MandelError::Dimensions(format!("image dimensions must be non-zero, got '{}'", s))),
+        Some(bounds) => Ok(bounds),
+    }
+}
+
+fn parse_corner(s: &str, which: &str) -> Result<Complex<f64>, MandelError>
+{
+    parse_complex(s).ok_or_else(|| MandelError::Parse(format!("error parsing {} corner point '{}'", which, s)))
+}
+
+fn parse_precise_complex(s: &str, bits: u64) -> Result<Complex<BigFloat>, MandelError>
+{
+    complex_parts(s)
+        .and_then(|(re, im)| Some(Complex { re: BigFloat::parse(re, bits)?, im: BigFloat::parse(im, bits)? }))
+        .ok_or_else(|| MandelError::Parse(format!("expected RE,IM in decimal, got '{}'", s)))
+}
+
+fn parse_precision(s: &str) -> Result<u64, MandelError>
+{
+    match u64::from_str(s) {
+        Ok(bits) if (2..=MAX_PRECISION).contains(&bits) => Ok(bits),
+        _ => Err(MandelError::Parse(format!("expected a precision from 2 to {} bits, got '{}'", MAX_PRECISION, s))),
+    }
+}
+
+fn parse_limit(s: &str) -> Result<usize, MandelError>
+{
+    match usize::from_str(s) {
+        Ok(limit) if limit >= 1 => Ok(limit),
+        _ => Err(MandelError::Parse(format!("expected a positive iteration limit, got '{}'", s))),
+    }
+}
+
+/// A count of at least one, `what` saying what it counts for the error.
+fn parse_positive(s: &str, what: &str) -> Result<usize, MandelError>
+{
+    match usize::from_str(s) {
+        Ok(count) if count >= 1 => Ok(count),
+        _ => Err(MandelError::Parse(format!("expected a positive {}, got '{}'", what, s))),
+    }
+}
+
+/// Parse `--roi X0,Y0,X1,Y1`, in pixels.
+fn parse_roi(s: &str) -> Result<[usize; 4], MandelError>
+{
+    let values: Option<Vec<usize>> = s.split(',').map(|value| usize::from_str(value.trim()).ok()).collect();
+    match values.as_deref() {
+        Some(&[x0, y0, x1, y1]) => Ok([x0, y0, x1, y1]),
+        _ => Err(MandelError::Parse(format!("expected the rectangle as X0,Y0,X1,Y1 in pixels, got '{}'", s))),
+    }
+}
+
+/// The tile of an image of size `bounds` from pixel `x0,y0` up to `x1,y1`.
+/// Fails with `MandelError::Dimensions` unless that is a rectangle of at
+/// least one pixel within the image.
+fn roi_tile([x0, y0, x1, y1]: [usize; 4], bounds: (usize, usize)) -> Result<Tile, MandelError>
+{
+    if x0 >= x1 || y0 >= y1 || x1 > bounds.0 || y1 > bounds.1 {
+        return Err(MandelError::Dimensions(format!("{},{},{},{} is not a rectangle within the {}x{} image", x0, y0,
+                                                   x1, y1, bounds.0, bounds.1)));
+    }
+    Ok(Tile { origin: (x0, y0), size: (x1 - x0, y1 - y0) })
+}
+
+fn parse_grid(s: &str) -> Result<Grid, MandelError>
+{
+    match parse_pair::<usize>(s, 'x') {
+        Some((columns, rows)) if columns > 0 && rows > 0 => Ok(Grid { columns, rows }),
+        _ => Err(MandelError::Parse(format!("expected the grid as COLUMNSxROWS, e.g. 4x3, got '{}'", s))),
+    }
+}
+
+fn parse_max_zoom(s: &str) -> Result<u32, MandelError>
+{
+    match u32::from_str(s) {
+        Ok(zoom) if zoom <= MAX_ZOOM_LEVEL => Ok(zoom),
+        _ => Err(MandelError::Parse(format!("expected a zoom level from 0 to {}, got '{}'", MAX_ZOOM_LEVEL, s))),
+    }
+}
+
+fn parse_iterations_per_level(s: &str) -> Result<usize, MandelError>
+{
+    usize::from_str(s).map_err(|_| MandelError::Parse(format!("expected a number of iterations, got '{}'", s)))
+}
+
+fn parse_zoom(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(zoom) if zoom > 0.0 && zoom.is_finite() => Ok(zoom),
+        _ => Err(MandelError::Parse(format!("expected a positive zoom factor, got '{}'", s))),
+    }
+}
+
+fn parse_width(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(width) if width > 0.0 && width.is_finite() => Ok(width),
+        _ => Err(MandelError::Parse(format!("expected a positive width, got '{}'", s))),
+    }
+}
+
+fn parse_format(s: &str) -> Result<ImageFormat, MandelError>
+{
+    ImageFormat::from_name(s).ok_or_else(|| {
+        MandelError::Parse(format!("expected png, ppm, pgm, bmp, tiff, jpeg, exr or dzi, got '{}'", s))
+    })
+}
+
+fn parse_tile_format(s: &str) -> Result<ImageFormat, MandelError>
+{
+    match ImageFormat::from_name(s) {
+        Some(format @ (ImageFormat::Png | ImageFormat::Jpeg(_))) => Ok(format),
+        _ => Err(MandelError::Parse(format!("expected png or jpeg, got '{}'", s))),
+    }
+}
+
+fn parse_overlap(s: &str) -> Result<usize, MandelError>
+{
+    usize::from_str(s).map_err(|_| MandelError::Parse(format!("expected an overlap in pixels, got '{}'", s)))
+}
+
+fn parse_quality(s: &str) -> Result<u8, MandelError>
+{
+    match u8::from_str(s) {
+        Ok(quality) if (1..=100).contains(&quality) => Ok(quality),
+        _ => Err(MandelError::Parse(format!("expected a JPEG quality from 1 to 100, got '{}'", s))),
+    }
+}
+
+fn parse_bailout(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(bailout) if bailout >= LIMIT_TO_CALL_IT_OFF_TO_INFINITY => Ok(bailout),
+        _ => Err(MandelError::Parse(format!("expected a squared bailout radius of at least {}, got '{}'",
+                                            LIMIT_TO_CALL_IT_OFF_TO_INFINITY, s))),
+    }
+}
+
+fn parse_escape_radius(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(radius) if radius >= LIMIT_TO_CALL_IT_OFF_TO_INFINITY.sqrt() && radius.is_finite() => Ok(radius * radius),
+        _ => Err(MandelError::Parse(format!("expected an escape radius of at least {}, got '{}'",
+                                            LIMIT_TO_CALL_IT_OFF_TO_INFINITY.sqrt(), s))),
+    }
+}
+
+fn parse_epsilon(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(epsilon) if epsilon >= 0.0 && epsilon.is_finite() => Ok(epsilon),
+        _ => Err(MandelError::Parse(format!("expected a non-negative epsilon, got '{}'", s))),
+    }
+}
+
+fn parse_newton_epsilon(s: &str) -> Result<f64, MandelError>
+{
+    match f64::from_str(s) {
+        Ok(epsilon) if epsilon > 0.0 && epsilon.is_finite() => Ok(epsilon),
+        _ => Err(MandelError::Parse(format!("expected a positive epsilon, got '{}'", s))),
+    }
+}
+
+fn parse_power(s: &str) -> Result<u32, MandelError>
+{
+    match u32::from_str(s) {
+        Ok(power) if (2..=MAX_POWER).contains(&power) => Ok(power),
+        _ => Err(MandelError::Parse(format!("expected a power from 2 to {}, got '{}'", MAX_POWER, s))),
+    }
+}
+
+fn parse_fractal(name: &str, julia_c: Option<Complex<f64>>, power: Option<u32>) -> Result<Fractal, MandelError>
+{
+    if power.is_some() && name != "mandelbrot" {
+        return Err(MandelError::Usage("--power only applies to --fractal mandelbrot".to_string()));
+    }
+    match (name, julia_c) {
+        // Power 2 is the Mandelbrot set itself, with its cardioid check and
+        // four-lane loop.
+        ("mandelbrot", None) => Ok(match power {
+            None | Some(2) => Fractal::Mandelbrot,
+            Some(power) => Fractal::Multibrot(power),
+        }),
+        ("burning-ship", None) => Ok(Fractal::BurningShip),
+        ("tricorn", None) => Ok(Fractal::Tricorn),
+        ("newton", None) => Ok(Fractal::Newton(NEWTON_EPSILON)),
+        ("julia", Some(c)) => Ok(Fractal::Julia(c)),
+        ("julia", None) => Err(MandelError::Usage("--fractal julia needs --julia-c RE,IM".to_string())),
+        ("mandelbrot" | "burning-ship" | "tricorn" | "newton", Some(_)) => {
+            Err(MandelError::Usage("--julia-c only applies to --fractal julia".to_string()))
+        }
+        _ => Err(MandelError::Parse(format!("expected mandelbrot, burning-ship, tricorn, julia or newton, got '{}'",
+                                            name))),
+    }
+}
+
+/// Prefix the message of a parse error with the flag whose value caused it.
+fn for_flag<T>(flag: &str, result: Result<T, MandelError>) -> Result<T, MandelError>
+{
+    result.map_err(|error| match error {
+        MandelError::Parse(message) => MandelError::Parse(format!("{}: {}", flag, message)),
+        MandelError::Dimensions(message) => MandelError::Dimensions(format!("{}: {}", flag, message)),
+        error => error,
+    })
+}
+
+fn required<T>(value: Option<T>, flag: &str) -> Result<T, MandelError>
+{
+    value.ok_or_else(|| MandelError::Usage(format!("missing required flag {}", flag)))
+}
+
+fn parse_positional(args: &[String]) -> Result<Options, MandelError>
+{
+    if !(6..=8).contains(&args.len()) {
+        return Err(MandelError::Usage(format!("expected 5 to 7 arguments, got {}", args.len().saturating_sub(1))));
+    }
+
+    let bounds = parse_bounds(&args[2])?;
+    let upper_left = parse_corner(&args[3], "upper left")?;
+    let lower_right = parse_corner(&args[4], "lower right")?;
+    let parallel = parse_bool(&args[5]).map_err(MandelError::Parse)?;
+    let color = match args.get(6) {
+        None => ColorMode::Gray,
+        Some(arg) => parse_color_mode(arg).map_err(MandelError::Parse)?,
+    };
+    let limit = match args.get(7) {
+        None => LIMIT_OF_ITERATION,
+        Some(arg) => parse_limit(arg)?,
+    };
+    let (region, swapped) = Region::new(bounds, upper_left, lower_right).normalized();
+    region.validate()?;
+
+    Ok(Options {
+        format: ImageFormat::for_output(&args[1], color.color_type())?,
+        filename: args[1].clone(),
+        params: RenderParams { color, limit, ..RenderParams::new(bounds, region.upper_left, region.lower_right) },
+        strategy: if parallel { Strategy::Rayon } else { Strategy::Sequential },
+        threads: default_threads(),
+        verbosity: Verbosity::Normal,
+        quiet: false,
+        dump: None,
+        raw: None,
+        checkpoint: None,
+        resume: false,
+        stream: false,
+        strip_rows: STREAM_BAND_ROWS,
+        grid: None,
+        roi: None,
+        estimate: false,
+        progressive: false,
+        deep_zoom: None,
+        palette_seed: None,
+        auto_iter: false,
+        swapped,
+        aspect: AspectPolicy::Ignore,
+        stats: false,
+    })
+}
+
+fn parse_flags(args: &[String]) -> Result<Options, MandelError>
+{
+    let mut filename = None;
+    let mut format = None;
+    let mut quality = None;
+    let mut dump = None;
+    let mut checkpoint = None;
+    let mut resume = None;
+    let mut raw = None;
+    let mut stream = false;
+    let mut strip_rows = None;
+    let mut grid = None;
+    let mut roi = None;
+    let mut estimate = false;
+    let mut progressive = false;
+    let mut stats = false;
+    let mut dzi = false;
+    let mut tile_format = None;
+    let mut overlap = None;
+    let mut depth = 8;
+    let mut coloring = Coloring::Linear;
+    let mut trap = None;
+    let mut distance_scale = None;
+    let mut stripe_density = None;
+    let mut light_angle = None;
+    let mut interior_color = None;
+    let mut palette_seed = None;
+    let mut gamma = 1.0;
+    let mut display_gamma = 1.0;
+    let mut log_scale = false;
+    let mut invert = false;
+    let mut bounds = None;
+    let mut upper_left = None;
+    let mut upper_left_text = None;
+    let mut lower_right = None;
+    let mut lower_right_text = None;
+    let mut preserve_aspect = false;
+    let mut square_pixels = false;
+    let mut aspect = None;
+    let mut strict = false;
+    let mut center = None;
+    let mut center_text = None;
+    let mut zoom = None;
+    let mut width = None;
+    let mut deep = false;
+    let mut precision_bits = None;
+    let mut strategy = Strategy::Sequential;
+    let mut threads = default_threads();
+    let mut verbosity = Verbosity::Normal;
+    let mut quiet = false;
+    let mut tile_size = None;
+    let mut color = ColorMode::Gray;
+    let mut limit = None;
+    let mut auto_iter = false;
+    let mut auto_iter_max = None;
+    let mut bailout = None;
+    let mut smooth = false;
+    let mut antialias = 1;
+    let mut interior_check = true;
+    let mut mirror = true;
+    let mut periodicity_epsilon = PERIODICITY_EPSILON;
+    let mut fractal_name = "mandelbrot".to_string();
+    let mut julia_c = None;
+    let mut julia_text = None;
+    let mut power = None;
+    let mut newton_epsilon = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone().or_else(|| args.next().cloned())
+                .ok_or_else(|| MandelError::Usage(format!("{} needs a value", flag)))
+        };
+
+        match flag {
+            "--output" => filename = Some(value()?),
+            "--coloring" => coloring = for_flag(flag, parse_coloring(&value()?))?,
+            "--trap" => trap = Some(for_flag(flag, parse_trap(&value()?))?),
+            "--distance-scale" => distance_scale = Some(for_flag(flag, parse_distance_scale(&value()?))?),
+            "--stripe-density" => stripe_density = Some(for_flag(flag, parse_stripe_density(&value()?))?),
+            "--light-angle" => light_angle = Some(for_flag(flag, parse_light_angle(&value()?))?),
+            "--gamma" => gamma = for_flag(flag, parse_gamma(&value()?))?,
+            "--display-gamma" => display_gamma = for_flag(flag, parse_gamma(&value()?))?,
+            "--gamma-correct" => display_gamma = DISPLAY_GAMMA,
+            "--log-scale" => log_scale = true,
+            "--invert" => invert = true,
+            "--depth" => depth = for_flag(flag, parse_depth(&value()?))?,
+            "--format" => match value()? {
+                name if name.eq_ignore_ascii_case("dzi") => (dzi, format) = (true, None),
+                name => (dzi, format) = (false, Some(for_flag(flag, parse_format(&name))?)),
+            },
+            "--tile-format" => tile_format = Some(for_flag(flag, parse_tile_format(&value()?))?),
+            "--overlap" => overlap = Some(for_flag(flag, parse_overlap(&value()?))?),
+            "--dump" => dump = Some(value()?),
+            "--checkpoint" => checkpoint = Some(value()?),
+            "--resume" => resume = Some(value()?),
+            "--output-raw" => {
+                let filename = value()?;
+                raw = Some((filename.clone(), for_flag(flag, RawFormat::from_filename(&filename))?));
+            }
+            "--stream" => stream = true,
+            "--strip-rows" | "--tile-rows" => {
+                strip_rows = Some(for_flag(flag, parse_positive(&value()?, "number of rows"))?);
+            }
+            "--grid" => grid = Some(for_flag(flag, parse_grid(&value()?))?),
+            "--roi" => roi = Some(for_flag(flag, parse_roi(&value()?))?),
+            "--quality" => quality = Some(for_flag(flag, parse_quality(&value()?))?),
+            "--size" => bounds = Some(for_flag(flag, parse_bounds(&value()?))?),
+            "--upper-left" => {
+                let value = value()?;
+                upper_left = Some(for_flag(flag, parse_corner(&value, "upper left"))?);
+                upper_left_text = Some(value);
+            }
+            "--lower-right" => {
+                let value = value()?;
+                lower_right = Some(for_flag(flag, parse_corner(&value, "lower right"))?);
+                lower_right_text = Some(value);
+            }
+            "--preserve-aspect" => preserve_aspect = true,
+            "--square-pixels" => square_pixels = true,
+            "--aspect" => aspect = Some(for_flag(flag, parse_aspect(&value()?))?),
+            "--strict" => strict = true,
+            "--center" => {
+                let value = value()?;
+                center = Some(for_flag(flag, parse_corner(&value, "center").map_err(|_| {
+                    MandelError::Parse("expected the center as RE,IM".to_string())
+                }))?);
+                center_text = Some(value);
+            }
+            "--zoom" => zoom = Some(for_flag(flag, parse_zoom(&value()?))?),
+            "--width" => width = Some(for_flag(flag, parse_width(&value()?))?),
+            "--deep" => deep = true,
+            "--precision" => precision_bits = Some(for_flag(flag, parse_precision(&value()?))?),
+            "--parallel" => strategy = Strategy::Rayon,
+            "--strategy" => strategy = for_flag(flag, parse_strategy(&value()?))?,
+            "--mode" => strategy = for_flag(flag, parse_mode(&value()?))?,
+            "--tile-size" => tile_size = Some(for_flag(flag, parse_positive(&value()?, "tile size"))?),
+            "--threads" => threads = for_flag(flag, parse_positive(&value()?, "thread count"))?,
+            "--smooth" => smooth = true,
+            "--aa" => antialias = for_flag(flag, parse_positive(&value()?, "number of samples per side"))?,
+            "--verbose" | "-v" => verbosity = verbosity.raised(),
+            "-vv" => verbosity = Verbosity::Debug,
+            "--quiet" => quiet = true,
+            "--estimate" => estimate = true,
+            "--progressive" => progressive = true,
+            "--stats" => stats = true,
+            "--no-interior-check" => interior_check = false,
+            "--no-mirror" => mirror = false,
+            "--periodicity-epsilon" => periodicity_epsilon = for_flag(flag, parse_epsilon(&value()?))?,
+            "--no-periodicity" => periodicity_epsilon = 0.0,
+            "--color" => color = for_flag(flag, parse_color_mode(&value()?).map_err(MandelError::Parse))?,
+            "--palette" => color = ColorMode::Rgb(for_flag(flag, parse_palette(&value()?))?),
+            "--interior-color" => {
+                interior_color = Some(for_flag(flag, parse_rgb(&value()?).map_err(MandelError::Parse))?);
+            }
+            "--palette-file" => color = ColorMode::Rgb(for_flag(flag, Palette::from_file(Path::new(&value()?)))?),
+            "--random-palette" => {
+                // The seed is optional, so only a number is taken for it.
+                let seed = match &inline {
+                    Some(seed) => for_flag(flag, parse_seed(seed))?,
+                    None => match args.as_slice().first().and_then(|next| parse_seed(next).ok()) {
+                        Some(seed) => {
+                            args.next();
+                            seed
+                        }
+                        None => *palette_seed.insert(fresh_seed()),
+                    },
+                };
+                color = ColorMode::Rgb(Palette::random(seed));
+            }
+            "--max-iter" | "--iterations" => limit = Some(for_flag(flag, parse_limit(&value()?))?),
+            "--auto-iter" => auto_iter = true,
+            "--auto-iter-max" => auto_iter_max = Some(for_flag(flag, parse_limit(&value()?))?),
+            "--bailout" => bailout = Some(for_flag(flag, parse_bailout(&value()?))?),
+            "--escape-radius" => bailout = Some(for_flag(flag, parse_escape_radius(&value()?))?),
+            "--fractal" => fractal_name = value()?,
+            "--julia-c" => {
+                let value = value()?;
+                julia_c = Some(for_flag(flag, parse_corner(&value, "julia").map_err(|_| {
+                    MandelError::Parse("expected the Julia constant as RE,IM".to_string())
+                }))?);
+                julia_text = Some(value);
+            }
+            "--power" => power = Some(for_flag(flag, parse_power(&value()?))?),
+            "--newton-epsilon" => newton_epsilon = Some(for_flag(flag, parse_newton_epsilon(&value()?))?),
+            _ => return Err(MandelError::Usage(format!("unknown flag '{}'", arg))),
+        }
+    }
+
+    // An estimate writes nothing, so it needs nowhere to write it.
+    let filename = match filename {
+        None if estimate => STDOUT.to_string(),
+        filename => required(filename, "--output")?,
+    };
+    let dzi = dzi || (format.is_none() && Path::new(&filename).extension().is_some_and(|extension| {
+        extension.eq_ignore_ascii_case("dzi")
+    }));
+    if !dzi && (tile_format.is_some() || overlap.is_some()) {
+        return Err(MandelError::Usage("--tile-format and --overlap only apply to --format dzi".to_string()));
+    }
+    let format = match format {
+        _ if dzi => tile_format.unwrap_or(ImageFormat::Png),
+        Some(format) => format,
+        None => for_flag("--output", ImageFormat::for_output(&filename, color.color_type()))?,
+    };
+    let format = match (format, quality) {
+        (ImageFormat::Jpeg(_), Some(quality)) => ImageFormat::Jpeg(quality),
+        (_, Some(_)) => return Err(MandelError::Usage("--quality only applies to JPEG output".to_string())),
+        (format, None) => format,
+    };
+    if format == ImageFormat::Exr && !cfg!(feature = "exr") {
+        return Err(MandelError::Usage(EXR_UNAVAILABLE.to_string()));
+    }
+    let palette = matches!(color, ColorMode::Rgb(_));
+    let color = match (color, depth) {
+        _ if format == ImageFormat::Exr => ColorMode::Values,
+        (color, 8) => color,
+        (ColorMode::Gray, _) if matches!(format, ImageFormat::Png | ImageFormat::Pgm | ImageFormat::Tiff) => {
+            ColorMode::Gray16
+        }
+        (ColorMode::Gray, _) => return Err(MandelError::Usage("--depth 16 needs PNG, PGM or TIFF output".to_string())),
+        _ => return Err(MandelError::Usage("--depth 16 only applies to grayscale output".to_string())),
+    };
+    let color = match (color, interior_color) {
+        (ColorMode::Rgb(palette), Some(interior_color)) => ColorMode::Rgb(palette.with_interior(interior_color)),
+        (color, None) => color,
+        _ => return Err(MandelError::Usage("--interior-color only applies to palette output".to_string())),
+    };
+    if invert && matches!(color, ColorMode::Rgb(_)) {
+        return Err(MandelError::Usage("--invert only applies to grayscale output".to_string()));
+    }
+    if trap.is_some() && coloring != Coloring::Trap {
+        return Err(MandelError::Usage("--trap only applies to --coloring trap".to_string()));
+    }
+    if distance_scale.is_some() && coloring != Coloring::Distance {
+        return Err(MandelError::Usage("--distance-scale only applies to --coloring distance".to_string()));
+    }
+    if stripe_density.is_some() && coloring != Coloring::Stripe {
+        return Err(MandelError::Usage("--stripe-density only applies to --coloring stripe".to_string()));
+    }
+    let bounds = required(bounds, "--size")?;
+    // The view is given either by its center and width, the width perhaps
+    // as a zoom, or by two corners.
+    let centered = preserve_aspect || center.is_some() || zoom.is_some() || width.is_some();
+    if centered && (upper_left.is_some() || lower_right.is_some()) {
+        return Err(MandelError::Usage("give the view either as --center with --zoom or --width, or as --upper-left \
+                                       and --lower-right, not both".to_string()));
+    }
+    let view_width = match (zoom, width) {
+        (Some(_), Some(_)) => return Err(MandelError::Usage("--zoom and --width both give the size of the view; \
+                                                             pick one".to_string())),
+        (Some(zoom), None) => Some(WIDTH_AT_ZOOM_ONE / zoom),
+        (None, width) => width,
+    };
+    let (upper_left, lower_right) = if centered {
+        let center = required(center, "--center")?;
+        let view_width = view_width.ok_or_else(|| MandelError::Usage("--center needs --zoom or --width".to_string()))?;
+        viewport_from_center(center, view_width, bounds)
+    } else {
+        (required(upper_left, "--upper-left")?, required(lower_right, "--lower-right")?)
+    };
+    let mut region = Region::new(bounds, upper_left, lower_right);
+    // Corners given the wrong way round are put right, unless --strict
+    // refuses them.
+    let swapped = if centered || strict {
+        CornerSwap::default()
+    } else {
+        let (normalized, swapped) = region.normalized();
+        region = normalized;
+        swapped
+    };
+    let aspect = match (aspect, square_pixels) {
+        (Some(_), true) => {
+            return Err(MandelError::Usage("--square-pixels is short for --aspect pad; give one of them".to_string()));
+        }
+        (Some(aspect), false) => aspect,
+        (None, true) => AspectPolicy::Pad,
+        (None, false) => AspectPolicy::Ignore,
+    };
+    // Perturbation keeps every digit of the center, which the corners above
+    // have rounded to f64; at depths where they round together only the
+    // view's size about its center can be checked.
+    let perturbation = match (deep, &center_text) {
+        (false, _) => None,
+        (true, Some(center)) if centered => {
+            let (from, to) = viewport_from_center(Complex { re: 0.0, im: 0.0 }, view_width.unwrap(), bounds);
+            Region::new(bounds, from, to).validate()?;
+            // Perturbation reads the center as RE,IM.
+            let center = complex_parts(center).map_or(center.clone(), |(re, im)| format!("{},{}", re, im));
+            Some(for_flag("--center", Perturbation::parse(&center, (to.re - from.re, from.im - to.im), bounds))?)
+        }
+        (true, _) => return Err(MandelError::Usage("--deep needs --center with --zoom or --width".to_string())),
+    };
+    // Likewise the corners are parsed again from their digits, and checked
+    // at the precision they will be rendered at.
+    let precision = match precision_bits {
+        None => None,
+        Some(bits) => {
+            let point = |text: &Option<String>, flag| {
+                for_flag(flag, parse_precise_complex(text.as_ref().unwrap(), bits))
+            };
+            let mut precision = if centered {
+                Precision::from_center(bits, &point(&center_text, "--center")?, view_width.unwrap(), bounds)
+            } else {
+                let mut upper_left = point(&upper_left_text, "--upper-left")?;
+                let mut lower_right = point(&lower_right_text, "--lower-right")?;
+                if swapped.re {
+                    std::mem::swap(&mut upper_left.re, &mut lower_right.re);
+                }
+                if swapped.im {
+                    std::mem::swap(&mut upper_left.im, &mut lower_right.im);
+                }
+                Precision::new(bits, upper_left, lower_right)
+            };
+            precision.validate(bounds)?;
+            match aspect {
+                AspectPolicy::Pad => precision = precision.with_square_pixels(bounds),
+                AspectPolicy::Crop => {
+                    return Err(MandelError::Usage("--aspect crop does not apply to --precision".to_string()));
+                }
+                AspectPolicy::Ignore | AspectPolicy::Warn => {}
+            }
+            if julia_text.is_some() {
+                precision.julia_c = Some(point(&julia_text, "--julia-c")?);
+            }
+            Some(precision)
+        }
+    };
+    if perturbation.is_none() && precision.is_none() {
+        region.validate()?;
+    }
+    if aspect != AspectPolicy::Ignore {
+        let (upper_left, lower_right) = fit_viewport(bounds, region.upper_left, region.lower_right, aspect);
+        region = Region::new(bounds, upper_left, lower_right);
+    }
+    if auto_iter_max.is_some() && !auto_iter {
+        return Err(MandelError::Usage("--auto-iter-max only applies to --auto-iter".to_string()));
+    }
+    let span = match (&perturbation, &precision) {
+        (Some(perturbation), _) => perturbation.span.0,
+        (_, Some(precision)) => precision.pixel_width(bounds) * bounds.0 as f64,
+        _ => region.lower_right.re - region.upper_left.re,
+    };
+    // An explicit --max-iter wins over --auto-iter.
+    let auto_iter = auto_iter && limit.is_none();
+    let limit = match limit {
+        Some(limit) => limit,
+        None if auto_iter => auto_limit(span, auto_iter_max.unwrap_or(AUTO_LIMIT_MAX)),
+        None => LIMIT_OF_ITERATION,
+    };
+    let fractal = for_flag("--fractal", parse_fractal(&fractal_name, julia_c, power))?;
+    let fractal = match (fractal, newton_epsilon) {
+        (Fractal::Newton(_), Some(epsilon)) => Fractal::Newton(epsilon),
+        (_, Some(_)) => return Err(MandelError::Usage("--newton-epsilon only applies to --fractal newton".to_string())),
+        (fractal, None) => fractal,
+    };
+    if coloring == Coloring::Distance && !matches!(fractal, Fractal::Mandelbrot | Fractal::Julia(_)) {
+        return Err(MandelError::Usage("--coloring distance only applies to mandelbrot and julia, at power 2"
+                                      .to_string()));
+    }
+    if perturbation.is_some() && fractal != Fractal::Mandelbrot {
+        return Err(MandelError::Usage("--deep renders the Mandelbrot set; --fractal and --power do not apply"
+                                      .to_string()));
+    }
+    if precision.is_some() && !matches!(fractal, Fractal::Mandelbrot | Fractal::Julia(_)) {
+        return Err(MandelError::Usage("--precision renders the Mandelbrot or Julia set; --power and --fractal \
+                                       burning-ship, tricorn and newton do not apply".to_string()));
+    }
+    // The distance estimate, the blend of the stripe average, and the
+    // slopes of the smooth count that --light-angle shades, are as
+    // sensitive to a small bailout as the smooth count itself.
+    let large_bailout = smooth || matches!(coloring, Coloring::Distance | Coloring::Stripe) || light_angle.is_some();
+    let bailout = bailout.unwrap_or(if large_bailout {
+        SMOOTH_BAILOUT
+    } else {
+        LIMIT_TO_CALL_IT_OFF_TO_INFINITY
+    });
+    // With --format dzi, --tile-size is the size of the Deep Zoom tiles.
+    let deep_zoom = dzi.then(|| DeepZoom {
+        tile_size: tile_size.take().unwrap_or(DZI_TILE_SIZE),
+        overlap: overlap.unwrap_or(DZI_OVERLAP),
+    });
+    let strategy = match (strategy, tile_size) {
+        (Strategy::Tiles(_), Some(size)) => Strategy::Tiles(size),
+        (_, Some(_)) => return Err(MandelError::Usage("--tile-size only applies to --strategy tiles".to_string())),
+        (strategy, None) => strategy,
+    };
+    let stream = stream || strip_rows.is_some();
+    if stream && (format != ImageFormat::Png || !strategy.renders_bands()) {
+        return Err(MandelError::Usage("--stream writes PNG output rendered with --strategy sequential or bands; \
+                                       the other strategies do not finish the rows in order".to_string()));
+    }
+    let given = [
+        ("EXR output", color == ColorMode::Values),
+        ("--palette", palette),
+        ("--depth 16", depth == 16),
+        ("--gamma", gamma != 1.0),
+        ("--log-scale", log_scale),
+        ("--invert", invert),
+        ("--display-gamma", display_gamma != 1.0),
+        ("--smooth", smooth),
+        ("--aa", antialias > 1),
+        ("--coloring histogram", coloring == Coloring::Histogram),
+        ("--coloring distance", coloring == Coloring::Distance),
+        ("--coloring trap", coloring == Coloring::Trap),
+        ("--coloring binary", coloring == Coloring::Binary),
+        ("--coloring binary-shaded", coloring == Coloring::BinaryShaded),
+        ("--coloring stripe", coloring == Coloring::Stripe),
+        ("--light-angle", light_angle.is_some()),
+        ("--fractal newton", matches!(fractal, Fractal::Newton(_))),
+        ("--dump", dump.is_some()),
+        ("--output-raw", raw.is_some()),
+        ("--deep", perturbation.is_some()),
+        ("--precision", precision.is_some()),
+        ("--estimate", estimate),
+        ("--format dzi", deep_zoom.is_some()),
+        ("--output -", filename == STDOUT),
+        ("--stream", stream),
+        ("--grid", grid.is_some()),
+        ("--roi", roi.is_some()),
+        ("--strategy", strategy != Strategy::Sequential),
+        ("--progressive", progressive),
+        ("--stats", stats),
+        ("--checkpoint", checkpoint.is_some() || resume.is_some()),
+    ];
+    check_conflicts(&given.into_iter().filter_map(|(name, given)| given.then_some(name)).collect::<Vec<_>>())?;
+    if let Some(grid) = grid {
+        for_flag("--grid", grid.tiles(bounds))?;
+    }
+    let roi = roi.map(|rect| for_flag("--roi", roi_tile(rect, bounds))).transpose()?;
+    let (checkpoint, resume) = match (checkpoint, resume) {
+        (Some(_), Some(_)) => return Err(MandelError::Usage("--resume carries on saving to the checkpoint it \
+                                                             reads; leave out --checkpoint".to_string())),
+        (checkpoint, None) => (checkpoint, false),
+        (None, resume) => (resume, true),
+    };
+    if checkpoint.is_some() && !strategy.renders_bands() {
+        return Err(MandelError::Usage("--checkpoint and --resume render band by band; they need --strategy \
+                                       sequential or bands".to_string()));
+    }
+    let held = roi.map_or(bounds, |roi| roi.size);
+    let held_bytes = held.0.checked_mul(held.1).and_then(|pixels| pixels.checked_mul(color.bytes_per_pixel()));
+    if !stream && grid.is_none() && !estimate && held_bytes.is_none() {
+        return Err(MandelError::Dimensions(format!("{}x{} pixels are too many to hold in memory; write them with \
+                                                    --stream", bounds.0, bounds.1)));
+    }
+
+    Ok(Options {
+        filename,
+        format,
+        params: RenderParams {
+            fractal, color, coloring, gamma, log_scale, invert, limit, bailout, smooth, antialias, interior_check,
+            mirror,
+            periodicity_epsilon,
+            trap: trap.unwrap_or_default(),
+            distance_scale: distance_scale.unwrap_or(DISTANCE_FALLOFF),
+            stripe_density: stripe_density.unwrap_or(STRIPE_DENSITY),
+            light_angle,
+            display_gamma,
+            perturbation,
+            precision,
+            ..RenderParams::new(bounds, region.upper_left, region.lower_right)
+        },
+        strategy,
+        threads,
+        verbosity,
+        quiet,
+        dump,
+        raw,
+        checkpoint,
+        resume,
+        stream,
+        strip_rows: strip_rows.unwrap_or(STREAM_BAND_ROWS),
+        grid,
+        roi,
+        estimate,
+        progressive,
+        deep_zoom,
+        palette_seed,
+        auto_iter,
+        swapped,
+        aspect,
+        stats,
+    })
+}
+
+/// Flags that `recolor` takes from `parse_flags`; the others describe how
+/// the counts are computed, which the dump has already settled.
+const RECOLOR_FLAGS: [&str; 15] = ["--output", "--format", "--quality", "--color", "--palette", "--palette-file",
+                                   "--random-palette", "--interior-color", "--coloring", "--gamma", "--log-scale",
+                                   "--invert", "--display-gamma", "--gamma-correct", "--depth"];
+
+/// Parse the arguments after `recolor`: the dump, then output and coloring
+/// flags. A size and corners are made up for `parse_flags` to accept, and
+/// replaced with the dump's once it has been read.
+fn parse_recolor(args: &[String]) -> Result<Recolor, MandelError>
+{
+    let input = match args.first() {
+        Some(input) if !input.starts_with("--") => input.clone(),
+        _ => return Err(MandelError::Usage("recolor needs the dump to read first".to_string())),
+    };
+    let mut rest = Vec::new();
+    for arg in &args[1..] {
+        let flag = arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag);
+        if flag.starts_with("--") && !RECOLOR_FLAGS.contains(&flag) {
+            return Err(MandelError::Usage(format!("{} does not apply to recolor", flag)));
+        }
+        rest.push(arg.clone());
+    }
+    rest.extend(["--size", "1x1", "--upper-left", "-1,1", "--lower-right", "1,-1"].map(String::from));
+    let options = parse_flags(&rest)?;
+    if options.params.coloring.needs_orbits() {
+        return Err(MandelError::Usage("--coloring distance, trap, binary, binary-shaded and stripe need the orbits, \
+                                       which a dump does not keep".to_string()));
+    }
+    Ok(Recolor { input, options })
+}
+
+/// Parse the arguments after `animate`. The animation's own flags are taken
+/// out and the rest handed to `parse_flags`, with the first frame as
+/// `--output` and, unless its corners are given, a view of `--zoom` centered
+/// on the target.
+fn parse_animate(args: &[String]) -> Result<Animation, MandelError>
+{
+    let mut target = None;
+    let mut frames = None;
+    let mut zoom_per_frame = None;
+    let mut out_dir = None;
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone().or_else(|| args.next().cloned())
+                .ok_or_else(|| MandelError::Usage(format!("{} needs a value", flag)))
+        };
+
+        match flag {
+            "--center" => {
+                let value = value()?;
+                target = Some((for_flag(flag, parse_corner(&value, "center").map_err(|_| {
+                    MandelError::Parse("expected the center as RE,IM".to_string())
+                }))?, value));
+            }
+            "--frames" => frames = Some(for_flag(flag, parse_positive(&value()?, "number of frames"))?),
+            "--zoom-per-frame" => zoom_per_frame = Some(for_flag(flag, parse_zoom(&value()?))?),
+            "--out-dir" => out_dir = Some(PathBuf::from(value()?)),
+            "--output" | "--format" | "--dump" | "--output-raw" | "--stream" | "--strip-rows" | "--tile-rows"
+            | "--grid" | "--estimate" | "--progressive" | "--preserve-aspect" | "--strategy" | "--parallel" | "--mode"
+            | "--tile-size" | "--deep" | "--precision" | "--auto-iter" | "--auto-iter-max" | "--roi" | "--stats"
+            | "--checkpoint" | "--resume" => {
+                return Err(MandelError::Usage(format!("{} does not apply to animate", flag)));
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let (target, center) = required(target, "--center")?;
+    let frames = required(frames, "--frames")?;
+    let zoom_per_frame = required(zoom_per_frame, "--zoom-per-frame")?;
+    let out_dir = required(out_dir, "--out-dir")?;
+
+    let has = |name: &str| rest.iter().any(|arg| arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag) == name);
+    let (corners, zoom) = (has("--upper-left") || has("--lower-right"), has("--zoom") || has("--width"));
+    if corners && zoom {
+        return Err(MandelError::Usage("animate takes either --zoom or --width, or --upper-left and --lower-right"
+            .to_string()));
+    }
+    if !corners {
+        rest.extend(["--preserve-aspect".to_string(), "--center".to_string(), center]);
+        if !zoom {
+            rest.extend(["--zoom".to_string(), "1".to_string()]);
+        }
+    }
+    let zoom = Zoom { target, frames, zoom_per_frame };
+    rest.extend(["--output".to_string(), zoom.frame_filename(&out_dir, 0).to_string_lossy().into_owned()]);
+    let mut options = parse_flags(&rest)?;
+    options.strategy = Strategy::Rayon;
+
+    Ok(Animation { options, out_dir, zoom })
+}
+
+/// Parse the arguments after `bench`: `--runs` is taken out and the rest
+/// handed to `parse_flags`, with standard output as a placeholder
+/// `--output`. Without a parallel `--strategy`, `Bands` is timed.
+fn parse_bench(args: &[String]) -> Result<Bench, MandelError>
+{
+    let mut runs = 1;
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        match flag {
+            "--runs" => {
+                let value = inline.or_else(|| args.next().cloned())
+                    .ok_or_else(|| MandelError::Usage(format!("{} needs a value", flag)))?;
+                runs = for_flag(flag, parse_positive(&value, "number of runs"))?;
+            }
+            "--output" | "--format" | "--quality" | "--dump" | "--output-raw" | "--stream" | "--strip-rows"
+            | "--tile-rows" | "--grid" | "--roi" | "--estimate" | "--progressive" | "--stats" | "--checkpoint"
+            | "--resume" => {
+                return Err(MandelError::Usage(format!("{} does not apply to bench", flag)));
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let explicit = rest.iter().any(|arg| {
+        ["--strategy", "--mode", "--parallel"].contains(&arg.split_once('=').map_or(arg.as_str(), |(flag, _)| flag))
+    });
+    rest.extend(["--output".to_string(), STDOUT.to_string()]);
+    let mut options = parse_flags(&rest)?;
+    if options.strategy == Strategy::Sequential {
+        if explicit {
+            return Err(MandelError::Usage("bench always times sequential rendering; \
+                                           give it a parallel --strategy".to_string()));
+        }
+        options.strategy = Strategy::Bands;
+    }
+    Ok(Bench { options, runs })
+}
+
+/// Parse the arguments after `batch`: the file of jobs, `--threads` and
+/// `--quiet`.
+fn parse_batch(args: &[String]) -> Result<Batch, MandelError>
+{
+    let mut input = None;
+    let mut threads = default_threads();
+    let mut quiet = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        match flag {
+            "--threads" => {
+                let value = inline.or_else(|| args.next().cloned())
+                    .ok_or_else(|| MandelError::Usage(format!("{} needs a value", flag)))?;
+                threads = for_flag(flag, parse_positive(&value, "thread count"))?;
+            }
+            "--quiet" => quiet = true,
+            _ if flag.starts_with("--") => return Err(MandelError::Usage(format!("unknown flag '{}'", arg))),
+            _ if input.is_none() => input = Some(arg.clone()),
+            _ => return Err(MandelError::Usage("batch takes the name of one file".to_string())),
+        }
+    }
+    let input = input.ok_or_else(|| MandelError::Usage("batch needs the name of a file of jobs".to_string()))?;
+    Ok(Batch { input, threads, quiet })
+}
+
+/// Parse one `line` of a batch file into the job it describes, rendered on
+/// `threads` threads if it is parallel, or `None` if it is blank or a
+/// comment.
+pub fn parse_job(line: &str, threads: usize) -> Option<Result<Options, MandelError>>
+{
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let words: Vec<String> = std::iter::once("batch").chain(line.split_whitespace()).map(String::from).collect();
+    Some(parse_positional(&words).and_then(|options| {
+        if options.filename == STDOUT {
+            return Err(MandelError::Usage("a batch job cannot write to standard output".to_string()));
+        }
+        Ok(Options { threads, quiet: true, ..options })
+    }))
+}
+
+/// Parse the arguments after `explore`, the flags of the first view.
+fn parse_explore(args: &[String]) -> Result<Options, MandelError>
+{
+    let options = parse_flags(args)?;
+    if options.estimate || options.filename == STDOUT || options.params.perturbation.is_some()
+        || options.params.precision.is_some() || options.auto_iter || options.roi.is_some()
+        || options.checkpoint.is_some() {
+        return Err(MandelError::Usage("explore reads standard input and renders each view to a file; --estimate, \
+                                       --deep, --precision, --auto-iter, --roi, --checkpoint, --resume and \
+                                       --output - do not apply".to_string()));
+    }
+    Ok(options)
+}
+
+/// Parse a line read by `explore`: `X Y ZOOM` for a `Click` on pixel X,Y of
+/// an image of size `bounds`, or `None` for `q` or `quit`.
+pub fn parse_click(line: &str, bounds: (usize, usize)) -> Result<Option<Click>, MandelError>
+{
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        ["q" | "quit"] => Ok(None),
+        [x, y, zoom] => {
+            let coordinate = |word: &str, length: usize| match usize::from_str(word) {
+                Ok(value) if value < length => Ok(value),
+                _ => Err(MandelError::Parse(format!("expected a pixel inside the {}x{} image, got '{}'", bounds.0,
+                                                    bounds.1, word))),
+            };
+            Ok(Some(Click { pixel: (coordinate(x, bounds.0)?, coordinate(y, bounds.1)?), zoom: parse_zoom(zoom)? }))
+        }
+        _ => Err(MandelError::Parse(format!("expected X Y ZOOM or q, got '{}'", line.trim()))),
+    }
+}
+
+/// Parse the arguments after `tiles`. The pyramid's own flags are taken out
+/// and the rest handed to `parse_flags`, with a square placeholder `--size`
+/// so that `--preserve-aspect` fits a square view, and a placeholder
+/// `--output`.
+fn parse_tiles(args: &[String]) -> Result<MapTiles, MandelError>
+{
+    let mut max_zoom = None;
+    let mut out_dir = None;
+    let mut iterations_per_level = 0;
+    let mut skip_empty = false;
+    let mut rest = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) => (flag, Some(value.to_string())),
+            None => (arg.as_str(), None),
+        };
+        let mut value = || {
+            inline.clone().or_else(|| args.next().cloned())
+                .ok_or_else(|| MandelError::Usage(format!("{} needs a value", flag)))
+        };
+
+        match flag {
+            "--max-zoom" => max_zoom = Some(for_flag(flag, parse_max_zoom(&value()?))?),
+            "--out-dir" => out_dir = Some(PathBuf::from(value()?)),
+            "--iterations-per-level" => iterations_per_level = for_flag(flag, parse_iterations_per_level(&value()?))?,
+            "--skip-empty" => skip_empty = true,
+            "--output" | "--format" | "--quality" | "--size" | "--dump" | "--output-raw" | "--stream"
+            | "--strip-rows" | "--tile-rows" | "--grid" | "--estimate" | "--progressive" | "--strategy" | "--parallel"
+            | "--mode" | "--tile-size" | "--light-angle" | "--deep" | "--precision" | "--auto-iter"
+            | "--auto-iter-max" | "--roi" | "--stats" | "--checkpoint" | "--resume" => {
+                return Err(MandelError::Usage(format!("{} does not apply to tiles", flag)));
+            }
+            _ => rest.push(arg.clone()),
+        }
+    }
+
+    let max_zoom = required(max_zoom, "--max-zoom")?;
+    let out_dir = required(out_dir, "--out-dir")?;
+    let side = MAP_TILE_SIZE.to_string();
+    rest.extend(["--size".to_string(), format!("{}x{}", side, side), "--output".to_string(), "tile.png".to_string()]);
+    let options = parse_flags(&rest)?;
+    if options.params.coloring == Coloring::Histogram {
+        return Err(MandelError::Usage("--coloring histogram would equalize each tile differently; it does not apply \
+                                       to tiles".to_string()));
+    }
+    let pyramid = Pyramid { max_zoom, tile_size: MAP_TILE_SIZE, iterations_per_level, skip_empty };
+    Ok(MapTiles { options, out_dir, pyramid })
+}
+
+/// Parse the whole command line, `args[0]` being the program name. Anything
+/// that does not start with a flag is handled as the old positional form.
+pub fn parse_args(args: &[String]) -> Result<Command, MandelError>
+{
+    let rest = args.get(1..).unwrap_or_default();
+    if rest.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return Ok(Command::Help);
+    }
+
+    match rest.first() {
+        Some(first) if first == "animate" => parse_animate(&rest[1..]).map(Command::Animate),
+        Some(first) if first == "recolor" => parse_recolor(&rest[1..]).map(Command::Recolor),
+        Some(first) if first == "bench" => parse_bench(&rest[1..]).map(Command::Bench),
+        Some(first) if first == "batch" => parse_batch(&rest[1..]).map(Command::Batch),
+        Some(first) if first == "tiles" => parse_tiles(&rest[1..]).map(Command::Tiles),
+        Some(first) if first == "explore" => parse_explore(&rest[1..]).map(Command::Explore),
+        Some(first) if ["--read-metadata", "--describe"].contains(&first.split('=').next().unwrap()) => {
+            let (flag, inline) = match first.split_once('=') {
+                Some((flag, file)) => (flag, Some(file)),
+                None => (first.as_str(), None),
+            };
+            let filenames: Vec<&str> = inline.into_iter().chain(rest[1..].iter().map(String::as_str)).collect();
+            match filenames[..] {
+                [filename] if flag == "--describe" => Ok(Command::Describe(filename.to_string())),
+                [filename] => Ok(Command::ReadMetadata(filename.to_string())),
+                _ => Err(MandelError::Usage(format!("{} takes the name of one PNG file", flag))),
+            }
+        }
+        Some(first) if first == "--legacy" => {
+            let positional: Vec<String> = args[..1].iter().chain(&rest[1..]).cloned().collect();
+            parse_positional(&positional).map(Command::Render)
+        }
+        // A lone - names standard output as the first positional argument.
+        Some(first) if first == STDOUT || !first.starts_with('-') => parse_positional(args).map(Command::Render),
+        _ => parse_flags(rest).map(Command::Render),
+    }
+}
+
+
+#[cfg(test)]
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+#[cfg(test)]
+fn parse_options(list: &[&str]) -> Result<Options, MandelError> {
+    match parse_args(&args(list))? {
+        Command::Render(options) => Ok(options),
+        Command::Help => panic!("unexpected --help"),
+        Command::Animate(_) => panic!("unexpected animate"),
+        Command::ReadMetadata(_) => panic!("unexpected --read-metadata"),
+        Command::Describe(_) => panic!("unexpected --describe"),
+        Command::Recolor(_) => panic!("unexpected recolor"),
+        Command::Bench(_) => panic!("unexpected bench"),
+        Command::Tiles(_) => panic!("unexpected tiles"),
+        Command::Batch(_) => panic!("unexpected batch"),
+        Command::Explore(_) => panic!("unexpected explore"),
+    }
+}
+
+/// A small render over the square from -1+i to 1-i, which most tests add their flags to.
+#[cfg(test)]
+const BASE_ARGS: [&str; 9] =
+    ["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+
+#[cfg(test)]
+fn parse_with(extra: &[&str]) -> Result<Options, MandelError> {
+    parse_options(&[&BASE_ARGS[..], extra].concat())
+}
+
+#[cfg(test)]
+fn parse_animation(list: &[&str]) -> Result<Animation, MandelError> {
+    match parse_args(&args(list))? {
+        Command::Animate(animation) => Ok(animation),
+        _ => panic!("expected animate"),
+    }
+}
+
+#[test]
+fn test_parse_bool() {
+    assert_eq!(parse_bool("0"), Ok(false));
+    assert_eq!(parse_bool("1"), Ok(true));
+    assert!(parse_bool("parallel").unwrap_err().contains("'parallel'"));
+}
+
+#[test]
+fn test_interior_color() {
+    // The palette may come before or after the color.
+    for extra in [&["--palette", "fire", "--interior-color", "255,255,255"][..],
+                  &["--interior-color", "255, 255, 255", "--palette", "fire"]] {
+        let options = parse_with(extra).unwrap();
+        let fire = Palette::named("fire").unwrap().with_interior([255, 255, 255]);
+        assert_eq!(options.params.color, ColorMode::Rgb(fire));
+    }
+    let options = parse_with(&["--color", "color", "--interior-color", "10,20,30"]).unwrap();
+    assert_eq!(options.params.color, ColorMode::Rgb(Palette::default().with_interior([10, 20, 30])));
+    for bad in ["255,255", "0,0,256", "white"] {
+        assert!(matches!(parse_with(&["--palette", "fire", "--interior-color", bad]),
+                         Err(MandelError::Parse(_))), "{}", bad);
+    }
+    assert!(matches!(parse_with(&["--interior-color", "255,255,255"]),
+                     Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_color_mode() {
+    assert_eq!(parse_color_mode("gray"), Ok(ColorMode::Gray));
+    assert_eq!(parse_color_mode("color"), Ok(ColorMode::Rgb(Palette::default())));
+    assert!(parse_color_mode("sepia").unwrap_err().contains("'sepia'"));
+}
+
+#[test]
+fn test_random_palette() {
+    let random = |extra: &[&str]| parse_with(extra);
+    for extra in [&["--random-palette", "7"][..], &["--random-palette=7"], &["--random-palette", "7", "--smooth"]] {
+        let options = random(extra).unwrap();
+        assert_eq!((options.params.color, options.palette_seed), (ColorMode::Rgb(Palette::random(7)), None),
+                   "{:?}", extra);
+    }
+    // Without a number after it the flag picks a seed, and says which.
+    let options = random(&["--random-palette", "--smooth"]).unwrap();
+    assert!(options.params.smooth);
+    assert_eq!(options.params.color, ColorMode::Rgb(Palette::random(options.palette_seed.unwrap())));
+    assert!(random(&["--random-palette"]).unwrap().palette_seed.is_some());
+    assert_eq!(random(&["--palette", "fire"]).unwrap().palette_seed, None);
+
+    let painted = random(&["--random-palette", "7", "--interior-color", "9,9,9"]).unwrap();
+    assert_eq!(painted.params.color, ColorMode::Rgb(Palette::random(7).with_interior([9, 9, 9])));
+    assert!(matches!(random(&["--random-palette=-1"]), Err(MandelError::Parse(_))));
+    assert!(matches!(random(&["--random-palette=seven"]), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_parse_palette() {
+    assert_eq!(parse_palette("fire").unwrap(), Palette::named("fire").unwrap());
+    assert!(matches!(parse_palette("sepia"), Err(MandelError::Parse(_))));
+
+    let options = parse_with(&["--palette", "ocean"]).unwrap();
+    assert_eq!(options.params.color, ColorMode::Rgb(Palette::named("ocean").unwrap()));
+
+    let map = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/short.map");
+    let options = parse_with(&["--palette-file", map]).unwrap();
+    assert_eq!(options.params.color, ColorMode::Rgb(Palette::from_file(Path::new(map)).unwrap()));
+    let broken = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/broken.map");
+    match parse_with(&["--palette-file", broken]) {
+        Err(MandelError::Parse(message)) => {
+            assert!(message.starts_with("--palette-file: ") && message.contains(":3: "));
+        }
+        _ => panic!("expected a parse error"),
+    }
+
+    let gradient = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/sunset.gradient");
+    let options = parse_with(&["--palette", gradient]).unwrap();
+    assert_eq!(options.params.color, ColorMode::Rgb(Palette::from_file(Path::new(gradient)).unwrap()));
+    match parse_with(&["--palette", broken]) {
+        Err(MandelError::Parse(message)) => assert!(message.starts_with("--palette: ") && message.contains(":3: ")),
+        _ => panic!("expected a parse error"),
+    }
+    // Only a file that is there is read; anything else is a misspelt name.
+    assert!(parse_palette("./missing.txt").unwrap_err().to_string().contains("or a palette file"));
+    assert!(parse_palette("sepia").unwrap_err().to_string().contains("or a palette file"));
+    let bare = std::env::temp_dir().join(format!("mandel-palette-{}", std::process::id()));
+    std::fs::copy(map, &bare).unwrap();
+    let read = parse_palette(bare.to_str().unwrap());
+    std::fs::remove_file(&bare).unwrap();
+    assert_eq!(read.unwrap(), Palette::from_file(Path::new(map)).unwrap());
+}
+
+#[test]
+fn test_parse_pair() {
+    assert_eq!(parse_pair::<u64>("     ", ','), None);
+    assert_eq!(parse_pair::<u64>("45*50", '*'), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>(" 45 , 50 ", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>("  45,50", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>("45,50\t", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>("45 ,50", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>("45, 50", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>("4 5,50", ','), None);
+    assert_eq!(parse_pair::<u64>(" , ", ','), None);
+    assert_eq!(parse_complex("-0.5 , 0.25"), Some(Complex { re: -0.5, im: 0.25 }));
+    assert_eq!(parse_complex(" -1.2 , 0.35 "), Some(Complex { re: -1.2, im: 0.35 }));
+    assert_eq!(parse_bounds(" 800 x 600 ").unwrap(), (800, 600));
+    assert_eq!(parse_bounds("1000 x 750").unwrap(), (1000, 750));
+}
+
+#[test]
+fn test_parse_pair_in_quotes() {
+    assert_eq!(parse_pair::<u64>("\"45,50\"", ','), Some((45, 50)));
+    assert_eq!(parse_pair::<u64>(" ' 45 , 50 ' ", ','), Some((45, 50)));
+    assert_eq!(parse_bounds("\"1000 x 750\"").unwrap(), (1000, 750));
+    assert_eq!(parse_complex("'-1.2,0.35'"), Some(Complex { re: -1.2, im: 0.35 }));
+    assert_eq!(parse_complex("\"-0.75+0.1i\""), Some(Complex { re: -0.75, im: 0.1 }));
+    for bad in ["\"45,50'", "\"45,50", "45,50\"", "\"\"", "\"", "''", "\"\"45,50\"\"", "\"45\",50"] {
+        assert_eq!(parse_pair::<u64>(bad, ','), None, "{:?}", bad);
+    }
+    assert!(parse_precise_complex("'1e-30,-2e-31'", 128).is_ok());
+    assert_eq!(unquote(" \"x\" "), "x");
+    assert_eq!(unquote("'x\""), "'x\"");
+}
+
+#[test]
+fn test_parse_complex() {
+    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex {re: 1.25, im: -0.0625}));
+    assert_eq!(parse_complex(",-0.0625"), None);
+    assert_eq!(parse_complex(" 1.25 , -0.0625 "), Some(Complex {re: 1.25, im: -0.0625}));
+    assert_eq!(parse_complex("\t1.25,\t-0.0625\n"), Some(Complex {re: 1.25, im: -0.0625}));
+    assert_eq!(parse_complex("+1.25,+0.0625"), Some(Complex {re: 1.25, im: 0.0625}));
+    assert_eq!(parse_complex(" +1.25 , -0.0625"), Some(Complex {re: 1.25, im: -0.0625}));
+    for bad in [" , -0.0625", "1.25,", "+,1", "++1.25,0", "+-1.25,0", "+ 1.25,0", "1.25,-0.0625,1"] {
+        assert_eq!(parse_complex(bad), None, "{:?}", bad);
+    }
+}
+
+#[test]
+fn test_parse_complex_with_i() {
+    let complex = |re, im| Some(Complex { re, im });
+    for (s, expected) in [("-0.75+0.1i", complex(-0.75, 0.1)), ("-0.75-0.1i", complex(-0.75, -0.1)),
+                          ("+0.75+0.1i", complex(0.75, 0.1)), (" 2-3i ", complex(2.0, -3.0)),
+                          ("1.25", complex(1.25, 0.0)), ("-2", complex(-2.0, 0.0)), ("0.3i", complex(0.0, 0.3)),
+                          ("-0.3i", complex(0.0, -0.3)), ("1e-3+2e-4i", complex(1e-3, 2e-4)),
+                          ("-1E-3-2E-4i", complex(-1e-3, -2e-4)), ("1e+3-2e+4i", complex(1e3, -2e4)),
+                          ("-2e-3i", complex(0.0, -2e-3)), ("1e5i", complex(0.0, 1e5)),
+                          ("-1e-3", complex(-1e-3, 0.0))] {
+        assert_eq!(parse_complex(s), expected, "{:?}", s);
+    }
+    for bad in ["", "i", "-i", "1+i", "1+2", "1+2ii", "1++2i", "1+-2i", "--1+2i", "1e-i", "1 + 2i", "0.3j", "i0.3",
+                "1+2i+3i", "0.75x"] {
+        assert_eq!(parse_complex(bad), None, "{:?}", bad);
+    }
+    assert_eq!(complex_parts("-0.75+0.1i"), Some(("-0.75", "+0.1")));
+    assert!(parse_precise_complex("1e-30-2e-31i", 128).is_ok());
+}
+
+#[test]
+fn test_parse_limit() {
+    assert_eq!(parse_limit("1000").unwrap(), 1000);
+    assert!(matches!(parse_limit("0"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_limit("-5"), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_parse_strategy() {
+    assert_eq!(parse_strategy("sequential").unwrap(), Strategy::Sequential);
+    assert_eq!(parse_strategy("bands").unwrap(), Strategy::Bands);
+    assert_eq!(parse_strategy("rayon").unwrap(), Strategy::Rayon);
+    assert_eq!(parse_strategy("dynamic").unwrap(), Strategy::Dynamic);
+    assert_eq!(parse_strategy("tiles").unwrap(), Strategy::Tiles(DEFAULT_TILE_SIZE));
+
+    let options = parse_with(&["--strategy", "tiles", "--tile-size", "64"]).unwrap();
+    assert_eq!(options.strategy, Strategy::Tiles(64));
+    assert!(matches!(parse_with(&["--tile-size", "64"]), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_with(&["--strategy", "tiles", "--tile-size", "0"]), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_strategy("cuda"), Err(MandelError::Parse(_))));
+    for strategy in [Strategy::Sequential, Strategy::Bands, Strategy::Rayon, Strategy::Dynamic,
+                     Strategy::Tiles(DEFAULT_TILE_SIZE)] {
+        assert_eq!(parse_strategy(strategy.name()).unwrap(), strategy);
+    }
+}
+
+#[test]
+fn test_parse_batch() {
+    match parse_args(&args(&["mandel", "batch", "jobs.txt", "--threads=3"])) {
+        Ok(Command::Batch(batch)) => {
+            assert_eq!((batch.input.as_str(), batch.threads, batch.quiet), ("jobs.txt", 3, false));
+        }
+        _ => panic!("expected batch"),
+    }
+    for list in [&["mandel", "batch"][..], &["mandel", "batch", "a", "b"], &["mandel", "batch", "a", "--size", "4x4"]] {
+        assert!(matches!(parse_args(&args(list)), Err(MandelError::Usage(_))), "{:?}", list);
+    }
+
+    let job = parse_job("  out.png 40x30 -2,1 1,-1 1 color 500 ", 6).unwrap().unwrap();
+    assert_eq!(job.filename, "out.png");
+    assert_eq!((job.params.region.bounds, job.params.limit), ((40, 30), 500));
+    assert_eq!((job.strategy, job.threads), (Strategy::Rayon, 6));
+    assert!(job.quiet);
+    assert!(parse_job("", 1).is_none());
+    assert!(parse_job("# out.png 40x30 -2,1 1,-1 1", 1).is_none());
+    assert!(matches!(parse_job("out.png 40x30 -2,1 1,-1", 1), Some(Err(MandelError::Usage(_)))));
+    assert!(matches!(parse_job("out.png 40x30 1,1 1,-1 0", 1), Some(Err(MandelError::Dimensions(_)))));
+    let job = parse_job("out.png 40x30 1,-1 -2,1 0", 1).unwrap().unwrap();
+    assert_eq!(job.params.region.upper_left, Complex { re: -2.0, im: 1.0 });
+    assert_eq!(job.swapped, CornerSwap { re: true, im: true });
+    assert!(matches!(parse_job("- 40x30 -2,1 1,-1 0", 1), Some(Err(MandelError::Usage(_)))));
+}
+
+#[test]
+fn test_parse_tiles() {
+    let base = ["mandel", "tiles", "--upper-left", "-2,1.5", "--lower-right", "1,-1.5", "--max-zoom", "3", "--out-dir",
+                "map"];
+    let tiles = |extra: &[&str]| match parse_args(&args(&[&base[..], extra].concat())) {
+        Ok(Command::Tiles(tiles)) => Ok(tiles),
+        Ok(_) => panic!("expected tiles"),
+        Err(error) => Err(error),
+    };
+    let plain = tiles(&[]).unwrap();
+    assert_eq!(plain.out_dir, PathBuf::from("map"));
+    assert_eq!(plain.pyramid,
+               Pyramid { max_zoom: 3, tile_size: MAP_TILE_SIZE, iterations_per_level: 0, skip_empty: false });
+    assert_eq!(plain.options.params.region.upper_left, Complex { re: -2.0, im: 1.5 });
+    let tuned = tiles(&["--skip-empty", "--iterations-per-level=100", "--palette", "fire"]).unwrap();
+    assert!(tuned.pyramid.skip_empty);
+    assert_eq!(tuned.pyramid.iterations_per_level, 100);
+
+    let centered = ["mandel", "tiles", "--preserve-aspect", "--center", "-0.5,0", "--zoom", "2", "--max-zoom", "1",
+                    "--out-dir", "map"];
+    match parse_args(&args(&centered)) {
+        Ok(Command::Tiles(tiles)) => {
+            let region = tiles.options.params.region;
+            assert_eq!(region.lower_right.re - region.upper_left.re, region.upper_left.im - region.lower_right.im);
+        }
+        _ => panic!("expected tiles"),
+    }
+
+    for extra in [&["--size", "10x10"][..], &["--output", "x.png"], &["--parallel"], &["--coloring", "histogram"]] {
+        assert!(matches!(tiles(extra), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(tiles(&["--max-zoom", "31"]), Err(MandelError::Parse(_))));
+    let missing = ["mandel", "tiles", "--upper-left", "-2,1.5", "--lower-right", "1,-1.5", "--out-dir", "map"];
+    assert!(matches!(parse_args(&args(&missing)), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_bench() {
+    let base = ["mandel", "bench", "--size", "300x200", "--upper-left", "-2,1", "--lower-right", "1,-1"];
+    let bench = |extra: &[&str]| match parse_args(&args(&[&base[..], extra].concat())) {
+        Ok(Command::Bench(bench)) => Ok(bench),
+        Ok(_) => panic!("expected bench"),
+        Err(error) => Err(error),
+    };
+    let plain = bench(&[]).unwrap();
+    assert_eq!((plain.options.strategy, plain.runs), (Strategy::Bands, 1));
+    assert_eq!(plain.options.params.region.bounds, (300, 200));
+    let swept = bench(&["--threads", "3", "--runs=5", "--strategy", "dynamic"]).unwrap();
+    assert_eq!((swept.options.strategy, swept.options.threads, swept.runs), (Strategy::Dynamic, 3, 5));
+    assert_eq!(bench(&["--parallel"]).unwrap().options.strategy, Strategy::Rayon);
+
+    for extra in [&["--output", "x.png"][..], &["--dump", "x.iter"], &["--strategy", "sequential"], &["--runs"]] {
+        assert!(matches!(bench(extra), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(bench(&["--runs", "0"]), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_preserve_aspect() {
+    let base = ["mandel", "--output", "x.png", "--size", "400x300"];
+    let options = parse_options(&[&base[..], &["--preserve-aspect", "--center", "-0.5,0", "--zoom", "2"]].concat())
+        .unwrap();
+    assert_eq!(options.params.region.upper_left, Complex { re: -1.5, im: 0.75 });
+    assert_eq!(options.params.region.lower_right, Complex { re: 0.5, im: -0.75 });
+
+    assert!(matches!(parse_options(&[&base[..], &["--preserve-aspect", "--center", "0,0"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&[&base[..], &["--preserve-aspect", "--center", "0,0", "--zoom", "1",
+                                                  "--upper-left", "-1,1"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&[&base[..], &["--center", "0,0", "--zoom", "1",
+                                                  "--upper-left", "-1,1", "--lower-right", "1,-1"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_zoom("0"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_zoom("inf"), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_center_and_width() {
+    let base = ["mandel", "--output", "x.png", "--size", "400x300", "--center", "-0.5,0"];
+    let corners = |extra: &[&str]| {
+        let region = parse_options(&[&base[..], extra].concat()).unwrap().params.region;
+        (region.upper_left, region.lower_right)
+    };
+    let expected = (Complex { re: -1.5, im: 0.75 }, Complex { re: 0.5, im: -0.75 });
+    assert_eq!(corners(&["--zoom", "2"]), expected);
+    assert_eq!(corners(&["--width", "2"]), expected);
+    assert_eq!(corners(&["--width=2", "--preserve-aspect"]), expected);
+
+    for extra in [&[][..], &["--zoom", "2", "--width", "2"], &["--width", "2", "--upper-left", "-1,1"],
+                  &["--width", "2", "--upper-left", "-1,1", "--lower-right", "1,-1"]] {
+        assert!(matches!(parse_options(&[&base[..], extra].concat()), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    for width in ["0", "-1", "nan", "inf", "wide"] {
+        assert!(matches!(parse_width(width), Err(MandelError::Parse(_))), "{}", width);
+    }
+}
+
+#[test]
+fn test_deep() {
+    let base = ["mandel", "--output", "x.png", "--size", "400x300", "--preserve-aspect", "--zoom", "4e20"];
+    let center = "-0.743643887037158704752191506114774,0.131825904205311970493132056385139";
+    let options = parse_options(&[&base[..], &["--center", center, "--deep"]].concat()).unwrap();
+    let perturbation = options.params.perturbation.unwrap();
+    assert!(perturbation.span.0 == 1e-20 && (perturbation.span.1 - 0.75e-20).abs() < 1e-35, "{:?}", perturbation.span);
+    assert_eq!(perturbation, Perturbation::parse(center, perturbation.span, (400, 300)).unwrap());
+    let written = "-0.743643887037158704752191506114774+0.131825904205311970493132056385139i";
+    let options = parse_options(&[&base[..], &["--center", written, "--deep"]].concat()).unwrap();
+    assert_eq!(options.params.perturbation, Some(perturbation.clone()));
+    // Without --deep the corners round together.
+    assert!(matches!(parse_options(&[&base[..], &["--center", center]].concat()), Err(MandelError::Dimensions(_))));
+    let shallow = ["mandel", "--output", "x.png", "--size", "400x300", "--preserve-aspect", "--zoom", "2",
+                   "--center", "-0.5,0"];
+    assert_eq!(parse_options(&shallow).unwrap().params.perturbation, None);
+
+    for extra in [&["--center", "0,0x", "--deep"][..], &["--center", center, "--deep", "--fractal", "burning-ship"],
+                  &["--center", center, "--deep", "--coloring", "trap"],
+                  &["--center", center, "--deep", "--estimate"]] {
+        assert!(parse_options(&[&base[..], extra].concat()).is_err(), "{:?}", extra);
+    }
+    assert!(matches!(parse_with(&["--deep"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_precision() {
+    let number = |s: &str| BigFloat::parse(s, 128).unwrap();
+    let (left, right) = ("-0.7436438870371587047521915061", "-0.7436438870371587047521915060");
+    let (upper_left, lower_right) = (format!("{},0.1318259042053119", left), format!("{},0.1318259042053118", right));
+    let corners = ["mandel", "--output", "x.png", "--size", "40x30", "--upper-left", &upper_left, "--lower-right",
+                   &lower_right];
+    // The corners round to the same f64 but not to the same 128 bits.
+    assert!(matches!(parse_options(&corners), Err(MandelError::Dimensions(_))));
+    let precision = parse_options(&[&corners[..], &["--precision", "128"]].concat()).unwrap().params.precision.unwrap();
+    assert_eq!(precision.bits, 128);
+    assert_eq!((&precision.upper_left.re, &precision.lower_right.re), (&number(left), &number(right)));
+    assert_eq!(precision.julia_c, None);
+
+    let base = ["mandel", "--output", "x.png", "--size", "400x300", "--preserve-aspect", "--zoom", "4e20"];
+    let center = "0.25000000000000000000001,1e-30";
+    let options = parse_options(&[&base[..], &["--center", center, "--precision", "100"]].concat()).unwrap();
+    let point = Complex {
+        re: BigFloat::parse("0.25000000000000000000001", 100).unwrap(),
+        im: BigFloat::parse("1e-30", 100).unwrap(),
+    };
+    assert_eq!(options.params.precision, Some(Precision::from_center(100, &point, 1e-20, (400, 300))));
+    let julia = ["--fractal", "julia", "--julia-c", "-0.8,0.156", "--precision", "128"];
+    let julia_c = parse_with(&julia).unwrap().params.precision.unwrap().julia_c.unwrap();
+    assert_eq!((julia_c.re, julia_c.im), (number("-0.8"), number("0.156")));
+
+    for bits in ["1", "65537", "x"] {
+        assert!(matches!(parse_options(&[&corners[..], &["--precision", bits]].concat()), Err(MandelError::Parse(_))));
+    }
+    let bad = [&["--precision", "128", "--deep"][..], &["--precision", "128", "--fractal", "tricorn"],
+               &["--precision", "128", "--power", "3"], &["--precision", "128", "--coloring", "stripe"],
+               &["--precision", "128", "--estimate"]];
+    for extra in bad {
+        let args = [&base[..], &["--center", center], extra].concat();
+        assert!(matches!(parse_options(&args), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+}
+
+#[test]
+fn test_auto_iter() {
+    let base = ["mandel", "--output", "x.png", "--size", "400x300", "--preserve-aspect", "--center", "-0.75,0.1"];
+    let limit = |extra: &[&str]| parse_options(&[&base[..], extra].concat()).map(|options| {
+        (options.params.limit, options.auto_iter)
+    });
+    assert_eq!(limit(&["--zoom", "1e6"]).unwrap(), (LIMIT_OF_ITERATION, false));
+    assert_eq!(limit(&["--zoom", "1e6", "--auto-iter"]).unwrap(), (735, true));
+    assert_eq!(limit(&["--zoom", "1", "--auto-iter"]).unwrap(), (LIMIT_OF_ITERATION, true));
+    assert_eq!(limit(&["--zoom", "1e12", "--auto-iter"]).unwrap(), (2078, true));
+    assert_eq!(limit(&["--zoom", "1e12", "--auto-iter", "--auto-iter-max", "1000"]).unwrap(), (1000, true));
+    // However deep the view, --max-iter wins, before or after --auto-iter.
+    let explicit = [&["--zoom", "1e6", "--auto-iter", "--max-iter", "300"][..],
+                    &["--zoom", "1e12", "--max-iter=300", "--auto-iter"],
+                    &["--zoom", "1", "--iterations", "300", "--auto-iter"]];
+    for extra in explicit {
+        assert_eq!(limit(extra).unwrap(), (300, false), "{:?}", extra);
+    }
+    // The width comes from the corners when there are corners.
+    let corners = ["mandel", "--output", "x.png", "--size", "40x40", "--upper-left", "-0.5,0.5", "--lower-right",
+                   "-0.499996,0.499996", "--auto-iter"];
+    assert_eq!(parse_options(&corners).unwrap().params.limit, auto_limit(4e-6, AUTO_LIMIT_MAX));
+
+    assert!(matches!(limit(&["--zoom", "2", "--auto-iter-max", "1000"]), Err(MandelError::Usage(_))));
+    assert!(matches!(limit(&["--zoom", "2", "--auto-iter", "--auto-iter-max", "0"]), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_smooth_raises_default_bailout() {
+    assert_eq!(parse_with(&[]).unwrap().params.bailout, LIMIT_TO_CALL_IT_OFF_TO_INFINITY);
+    assert_eq!(parse_with(&["--smooth"]).unwrap().params.bailout, SMOOTH_BAILOUT);
+    assert_eq!(parse_with(&["--smooth", "--bailout", "16"]).unwrap().params.bailout, 16.0);
+}
+
+#[test]
+fn test_no_interior_check() {
+    assert!(parse_with(&[]).unwrap().params.interior_check);
+    assert!(!parse_with(&["--no-interior-check"]).unwrap().params.interior_check);
+}
+
+#[test]
+fn test_no_mirror() {
+    assert!(parse_with(&[]).unwrap().params.mirror);
+    assert!(!parse_with(&["--no-mirror"]).unwrap().params.mirror);
+}
+
+#[test]
+fn test_square_pixels() {
+    let base = ["mandel", "--output", "x.png", "--size", "200x100", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    let region = parse_options(&base).unwrap().params.region;
+    assert_eq!((region.upper_left, region.lower_right), (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }));
+    let square = parse_options(&[&base[..], &["--square-pixels"]].concat()).unwrap().params.region;
+    assert_eq!((square.upper_left, square.lower_right), (Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -1.0 }));
+}
+
+#[test]
+fn test_aspect() {
+    let base = ["mandel", "--output", "x.png", "--size", "200x100", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    let corners = |extra: &[&str]| {
+        let options = parse_options(&[&base[..], extra].concat()).unwrap();
+        (options.params.region.upper_left, options.params.region.lower_right, options.aspect)
+    };
+    let given = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(corners(&[]), (given.0, given.1, AspectPolicy::Ignore));
+    assert_eq!(corners(&["--aspect", "ignore"]), (given.0, given.1, AspectPolicy::Ignore));
+    assert_eq!(corners(&["--aspect=warn"]), (given.0, given.1, AspectPolicy::Warn));
+    assert_eq!(corners(&["--aspect", "pad"]),
+               (Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -1.0 }, AspectPolicy::Pad));
+    assert_eq!(corners(&["--aspect", "crop"]),
+               (Complex { re: -1.0, im: 0.5 }, Complex { re: 1.0, im: -0.5 }, AspectPolicy::Crop));
+    assert_eq!(corners(&["--square-pixels"]), corners(&["--aspect", "pad"]));
+
+    // A view already the shape of the image is left exactly as it was.
+    let matched = ["mandel", "--output", "x.png", "--size", "30x20", "--upper-left", "-2.1,1.3", "--lower-right",
+                   "0.9,-0.7"];
+    let plain = parse_options(&matched).unwrap().params;
+    assert_eq!(plain.region.pixel_aspect(), 1.0);
+    for policy in ["pad", "crop"] {
+        assert_eq!(parse_options(&[&matched[..], &["--aspect", policy]].concat()).unwrap().params.region, plain.region);
+    }
+
+    assert!(matches!(parse_options(&[&base[..], &["--aspect", "stretch"]].concat()), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_options(&[&base[..], &["--aspect", "pad", "--square-pixels"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&[&base[..], &["--aspect", "crop", "--precision", "128"]].concat()),
+                     Err(MandelError::Usage(_))));
+    let precise = parse_options(&[&base[..], &["--aspect", "pad", "--precision", "128"]].concat()).unwrap();
+    let precision = precise.params.precision.unwrap();
+    assert_eq!((precision.upper_left.re.to_f64(), precision.lower_right.re.to_f64()), (-2.0, 2.0));
+}
+
+#[test]
+fn test_quiet() {
+    assert!(!parse_with(&[]).unwrap().quiet);
+    assert!(parse_with(&["--quiet"]).unwrap().quiet);
+    assert!(!parse_options(&["mandel", "x.png", "10x10", "-1,1", "1,-1", "0"]).unwrap().quiet);
+}
+
+#[test]
+fn test_parse_recolor() {
+    let recolor = match parse_args(&args(&["mandel", "recolor", "view.iter", "--output", "out.png", "--palette", "fire",
+                                           "--coloring=histogram"])).unwrap() {
+        Command::Recolor(recolor) => recolor,
+        _ => panic!("expected recolor"),
+    };
+    assert_eq!((recolor.input.as_str(), recolor.options.filename.as_str()), ("view.iter", "out.png"));
+    assert_eq!(recolor.options.params.coloring, Coloring::Histogram);
+    assert!(matches!(recolor.options.params.color, ColorMode::Rgb(_)));
+
+    for list in [&["mandel", "recolor", "--output", "out.png"][..], &["mandel", "recolor", "view.iter"],
+                 &["mandel", "recolor", "view.iter", "--output", "out.png", "--size", "10x10"],
+                 &["mandel", "recolor", "view.iter", "--output", "out.png", "--smooth"],
+                 &["mandel", "recolor", "view.iter", "--output", "out.png", "--coloring", "distance"]] {
+        assert!(matches!(parse_args(&args(list)), Err(MandelError::Usage(_))), "{:?}", list);
+    }
+}
+
+#[test]
+fn test_read_metadata() {
+    for list in [&["mandel", "--read-metadata", "x.png"][..], &["mandel", "--read-metadata=x.png"]] {
+        assert!(matches!(parse_args(&args(list)), Ok(Command::ReadMetadata(filename)) if filename == "x.png"));
+    }
+    for list in [&["mandel", "--read-metadata"][..], &["mandel", "--read-metadata", "x.png", "y.png"],
+                 &["mandel", "--read-metadata=x.png", "y.png"], &["mandel", "--describe"]] {
+        assert!(matches!(parse_args(&args(list)), Err(MandelError::Usage(_))), "{:?}", list);
+    }
+    for list in [&["mandel", "--describe", "x.png"][..], &["mandel", "--describe=x.png"]] {
+        assert!(matches!(parse_args(&args(list)), Ok(Command::Describe(filename)) if filename == "x.png"));
+    }
+}
+
+#[test]
+fn test_describe_round_trip() {
+    use mandelbrot_set::metadata::{render_args, render_metadata};
+
+    let base = RenderParams::new((40, 30), Complex { re: -0.75, im: 0.125 }, Complex { re: -0.5, im: -0.0625 });
+    for params in [RenderParams { limit: 600, ..base.clone() },
+                   RenderParams { fractal: Fractal::Julia(Complex { re: -0.8, im: 0.156 }), ..base.clone() },
+                   RenderParams { fractal: Fractal::Multibrot(4), ..base.clone() },
+                   RenderParams { fractal: Fractal::Newton(1e-9), ..base.clone() }] {
+        let flags = render_args(&render_metadata(&params)).unwrap();
+        let list: Vec<&str> =
+            ["mandel", "--output", "x.png"].into_iter().chain(flags.iter().map(String::as_str)).collect();
+        let parsed = parse_options(&list).unwrap().params;
+        assert_eq!((parsed.region, parsed.limit, parsed.fractal), (params.region, params.limit, params.fractal));
+    }
+}
+
+#[test]
+fn test_stream() {
+    assert!(!parse_with(&[]).unwrap().stream);
+    assert!(parse_with(&["--stream"]).unwrap().stream);
+    assert!(parse_with(&["--stream", "--strategy", "bands", "--smooth"]).unwrap().stream);
+    for extra in [&["--strategy", "rayon"][..], &["--parallel"], &["--strategy", "dynamic"], &["--format", "bmp"],
+                  &["--dump", "x.iter"], &["--coloring", "histogram"]] {
+        assert!(matches!(parse_with(&[&["--stream"][..], extra].concat()), Err(MandelError::Usage(_))),
+                "{:?}", extra);
+    }
+
+    assert_eq!(parse_with(&[]).unwrap().strip_rows, STREAM_BAND_ROWS);
+    let strips = parse_with(&["--strip-rows", "3"]).unwrap();
+    assert!(strips.stream);
+    assert_eq!(strips.strip_rows, 3);
+    assert_eq!(parse_with(&["--tile-rows=5"]).unwrap().strip_rows, 5);
+    assert!(matches!(parse_with(&["--strip-rows", "0"]), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_with(&["--strip-rows", "3", "--parallel"]),
+                     Err(MandelError::Usage(_))));
+
+    // Too big for one buffer, but fine a strip at a time.
+    let huge = format!("{}x{}", usize::MAX / 8, 4);
+    let poster = ["mandel", "--output", "x.png", "--size", &huge, "--upper-left", "-1,1", "--lower-right", "1,-1",
+                  "--palette", "fire"];
+    assert!(matches!(parse_options(&poster), Err(MandelError::Dimensions(_))));
+    assert!(parse_options(&[&poster[..], &["--stream"]].concat()).is_ok());
+}
+
+#[test]
+fn test_verbosity() {
+    let verbosity = |flags: &[&str]| parse_with(flags).unwrap().verbosity;
+    assert_eq!(verbosity(&[]), Verbosity::Normal);
+    assert_eq!(verbosity(&["-v"]), Verbosity::Info);
+    assert_eq!(verbosity(&["--verbose"]), Verbosity::Info);
+    for flags in [&["-vv"][..], &["-v", "--verbose"], &["-v", "-v", "-v"], &["-vv", "-v"]] {
+        assert_eq!(verbosity(flags), Verbosity::Debug, "{:?}", flags);
+    }
+    // Before the other flags, -v is still a flag and not the first of the
+    // positional arguments.
+    let first = parse_options(&[&["mandel", "-v"][..], &BASE_ARGS[1..]].concat()).unwrap();
+    assert_eq!(first.verbosity, Verbosity::Info);
+    assert!(matches!(parse_with(&["-vvv"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_estimate() {
+    let view = ["--size", "10x10", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    let written = parse_options(&[&["mandel", "--output", "x.png"][..], &view].concat()).unwrap();
+    assert!(!written.estimate);
+    let estimated = parse_options(&[&["mandel", "--estimate"][..], &view].concat()).unwrap();
+    assert!(estimated.estimate);
+    assert!(matches!(parse_options(&[&["mandel", "--verbose"][..], &view].concat()), Err(MandelError::Usage(_))));
+
+    // Too big to render in one buffer, but not to estimate.
+    let huge = format!("{}x{}", usize::MAX / 8, 4);
+    let poster = ["mandel", "--estimate", "--size", &huge, "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    assert!(parse_options(&poster).unwrap().estimate);
+}
+
+#[test]
+fn test_progressive() {
+    assert!(!parse_with(&[]).unwrap().progressive);
+    assert!(parse_with(&["--progressive"]).unwrap().progressive);
+    assert!(parse_with(&["--progressive", "--dump", "x.iter"]).is_ok());
+    for extra in [&["--stream"][..], &["--grid", "2x2"], &["--estimate"], &["--output", "-"]] {
+        assert!(matches!(parse_with(&[&["--progressive"][..], extra].concat()), Err(MandelError::Usage(_))),
+                "{:?}", extra);
+    }
+}
+
+#[test]
+fn test_deep_zoom() {
+    let base = ["mandel", "--size", "30x20", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    let options = parse_options(&[&base[..], &["--output", "zoom.dzi"]].concat()).unwrap();
+    assert_eq!((options.deep_zoom, options.format), (Some(DeepZoom::default()), ImageFormat::Png));
+    assert_eq!(parse_options(&[&base[..], &["--output", "x.png"]].concat()).unwrap().deep_zoom, None);
+
+    let tuned = parse_options(&[&base[..], &["--output", "zoom", "--format", "dzi", "--tile-size", "128",
+                                            "--overlap=0", "--tile-format", "jpeg", "--quality", "70"]].concat())
+        .unwrap();
+    assert_eq!(tuned.deep_zoom, Some(DeepZoom { tile_size: 128, overlap: 0 }));
+    assert_eq!(tuned.format, ImageFormat::Jpeg(70));
+    // The strategy keeps its own tile size.
+    let tiled = parse_options(&[&base[..], &["--output", "zoom.dzi", "--strategy", "tiles", "--tile-size",
+                                            "64"]].concat()).unwrap();
+    assert_eq!((tiled.strategy, tiled.deep_zoom.unwrap().tile_size), (Strategy::Tiles(DEFAULT_TILE_SIZE), 64));
+
+    for extra in [&["--output", "x.png", "--overlap", "2"][..], &["--output", "x.png", "--tile-format", "png"],
+                  &["--output", "x.dzi", "--stream"], &["--output", "x.dzi", "--grid", "2x2"],
+                  &["--output", "-", "--format", "dzi"], &["--output", "x.dzi", "--depth", "16"]] {
+        assert!(matches!(parse_options(&[&base[..], extra].concat()), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(parse_options(&[&base[..], &["--output", "x.dzi", "--tile-format", "bmp"]].concat()),
+                     Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_roi() {
+    let base = ["mandel", "--output", "x.png", "--size", "40x30", "--upper-left", "-2,1", "--lower-right", "1,-1"];
+    assert_eq!(parse_options(&base).unwrap().roi, None);
+    let options = parse_options(&[&base[..], &["--roi", "10, 5,30,25"]].concat()).unwrap();
+    assert_eq!(options.roi, Some(Tile { origin: (10, 5), size: (20, 20) }));
+    assert_eq!(options.params.region.bounds, (40, 30));
+    for roi in ["1,2,3", "1,2,3,4,5", "a,0,1,1", "-1,0,1,1", ""] {
+        assert!(matches!(parse_roi(roi), Err(MandelError::Parse(_))), "{}", roi);
+    }
+    for roi in ["0,0,41,30", "0,0,40,31", "10,5,10,25", "30,5,10,25"] {
+        match parse_options(&[&base[..], &["--roi", roi]].concat()) {
+            Err(MandelError::Dimensions(message)) => assert!(message.starts_with("--roi: "), "{}", message),
+            _ => panic!("{}", roi),
+        }
+    }
+    for extra in [&["--grid", "2x2"][..], &["--stream"], &["--dump", "x.iter"], &["--coloring", "histogram"],
+                  &["--light-angle", "45"], &["--strategy", "rayon"], &["--progressive"], &["--estimate"]] {
+        let args = [&base[..], &["--roi", "0,0,4,4"], extra].concat();
+        assert!(matches!(parse_options(&args), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+}
+
+#[test]
+fn test_grid() {
+    let base = ["mandel", "--output", "out.png", "--size", "30x20", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    assert_eq!(parse_options(&base).unwrap().grid, None);
+    let options = parse_options(&[&base[..], &["--grid", "4x3", "--threads", "2"]].concat()).unwrap();
+    assert_eq!(options.grid, Some(Grid { columns: 4, rows: 3 }));
+    for grid in ["4", "0x3", "4x-1", "x"] {
+        assert!(matches!(parse_grid(grid), Err(MandelError::Parse(_))), "{}", grid);
+    }
+    for extra in [&["--stream"][..], &["--dump", "x.iter"], &["--coloring", "histogram"], &["--parallel"],
+                  &["--output", "-"]] {
+        assert!(matches!(parse_options(&[&base[..], &["--grid", "4x3"], extra].concat()), Err(MandelError::Usage(_))),
+                "{:?}", extra);
+    }
+    match parse_options(&[&base[..], &["--grid", "16x1"]].concat()) {
+        Err(MandelError::Dimensions(message)) => assert!(message.starts_with("--grid: "), "{}", message),
+        _ => panic!("expected a dimensions error"),
+    }
+}
+
+#[test]
+fn test_dump() {
+    assert_eq!(parse_with(&[]).unwrap().dump, None);
+    assert_eq!(parse_with(&["--dump", "x.iter"]).unwrap().dump.as_deref(), Some("x.iter"));
+    for extra in [&["--smooth"][..], &["--aa", "2"]] {
+        assert!(matches!(parse_with(&[&["--dump", "x.iter"][..], extra].concat()),
+                         Err(MandelError::Usage(_))));
+    }
+}
+
+#[test]
+fn test_stats() {
+    assert!(!parse_with(&[]).unwrap().stats);
+    assert!(parse_with(&["--stats"]).unwrap().stats);
+    assert!(parse_with(&["--stats", "--coloring", "histogram", "--dump", "x.iter"]).is_ok());
+    for extra in [&["--smooth"][..], &["--aa", "2"], &["--coloring", "trap"], &["--fractal", "newton"], &["--stream"],
+                  &["--grid", "2x2"], &["--roi", "0,0,5,5"], &["--estimate"]] {
+        assert!(matches!(parse_with(&[&["--stats"][..], extra].concat()), Err(MandelError::Usage(_))),
+                "{:?}", extra);
+    }
+    for name in ["animate", "tiles", "bench"] {
+        let args: Vec<String> = ["mandel", name, "--stats"].iter().map(|arg| arg.to_string()).collect();
+        let error = parse_args(&args);
+        assert!(matches!(error, Err(MandelError::Usage(message)) if message.contains("--stats")), "{}", name);
+    }
+}
+
+#[test]
+fn test_checkpoint() {
+    let checkpoint = |extra: &[&str]| {
+        let options = parse_with(extra).unwrap();
+        (options.checkpoint, options.resume)
+    };
+    assert_eq!(checkpoint(&[]), (None, false));
+    assert_eq!(checkpoint(&["--checkpoint", "x.ckpt"]), (Some("x.ckpt".to_string()), false));
+    assert_eq!(checkpoint(&["--resume=x.ckpt", "--strategy", "bands"]), (Some("x.ckpt".to_string()), true));
+    for extra in [&["--resume", "x.ckpt"][..], &["--stream"], &["--grid", "2x2"], &["--roi", "0,0,5,5"],
+                  &["--estimate"], &["--progressive"], &["--dump", "x.iter"], &["--stats"], &["--light-angle", "45"],
+                  &["--strategy", "rayon"], &["--parallel"]] {
+        assert!(matches!(parse_with(&[&["--checkpoint", "x.ckpt"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    for name in ["animate", "tiles", "bench"] {
+        let args: Vec<String> = ["mandel", name, "--resume", "x.ckpt"].iter().map(|arg| arg.to_string()).collect();
+        assert!(matches!(parse_args(&args), Err(MandelError::Usage(_))), "{}", name);
+    }
+}
+
+#[test]
+fn test_output_raw() {
+    assert_eq!(parse_with(&[]).unwrap().raw, None);
+    let raw = |filename: &str| {
+        parse_with(&["--output-raw", filename]).map(|options| options.raw)
+    };
+    assert_eq!(raw("x.csv").unwrap(), Some(("x.csv".to_string(), RawFormat::Csv)));
+    assert_eq!(raw("x.bin").unwrap(), Some(("x.bin".to_string(), RawFormat::Binary)));
+    assert!(matches!(raw("x.png"), Err(MandelError::Parse(_))));
+    for extra in [&["--smooth"][..], &["--aa", "2"], &["--stream"], &["--grid", "2x2"], &["--fractal", "newton"]] {
+        assert!(matches!(parse_with(&[&["--output-raw", "x.csv"][..], extra].concat()),
+                         Err(MandelError::Usage(_))));
+    }
+    assert!(parse_with(&["--output-raw", "x.csv", "--dump", "x.iter"]).is_ok());
+}
+
+#[test]
+fn test_parse_epsilon() {
+    assert_eq!(parse_epsilon("1e-9").unwrap(), 1e-9);
+    assert_eq!(parse_epsilon("0").unwrap(), 0.0);
+    assert!(matches!(parse_epsilon("-1e-9"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_epsilon("NaN"), Err(MandelError::Parse(_))));
+
+    assert_eq!(parse_with(&[]).unwrap().params.periodicity_epsilon, PERIODICITY_EPSILON);
+    assert_eq!(parse_with(&["--no-periodicity"]).unwrap().params.periodicity_epsilon, 0.0);
+    let options = parse_with(&["--no-periodicity", "--periodicity-epsilon", "1e-9"]).unwrap();
+    assert_eq!(options.params.periodicity_epsilon, 1e-9);
+}
+
+#[test]
+fn test_parse_positive() {
+    assert_eq!(parse_positive("64", "thread count").unwrap(), 64);
+    assert!(matches!(parse_positive("0", "thread count"), Err(MandelError::Parse(_))));
+    assert_eq!(parse_positive("many", "thread count").unwrap_err().to_string(),
+               "expected a positive thread count, got 'many'");
+    match parse_with(&["--threads", "0"]) {
+        Err(MandelError::Parse(message)) => assert_eq!(message, "--threads: expected a positive thread count, got '0'"),
+        _ => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn test_parse_coloring() {
+    assert_eq!(parse_coloring("linear").unwrap(), Coloring::Linear);
+    assert_eq!(parse_coloring("histogram").unwrap(), Coloring::Histogram);
+    assert_eq!(parse_coloring("orbit-trap").unwrap(), Coloring::Trap);
+    assert_eq!(parse_coloring("binary").unwrap(), Coloring::Binary);
+    assert_eq!(parse_coloring("binary-shaded").unwrap(), Coloring::BinaryShaded);
+    assert_eq!(parse_coloring("stripe-average").unwrap(), Coloring::Stripe);
+    assert!(matches!(parse_coloring("log"), Err(MandelError::Parse(_))));
+
+    let options = parse_with(&["--coloring", "histogram"]).unwrap();
+    assert_eq!(options.params.coloring, Coloring::Histogram);
+    assert!(matches!(parse_with(&["--coloring", "histogram", "--smooth"]),
+                     Err(MandelError::Usage(_))));
+
+    let options = parse_with(&["--coloring", "distance"]).unwrap();
+    assert_eq!((options.params.coloring, options.params.bailout), (Coloring::Distance, SMOOTH_BAILOUT));
+    let julia = ["--coloring", "distance", "--fractal", "julia", "--julia-c", "0,1"];
+    assert!(parse_with(&julia).is_ok());
+    for extra in [&["--smooth"][..], &["--fractal", "tricorn"], &["--dump", "x.iter"]] {
+        assert!(matches!(parse_with(&[&["--coloring", "distance"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+
+    let options = parse_with(&["--coloring", "trap"]).unwrap();
+    assert_eq!((options.params.coloring, options.params.trap), (Coloring::Trap, Trap::default()));
+    let options = parse_with(&["--coloring", "trap", "--trap", "circle:0.5,0:0.25"]).unwrap();
+    assert_eq!(options.params.trap, Trap::Circle(Complex { re: 0.5, im: 0.0 }, 0.25));
+    for extra in [&["--smooth"][..], &["--dump", "x.iter"]] {
+        assert!(matches!(parse_with(&[&["--coloring", "trap"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(parse_with(&["--trap", "point:0,0"]), Err(MandelError::Usage(_))));
+
+    let options = parse_with(&["--coloring", "binary", "--fractal", "burning-ship"]).unwrap();
+    assert_eq!(options.params.coloring, Coloring::Binary);
+    for extra in [&["--smooth"][..], &["--dump", "x.iter"], &["--fractal", "newton"], &["--output", "x.exr"]] {
+        assert!(matches!(parse_with(&[&["--coloring", "binary-shaded"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+
+    let options = parse_with(&["--coloring", "distance"]).unwrap();
+    assert_eq!(options.params.distance_scale, DISTANCE_FALLOFF);
+    let options = parse_with(&["--coloring", "distance", "--distance-scale", "1.5"]).unwrap();
+    assert_eq!(options.params.distance_scale, 1.5);
+    assert!(matches!(parse_with(&["--coloring", "distance", "--distance-scale", "0"]),
+                     Err(MandelError::Parse(_))));
+    assert!(matches!(parse_with(&["--distance-scale", "2"]), Err(MandelError::Usage(_))));
+
+    let options = parse_with(&["--coloring", "stripe"]).unwrap();
+    assert_eq!((options.params.coloring, options.params.bailout), (Coloring::Stripe, SMOOTH_BAILOUT));
+    assert_eq!(options.params.stripe_density, STRIPE_DENSITY);
+    let options = parse_with(&["--coloring", "stripe", "--stripe-density", "7.5"]).unwrap();
+    assert_eq!(options.params.stripe_density, 7.5);
+    for bad in ["0", "-2", "inf", "many"] {
+        assert!(matches!(parse_with(&["--coloring", "stripe", "--stripe-density", bad]),
+                         Err(MandelError::Parse(_))), "{}", bad);
+    }
+    for extra in [&["--smooth"][..], &["--dump", "x.iter"], &["--fractal", "newton"]] {
+        assert!(matches!(parse_with(&[&["--coloring", "stripe"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(parse_with(&["--stripe-density", "2"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_light_angle() {
+    assert_eq!(parse_with(&[]).unwrap().params.light_angle, None);
+    let options = parse_with(&["--light-angle", "45"]).unwrap();
+    assert_eq!((options.params.light_angle, options.params.bailout), (Some(45.0), SMOOTH_BAILOUT));
+    assert!(parse_with(&["--light-angle", "-30", "--coloring", "distance"]).is_ok());
+    assert!(matches!(parse_with(&["--light-angle", "up"]), Err(MandelError::Parse(_))));
+    for extra in [&["--stream"][..], &["--grid", "2x2"], &["--output", "x.exr"], &["--fractal", "newton"]] {
+        assert!(matches!(parse_with(&[&["--light-angle", "45"][..], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+}
+
+#[test]
+fn test_parse_trap() {
+    assert_eq!(parse_trap("point:-1,0.5").unwrap(), Trap::Point(Complex { re: -1.0, im: 0.5 }));
+    assert_eq!(parse_trap("line:0,0:1,1").unwrap(),
+               Trap::Line(Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: 1.0 }));
+    assert_eq!(parse_trap("circle:0,0:2").unwrap(), Trap::Circle(Complex { re: 0.0, im: 0.0 }, 2.0));
+    assert_eq!(parse_trap("cross:0.5,-1").unwrap(), Trap::Cross(Complex { re: 0.5, im: -1.0 }));
+    assert_eq!(parse_trap("point:1").unwrap(), Trap::Point(Complex { re: 1.0, im: 0.0 }));
+    for bad in ["point", "point:1+", "square:0,0", "line:0,0", "line:1,1:1,1", "circle:0,0:0", "circle:0,0:-1",
+                "circle:0,0", "point:0,0:1"] {
+        assert!(matches!(parse_trap(bad), Err(MandelError::Parse(_))), "{}", bad);
+    }
+}
+
+#[test]
+fn test_parse_gamma() {
+    assert_eq!(parse_gamma("2.2").unwrap(), 2.2);
+    assert!(matches!(parse_gamma("0"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_gamma("-1"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_gamma("inf"), Err(MandelError::Parse(_))));
+
+    let options = parse_with(&["--gamma", "0.5", "--log-scale"]).unwrap();
+    assert_eq!((options.params.gamma, options.params.log_scale), (0.5, true));
+}
+
+#[test]
+fn test_display_gamma() {
+    assert_eq!(parse_with(&[]).unwrap().params.display_gamma, 1.0);
+    let options = parse_with(&["--display-gamma", "1.8", "--palette", "fire"]).unwrap();
+    assert_eq!((options.params.display_gamma, options.params.gamma), (1.8, 1.0));
+    assert_eq!(parse_with(&["--gamma-correct"]).unwrap().params.display_gamma, DISPLAY_GAMMA);
+    assert!(matches!(parse_with(&["--display-gamma", "0"]), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_with(&["--gamma-correct", "--format", "exr"]),
+                     Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_depth() {
+    assert_eq!(parse_with(&[]).unwrap().params.color, ColorMode::Gray);
+    assert_eq!(parse_with(&["--depth", "16"]).unwrap().params.color, ColorMode::Gray16);
+    assert!(matches!(parse_with(&["--depth", "16", "--palette", "fire"]),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&["mandel", "--output", "x.jpg", "--size", "10x10", "--upper-left", "-1,1",
+                                     "--lower-right", "1,-1", "--depth", "16"]),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_depth("12"), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_output_format() {
+    let base = ["mandel", "--size", "10x10", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    assert_eq!(parse_options(&[&base[..], &["--output", "x.ppm"]].concat()).unwrap().format, ImageFormat::Ppm);
+    assert_eq!(parse_options(&[&base[..], &["--output", "x.jpg", "--quality", "60"]].concat()).unwrap().format,
+               ImageFormat::Jpeg(60));
+    assert!(matches!(parse_options(&[&base[..], &["--output", "x.png", "--quality", "60"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&[&base[..], &["--output", "x.tga"]].concat()), Err(MandelError::Parse(_))));
+    assert_eq!(parse_options(&[&base[..], &["--output", "x.pgm", "--depth", "16"]].concat()).unwrap().format,
+               ImageFormat::Pgm);
+    assert_eq!(parse_options(&[&base[..], &["--output", "-"]].concat()).unwrap().format, ImageFormat::Pgm);
+    assert_eq!(parse_options(&[&base[..], &["--output", "-", "--palette", "fire"]].concat()).unwrap().format,
+               ImageFormat::Ppm);
+    assert!(matches!(parse_quality("0"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_quality("101"), Err(MandelError::Parse(_))));
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn test_exr_format() {
+    let base = ["mandel", "--size", "10x10", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    let options = parse_options(&[&base[..], &["--output", "x.exr", "--smooth"]].concat()).unwrap();
+    assert_eq!((options.format, options.params.color), (ImageFormat::Exr, ColorMode::Values));
+    let options = parse_options(&[&base[..], &["--output", "x.raw", "--format", "exr"]].concat()).unwrap();
+    assert_eq!((options.format, options.params.color), (ImageFormat::Exr, ColorMode::Values));
+    assert_eq!(parse_options(&[&base[..], &["--output", "x.png", "--format", "TIFF"]].concat()).unwrap().format,
+               ImageFormat::Tiff);
+    assert!(matches!(parse_options(&[&base[..], &["--output", "x.png", "--format", "gif"]].concat()),
+                     Err(MandelError::Parse(_))));
+
+    for extra in [&["--palette", "fire"][..], &["--depth", "16"], &["--gamma", "2"], &["--log-scale"], &["--invert"],
+                  &["--coloring", "histogram"], &["--aa", "2"], &["--quality", "80"]] {
+        assert!(matches!(parse_options(&[&base[..], &["--output", "x.exr"], extra].concat()),
+                         Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+}
+
+#[cfg(not(feature = "exr"))]
+#[test]
+fn test_exr_needs_feature() {
+    let base = ["mandel", "--size", "10x10", "--upper-left", "-1,1", "--lower-right", "1,-1"];
+    for extra in [&["--output", "x.exr"][..], &["--output", "x.raw", "--format", "exr"]] {
+        assert!(matches!(parse_options(&[&base[..], extra].concat()), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+}
+
+#[test]
+fn test_parse_bailout() {
+    assert_eq!(parse_bailout("4").unwrap(), 4.0);
+    assert_eq!(parse_bailout("1e6").unwrap(), 1e6);
+    assert!(matches!(parse_bailout("3.9"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_bailout("far"), Err(MandelError::Parse(_))));
+
+    assert_eq!(parse_escape_radius("2").unwrap(), 4.0);
+    assert_eq!(parse_escape_radius("256").unwrap(), 65536.0);
+    assert!(matches!(parse_escape_radius("1.5"), Err(MandelError::Parse(_))));
+    assert!(matches!(parse_escape_radius("-3"), Err(MandelError::Parse(_))));
+
+    assert_eq!(parse_with(&["--escape-radius", "10"]).unwrap().params.bailout, 100.0);
+}
+
+#[test]
+fn test_parse_fractal() {
+    let c = Complex { re: -0.8, im: 0.156 };
+    assert_eq!(parse_fractal("mandelbrot", None, None).unwrap(), Fractal::Mandelbrot);
+    assert_eq!(parse_fractal("julia", Some(c), None).unwrap(), Fractal::Julia(c));
+    assert_eq!(parse_fractal("burning-ship", None, None).unwrap(), Fractal::BurningShip);
+    assert_eq!(parse_fractal("tricorn", None, None).unwrap(), Fractal::Tricorn);
+    assert!(matches!(parse_fractal("tricorn", Some(c), None), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_fractal("julia", None, None), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_fractal("mandelbrot", Some(c), None), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_fractal("fatou", None, None), Err(MandelError::Parse(_))));
+    assert_eq!(parse_fractal("mandelbrot", None, Some(2)).unwrap(), Fractal::Mandelbrot);
+    assert_eq!(parse_fractal("mandelbrot", None, Some(5)).unwrap(), Fractal::Multibrot(5));
+    assert!(matches!(parse_fractal("julia", Some(c), Some(3)), Err(MandelError::Usage(_))));
+    assert_eq!(parse_fractal("newton", None, None).unwrap(), Fractal::Newton(NEWTON_EPSILON));
+    assert!(matches!(parse_fractal("newton", Some(c), None), Err(MandelError::Usage(_))));
+
+    assert_eq!(parse_power("8").unwrap(), 8);
+    for bad in ["1", "17", "2.5", "cubed"] {
+        assert!(matches!(parse_power(bad), Err(MandelError::Parse(_))), "{}", bad);
+    }
+}
+
+#[test]
+fn test_newton() {
+    let base = [&BASE_ARGS[..], &["--fractal", "newton"]].concat();
+    assert_eq!(parse_options(&base).unwrap().params.fractal, Fractal::Newton(NEWTON_EPSILON));
+    let fine = parse_options(&[&base[..], &["--newton-epsilon", "1e-10"]].concat()).unwrap();
+    assert_eq!(fine.params.fractal, Fractal::Newton(1e-10));
+    for bad in ["0", "-1", "close"] {
+        assert!(matches!(parse_options(&[&base[..], &["--newton-epsilon", bad]].concat()), Err(MandelError::Parse(_))));
+    }
+    for extra in [&["--smooth"][..], &["--dump", "x.iter"], &["--coloring", "histogram"], &["--coloring", "distance"]] {
+        assert!(matches!(parse_options(&[&base[..], extra].concat()), Err(MandelError::Usage(_))), "{:?}", extra);
+    }
+    assert!(matches!(parse_with(&["--newton-epsilon", "1e-3"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_click() {
+    assert_eq!(parse_click("120 40 2\n", (200, 100)).unwrap(), Some(Click { pixel: (120, 40), zoom: 2.0 }));
+    assert_eq!(parse_click("  0 99   0.5 ", (200, 100)).unwrap(), Some(Click { pixel: (0, 99), zoom: 0.5 }));
+    assert_eq!(parse_click("q\n", (200, 100)).unwrap(), None);
+    assert_eq!(parse_click("quit", (200, 100)).unwrap(), None);
+    for bad in ["", "120 40", "200 40 2", "120 100 2", "-1 40 2", "120 40 0", "120 40 2 9", "zoom in"] {
+        assert!(matches!(parse_click(bad, (200, 100)), Err(MandelError::Parse(_))), "{:?}", bad);
+    }
+
+    let explore = |extra: &[&str]| {
+        let view = ["mandel", "explore", "--size", "20x10", "--upper-left", "-2,1", "--lower-right", "2,-1"];
+        let list = [&view[..], extra].concat();
+        parse_args(&args(&list))
+    };
+    assert!(matches!(explore(&["--output", "view.png"]),
+                     Ok(Command::Explore(options)) if options.filename == "view.png"));
+    assert!(matches!(explore(&["--output", "-"]), Err(MandelError::Usage(_))));
+    assert!(matches!(explore(&["--output", "view.png", "--estimate"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_args_errors() {
+    assert!(matches!(parse_options(&["mandel", "out.png"]), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "wide", "-1,1", "1,-1", "0"]),
+                     Err(MandelError::Parse(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "0x10", "-1,1", "1,-1", "0"]),
+                     Err(MandelError::Dimensions(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "10x10", "-1;1", "1,-1", "0"]),
+                     Err(MandelError::Parse(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "10x10", "-1,1", "1,-1", "yes"]),
+                     Err(MandelError::Parse(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "10x10", "-1,1", "1,-1", "1", "sepia"]),
+                     Err(MandelError::Parse(_))));
+    assert!(matches!(parse_options(&["mandel", "out.png", "10x10", "-1,1", "1,-1", "1", "gray", "0"]),
+                     Err(MandelError::Parse(_))));
+    assert!(parse_options(&["mandel", "out.png", "10x10", "-1,1", "1,-1", "1", "color"]).is_ok());
+}
+
+#[test]
+fn test_parse_flags() {
+    let options = parse_options(&["mandel", "--size", "1000x750", "--upper-left", "-1.20,0.35",
+                                  "--lower-right=-1,0.20", "--output", "mandel.png", "--parallel"]).unwrap();
+    assert_eq!(options.filename, "mandel.png");
+    assert_eq!(options.params.region.bounds, (1000, 750));
+    assert_eq!(options.params.region.upper_left, Complex { re: -1.20, im: 0.35 });
+    assert_eq!(options.params.region.lower_right, Complex { re: -1.0, im: 0.20 });
+    assert_eq!(options.strategy, Strategy::Rayon);
+
+    assert!(matches!(parse_args(&args(&["mandel", "--output", "x.png", "--help"])), Ok(Command::Help)));
+}
+
+#[test]
+fn test_parse_flag_aliases() {
+    let options = parse_options(&["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", "-1,1",
+                                  "--lower-right", "1,-1", "--mode", "par", "--threads", "3", "--iterations", "1000"])
+        .unwrap();
+    assert_eq!(options.strategy, Strategy::Rayon);
+    assert_eq!(options.threads, 3);
+    assert_eq!(options.params.limit, 1000);
+    assert!(matches!(parse_mode("parallel"), Err(MandelError::Parse(_))));
+
+    let options = parse_options(&["mandel", "--legacy", "x.png", "10x10", "-1,1", "1,-1", "0"]).unwrap();
+    assert_eq!(options.filename, "x.png");
+    assert_eq!(options.strategy, Strategy::Sequential);
+}
+
+#[test]
+fn test_swapped_or_equal_corners() {
+    let flags = |upper_left: &str, lower_right: &str| {
+        parse_options(&["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", upper_left,
+                        "--lower-right", lower_right])
+    };
+    let strict = |upper_left: &str, lower_right: &str| {
+        parse_options(&["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", upper_left,
+                        "--lower-right", lower_right, "--strict"])
+    };
+    let expected = (Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for (upper_left, lower_right, re, im) in [("-2,1", "1,-1", false, false), ("1,1", "-2,-1", true, false),
+                                              ("-2,-1", "1,1", false, true), ("1,-1", "-2,1", true, true)] {
+        let swapped = CornerSwap { re, im };
+        for options in [flags(upper_left, lower_right).unwrap(),
+                        parse_options(&["mandel", "x.png", "10x10", upper_left, lower_right, "0"]).unwrap()] {
+            let region = options.params.region;
+            assert_eq!((region.upper_left, region.lower_right), expected, "{} {}", upper_left, lower_right);
+            assert_eq!(options.swapped, swapped, "{} {}", upper_left, lower_right);
+        }
+        assert_eq!(strict(upper_left, lower_right).is_ok(), !swapped.any(), "{} {}", upper_left, lower_right);
+        if swapped.any() {
+            assert!(matches!(strict(upper_left, lower_right), Err(MandelError::Dimensions(_))));
+        }
+    }
+    for (upper_left, lower_right) in [("0.5,0.5", "0.5,0.5"), ("0.5,1", "0.5,-1"), ("-1,0.5", "1,0.5")] {
+        assert!(matches!(flags(upper_left, lower_right), Err(MandelError::Dimensions(_))), "{} {}", upper_left,
+                lower_right);
+        assert!(matches!(parse_options(&["mandel", "x.png", "10x10", upper_left, lower_right, "0"]),
+                         Err(MandelError::Dimensions(_))), "{} {}", upper_left, lower_right);
+    }
+    // At higher precision the digits of the corners are swapped too.
+    let options = parse_options(&["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", "1,-1",
+                                  "--lower-right", "-2,1", "--precision", "128"]).unwrap();
+    let precision = options.params.precision.unwrap();
+    assert_eq!((precision.upper_left.re.to_f64(), precision.upper_left.im.to_f64()), (-2.0, 1.0));
+    assert_eq!((precision.lower_right.re.to_f64(), precision.lower_right.im.to_f64()), (1.0, -1.0));
+}
+
+#[test]
+fn test_parse_flags_errors() {
+    assert!(parse_with(&[]).is_ok());
+
+    match parse_options(&["mandel", "--output", "x.png", "--size", "10x10", "--upper-left", "-1,1", "--lower-right",
+                          "1;-1"]) {
+        Err(MandelError::Parse(message)) => assert!(message.starts_with("--lower-right:")),
+        _ => panic!("expected a parse error"),
+    }
+    match parse_options(&BASE_ARGS[..5]) {
+        Err(MandelError::Usage(message)) => assert!(message.contains("--upper-left")),
+        _ => panic!("expected a usage error"),
+    }
+    assert!(matches!(parse_with(&["--bogus"]), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_with(&["--max-iter"]), Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_invert() {
+    assert!(!parse_with(&[]).unwrap().params.invert);
+    assert!(parse_with(&["--invert"]).unwrap().params.invert);
+    assert!(matches!(parse_with(&["--invert", "--palette", "fire"]),
+                     Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_antialias() {
+    assert!(matches!(parse_with(&["--aa", "0"]), Err(MandelError::Parse(_))));
+
+    assert_eq!(parse_with(&[]).unwrap().params.antialias, 1);
+    assert_eq!(parse_with(&["--aa", "4"]).unwrap().params.antialias, 4);
+    assert!(matches!(parse_with(&["--aa", "2", "--coloring", "histogram"]),
+                     Err(MandelError::Usage(_))));
+}
+
+#[test]
+fn test_parse_animate() {
+    let base = ["mandel", "animate", "--center", "-0.75,0.1", "--frames", "120", "--zoom-per-frame", "1.05",
+                "--out-dir", "zoom", "--size", "40x30"];
+    let animation = parse_animation(&base).unwrap();
+    assert_eq!(animation.zoom, Zoom { target: Complex { re: -0.75, im: 0.1 }, frames: 120, zoom_per_frame: 1.05 });
+    assert_eq!(animation.out_dir, Path::new("zoom"));
+    assert_eq!(animation.options.filename, "zoom/frame_0000.png");
+    assert_eq!(animation.options.strategy, Strategy::Rayon);
+    let region = &animation.options.params.region;
+    assert_eq!((region.upper_left, region.lower_right),
+               mandelbrot_set::bounds_from_center(Complex { re: -0.75, im: 0.1 }, 1.0, (40, 30)));
+
+    let zoomed = parse_animation(&[&base[..], &["--zoom", "8", "--palette", "fire"]].concat()).unwrap();
+    assert_eq!(zoomed.options.params.region.upper_left,
+               mandelbrot_set::bounds_from_center(Complex { re: -0.75, im: 0.1 }, 8.0, (40, 30)).0);
+    assert!(matches!(zoomed.options.params.color, ColorMode::Rgb(_)));
+    let widened = parse_animation(&[&base[..], &["--width", "0.5"]].concat()).unwrap();
+    assert_eq!(widened.options.params.region.upper_left, zoomed.options.params.region.upper_left);
+
+    let cornered = parse_animation(&[&base[..], &["--upper-left", "-2,1", "--lower-right", "1,-1"]].concat()).unwrap();
+    assert_eq!(cornered.options.params.region.upper_left, Complex { re: -2.0, im: 1.0 });
+
+    assert!(matches!(parse_animation(&base[..4]), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_animation(&[&base[..], &["--output", "x.png"]].concat()), Err(MandelError::Usage(_))));
+    assert!(matches!(parse_animation(&[&base[..], &["--zoom", "2", "--upper-left", "-2,1"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_animation(&[&base[..], &["--width", "2", "--upper-left", "-2,1"]].concat()),
+                     Err(MandelError::Usage(_))));
+    assert!(matches!(parse_animation(&[&base[..], &["--frames", "0"]].concat()), Err(MandelError::Parse(_))));
+}
+
+#[cfg(test)]
+fn conflict_args(name: &str) -> &'static [&'static str] {
+    match name {
+        "EXR output" => &["--format", "exr"],
+        "--palette" => &["--palette", "fire"],
+        "--depth 16" => &["--depth", "16"],
+        "--gamma" => &["--gamma", "2"],
+        "--log-scale" => &["--log-scale"],
+        "--invert" => &["--invert"],
+        "--display-gamma" => &["--display-gamma", "2.2"],
+        "--smooth" => &["--smooth"],
+        "--aa" => &["--aa", "2"],
+        "--coloring histogram" => &["--coloring", "histogram"],
+        "--coloring distance" => &["--coloring", "distance"],
+        "--coloring trap" => &["--coloring", "trap"],
+        "--coloring binary" => &["--coloring", "binary"],
+        "--coloring binary-shaded" => &["--coloring", "binary-shaded"],
+        "--coloring stripe" => &["--coloring", "stripe"],
+        "--light-angle" => &["--light-angle", "45"],
+        "--fractal newton" => &["--fractal", "newton"],
+        "--dump" => &["--dump", "x.iter"],
+        "--output-raw" => &["--output-raw", "x.raw"],
+        "--deep" => &["--deep"],
+        "--precision" => &["--precision", "128"],
+        "--estimate" => &["--estimate"],
+        "--format dzi" => &["--format", "dzi"],
+        "--output -" => &["--output", "-"],
+        "--stream" => &["--stream"],
+        "--grid" => &["--grid", "2x2"],
+        "--roi" => &["--roi", "0,0,5,5"],
+        "--strategy" => &["--strategy", "rayon"],
+        "--progressive" => &["--progressive"],
+        "--stats" => &["--stats"],
+        "--checkpoint" => &["--checkpoint", "x.ckpt"],
+        _ => panic!("no arguments for {}", name),
+    }
+}
+
+#[test]
+fn test_every_conflict_is_refused() {
+    // Without the feature, EXR output is refused whatever it comes with;
+    // --deep needs a center.
+    let built = |name: &&str| cfg!(feature = "exr") || *name != "EXR output";
+    let parse_with = |extra: &[&str]| {
+        parse_options(&[&["mandel", "--output", "x.png", "--size", "10x10", "--center", "-0.5,0", "--zoom", "1"][..],
+                        extra].concat())
+    };
+    for (name, _, others) in CONFLICTS.iter().filter(|(name, _, _)| built(name)) {
+        assert!(parse_with(conflict_args(name)).is_ok(), "{}", name);
+        for other in others.iter().filter(|other| built(other)) {
+            assert!(parse_with(conflict_args(other)).is_ok(), "{}", other);
+            let both = [conflict_args(name), conflict_args(other)].concat();
+            match parse_with(&both) {
+                Err(MandelError::Usage(message)) => assert!(message.contains("cannot be combined"), "{}", message),
+                result => panic!("{} with {} gave {:?}", name, other, result.map(|_| ())),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_conflicts_are_named_once() {
+    let mut pairs: Vec<_> = CONFLICTS.iter()
+        .flat_map(|(name, _, others)| others.iter().map(move |other| (name.min(other), name.max(other))))
+        .collect();
+    let count = pairs.len();
+    pairs.sort();
+    pairs.dedup();
+    assert_eq!(pairs.len(), count);
+}
+
+#[test]
+fn test_usage_descriptions_fit() {
+    for line in usage("mandel").lines().filter(|line| line.starts_with(&" ".repeat(25)) || line.starts_with("  --")) {
+        assert!(line.len() <= 79, "{}", line);
+    }
+}
@@ -0,0 +1,143 @@
+//! Buffers of raw escape counts, kept apart from the pixel bytes so that
+//! coloring passes which need the whole image, such as histogram
+//! equalization, can run after every thread has finished.
+
+/// The count stored for a point that never escaped.
+pub const NEVER_ESCAPED: u32 = u32::MAX;
+
+
+/// The escape count of one pixel as stored in a count buffer.
+pub fn encode_count(count: Option<usize>) -> u32
+{
+    match count {
+        None => NEVER_ESCAPED,
+        Some(count) => count as u32,
+    }
+}
+
+/// The escape count a count buffer entry stands for.
+pub fn decode_count(count: u32) -> Option<usize>
+{
+    if count == NEVER_ESCAPED { None } else { Some(count as usize) }
+}
+
+/// Read the counts out of a buffer rendered with `ColorMode::Counts`, four
+/// little-endian bytes per pixel.
+pub fn decode_counts(bytes: &[u8]) -> Vec<u32>
+{
+    assert!(bytes.len().is_multiple_of(4));
+    bytes.chunks_exact(4).map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect()
+}
+
+/// How many of `counts` escaped after each number of iterations from 0 to
+/// `limit`, counts beyond the limit being put with it. Pixels that never
+/// escaped are left out.
+pub fn histogram(counts: &[Option<usize>], limit: usize) -> Vec<usize>
+{
+    let mut histogram = vec![0; limit + 1];
+    for &count in counts.iter().flatten() {
+        histogram[count.min(limit)] += 1;
+    }
+    histogram
+}
+
+/// Histogram equalization: give every escaping pixel the fraction of all
+/// escaping pixels that escaped strictly sooner than it did. The result runs
+/// from 0 for the fastest pixels towards 1 for the slowest, with each count
+/// getting a share of the range proportional to how many pixels have it,
+/// so an image whose counts crowd into a narrow band still uses every
+/// shade. Pixels that never escaped get `None`. `limit` is the iteration
+/// limit the counts were computed with.
+pub fn equalize(counts: &[Option<usize>], limit: usize) -> Vec<Option<f64>>
+{
+    let histogram = histogram(counts, limit);
+    let total: usize = histogram.iter().sum();
+    let mut sooner = Vec::with_capacity(histogram.len());
+    let mut running = 0;
+    for frequency in &histogram {
+        sooner.push(running);
+        running += frequency;
+    }
+
+    counts.iter()
+        .map(|count| count.map(|count| sooner[count.min(limit)] as f64 / total as f64))
+        .collect()
+}
+
+/// What the escape counts of a render say about its iteration limit: a
+/// `max` at the limit, or a large share of interior points, suggests
+/// raising it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CountStats {
+    /// The fewest and most iterations an escaping pixel took, `None` if
+    /// none escaped.
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+    /// The mean count of the escaping pixels, 0 if none escaped.
+    pub mean: f64,
+    pub escaped: usize,
+    /// The pixels that never escaped.
+    pub interior: usize,
+}
+
+impl CountStats {
+    pub fn new(counts: &[Option<usize>]) -> CountStats
+    {
+        let escaped = counts.iter().flatten();
+        let (total, sum) = escaped.clone().fold((0, 0u64), |(total, sum), &count| (total + 1, sum + count as u64));
+        CountStats {
+            min: escaped.clone().min().copied(),
+            max: escaped.max().copied(),
+            mean: if total == 0 { 0.0 } else { sum as f64 / total as f64 },
+            escaped: total,
+            interior: counts.len() - total,
+        }
+    }
+
+    /// The share of the pixels that never escaped, from 0 to 1.
+    pub fn interior_fraction(&self) -> f64
+    {
+        self.interior as f64 / (self.escaped + self.interior).max(1) as f64
+    }
+}
+
+
+#[test]
+fn test_count_encoding() {
+    assert_eq!(encode_count(None), NEVER_ESCAPED);
+    assert_eq!(decode_count(encode_count(Some(17))), Some(17));
+    assert_eq!(decode_counts(&[1, 0, 0, 0, 255, 255, 255, 255]), vec![1, NEVER_ESCAPED]);
+}
+
+#[test]
+fn test_equalize() {
+    // Two pixels escape at once, one after a single iteration and one after
+    // three: 0 of 4 escaped sooner than the first two, 2 of 4 sooner than
+    // the third and 3 of 4 sooner than the fourth.
+    let positions = equalize(&[Some(0), Some(0), Some(1), Some(3), None], 4);
+    assert_eq!(positions, vec![Some(0.0), Some(0.0), Some(0.5), Some(0.75), None]);
+
+    // Counts bunched together near the limit are spread over the range.
+    let positions = equalize(&[Some(250), Some(251), Some(252), Some(253)], 255);
+    assert_eq!(positions, vec![Some(0.0), Some(0.25), Some(0.5), Some(0.75)]);
+
+    assert_eq!(equalize(&[None; 3], 255), vec![None; 3]);
+}
+
+#[test]
+fn test_histogram() {
+    assert_eq!(histogram(&[Some(0), Some(2), None, Some(2), Some(9)], 3), vec![1, 0, 2, 1]);
+}
+
+#[test]
+fn test_count_stats() {
+    let stats = CountStats::new(&[Some(3), None, Some(10), Some(5), None, Some(10), None, None]);
+    assert_eq!(stats, CountStats { min: Some(3), max: Some(10), mean: 7.0, escaped: 4, interior: 4 });
+    assert_eq!(stats.interior_fraction(), 0.5);
+
+    let inside = CountStats::new(&[None; 3]);
+    assert_eq!(inside, CountStats { min: None, max: None, mean: 0.0, escaped: 0, interior: 3 });
+    assert_eq!(inside.interior_fraction(), 1.0);
+    assert_eq!(CountStats::new(&[]).interior_fraction(), 0.0);
+    assert_eq!(CountStats::new(&[Some(0)]).max, Some(0));
+}
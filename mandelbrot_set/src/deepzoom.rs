@@ -0,0 +1,214 @@
+//! Deep Zoom Images, the tiled pyramid OpenSeadragon and other viewers load
+//! as the user zooms: a `.dzi` XML descriptor beside a `_files` directory
+//! holding one folder of tiles per level.
+
+use std::path::{Path, PathBuf};
+use image::ColorType;
+use crate::{ImageFormat, MandelError, write_image_as};
+use crate::tile::Tile;
+
+
+/// The tile size Deep Zoom tools use by default: with the overlap, tiles
+/// inside the image come out 256 pixels square.
+pub const DZI_TILE_SIZE: usize = 254;
+/// How many pixels each tile shares with its neighbours by default.
+pub const DZI_OVERLAP: usize = 1;
+
+/// Tiles of `tile_size` pixels square, each extended by `overlap` pixels on
+/// every side that has a neighbouring tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DeepZoom {
+    pub tile_size: usize,
+    pub overlap: usize,
+}
+
+impl Default for DeepZoom {
+    fn default() -> DeepZoom
+    {
+        DeepZoom { tile_size: DZI_TILE_SIZE, overlap: DZI_OVERLAP }
+    }
+}
+
+impl DeepZoom {
+    /// The size of every level of an image of size `bounds`, from level 0,
+    /// one pixel square, to the full image. Each level is half the size of
+    /// the next, rounded up.
+    pub fn levels(bounds: (usize, usize)) -> Vec<(usize, usize)>
+    {
+        let mut levels = vec![bounds];
+        let mut size = bounds;
+        while size.0 > 1 || size.1 > 1 {
+            size = (size.0.div_ceil(2), size.1.div_ceil(2));
+            levels.push(size);
+        }
+        levels.reverse();
+        levels
+    }
+
+    /// The tiles of a level of size `size`, with their column and row, row by
+    /// row. The tiles meet every `tile_size` pixels, and each reaches
+    /// `overlap` pixels past the edges it shares with a neighbour.
+    pub fn tiles(&self, size: (usize, usize)) -> Vec<(usize, usize, Tile)>
+    {
+        assert!(self.tile_size > 0);
+        let span = |index: usize, length: usize| {
+            let start = (index * self.tile_size).saturating_sub(self.overlap);
+            let end = ((index + 1) * self.tile_size + self.overlap).min(length);
+            (start, end - start)
+        };
+        let mut tiles = vec![];
+        for row in 0..size.1.div_ceil(self.tile_size) {
+            for column in 0..size.0.div_ceil(self.tile_size) {
+                let ((left, width), (top, height)) = (span(column, size.0), span(row, size.1));
+                tiles.push((column, row, Tile { origin: (left, top), size: (width, height) }));
+            }
+        }
+        tiles
+    }
+
+    /// The XML descriptor of an image of size `bounds` whose tiles are in
+    /// `format`.
+    pub fn descriptor(&self, bounds: (usize, usize), format: ImageFormat) -> String
+    {
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                 <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"{}\" Overlap=\"{}\" \
+                 TileSize=\"{}\">\n  <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+                tile_extension(format), self.overlap, self.tile_size, bounds.0, bounds.1)
+    }
+
+    /// The directory holding the levels of the image described by `output`:
+    /// `out.dzi` keeps them in `out_files`.
+    pub fn files_dir(output: &Path) -> PathBuf
+    {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        output.with_file_name(format!("{}_files", stem))
+    }
+
+    /// Where the tile in `column` and `row` of `level` goes in `files_dir`.
+    pub fn tile_filename(files_dir: &Path, level: usize, column: usize, row: usize, format: ImageFormat) -> PathBuf
+    {
+        files_dir.join(level.to_string()).join(format!("{}_{}.{}", column, row, tile_extension(format)))
+    }
+}
+
+/// The extension of tiles in `format`, which Deep Zoom only allows to be PNG
+/// or JPEG.
+fn tile_extension(format: ImageFormat) -> &'static str
+{
+    match format {
+        ImageFormat::Png => "png",
+        ImageFormat::Jpeg(_) => "jpg",
+        _ => panic!("Deep Zoom tiles are PNG or JPEG, not {:?}", format),
+    }
+}
+
+/// Halve an image of size `bounds`, averaging each 2x2 block of pixels into
+/// one. Along an odd edge the last pixels are averaged with fewer
+/// neighbours. Each byte is averaged on its own, so the pixels must be 8 bits
+/// per channel.
+pub fn downsample(pixels: &[u8], bounds: (usize, usize), bytes_per_pixel: usize) -> (Vec<u8>, (usize, usize))
+{
+    assert_eq!(pixels.len(), bounds.0 * bounds.1 * bytes_per_pixel);
+    let half = (bounds.0.div_ceil(2), bounds.1.div_ceil(2));
+    let mut smaller = Vec::with_capacity(half.0 * half.1 * bytes_per_pixel);
+    for y in 0..half.1 {
+        let rows = (2 * y)..(2 * y + 2).min(bounds.1);
+        for x in 0..half.0 {
+            let columns = (2 * x)..(2 * x + 2).min(bounds.0);
+            let count = rows.len() * columns.len();
+            for channel in 0..bytes_per_pixel {
+                let sum: usize = rows.clone().flat_map(|row| columns.clone().map(move |column| (row, column)))
+                    .map(|(row, column)| pixels[(row * bounds.0 + column) * bytes_per_pixel + channel] as usize).sum();
+                smaller.push(((sum + count / 2) / count) as u8);
+            }
+        }
+    }
+    (smaller, half)
+}
+
+/// Write `pixels`, an image of size `bounds`, as a Deep Zoom Image: the
+/// descriptor to `output` and the tiles of every level, in `format`, to
+/// `DeepZoom::files_dir(output)`. The full image is cut into the top level
+/// and each level below is downsampled from the one above, which aliases
+/// far less than rendering it again at the lower resolution would. `on_tile`
+/// is called with the name of each tile once it is written.
+pub fn write_deep_zoom(output: &Path, pixels: &[u8], bounds: (usize, usize), color_type: ColorType,
+                       format: ImageFormat, deep_zoom: &DeepZoom, mut on_tile: impl FnMut(&Path))
+    -> Result<(), MandelError>
+{
+    let bytes_per_pixel = pixels.len() / (bounds.0 * bounds.1);
+    let files_dir = DeepZoom::files_dir(output);
+    let levels = DeepZoom::levels(bounds);
+    let mut level_pixels = pixels.to_vec();
+    for (level, &size) in levels.iter().enumerate().rev() {
+        if level + 1 < levels.len() {
+            (level_pixels, _) = downsample(&level_pixels, levels[level + 1], bytes_per_pixel);
+        }
+        std::fs::create_dir_all(files_dir.join(level.to_string()))?;
+        for (column, row, tile) in deep_zoom.tiles(size) {
+            let mut piece = Vec::with_capacity(tile.size.0 * tile.size.1 * bytes_per_pixel);
+            for line in tile.origin.1..tile.origin.1 + tile.size.1 {
+                let start = (line * size.0 + tile.origin.0) * bytes_per_pixel;
+                piece.extend_from_slice(&level_pixels[start..start + tile.size.0 * bytes_per_pixel]);
+            }
+            let filename = DeepZoom::tile_filename(&files_dir, level, column, row, format);
+            write_image_as(&filename.to_string_lossy(), &piece, tile.size, color_type, format)?;
+            on_tile(&filename);
+        }
+    }
+    std::fs::write(output, deep_zoom.descriptor(bounds, format))?;
+    Ok(())
+}
+
+
+#[test]
+fn test_levels() {
+    let levels = DeepZoom::levels((1000, 700));
+    assert_eq!(levels.len(), 11);
+    assert_eq!(levels[0], (1, 1));
+    assert_eq!(levels[7], (125, 88));
+    assert_eq!(levels[8], (250, 175));
+    assert_eq!(levels[10], (1000, 700));
+    assert_eq!(DeepZoom::levels((1, 1)), [(1, 1)]);
+    assert_eq!(DeepZoom::levels((3, 1)), [(1, 1), (2, 1), (3, 1)]);
+}
+
+#[test]
+fn test_tiles_overlap() {
+    let tiles = DeepZoom::default().tiles((600, 300));
+    assert_eq!(tiles.len(), 3 * 2);
+    assert_eq!(tiles[0], (0, 0, Tile { origin: (0, 0), size: (255, 255) }));
+    assert_eq!(tiles[1], (1, 0, Tile { origin: (253, 0), size: (256, 255) }));
+    assert_eq!(tiles[2], (2, 0, Tile { origin: (507, 0), size: (93, 255) }));
+    assert_eq!(tiles[5], (2, 1, Tile { origin: (507, 253), size: (93, 47) }));
+    let plain = DeepZoom { tile_size: 4, overlap: 0 }.tiles((6, 4));
+    assert_eq!(plain.iter().map(|&(_, _, tile)| tile).collect::<Vec<_>>(),
+               [Tile { origin: (0, 0), size: (4, 4) }, Tile { origin: (4, 0), size: (2, 4) }]);
+    assert_eq!(DeepZoom::default().tiles((1, 1)), [(0, 0, Tile { origin: (0, 0), size: (1, 1) })]);
+}
+
+#[test]
+fn test_descriptor() {
+    assert_eq!(DeepZoom::default().descriptor((1000, 700), ImageFormat::Png),
+               "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+                <Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" \
+                Format=\"png\" Overlap=\"1\" TileSize=\"254\">\n  \
+                <Size Width=\"1000\" Height=\"700\"/>\n\
+                </Image>\n");
+    assert!(DeepZoom::default().descriptor((5, 5), ImageFormat::Jpeg(90)).contains("Format=\"jpg\""));
+    assert_eq!(DeepZoom::files_dir(Path::new("zoom/out.dzi")), Path::new("zoom/out_files"));
+    assert_eq!(DeepZoom::tile_filename(Path::new("out_files"), 9, 2, 1, ImageFormat::Png),
+               Path::new("out_files/9/2_1.png"));
+}
+
+#[test]
+fn test_downsample() {
+    let pixels = [0, 10, 20, 30, 40,
+                  2, 12, 22, 32, 42,
+                  100, 100, 100, 100, 255];
+    let (smaller, size) = downsample(&pixels, (5, 3), 1);
+    assert_eq!(size, (3, 2));
+    assert_eq!(smaller, [6, 26, 41, 100, 100, 255]);
+    let rgb = [0, 0, 0, 255, 255, 255];
+    assert_eq!(downsample(&rgb, (2, 1), 3), (vec![128, 128, 128], (1, 1)));
+}
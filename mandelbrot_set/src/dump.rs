@@ -0,0 +1,163 @@
+//! Raw escape counts saved to a file, so that an expensive render can be
+//! colored again without computing it a second time.
+//!
+//! A dump starts with `MAGIC`, then the format `VERSION`, width, height and
+//! iteration limit as little-endian `u32`s, then the squared bailout and the
+//! real and imaginary parts of the upper left and lower right corners as
+//! little-endian `f64`s. After this header come the counts of every pixel
+//! row by row, each a little-endian `u32`, `counts::NEVER_ESCAPED` for
+//! points that never escaped.
+
+use std::io::{self, Read, Write};
+use num::Complex;
+use crate::{MandelError, Region, RenderParams};
+use crate::counts::{decode_count, decode_counts, encode_count};
+
+
+/// The first bytes of every dump.
+pub const MAGIC: &[u8; 8] = b"MANDITER";
+/// The version of the format `IterDump::write` produces, and the only one
+/// `IterDump::read` accepts.
+pub const VERSION: u32 = 1;
+/// The size of the header in front of the counts.
+pub const HEADER_LEN: usize = 8 + 4 * 4 + 5 * 8;
+
+
+/// The escape counts of an image, together with everything needed to color
+/// them the way the render that produced them would have.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IterDump {
+    pub region: Region,
+    pub limit: usize,
+    pub bailout: f64,
+    pub counts: Vec<Option<usize>>,
+}
+
+impl IterDump {
+    /// Keep the `counts` rendered for `params`, e.g. by `render_counts`.
+    pub fn new(params: &RenderParams, counts: Vec<Option<usize>>) -> IterDump
+    {
+        assert!(counts.len() == params.region.bounds.0 * params.region.bounds.1);
+        IterDump { region: params.region, limit: params.limit, bailout: params.bailout, counts }
+    }
+
+    /// Parameters with the region, limit and bailout of the dump, and the
+    /// default coloring, for `color_counts`.
+    pub fn params(&self) -> RenderParams
+    {
+        let region = &self.region;
+        RenderParams {
+            limit: self.limit,
+            bailout: self.bailout,
+            ..RenderParams::new(region.bounds, region.upper_left, region.lower_right)
+        }
+    }
+
+    /// Write the dump in the format described at the top of this module.
+    pub fn write<W: Write>(&self, output: &mut W) -> Result<(), MandelError>
+    {
+        let region = &self.region;
+        output.write_all(MAGIC)?;
+        output.write_all(&VERSION.to_le_bytes())?;
+        for field in [region.bounds.0, region.bounds.1, self.limit] {
+            output.write_all(&(field as u32).to_le_bytes())?;
+        }
+        for field in [self.bailout, region.upper_left.re, region.upper_left.im, region.lower_right.re,
+                      region.lower_right.im] {
+            output.write_all(&field.to_le_bytes())?;
+        }
+        let counts: Vec<u8> = self.counts.iter().flat_map(|&count| encode_count(count).to_le_bytes()).collect();
+        output.write_all(&counts)?;
+        Ok(())
+    }
+
+    /// Read a dump written by `write`. Fails with `MandelError::Parse` if the
+    /// input does not start with `MAGIC`, is of another `VERSION`, or holds
+    /// more or fewer counts than its width and height call for.
+    pub fn read<R: Read>(input: &mut R) -> Result<IterDump, MandelError>
+    {
+        let truncated = |error: io::Error| match error.kind() {
+            io::ErrorKind::UnexpectedEof => MandelError::Parse("truncated iteration dump header".to_string()),
+            _ => MandelError::Io(error),
+        };
+        // The magic number and version come first, so that a dump of another
+        // version is reported as such whatever the length of its header.
+        let mut header = [0; HEADER_LEN];
+        input.read_exact(&mut header[..12]).map_err(truncated)?;
+        if &header[..8] != MAGIC {
+            return Err(MandelError::Parse("not an iteration dump".to_string()));
+        }
+        let word = |header: &[u8], index: usize| {
+            u32::from_le_bytes(header[8 + index * 4..12 + index * 4].try_into().unwrap()) as usize
+        };
+        if word(&header, 0) != VERSION as usize {
+            return Err(MandelError::Parse(format!("iteration dump version {} is not supported; expected {}",
+                                                  word(&header, 0), VERSION)));
+        }
+        input.read_exact(&mut header[12..]).map_err(truncated)?;
+        let word = |index: usize| word(&header, index);
+        let float = |index: usize| f64::from_le_bytes(header[24 + index * 8..32 + index * 8].try_into().unwrap());
+        let bounds = (word(1), word(2));
+        let corner = |index: usize| Complex { re: float(index), im: float(index + 1) };
+
+        let mut counts = Vec::new();
+        input.read_to_end(&mut counts)?;
+        let expected = bounds.0 as u64 * bounds.1 as u64 * 4;
+        if counts.len() as u64 != expected {
+            return Err(MandelError::Parse(format!("iteration dump of {}x{} pixels should hold {} bytes of counts, \
+                                                   found {}", bounds.0, bounds.1, expected, counts.len())));
+        }
+
+        Ok(IterDump {
+            region: Region::new(bounds, corner(1), corner(3)),
+            limit: word(3),
+            bailout: float(0),
+            counts: decode_counts(&counts).into_iter().map(decode_count).collect(),
+        })
+    }
+}
+
+
+#[cfg(test)]
+fn sample() -> IterDump {
+    let params = RenderParams { limit: 500, ..RenderParams::new((3, 2), Complex { re: -2.0, im: 1.5 },
+                                                                 Complex { re: 1.0, im: -0.5 }) };
+    IterDump::new(&params, vec![Some(0), Some(1), None, Some(499), Some(500), None])
+}
+
+#[test]
+fn test_dump_round_trip() {
+    let dump = sample();
+    let mut bytes = vec![];
+    dump.write(&mut bytes).unwrap();
+    assert_eq!(bytes.len(), HEADER_LEN + 6 * 4);
+    assert_eq!(&bytes[..8], MAGIC);
+    assert_eq!(&bytes[8..12], &VERSION.to_le_bytes());
+    assert_eq!(&bytes[12..16], &3u32.to_le_bytes());
+    assert_eq!(&bytes[HEADER_LEN + 8..HEADER_LEN + 12], &[0xff; 4]);
+
+    assert_eq!(IterDump::read(&mut &bytes[..]).unwrap(), dump);
+    assert_eq!(dump.params().region, dump.region);
+    assert_eq!(dump.params().limit, 500);
+}
+
+#[test]
+fn test_dump_errors() {
+    let mut bytes = vec![];
+    sample().write(&mut bytes).unwrap();
+
+    for truncated in [&bytes[..bytes.len() - 1], &bytes[..HEADER_LEN], &bytes[..20]] {
+        assert!(matches!(IterDump::read(&mut &truncated[..]), Err(MandelError::Parse(_))), "{} bytes", truncated.len());
+    }
+    let longer = [&bytes[..], &[0; 4]].concat();
+    assert!(matches!(IterDump::read(&mut &longer[..]), Err(MandelError::Parse(_))));
+    let mut wrong = bytes.clone();
+    wrong[0] = b'X';
+    assert!(matches!(IterDump::read(&mut &wrong[..]), Err(MandelError::Parse(_))));
+    let mut newer = bytes.clone();
+    newer[8] = VERSION as u8 + 1;
+    match IterDump::read(&mut &newer[..]) {
+        Err(MandelError::Parse(message)) => assert!(message.contains("version 2"), "{}", message),
+        other => panic!("expected a version error, got {:?}", other),
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt;
+use std::io;
+
+
+/// Everything that can go wrong between reading the command line and
+/// writing the finished image.
+#[derive(Debug)]
+pub enum MandelError {
+    /// The command line has the wrong shape, e.g. too few arguments.
+    Usage(String),
+    /// An argument could not be parsed.
+    Parse(String),
+    /// The requested image size or region cannot be rendered.
+    Dimensions(String),
+    /// Reading or writing a file failed.
+    Io(io::Error),
+    /// `failed` of the `jobs` of a batch failed, each reported as it did.
+    Batch { failed: usize, jobs: usize },
+}
+
+impl MandelError {
+    /// The process exit code for this kind of error: 2 for mistakes on the
+    /// command line, 1 for failures while doing the work.
+    pub fn exit_code(&self) -> i32
+    {
+        match self {
+            MandelError::Usage(_) | MandelError::Parse(_) | MandelError::Dimensions(_) => 2,
+            MandelError::Io(_) | MandelError::Batch { .. } => 1,
+        }
+    }
+}
+
+impl fmt::Display for MandelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        match self {
+            MandelError::Usage(message) => write!(f, "{}", message),
+            MandelError::Parse(message) => write!(f, "{}", message),
+            MandelError::Dimensions(message) => write!(f, "{}", message),
+            MandelError::Io(error) => write!(f, "I/O error: {}", error),
+            MandelError::Batch { failed, jobs } => write!(f, "{} of {} jobs failed", failed, jobs),
+        }
+    }
+}
+
+impl std::error::Error for MandelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)>
+    {
+        match self {
+            MandelError::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for MandelError {
+    fn from(error: io::Error) -> MandelError
+    {
+        MandelError::Io(error)
+    }
+}
+
+
+#[test]
+fn test_exit_codes() {
+    assert_eq!(MandelError::Usage("usage".to_string()).exit_code(), 2);
+    assert_eq!(MandelError::Parse("bad".to_string()).exit_code(), 2);
+    assert_eq!(MandelError::Dimensions("0x0".to_string()).exit_code(), 2);
+    assert_eq!(MandelError::from(io::Error::new(io::ErrorKind::NotFound, "gone")).exit_code(), 1);
+    assert_eq!(MandelError::Batch { failed: 1, jobs: 3 }.exit_code(), 1);
+}
+
+#[test]
+fn test_io_conversion() {
+    use std::error::Error;
+
+    let error = MandelError::from(io::Error::new(io::ErrorKind::PermissionDenied, "read-only"));
+    assert!(matches!(&error, MandelError::Io(inner) if inner.kind() == io::ErrorKind::PermissionDenied));
+    assert_eq!(error.to_string(), "I/O error: read-only");
+    assert_eq!(error.source().unwrap().to_string(), "read-only");
+
+    let question_mark = || -> Result<(), MandelError> { Err(io::Error::other("disk full"))? };
+    assert!(matches!(question_mark(), Err(MandelError::Io(_))));
+}
+
+#[test]
+fn test_display() {
+    use std::error::Error;
+
+    assert_eq!(MandelError::Parse("--size: expected WxH".to_string()).to_string(), "--size: expected WxH");
+    assert!(MandelError::Usage("usage".to_string()).source().is_none());
+    assert_eq!(MandelError::Batch { failed: 2, jobs: 5 }.to_string(), "2 of 5 jobs failed");
+}
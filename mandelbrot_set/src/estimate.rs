@@ -0,0 +1,88 @@
+//! A rough forecast of what a render will cost, from a coarse sample of its
+//! pixels, for choosing a size and iteration limit before a long render.
+
+use std::time::Instant;
+use num::Complex;
+use crate::RenderParams;
+use crate::progress::Skipped;
+
+
+/// How many pixels along each side of the image `estimate` samples.
+pub const ESTIMATE_SAMPLES: usize = 64;
+
+/// What `estimate` found: the work a whole render would take, extrapolated
+/// from `samples` pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Estimate {
+    pub samples: usize,
+    pub iterations: f64,
+    pub seconds: f64,
+}
+
+/// At most `per_side` by `per_side` pixel centers spread evenly over the
+/// image of `params`.
+fn sample_points(params: &RenderParams, per_side: usize) -> Vec<Complex<f64>>
+{
+    let (width, height) = params.region.bounds;
+    let (columns, rows) = (per_side.min(width), per_side.min(height));
+    (0..rows).flat_map(|row| (0..columns).map(move |column| {
+        params.region.pixel_to_point(((2 * column + 1) * width / (2 * columns), (2 * row + 1) * height / (2 * rows)))
+    })).collect()
+}
+
+/// The number of pixels sampled and the iterations they took, on the grid
+/// of `sample_points`. A point skipped as lying in the main cardioid or period-2 bulb
+/// costs nothing; any other point that never escapes is counted at the full
+/// limit, though its orbit may have been abandoned early as a cycle, so the
+/// total errs on the high side.
+pub fn sample_iterations(params: &RenderParams, per_side: usize) -> (usize, u64)
+{
+    let points = sample_points(params, per_side);
+    let iterations = points.iter().map(|&point| match params.escape_time(point, &mut Skipped::default()) {
+        Some(count) => count + 1,
+        None if params.known_interior(point) => 0,
+        None => params.limit,
+    } as u64).sum();
+    (points.len(), iterations)
+}
+
+/// Estimate the iterations and time a render of `params` on `threads`
+/// threads would take, by timing `sample_iterations` and scaling both up to
+/// every sample of every pixel. Coloring and writing the image are left out,
+/// and a perfect speedup is assumed, so treat the time as a lower bound.
+pub fn estimate(params: &RenderParams, threads: usize) -> Estimate
+{
+    let start = Instant::now();
+    let (samples, iterations) = sample_iterations(params, ESTIMATE_SAMPLES);
+    let elapsed = start.elapsed().as_secs_f64();
+    let (width, height) = params.region.bounds;
+    let scale = width as f64 * height as f64 * (params.antialias * params.antialias) as f64 / samples as f64;
+    Estimate { samples, iterations: iterations as f64 * scale, seconds: elapsed * scale / threads.max(1) as f64 }
+}
+
+
+#[test]
+fn test_sample_iterations() {
+    use crate::{ColorMode, Strategy, render_counts};
+
+    // A grid as large as the image samples every pixel.
+    let params = RenderParams {
+        limit: 100,
+        interior_check: false,
+        color: ColorMode::Counts,
+        ..RenderParams::new((12, 8), Complex { re: 0.25, im: 1.0 }, Complex { re: 1.0, im: 0.5 })
+    };
+    let (counts, _) = render_counts(&params, Strategy::Sequential, 1);
+    let expected: u64 = counts.iter().map(|count| count.map_or(100, |count| count as u64 + 1)).sum();
+    assert_eq!(sample_iterations(&params, 64), (96, expected));
+    assert_eq!(sample_iterations(&params, 4).0, 16);
+
+    // The middle of the main cardioid is skipped, unless the check is off.
+    let inside = RenderParams::new((8, 8), Complex { re: -0.3, im: 0.1 }, Complex { re: -0.1, im: -0.1 });
+    assert_eq!(sample_iterations(&inside, 64), (64, 0));
+    let unchecked = RenderParams { interior_check: false, ..inside.clone() };
+    assert_eq!(sample_iterations(&unchecked, 64), (64, 64 * unchecked.limit as u64));
+
+    let doubled = RenderParams { antialias: 2, ..params.clone() };
+    assert_eq!(estimate(&doubled, 4).iterations, 4.0 * expected as f64);
+}
@@ -0,0 +1,1228 @@
+use num::Complex;
+
+
+/// How close `z` must come to a value it had before for `escape_time` to
+/// decide its orbit has fallen into a cycle and will never escape. See
+/// `RenderParams::periodicity_epsilon`.
+pub const PERIODICITY_EPSILON: f64 = 1e-12;
+
+/// The highest power of `Fractal::Multibrot` the command line accepts. Past
+/// it the set is nearly a disc and the escape counts barely vary.
+pub const MAX_POWER: u32 = 16;
+
+/// How close Newton's method must bring `z` to a root for `--fractal newton`
+/// to count it as converged, unless told otherwise.
+pub const NEWTON_EPSILON: f64 = 1e-6;
+
+/// The roots of `z^3 - 1` that `newton_convergence` finds: 1 and the two
+/// others a third of a turn either way, in counterclockwise order.
+pub const NEWTON_ROOTS: [Complex<f64>; 3] = [
+    Complex { re: 1.0, im: 0.0 },
+    Complex { re: -0.5, im: 0.8660254037844386 },
+    Complex { re: -0.5, im: -0.8660254037844386 },
+];
+
+/// A shape `Coloring::Trap` measures each orbit against: the pixel is
+/// colored by how close its orbit comes to the shape.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trap {
+    /// A single point.
+    Point(Complex<f64>),
+    /// The straight line through two distinct points.
+    Line(Complex<f64>, Complex<f64>),
+    /// The horizontal and the vertical line through a point, `Im z` and
+    /// `Re z` equal to its own.
+    Cross(Complex<f64>),
+    /// A circle with this center and radius.
+    Circle(Complex<f64>, f64),
+}
+
+impl Default for Trap {
+    fn default() -> Trap
+    {
+        Trap::Point(Complex { re: 0.0, im: 0.0 })
+    }
+}
+
+impl Trap {
+    /// How far `z` lies from this shape.
+    pub fn distance(&self, z: Complex<f64>) -> f64
+    {
+        match *self {
+            Trap::Point(point) => (z - point).norm(),
+            Trap::Line(from, to) => {
+                let (along, offset) = (to - from, z - from);
+                (along.re * offset.im - along.im * offset.re).abs() / along.norm()
+            }
+            Trap::Cross(center) => (z.re - center.re).abs().min((z.im - center.im).abs()),
+            Trap::Circle(center, radius) => ((z - center).norm() - radius).abs(),
+        }
+    }
+}
+
+/// The escape-time fractals `render` knows how to draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Fractal {
+    /// Each pixel is `c`, iterated from `z = 0`.
+    #[default]
+    Mandelbrot,
+    /// Each pixel is the starting `z`, iterated with the given fixed `c`.
+    Julia(Complex<f64>),
+    /// Like the Mandelbrot set, but with `z = (|Re z| + i|Im z|)^2 + c`.
+    BurningShip,
+    /// Like the Mandelbrot set, but with `z = conj(z)^2 + c`.
+    Tricorn,
+    /// The Mandelbrot set of a higher power, `z = z^d + c` for `d` of at
+    /// least 3. The set has `d - 1`-fold rotational symmetry.
+    Multibrot(u32),
+    /// Newton's method for `z^3 - 1`, from each pixel as the starting `z`,
+    /// until `z` comes within the given distance of one of `NEWTON_ROOTS`.
+    /// Pixels are colored by the root rather than by an escape count.
+    Newton(f64),
+}
+
+/// Where Newton's method took a starting point: to `NEWTON_ROOTS[root]`,
+/// within the epsilon asked for, after `iterations` steps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Convergence {
+    pub root: usize,
+    pub iterations: usize,
+}
+
+/// How an orbit ended: the iteration at which it escaped, `None` if it never
+/// did, and the last value of `z`, which smooth and binary decomposition
+/// coloring both read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EscapeResult {
+    pub iterations: Option<usize>,
+    pub final_z: Complex<f64>,
+}
+
+impl EscapeResult {
+    /// The normalized iteration count of an orbit of `z = z^power + c`, or
+    /// `None` if it never escaped.
+    pub fn smooth_count(&self, power: u32) -> Option<f64>
+    {
+        self.iterations.map(|n| n as f64 + 1.0 - self.final_z.norm().ln().ln() / (power as f64).ln())
+    }
+
+    /// Whether the orbit escaped into the upper half of the plane, `Im z` at
+    /// least 0, for binary decomposition; `None` if it never escaped.
+    pub fn upper_half(&self) -> Option<bool>
+    {
+        self.iterations.map(|_| self.final_z.im >= 0.0)
+    }
+}
+
+impl Fractal {
+    /// The name `--fractal` knows this fractal by.
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Fractal::Mandelbrot => "mandelbrot",
+            Fractal::Julia(_) => "julia",
+            Fractal::BurningShip => "burning-ship",
+            Fractal::Tricorn => "tricorn",
+            Fractal::Multibrot(_) => "mandelbrot",
+            Fractal::Newton(_) => "newton",
+        }
+    }
+
+    /// The power `z` is raised to on each iteration.
+    pub fn power(&self) -> u32
+    {
+        match *self {
+            Fractal::Multibrot(power) => power,
+            _ => 2,
+        }
+    }
+
+    /// The starting `z` and the constant `c` of the orbit for the pixel at
+    /// `point`.
+    pub fn start(&self, point: Complex<f64>) -> (Complex<f64>, Complex<f64>)
+    {
+        match *self {
+            Fractal::Mandelbrot | Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) => {
+                (Complex { re: 0.0, im: 0.0 }, point)
+            }
+            Fractal::Julia(c) => (point, c),
+            Fractal::Newton(_) => (point, Complex { re: 0.0, im: 0.0 }),
+        }
+    }
+
+    /// Run the escape-time function for this fractal at `point`.
+    pub fn escape_time(&self, point: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+    {
+        match *self {
+            Fractal::Mandelbrot => escape_time(point, limit, bailout, epsilon),
+            Fractal::Julia(c) => julia_escape_time(point, c, limit, bailout, epsilon),
+            Fractal::BurningShip => burning_ship_escape_time(point, limit, bailout, epsilon),
+            Fractal::Tricorn => tricorn_escape_time(point, limit, bailout, epsilon),
+            Fractal::Multibrot(power) => multibrot_escape_time(point, power, limit, bailout, epsilon),
+            Fractal::Newton(tolerance) => newton_convergence(point, limit, tolerance).map(|found| found.iterations),
+        }
+    }
+
+    /// Iterate the orbit of `point` as `escape_time` does, checking for
+    /// cycles to within `epsilon`, and return where it ended as well as
+    /// when. Points the Mandelbrot set skips as lying in its main cardioid or
+    /// period-2 bulb end at `point` itself.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::{Fractal, PERIODICITY_EPSILON};
+    ///
+    /// let escape = Fractal::Mandelbrot.escape(Complex { re: 1.0, im: 1.0 }, 255, 4.0, PERIODICITY_EPSILON);
+    /// assert_eq!(escape.iterations, Some(2));
+    /// assert_eq!(escape.final_z, Complex { re: 1.0, im: 3.0 });
+    /// assert_eq!(escape.upper_half(), Some(true));
+    /// ```
+    pub fn escape(&self, point: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> EscapeResult
+    {
+        if *self == Fractal::Mandelbrot && in_cardioid_or_bulb(point) {
+            return EscapeResult { iterations: None, final_z: point };
+        }
+        self.orbit(point, limit, bailout, epsilon)
+    }
+
+    /// Run the smooth escape-time function for this fractal at `point`.
+    pub fn escape_time_smooth(&self, point: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<f64>
+    {
+        match *self {
+            Fractal::Mandelbrot => escape_time_smooth(point, limit, bailout, epsilon),
+            Fractal::Julia(c) => julia_escape_time_smooth(point, c, limit, bailout, epsilon),
+            Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) => {
+                self.orbit(point, limit, bailout, epsilon).smooth_count(self.power())
+            }
+            Fractal::Newton(_) => self.escape_time(point, limit, bailout, epsilon).map(|count| count as f64),
+        }
+    }
+
+    /// Estimate the distance from `point` to this fractal, as
+    /// `escape_distance` does. Only the Mandelbrot and Julia sets have the
+    /// complex derivative the estimate works from, and only at power 2; for
+    /// the other fractals, which includes Newton's method where nothing
+    /// escapes, this is always `None`.
+    pub fn escape_distance(&self, point: Complex<f64>, limit: usize, bailout: f64) -> Option<f64>
+    {
+        match *self {
+            Fractal::Mandelbrot => escape_distance(point, limit, bailout),
+            Fractal::Julia(c) => julia_escape_distance(point, c, limit, bailout),
+            Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) | Fractal::Newton(_) => None,
+        }
+    }
+
+    /// Run `escape_time_orbit_trap` for this fractal at `point`.
+    pub fn orbit_trap(&self, point: Complex<f64>, limit: usize, bailout: f64, trap: &Trap) -> f64
+    {
+        let mut nearest = f64::INFINITY;
+        self.visit_orbit(point, limit, bailout, |z| nearest = nearest.min(trap.distance(z)));
+        nearest
+    }
+
+    /// The stripe average of the orbit of the pixel at `point`: the mean of
+    /// `0.5 + 0.5 * sin(density * arg z)` over the values of `z` up to the
+    /// one that escapes, blended with the mean without that last value by
+    /// how far past the bailout it landed, so that the result runs on
+    /// smoothly where the escape count steps. Between 0 and 1, or `None` if
+    /// the orbit never escapes. Like the smooth count it wants a large
+    /// `bailout`.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::{Fractal, SMOOTH_BAILOUT};
+    ///
+    /// let stripes = |c| Fractal::Mandelbrot.stripe_average(c, 255, SMOOTH_BAILOUT, 4.0);
+    /// assert_eq!(stripes(Complex { re: -1.0, im: 0.0 }), None);
+    /// assert!((0.0..=1.0).contains(&stripes(Complex { re: 0.5, im: 0.5 }).unwrap()));
+    /// ```
+    pub fn stripe_average(&self, point: Complex<f64>, limit: usize, bailout: f64, density: f64) -> Option<f64>
+    {
+        let stripe = |z: Complex<f64>| 0.5 + 0.5 * (density * z.arg()).sin();
+        let (mut sum, mut steps) = (0.0, 0);
+        let escaped = self.visit_orbit(point, limit, bailout, |z| {
+            sum += stripe(z);
+            steps += 1;
+        })?;
+        let before = if steps == 0 { stripe(escaped) } else { sum / steps as f64 };
+        let after = (sum + stripe(escaped)) / (steps + 1) as f64;
+        // 0 where the last z only just left the circle, 1 where one step took
+        // it as far out as it can go. Leaving it out of the mean by that much
+        // meets the orbits that stay inside a step longer where they only
+        // just leave.
+        let overshoot = escaped.norm().ln() / (bailout.ln() / 2.0);
+        let fraction = (overshoot.ln() / (self.power() as f64).ln()).clamp(0.0, 1.0);
+        Some(after + (before - after) * fraction)
+    }
+
+    /// Iterate the orbit of the pixel at `point` for at most `limit`
+    /// iterations, passing each value of `z` from the first iteration on to
+    /// `visit` until one leaves the circle of squared radius `bailout`, and
+    /// return that one, or `None` if none did. There is no check for cycles,
+    /// which would cut short the orbits that never escape, but only once
+    /// they have shown all they ever will.
+    pub(crate) fn visit_orbit(&self, point: Complex<f64>, limit: usize, bailout: f64,
+                              visit: impl FnMut(Complex<f64>)) -> Option<Complex<f64>>
+    {
+        let (z0, c) = self.start(point);
+        match *self {
+            Fractal::Mandelbrot | Fractal::Julia(_) => mapped_steps(z0, c, limit, bailout, visit, |z| z * z),
+            Fractal::BurningShip => mapped_steps(z0, c, limit, bailout, visit, burning_ship_square),
+            Fractal::Tricorn => mapped_steps(z0, c, limit, bailout, visit, |z| z.conj() * z.conj()),
+            Fractal::Multibrot(power) => mapped_steps(z0, c, limit, bailout, visit, |z| z.powu(power)),
+            Fractal::Newton(_) => mapped_steps(z0, c, limit, bailout, visit, newton_step),
+        }
+    }
+
+    /// Run `escape_time` for this fractal at four points at once.
+    pub fn escape_time_x4(&self, points: [Complex<f64>; 4], limit: usize, bailout: f64, epsilon: f64)
+        -> [Option<usize>; 4]
+    {
+        match *self {
+            Fractal::Mandelbrot => escape_time_x4(points, limit, bailout, epsilon),
+            Fractal::Julia(c) => julia_escape_time_x4(points, [c; 4], limit, bailout, epsilon),
+            Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) | Fractal::Newton(_) => {
+                self.orbit_x4(points, limit, bailout, epsilon)
+            }
+        }
+    }
+
+    /// Iterate the orbit of the pixel at `point`, as `julia_orbit` does. For
+    /// Newton's method the count is the iteration at which `z` converged,
+    /// as `newton_convergence` finds it, and `epsilon` is not used.
+    pub(crate) fn orbit(&self, point: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> EscapeResult
+    {
+        self.orbit_cycle(point, limit, bailout, epsilon).0
+    }
+
+    /// Like `orbit`, also returning the iteration at which the orbit was
+    /// abandoned as a cycle, if it was.
+    pub(crate) fn orbit_cycle(&self, point: Complex<f64>, limit: usize, bailout: f64, epsilon: f64)
+        -> (EscapeResult, Option<usize>)
+    {
+        let (z0, c) = self.start(point);
+        match *self {
+            Fractal::Mandelbrot | Fractal::Julia(_) => mapped_cycle(z0, c, limit, bailout, epsilon, |z| z * z),
+            Fractal::BurningShip => mapped_cycle(z0, c, limit, bailout, epsilon, burning_ship_square),
+            Fractal::Tricorn => mapped_cycle(z0, c, limit, bailout, epsilon, |z| z.conj() * z.conj()),
+            Fractal::Multibrot(power) => mapped_cycle(z0, c, limit, bailout, epsilon, |z| z.powu(power)),
+            Fractal::Newton(epsilon) => {
+                let (found, z) = newton_orbit(z0, limit, epsilon);
+                (EscapeResult { iterations: found.map(|found| found.iterations), final_z: z }, None)
+            }
+        }
+    }
+
+    /// The escape counts of four pixels. Only the Mandelbrot and Julia sets
+    /// have a four-lane loop; the variants run `orbit` on each point.
+    pub(crate) fn orbit_x4(&self, points: [Complex<f64>; 4], limit: usize, bailout: f64, epsilon: f64)
+        -> [Option<usize>; 4]
+    {
+        match *self {
+            Fractal::Mandelbrot | Fractal::Julia(_) => {
+                let starts = points.map(|point| self.start(point));
+                julia_orbit_x4(starts.map(|start| start.0), starts.map(|start| start.1), limit, bailout, epsilon)
+            }
+            Fractal::BurningShip | Fractal::Tricorn | Fractal::Multibrot(_) | Fractal::Newton(_) => {
+                points.map(|point| self.orbit(point, limit, bailout, epsilon).iterations)
+            }
+        }
+    }
+}
+
+
+/// Try to determine whether `c` is in the Mandelbrot set, using at most
+/// `limit` iterations. Returns the iteration at which the orbit of `c` left
+/// the circle whose squared radius is `bailout`, or `None` if it never did.
+///
+/// Any `bailout` of at least 4.0 (radius two) classifies points correctly;
+/// larger values only delay the escape, which smooth coloring and distance
+/// estimation rely on.
+///
+/// Points in the main cardioid or the period-2 bulb are answered at once
+/// without iterating; see `in_cardioid_or_bulb`. Every other orbit is checked
+/// for cycles as it is iterated, and given up on as `None` once `z` comes
+/// back within `epsilon` of a value it had before.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{escape_time, PERIODICITY_EPSILON};
+///
+/// assert_eq!(escape_time(Complex { re: 0.0, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert_eq!(escape_time(Complex { re: 2.0, im: 2.0 }, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+/// ```
+pub fn escape_time(c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+{
+    escape_time_full(c, limit, bailout, epsilon).0
+}
+
+/// Like `escape_time`, also returning `z` as the orbit ended: the first
+/// value past the bailout for points that escape, the last one iterated for
+/// those that do not, and `c` itself for points of the main cardioid or the
+/// period-2 bulb, which are not iterated at all. Colorings that read the end
+/// of the orbit can start from this rather than iterating it again.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{escape_time_full, PERIODICITY_EPSILON};
+///
+/// let ended = |c| escape_time_full(c, 255, 4.0, PERIODICITY_EPSILON);
+/// assert_eq!(ended(Complex { re: 1.0, im: 1.0 }), (Some(2), Complex { re: 1.0, im: 3.0 }));
+/// assert_eq!(ended(Complex { re: 0.0, im: 0.0 }), (None, Complex { re: 0.0, im: 0.0 }));
+/// ```
+pub fn escape_time_full(c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> (Option<usize>, Complex<f64>)
+{
+    if in_cardioid_or_bulb(c) {
+        return (None, c);
+    }
+    let escape = julia_orbit(Complex { re: 0.0, im: 0.0 }, c, limit, bailout, epsilon);
+    (escape.iterations, escape.final_z)
+}
+
+/// Iterate `z = z * z + c` starting from `z0` rather than the origin, which
+/// draws the Julia set for the constant `c` when `z0` ranges over the image.
+/// Returns the iteration at which `z` escaped, like `escape_time`, including
+/// its check for cycles.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{julia_escape_time, PERIODICITY_EPSILON};
+///
+/// let c = Complex { re: -0.5, im: 0.0 };
+/// assert_eq!(julia_escape_time(Complex { re: 0.0, im: 0.0 }, c, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert_eq!(julia_escape_time(Complex { re: 3.0, im: 0.0 }, c, 255, 4.0, PERIODICITY_EPSILON), Some(0));
+/// ```
+pub fn julia_escape_time(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+{
+    julia_orbit(z0, c, limit, bailout, epsilon).iterations
+}
+
+/// The Burning Ship: iterate `z = (|Re z| + i|Im z|)^2 + c` from the origin,
+/// folding `z` into the first quadrant before each squaring. Returns the
+/// escape iteration like `escape_time`, including its check for cycles. On
+/// the real axis the fold changes nothing, so there it agrees with the
+/// Mandelbrot set.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{burning_ship_escape_time, PERIODICITY_EPSILON};
+///
+/// assert_eq!(burning_ship_escape_time(Complex { re: -1.0, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert_eq!(burning_ship_escape_time(Complex { re: 2.0, im: 2.0 }, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+/// ```
+pub fn burning_ship_escape_time(c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+{
+    Fractal::BurningShip.orbit(c, limit, bailout, epsilon).iterations
+}
+
+/// The Tricorn, or Mandelbar: iterate `z = conj(z)^2 + c` from the origin.
+/// Returns the escape iteration like `escape_time`, including its check for
+/// cycles.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{tricorn_escape_time, PERIODICITY_EPSILON};
+///
+/// assert_eq!(tricorn_escape_time(Complex { re: 0.0, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert_eq!(tricorn_escape_time(Complex { re: 2.0, im: 2.0 }, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+/// ```
+pub fn tricorn_escape_time(c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+{
+    Fractal::Tricorn.orbit(c, limit, bailout, epsilon).iterations
+}
+
+/// The Multibrot set of `power`: iterate `z = z^power + c` from the origin,
+/// raising `z` to the power by repeated squaring. Returns the escape
+/// iteration like `escape_time`, including its check for cycles, though not
+/// its shortcut for the main cardioid, whose shape only holds at power 2.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{multibrot_escape_time, PERIODICITY_EPSILON};
+///
+/// assert_eq!(multibrot_escape_time(Complex { re: 0.0, im: 0.0 }, 3, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert_eq!(multibrot_escape_time(Complex { re: 2.0, im: 2.0 }, 3, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+/// ```
+pub fn multibrot_escape_time(c: Complex<f64>, power: u32, limit: usize, bailout: f64, epsilon: f64) -> Option<usize>
+{
+    let z0 = Complex { re: 0.0, im: 0.0 };
+    mapped_orbit(z0, c, limit, bailout, epsilon, |z| z.powu(power)).iterations
+}
+
+/// Run Newton's method for `z^3 - 1` from `z0`, `z = z - (z^3 - 1) / 3z^2`,
+/// for at most `limit` steps. Returns the root `z` came within `epsilon` of
+/// and how many steps that took, or `None` if it never settled, as happens
+/// on the boundaries between the basins of the three roots, or if `z` hit
+/// zero, where the step is undefined.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{Convergence, newton_convergence};
+///
+/// let on_the_root = Some(Convergence { root: 0, iterations: 0 });
+/// assert_eq!(newton_convergence(Complex { re: 1.0, im: 0.0 }, 50, 1e-6), on_the_root);
+/// assert_eq!(newton_convergence(Complex { re: -2.0, im: 0.0 }, 50, 1e-6).unwrap().root, 0);
+/// assert_eq!(newton_convergence(Complex { re: 0.0, im: 0.0 }, 50, 1e-6), None);
+/// ```
+pub fn newton_convergence(z0: Complex<f64>, limit: usize, epsilon: f64) -> Option<Convergence>
+{
+    newton_orbit(z0, limit, epsilon).0
+}
+
+/// Like `newton_convergence`, also returning the last value of `z`.
+fn newton_orbit(z0: Complex<f64>, limit: usize, epsilon: f64) -> (Option<Convergence>, Complex<f64>)
+{
+    let mut z = z0;
+    for iterations in 0..limit {
+        if let Some(root) = NEWTON_ROOTS.iter().position(|&root| (z - root).norm_sqr() < epsilon * epsilon) {
+            return (Some(Convergence { root, iterations }), z);
+        }
+        if !z.is_finite() {
+            break;
+        }
+        z = newton_step(z);
+    }
+    (None, z)
+}
+
+/// One step of Newton's method for `z^3 - 1`.
+fn newton_step(z: Complex<f64>) -> Complex<f64>
+{
+    z - (z * z * z - 1.0) / (3.0 * z * z)
+}
+
+/// Whether `c` lies in the main cardioid or the period-2 bulb of the
+/// Mandelbrot set. Such points never escape, and together they make up most
+/// of the set's interior, so testing for them first saves running the
+/// iteration all the way to the limit.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::in_cardioid_or_bulb;
+///
+/// assert!(in_cardioid_or_bulb(Complex { re: 0.0, im: 0.0 }));
+/// assert!(in_cardioid_or_bulb(Complex { re: -1.0, im: 0.0 }));
+/// assert!(!in_cardioid_or_bulb(Complex { re: 0.3, im: 0.0 }));
+/// ```
+pub fn in_cardioid_or_bulb(c: Complex<f64>) -> bool
+{
+    let x = c.re - 0.25;
+    let q = x * x + c.im * c.im;
+    let in_cardioid = q * (q + x) <= 0.25 * c.im * c.im;
+    let in_bulb = (c.re + 1.0) * (c.re + 1.0) + c.im * c.im <= 0.0625;
+    in_cardioid || in_bulb
+}
+
+/// Like `escape_time`, but returns the normalized iteration count
+/// `n + 1 - ln(ln|z|) / ln 2`, which varies continuously across the image
+/// instead of jumping from one integer to the next. The result is not clamped
+/// and can fall slightly outside `0..limit`. A large `bailout` makes it more
+/// accurate.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{escape_time_smooth, PERIODICITY_EPSILON};
+///
+/// assert_eq!(escape_time_smooth(Complex { re: 0.0, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON), None);
+/// assert!(escape_time_smooth(Complex { re: 0.3, im: 0.0 }, 255, 1e6, PERIODICITY_EPSILON).is_some());
+/// ```
+pub fn escape_time_smooth(c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64) -> Option<f64>
+{
+    if in_cardioid_or_bulb(c) {
+        return None;
+    }
+    julia_escape_time_smooth(Complex { re: 0.0, im: 0.0 }, c, limit, bailout, epsilon)
+}
+
+/// The smooth counterpart of `julia_escape_time`.
+pub fn julia_escape_time_smooth(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64)
+    -> Option<f64>
+{
+    julia_orbit(z0, c, limit, bailout, epsilon).smooth_count(2)
+}
+
+/// Estimate how far `c` lies from the Mandelbrot set, using at most `limit`
+/// iterations. Alongside `z` the orbit tracks its derivative with respect to
+/// `c`, `dz = 2 * z * dz + 1`, and once `z` escapes `distance_estimate`
+/// turns the two into a distance. Returns `None` for points that never escape.
+///
+/// The estimate is within a small factor of the true distance, and most
+/// accurate with a large `bailout` such as `SMOOTH_BAILOUT`. Unlike the
+/// escape count it shrinks smoothly towards 0 close to the set, which is what
+/// draws its thin filaments.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::escape_distance;
+///
+/// assert_eq!(escape_distance(Complex { re: -0.5, im: 0.0 }, 1000, 1e6), None);
+/// // The nearest point of the set to 0.3 is on the main cardioid, 0.0204 away.
+/// let distance = escape_distance(Complex { re: 0.3, im: 0.0 }, 1000, 1e6).unwrap();
+/// assert!((distance - 0.0204).abs() < 0.001);
+/// ```
+pub fn escape_distance(c: Complex<f64>, limit: usize, bailout: f64) -> Option<f64>
+{
+    if in_cardioid_or_bulb(c) {
+        return None;
+    }
+    match escape_time_with_derivative(c, limit, bailout) {
+        (Some(count), z, dz) => Some(distance_estimate(z, dz, count - 1)),
+        (None, _, _) => None,
+    }
+}
+
+/// The counterpart of `escape_distance` for the Julia set of `c`, where the
+/// derivative is taken with respect to the starting point `z0` instead.
+pub fn julia_escape_distance(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64) -> Option<f64>
+{
+    match derivative_orbit(z0, Complex { re: 1.0, im: 0.0 }, c, 0.0, limit, bailout) {
+        (Some(count), z, dz) => Some(distance_estimate(z, dz, count)),
+        (None, _, _) => None,
+    }
+}
+
+/// Iterate `z = z * z + c` from 0 for at most `limit` iterations, together
+/// with its derivative with respect to `c`, `dz = 2 * z * dz + 1`. Returns
+/// the escape count, `None` if `z` never escaped, along with the last `z`
+/// and `dz`. The count is that of `escape_time`, except that there is no
+/// check for the main cardioid or for cycles.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{distance_estimate, escape_distance, escape_time, escape_time_with_derivative,
+///                      PERIODICITY_EPSILON};
+///
+/// let c = Complex { re: 0.5, im: 0.0 };
+/// let (count, z, dz) = escape_time_with_derivative(c, 1000, 1e6);
+/// assert_eq!(count, escape_time(c, 1000, 1e6, PERIODICITY_EPSILON));
+/// assert_eq!(Some(distance_estimate(z, dz, count.unwrap() - 1)), escape_distance(c, 1000, 1e6));
+/// ```
+pub fn escape_time_with_derivative(c: Complex<f64>, limit: usize, bailout: f64)
+    -> (Option<usize>, Complex<f64>, Complex<f64>)
+{
+    derivative_orbit(Complex { re: 0.0, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, c, 1.0, limit, bailout)
+}
+
+/// The distance from the set that an orbit which escaped at `z` with
+/// derivative `dz` after `squarings` squarings started at: its escape count
+/// for a Julia set, one less for the Mandelbrot set, whose first step only
+/// goes from 0 to `c`.
+///
+/// Close to the set this is about `2 * |z| * ln|z| / |dz|`, which grows too
+/// fast further out. The estimate is `(1 - e^(-2 * g)) / |g'|` for the set's
+/// Green's function `g = ln|z| / 2^squarings`, which tends to both the
+/// formula above near the set and to the true distance far from it, and by
+/// Koebe's quarter theorem is never more than four times that distance.
+pub fn distance_estimate(z: Complex<f64>, dz: Complex<f64>, squarings: usize) -> f64
+{
+    let radius = z.norm();
+    let near = 2.0 * radius * radius.ln() / dz.norm();
+    // 2^squarings overflows long before deep orbits escape, where g is 0 and
+    // the near formula exact.
+    let twice_green = 2.0 * radius.ln() * 0.5f64.powi(squarings.min(2048) as i32);
+    if twice_green == 0.0 {
+        near
+    } else {
+        near * -(-twice_green).exp_m1() / twice_green
+    }
+}
+
+/// The smallest distance from `trap` to the orbit of `z = z * z + c` from
+/// the origin, over the values of `z` from the first iteration on until it
+/// escapes or `limit` iterations have run. Points inside the set have an
+/// orbit too, so unlike the escape count this varies over the interior.
+/// Infinite if `z` escapes at once.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{Trap, escape_time_orbit_trap};
+///
+/// let origin = Trap::Point(Complex { re: 0.0, im: 0.0 });
+/// // The orbit of -1 is -1, 0, -1, 0, ...
+/// assert_eq!(escape_time_orbit_trap(Complex { re: -1.0, im: 0.0 }, 255, 4.0, origin), 0.0);
+/// assert_eq!(escape_time_orbit_trap(Complex { re: 2.0, im: 0.0 }, 255, 4.0, origin), 2.0);
+/// ```
+pub fn escape_time_orbit_trap(c: Complex<f64>, limit: usize, bailout: f64, trap: Trap) -> f64
+{
+    Fractal::Mandelbrot.orbit_trap(c, limit, bailout, &trap)
+}
+
+/// Iterate `z = map(z) + c` from `z0` as `Fractal::visit_orbit` describes.
+fn mapped_steps(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, mut visit: impl FnMut(Complex<f64>),
+                map: impl Fn(Complex<f64>) -> Complex<f64>) -> Option<Complex<f64>>
+{
+    let mut z = z0;
+    for _ in 0..limit {
+        z = map(z) + c;
+        if z.norm_sqr() > bailout {
+            return Some(z);
+        }
+        visit(z);
+    }
+    None
+}
+
+/// Iterate `z = z * z + c` and `dz = 2 * z * dz + dc` from `z0` and `dz0`
+/// until `z` escapes, and return the escape count and the last `z` and `dz`.
+/// There is no check for cycles: near the set the derivative is still
+/// growing when the values come back around.
+fn derivative_orbit(z0: Complex<f64>, dz0: Complex<f64>, c: Complex<f64>, dc: f64, limit: usize, bailout: f64)
+    -> (Option<usize>, Complex<f64>, Complex<f64>)
+{
+    let (mut z, mut dz) = (z0, dz0);
+    for i in 0..limit {
+        if z.norm_sqr() > bailout {
+            return (Some(i), z, dz);
+        }
+        dz = 2.0 * z * dz + dc;
+        z = z * z + c;
+    }
+    (None, z, dz)
+}
+
+/// Run `escape_time` on four points at once. The orbits are iterated side by
+/// side in plain `[f64; 4]` lanes, which the compiler can turn into vector
+/// instructions, until every lane has escaped or `limit` is reached. The
+/// results are exactly those of four separate `escape_time` calls.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{escape_time_x4, PERIODICITY_EPSILON};
+///
+/// let points = [Complex { re: 0.0, im: 0.0 }, Complex { re: 2.0, im: 2.0 },
+///               Complex { re: 0.3, im: 0.0 }, Complex { re: -1.0, im: 0.0 }];
+/// assert_eq!(escape_time_x4(points, 255, 4.0, PERIODICITY_EPSILON), [None, Some(1), Some(12), None]);
+/// ```
+pub fn escape_time_x4(points: [Complex<f64>; 4], limit: usize, bailout: f64, epsilon: f64) -> [Option<usize>; 4]
+{
+    if points.iter().all(|&point| in_cardioid_or_bulb(point)) {
+        return [None; 4];
+    }
+    julia_escape_time_x4([Complex { re: 0.0, im: 0.0 }; 4], points, limit, bailout, epsilon)
+}
+
+/// Run `escape_time` on the four points `cs` at once with the usual bailout
+/// of radius two and `PERIODICITY_EPSILON`, as `escape_time_x4` does.
+/// `escape_time` stays the scalar version, and gives the same counts.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{escape_time, escape_time_simd, PERIODICITY_EPSILON};
+///
+/// let cs = [Complex { re: 0.3, im: 0.0 }, Complex { re: -0.75, im: 0.1 },
+///           Complex { re: 1.0, im: 1.0 }, Complex { re: -2.0, im: 0.0 }];
+/// assert_eq!(escape_time_simd(cs, 255), cs.map(|c| escape_time(c, 255, 4.0, PERIODICITY_EPSILON)));
+/// ```
+pub fn escape_time_simd(cs: [Complex<f64>; 4], limit: usize) -> [Option<usize>; 4]
+{
+    escape_time_x4(cs, limit, crate::LIMIT_TO_CALL_IT_OFF_TO_INFINITY, PERIODICITY_EPSILON)
+}
+
+/// The four-lane counterpart of `julia_escape_time`, iterating each `z0[i]`
+/// with its own constant `c[i]`.
+pub fn julia_escape_time_x4(z0: [Complex<f64>; 4], c: [Complex<f64>; 4], limit: usize, bailout: f64, epsilon: f64)
+    -> [Option<usize>; 4]
+{
+    julia_orbit_x4(z0, c, limit, bailout, epsilon)
+}
+
+/// The four-lane counterpart of `julia_orbit`, returning only the counts.
+pub(crate) fn julia_orbit_x4(z0: [Complex<f64>; 4], c: [Complex<f64>; 4], limit: usize, bailout: f64, epsilon: f64)
+    -> [Option<usize>; 4]
+{
+    let (mut re, mut im) = (z0.map(|z| z.re), z0.map(|z| z.im));
+    let (c_re, c_im) = (c.map(|c| c.re), c.map(|c| c.im));
+    let mut counts = [None; 4];
+    let mut done = [false; 4];
+    let mut cycle = Cycle::new(z0);
+
+    for i in 0..limit {
+        for lane in 0..4 {
+            if !done[lane] && re[lane] * re[lane] + im[lane] * im[lane] > bailout {
+                counts[lane] = Some(i);
+                done[lane] = true;
+            }
+        }
+        if done.iter().all(|&done| done) {
+            break;
+        }
+        // The same operations, in the same order, as `z * z + c` on a
+        // `Complex<f64>`, so that every lane rounds exactly like the scalar
+        // loop. Lanes that are done keep iterating harmlessly.
+        let (old_re, old_im) = (re, im);
+        for lane in 0..4 {
+            re[lane] = old_re[lane] * old_re[lane] - old_im[lane] * old_im[lane] + c_re[lane];
+            im[lane] = old_re[lane] * old_im[lane] + old_im[lane] * old_re[lane] + c_im[lane];
+        }
+        if epsilon > 0.0 {
+            let z = [0, 1, 2, 3].map(|lane| Complex { re: re[lane], im: im[lane] });
+            for lane in 0..4 {
+                if !done[lane] && cycle.revisits(lane, z[lane], epsilon) {
+                    done[lane] = true;
+                }
+            }
+            cycle.advance(z);
+        }
+    }
+
+    counts
+}
+
+/// Iterate from `z0` until `z` escapes or `limit` is reached, returning the
+/// escape iteration along with the last value of `z`. Unless `epsilon` is
+/// zero, the orbit is also abandoned as `None` as soon as `z` comes within
+/// `epsilon` of a value it had before.
+pub(crate) fn julia_orbit(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64)
+    -> EscapeResult
+{
+    mapped_orbit(z0, c, limit, bailout, epsilon, |z| z * z)
+}
+
+/// `z` folded into the first quadrant and squared, the step of the Burning
+/// Ship.
+fn burning_ship_square(z: Complex<f64>) -> Complex<f64>
+{
+    let folded = Complex { re: z.re.abs(), im: z.im.abs() };
+    folded * folded
+}
+
+/// Like `julia_orbit`, but iterating `z = map(z) + c`, which is how the
+/// Burning Ship, the Tricorn and the Multibrot sets differ from the
+/// Mandelbrot set.
+fn mapped_orbit(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64,
+                map: impl Fn(Complex<f64>) -> Complex<f64>) -> EscapeResult
+{
+    mapped_cycle(z0, c, limit, bailout, epsilon, map).0
+}
+
+/// Like `mapped_orbit`, also returning how many steps the orbit took before
+/// it was abandoned as a cycle, if it was.
+fn mapped_cycle(z0: Complex<f64>, c: Complex<f64>, limit: usize, bailout: f64, epsilon: f64,
+                map: impl Fn(Complex<f64>) -> Complex<f64>) -> (EscapeResult, Option<usize>)
+{
+    let mut z = z0;
+    let mut cycle = Cycle::new([z0]);
+
+    for i in 0..limit {
+        if z.norm_sqr() > bailout {
+            return (EscapeResult { iterations: Some(i), final_z: z }, None);
+        }
+        z = map(z) + c;
+        if epsilon > 0.0 {
+            if cycle.revisits(0, z, epsilon) {
+                return (EscapeResult { iterations: None, final_z: z }, Some(i + 1));
+            }
+            cycle.advance([z]);
+        }
+    }
+
+    (EscapeResult { iterations: None, final_z: z }, None)
+}
+
+/// Brent-style cycle detection for `LANES` orbits iterated in step: each
+/// orbit is compared against a saved value of `z`, which is replaced after
+/// 1, 2, 4, 8, ... further iterations, so a cycle of any period is caught
+/// within a few times its length.
+struct Cycle<const LANES: usize> {
+    saved: [Complex<f64>; LANES],
+    steps: usize,
+    period: usize,
+}
+
+impl<const LANES: usize> Cycle<LANES> {
+    fn new(start: [Complex<f64>; LANES]) -> Cycle<LANES>
+    {
+        Cycle { saved: start, steps: 0, period: 1 }
+    }
+
+    /// Whether `z`, the newest value of orbit `lane`, is within `epsilon` of
+    /// the saved one.
+    fn revisits(&self, lane: usize, z: Complex<f64>, epsilon: f64) -> bool
+    {
+        let saved = self.saved[lane];
+        (z.re - saved.re).abs() <= epsilon && (z.im - saved.im).abs() <= epsilon
+    }
+
+    /// Count one more iteration, saving `z` when the current stretch is over.
+    fn advance(&mut self, z: [Complex<f64>; LANES])
+    {
+        self.steps += 1;
+        if self.steps == self.period {
+            self.saved = z;
+            self.steps = 0;
+            self.period *= 2;
+        }
+    }
+}
+
+
+#[test]
+fn test_escape_time_bailout() {
+    let c = Complex { re: 2.1, im: 0.0 };
+    let near = escape_time(c, 255, 4.0, PERIODICITY_EPSILON).unwrap();
+    let far = escape_time(c, 255, 1e6, PERIODICITY_EPSILON).unwrap();
+    assert_eq!(near, 1);
+    assert!(near < far);
+    assert_eq!(escape_time(Complex { re: -1.0, im: 0.0 }, 255, 1e6, PERIODICITY_EPSILON), None);
+}
+
+#[test]
+fn test_larger_bailout_never_escapes_sooner() {
+    for row in 0..40 {
+        for column in 0..60 {
+            let c = Complex { re: -2.2 + column as f64 * 0.05, im: -1.0 + row as f64 * 0.05 };
+            let default = escape_time(c, 500, 4.0, PERIODICITY_EPSILON);
+            for bailout in [16.0, 65536.0, 1e12] {
+                let larger = escape_time(c, 500, bailout, PERIODICITY_EPSILON);
+                match (default, larger) {
+                    (Some(near), Some(far)) => assert!(near <= far, "{} at bailout {}", c, bailout),
+                    // A later escape can run past the limit instead.
+                    (Some(_), None) => {}
+                    (None, larger) => assert_eq!(larger, None, "{} at bailout {}", c, bailout),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_trap_distance() {
+    let z = Complex { re: 3.0, im: 4.0 };
+    assert_eq!(Trap::Point(Complex { re: 0.0, im: 0.0 }).distance(z), 5.0);
+    assert_eq!(Trap::Point(Complex { re: 3.0, im: 1.0 }).distance(z), 3.0);
+    // The real axis, and the diagonal through 0 and 1 + i.
+    assert_eq!(Trap::Line(Complex { re: -1.0, im: 0.0 }, Complex { re: 1.0, im: 0.0 }).distance(z), 4.0);
+    let diagonal = Trap::Line(Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: 1.0 });
+    assert!((diagonal.distance(z) - 0.5f64.sqrt()).abs() < 1e-12);
+    assert_eq!(Trap::Circle(Complex { re: 0.0, im: 0.0 }, 2.0).distance(z), 3.0);
+    assert_eq!(Trap::Circle(Complex { re: 0.0, im: 0.0 }, 7.0).distance(z), 2.0);
+    assert_eq!(Trap::Cross(Complex { re: 0.0, im: 0.0 }).distance(z), 3.0);
+    assert_eq!(Trap::Cross(Complex { re: 1.0, im: 3.5 }).distance(z), 0.5);
+}
+
+#[test]
+fn test_orbit_trap() {
+    let minus_one = Complex { re: -1.0, im: 0.0 };
+    assert_eq!(escape_time_orbit_trap(minus_one, 100, 4.0, Trap::Point(Complex { re: 1.0, im: 0.0 })), 1.0);
+    assert_eq!(escape_time_orbit_trap(minus_one, 100, 4.0, Trap::Circle(Complex { re: 0.0, im: 0.0 }, 1.0)), 0.0);
+    // 3 escapes at the first iteration.
+    assert_eq!(escape_time_orbit_trap(Complex { re: 3.0, im: 0.0 }, 100, 4.0, Trap::default()), f64::INFINITY);
+    // The orbit of i is i, -1 + i, -i, -1 + i, ...
+    let i = Complex { re: 0.0, im: 1.0 };
+    assert_eq!(escape_time_orbit_trap(i, 100, 4.0, Trap::Point(Complex { re: -1.0, im: 0.0 })), 1.0);
+    assert_eq!(Fractal::Mandelbrot.orbit_trap(i, 100, 4.0, &Trap::default()), 1.0);
+    // For the Burning Ship, i runs to -1 + i, then 3i, which has escaped.
+    assert_eq!(Fractal::BurningShip.orbit_trap(i, 100, 4.0, &Trap::default()), 1.0);
+    assert_eq!(Fractal::BurningShip.orbit_trap(i, 100, 4.0, &Trap::Point(Complex { re: -1.0, im: 1.0 })), 0.0);
+    // The Julia set of 0 leaves points on the unit circle on it.
+    let julia = Fractal::Julia(Complex { re: 0.0, im: 0.0 });
+    let unit = Trap::Circle(Complex { re: 0.0, im: 0.0 }, 1.0);
+    assert!(julia.orbit_trap(Complex { re: 0.6, im: 0.8 }, 50, 4.0, &unit) < 1e-12);
+}
+
+#[test]
+fn test_variants() {
+    let grid = (0..30).flat_map(|row| (0..40).map(move |column| {
+        Complex { re: -2.2 + column as f64 * 0.1, im: -1.5 + row as f64 * 0.1 }
+    }));
+    for c in grid {
+        // The Tricorn is symmetric about the real axis.
+        assert_eq!(tricorn_escape_time(c, 500, 4.0, PERIODICITY_EPSILON),
+                   tricorn_escape_time(c.conj(), 500, 4.0, PERIODICITY_EPSILON), "{}", c);
+    }
+
+    for re in [-1.9, -1.78, -1.5, -0.5, 0.1, 0.26, 0.4] {
+        let c = Complex { re, im: 0.0 };
+        assert_eq!(burning_ship_escape_time(c, 1000, 4.0, PERIODICITY_EPSILON),
+                   escape_time(c, 1000, 4.0, PERIODICITY_EPSILON), "{}", c);
+    }
+
+    let c = Complex { re: 0.2, im: 0.5 };
+    assert_eq!(escape_time(c, 255, 4.0, PERIODICITY_EPSILON), None);
+    assert_eq!(burning_ship_escape_time(c, 255, 4.0, PERIODICITY_EPSILON), Some(9));
+    assert_eq!(tricorn_escape_time(c, 255, 4.0, PERIODICITY_EPSILON), Some(20));
+    for fractal in [Fractal::BurningShip, Fractal::Tricorn] {
+        assert_eq!(fractal.escape_time_x4([c; 4], 255, 4.0, PERIODICITY_EPSILON),
+                   [fractal.escape_time(c, 255, 4.0, PERIODICITY_EPSILON); 4]);
+    }
+}
+
+#[test]
+fn test_interior_shortcut() {
+    let origin = Complex { re: 0.0, im: 0.0 };
+    for c in [Complex { re: -0.1, im: 0.1 }, Complex { re: -1.1, im: 0.1 }, Complex { re: 0.2, im: 0.5 }] {
+        assert!(in_cardioid_or_bulb(c));
+        assert_eq!(escape_time(c, 10_000, 4.0, PERIODICITY_EPSILON), None);
+        assert_eq!(julia_escape_time(origin, c, 10_000, 4.0, PERIODICITY_EPSILON), None);
+    }
+
+    // Points just outside, and inside other bulbs, still go through the loop.
+    for c in [Complex { re: 0.26, im: 0.0 }, Complex { re: -1.26, im: 0.0 }, Complex { re: -0.12, im: 0.75 }] {
+        assert!(!in_cardioid_or_bulb(c));
+        assert_eq!(escape_time(c, 1000, 4.0, PERIODICITY_EPSILON),
+                   julia_escape_time(origin, c, 1000, 4.0, PERIODICITY_EPSILON));
+    }
+}
+
+#[test]
+fn test_periodicity_check() {
+    let origin = Complex { re: 0.0, im: 0.0 };
+    // Interior points outside the cardioid and the period-2 bulb: in the
+    // period-3 bulb, and in the period-4 bulb on the real axis.
+    for c in [Complex { re: -0.12, im: 0.75 }, Complex { re: -1.31, im: 0.0 }] {
+        assert!(!in_cardioid_or_bulb(c));
+        assert_eq!(julia_orbit(origin, c, 100_000, 4.0, PERIODICITY_EPSILON).iterations, None);
+        assert_eq!(julia_orbit(origin, c, 100_000, 4.0, 0.0).iterations, None);
+    }
+
+    // Counting the steps as they are taken: the period-3 point gives up
+    // within a few hundred of them, far short of the limit.
+    let steps = std::cell::Cell::new(0);
+    let c = Complex { re: -0.12, im: 0.75 };
+    let counted = |z: Complex<f64>| {
+        steps.set(steps.get() + 1);
+        z * z
+    };
+    assert_eq!(mapped_orbit(origin, c, 100_000, 4.0, PERIODICITY_EPSILON, counted).iterations, None);
+    assert!(steps.get() < 1000, "{} steps", steps.get());
+    assert_eq!(Fractal::Mandelbrot.orbit_cycle(c, 100_000, 4.0, PERIODICITY_EPSILON).1, Some(steps.get()));
+    steps.set(0);
+    mapped_orbit(origin, c, 100_000, 4.0, 0.0, counted);
+    assert_eq!(steps.get(), 100_000);
+
+    // Escaping points are unaffected.
+    for step in 0..100 {
+        let c = Complex { re: -2.0 + step as f64 * 0.03, im: 0.6 };
+        assert_eq!(julia_orbit(origin, c, 1000, 4.0, PERIODICITY_EPSILON).iterations,
+                   julia_orbit(origin, c, 1000, 4.0, 0.0).iterations);
+    }
+}
+
+#[test]
+fn test_julia_escape_time() {
+    let c = Complex { re: -0.8, im: 0.156 };
+    assert_eq!(julia_escape_time(Complex { re: 0.0, im: 0.0 }, Complex { re: 0.3, im: 0.0 }, 255, 4.0,
+                                 PERIODICITY_EPSILON),
+               escape_time(Complex { re: 0.3, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON));
+    assert_eq!(julia_escape_time(Complex { re: 1.9, im: 0.0 }, c, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+    assert_eq!(Fractal::Julia(c).escape_time(Complex { re: 1.9, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON), Some(1));
+}
+
+#[test]
+fn test_escape() {
+    let escape = Fractal::Mandelbrot.escape(Complex { re: 1.0, im: -1.0 }, 255, 4.0, PERIODICITY_EPSILON);
+    assert_eq!(escape, EscapeResult { iterations: Some(2), final_z: Complex { re: 1.0, im: -3.0 } });
+    assert_eq!(escape.upper_half(), Some(false));
+    let real = Fractal::Mandelbrot.escape(Complex { re: 1.0, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON);
+    assert_eq!((real.iterations, real.upper_half()), (Some(3), Some(true)));
+    let inside = Fractal::Mandelbrot.escape(Complex { re: -0.1, im: 0.1 }, 255, 4.0, PERIODICITY_EPSILON);
+    assert_eq!((inside.upper_half(), inside.smooth_count(2)), (None, None));
+
+    // The smooth count is that of the orbit the result describes.
+    let c = Complex { re: 0.3, im: 0.0 };
+    assert_eq!(Fractal::Mandelbrot.escape(c, 255, 1e6, PERIODICITY_EPSILON).smooth_count(2),
+               escape_time_smooth(c, 255, 1e6, PERIODICITY_EPSILON));
+    assert_eq!(Fractal::Tricorn.escape(c, 255, 4.0, PERIODICITY_EPSILON).iterations,
+               tricorn_escape_time(c, 255, 4.0, PERIODICITY_EPSILON));
+
+    // The cycle check uses the epsilon it is given: one so loose that the
+    // slow orbit of c seems to come back gives up on it, and none at all
+    // follows it out.
+    assert_eq!(Fractal::Mandelbrot.escape(c, 255, 4.0, 1.0).iterations, None);
+    assert_eq!(Fractal::Mandelbrot.escape(c, 255, 4.0, 0.0).iterations, escape_time(c, 255, 4.0, PERIODICITY_EPSILON));
+}
+
+#[test]
+fn test_escape_time_full() {
+    for row in 0..40 {
+        for column in 0..60 {
+            let c = Complex { re: -2.2 + column as f64 * 0.05, im: 1.2 - row as f64 * 0.06 };
+            for (limit, bailout) in [(255, 4.0), (1000, 1e6), (3, 4.0)] {
+                let (count, z) = escape_time_full(c, limit, bailout, PERIODICITY_EPSILON);
+                assert_eq!(count, escape_time(c, limit, bailout, PERIODICITY_EPSILON), "{}", c);
+                assert_eq!((count, z), {
+                    let escape = Fractal::Mandelbrot.escape(c, limit, bailout, PERIODICITY_EPSILON);
+                    (escape.iterations, escape.final_z)
+                }, "{}", c);
+                if count.is_some() {
+                    assert!(z.norm_sqr() > bailout, "{} ended at {}", c, z);
+                }
+            }
+        }
+    }
+    // An orbit that never escapes ends on one of its own values, here of
+    // the cycle 0, -1, 0, -1...
+    let (count, z) = escape_time_full(Complex { re: -1.0, im: 0.0 }, 10, 4.0, PERIODICITY_EPSILON);
+    assert!(count.is_none() && (z == Complex { re: 0.0, im: 0.0 } || z == Complex { re: -1.0, im: 0.0 }), "{}", z);
+    assert_eq!(escape_time_full(Complex { re: 0.1, im: 0.1 }, 255, 4.0, PERIODICITY_EPSILON),
+               (None, Complex { re: 0.1, im: 0.1 }));
+}
+
+#[test]
+fn test_escape_time_smooth() {
+    let expected = 2.0 - 2.1f64.ln().ln() / 2f64.ln();
+    let smooth = escape_time_smooth(Complex { re: 2.1, im: 0.0 }, 255, 4.0, PERIODICITY_EPSILON).unwrap();
+    assert!((smooth - expected).abs() < 1e-12);
+    assert_eq!(escape_time_smooth(Complex { re: -1.0, im: 0.0 }, 1000, 1e6, PERIODICITY_EPSILON), None);
+    let c = Complex { re: 0.3, im: 0.0 };
+    let smooth = escape_time_smooth(c, 255, 1e6, PERIODICITY_EPSILON).unwrap();
+    assert!((smooth - 13.288842201776895).abs() < 1e-9);
+    assert!(smooth > escape_time(c, 255, 4.0, PERIODICITY_EPSILON).unwrap() as f64);
+
+    let mut jumps = 0;
+    for step in 0..1000 {
+        let (a, b) = (Complex { re: 0.3 + step as f64 * 1e-4, im: 0.0 },
+                      Complex { re: 0.3 + (step + 1) as f64 * 1e-4, im: 0.0 });
+        if escape_time(a, 1000, 1e6, PERIODICITY_EPSILON) != escape_time(b, 1000, 1e6, PERIODICITY_EPSILON) {
+            jumps += 1;
+        }
+        let (smooth_a, smooth_b) = (escape_time_smooth(a, 1000, 1e6, PERIODICITY_EPSILON).unwrap(),
+                                    escape_time_smooth(b, 1000, 1e6, PERIODICITY_EPSILON).unwrap());
+        assert!((smooth_a - smooth_b).abs() < 0.5, "{} jumps to {}", smooth_a, smooth_b);
+    }
+    assert!(jumps > 0);
+}
+
+#[test]
+fn test_escape_time_simd_matches_scalar() {
+    // A grid over the whole set, taken four points of a row at a time, with
+    // limits that stop some orbits short.
+    for limit in [1, 20, 1000] {
+        for row in 0..60 {
+            for column in (0..80).step_by(4) {
+                let cs = [0, 1, 2, 3].map(|lane| {
+                    Complex { re: -2.2 + (column + lane) as f64 * 0.04, im: 1.3 - row as f64 * 0.044 }
+                });
+                assert_eq!(escape_time_simd(cs, limit), cs.map(|c| escape_time(c, limit, 4.0, PERIODICITY_EPSILON)),
+                           "{:?}", cs);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_escape_time_x4_matches_scalar() {
+    let c = Complex { re: -0.8, im: 0.156 };
+    for row in 0..32 {
+        for column in (0..32).step_by(4) {
+            let points = [0, 1, 2, 3].map(|lane| {
+                Complex { re: -2.0 + (column + lane) as f64 * 0.09, im: 1.2 - row as f64 * 0.075 }
+            });
+            assert_eq!(escape_time_x4(points, 300, 4.0, PERIODICITY_EPSILON),
+                       points.map(|point| escape_time(point, 300, 4.0, PERIODICITY_EPSILON)));
+            assert_eq!(julia_orbit_x4([Complex { re: 0.0, im: 0.0 }; 4], points, 300, 4.0, 0.0),
+                       points.map(|point| julia_orbit(Complex { re: 0.0, im: 0.0 }, point, 300, 4.0, 0.0).iterations));
+            assert_eq!(Fractal::Julia(c).escape_time_x4(points, 300, 1e6, PERIODICITY_EPSILON),
+                       points.map(|point| julia_escape_time(point, c, 300, 1e6, PERIODICITY_EPSILON)));
+        }
+    }
+}
+
+#[test]
+fn test_escape_distance() {
+    // Along the real axis past the cusp at 0.25 the estimate grows with the
+    // true distance.
+    let distances: Vec<f64> = [0.3, 0.5, 1.0, 2.0].iter()
+        .map(|&re| escape_distance(Complex { re, im: 0.0 }, 1000, 1e10).unwrap())
+        .collect();
+    assert!(distances.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", distances);
+    assert_eq!(escape_distance(Complex { re: -1.0, im: 0.0 }, 1000, 1e10), None);
+    assert_eq!(Fractal::Mandelbrot.escape_distance(Complex { re: 0.5, im: 0.0 }, 1000, 1e10), Some(distances[1]));
+    assert_eq!(Fractal::Tricorn.escape_distance(Complex { re: 0.5, im: 0.0 }, 1000, 1e10), None);
+
+    // The Julia set of 0 is the unit circle, whose Green's function is ln|z|,
+    // so from 2 the estimate works out to exactly (1 - 1/4) * 2.
+    let unit_circle = Fractal::Julia(Complex { re: 0.0, im: 0.0 });
+    let julia = unit_circle.escape_distance(Complex { re: 2.0, im: 0.0 }, 100, 1e10).unwrap();
+    assert!((julia - 1.5).abs() < 1e-9, "{}", julia);
+    assert_eq!(julia_escape_distance(Complex { re: 0.5, im: 0.0 }, Complex { re: 0.0, im: 0.0 }, 100, 1e10), None);
+}
+
+#[test]
+fn test_distance_estimate_near_the_cardioid() {
+    // Right of the cusp nothing grows off the main cardioid, so the set's
+    // nearest point lies on the cardioid itself, found here by sampling its
+    // boundary c = w / 2 - w^2 / 4 for w around the unit circle.
+    let to_cardioid = |c: Complex<f64>| (0..100_000).map(|step| {
+        let w = Complex::from_polar(1.0, step as f64 * std::f64::consts::TAU / 100_000.0);
+        (w / 2.0 - w * w / 4.0 - c).norm()
+    }).fold(f64::INFINITY, f64::min);
+    for re in [0.27, 0.28, 0.3] {
+        let c = Complex { re, im: 0.0 };
+        let (count, z, dz) = escape_time_with_derivative(c, 10_000, 1e10);
+        assert_eq!(count, escape_time(c, 10_000, 1e10, PERIODICITY_EPSILON));
+        let ratio = distance_estimate(z, dz, count.unwrap() - 1) / to_cardioid(c);
+        assert!((ratio - 1.0).abs() < 0.05, "{}: {}", re, ratio);
+    }
+    // Far out the estimate stays within a factor of two. The nearest point
+    // to -3 is the tip of the set at -2; that to 2 + 2i is more than
+    // |c| - 2 away, the set lying inside the circle of radius 2, and no
+    // further than the cardioid.
+    let far = |c| escape_distance(c, 10_000, 1e10).unwrap();
+    let distance = far(Complex { re: -3.0, im: 0.0 });
+    assert!((1.0..2.0).contains(&distance), "{}", distance);
+    let c = Complex { re: 2.0, im: 2.0 };
+    let distance = far(c);
+    assert!(c.norm() - 2.0 < distance && distance < 2.0 * to_cardioid(c), "{}", distance);
+    assert_eq!(escape_time_with_derivative(Complex { re: -0.5, im: 0.0 }, 100, 1e10).0, None);
+}
+
+#[test]
+fn test_newton_converges_near_each_root() {
+    for (index, &root) in NEWTON_ROOTS.iter().enumerate() {
+        assert!(((root * root * root) - 1.0).norm() < 1e-15);
+        assert_eq!(newton_convergence(root, 50, NEWTON_EPSILON), Some(Convergence { root: index, iterations: 0 }));
+        for offset in [Complex { re: 1e-4, im: 0.0 }, Complex { re: 0.0, im: -1e-3 }, Complex { re: -2e-4, im: 2e-4 }] {
+            let found = newton_convergence(root + offset, 50, NEWTON_EPSILON).unwrap();
+            assert_eq!(found.root, index, "{}", root + offset);
+            assert!((1..=2).contains(&found.iterations), "{}: {}", root + offset, found.iterations);
+        }
+    }
+    let near_one = Complex { re: 1.0, im: 1e-4 };
+    assert_eq!(Fractal::Newton(NEWTON_EPSILON).escape_time(near_one, 50, 4.0, PERIODICITY_EPSILON), Some(1));
+    assert_eq!(newton_convergence(Complex { re: 0.0, im: 0.0 }, 50, NEWTON_EPSILON), None);
+}
+
+#[test]
+fn test_newton_basins_are_symmetric() {
+    // Turning z a third of the way round turns its whole orbit, so it lands
+    // on the next root in as many steps. Rounding can tip points on the
+    // boundaries between basins either way, but no more.
+    let turn = NEWTON_ROOTS[1];
+    let (mut agree, mut basins) = (0, [0; 3]);
+    for y in -20..20 {
+        for x in -20..20 {
+            let z = Complex { re: x as f64 / 10.0 + 0.05, im: y as f64 / 10.0 + 0.05 };
+            let found = newton_convergence(z, 100, NEWTON_EPSILON).unwrap();
+            let turned = newton_convergence(z * turn, 100, NEWTON_EPSILON).unwrap();
+            agree += (turned == Convergence { root: (found.root + 1) % 3, ..found }) as usize;
+            basins[found.root] += 1;
+        }
+    }
+    assert!(agree * 100 >= 1600 * 98, "{} of 1600", agree);
+    // Each basin holds about a third of the square around the origin.
+    assert!(basins.iter().all(|&count: &usize| count.abs_diff(1600 / 3) < 40), "{:?}", basins);
+}
+
+#[test]
+fn test_stripe_average_is_continuous() {
+    // Along a ray out of the set the escape count steps down, but the
+    // blended average runs on across each step.
+    let (mut steps, mut last) = (0, None);
+    for i in 0..20000 {
+        let radius = 1.0 + 2.0 * i as f64 / 20000.0;
+        let c = Complex::from_polar(radius, 0.6);
+        let average = Fractal::Mandelbrot.stripe_average(c, 1000, 1e6, 4.0).unwrap();
+        assert!((0.0..=1.0).contains(&average));
+        let count = escape_time(c, 1000, 1e6, PERIODICITY_EPSILON);
+        if let Some((last_average, last_count)) = last {
+            if count != last_count {
+                steps += 1;
+                assert!(f64::abs(average - last_average) < 1e-3, "{} to {} at {}", last_average, average, c);
+            }
+        }
+        last = Some((average, count));
+    }
+    assert!(steps > 0);
+    assert_eq!(Fractal::Mandelbrot.stripe_average(Complex { re: -0.1, im: 0.1 }, 1000, 1e6, 4.0), None);
+}
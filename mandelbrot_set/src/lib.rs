@@ -0,0 +1,1565 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::Range;
+use num::Complex;
+use image::ColorType;
+use counts::encode_count;
+use perturbation::Perturbation;
+use precision::Precision;
+use progress::{Progress, Skipped};
+
+pub mod animation;
+pub mod bigfloat;
+pub mod checkpoint;
+pub mod counts;
+pub mod deepzoom;
+pub mod dump;
+pub mod error;
+pub mod estimate;
+pub mod fractal;
+pub mod metadata;
+pub mod output;
+pub mod palette;
+pub mod perturbation;
+pub mod precision;
+pub mod poster;
+pub mod progress;
+pub mod progressive;
+pub mod pyramid;
+pub mod raw;
+pub mod renderer;
+pub mod slope;
+pub mod strategy;
+pub mod symmetry;
+pub mod tile;
+
+pub use dump::IterDump;
+pub use error::MandelError;
+pub use fractal::{Convergence, EscapeResult, Fractal, MAX_POWER, NEWTON_EPSILON, NEWTON_ROOTS, PERIODICITY_EPSILON,
+                  Trap, burning_ship_escape_time, distance_estimate, escape_distance, escape_time, escape_time_full,
+                  escape_time_orbit_trap, escape_time_smooth, escape_time_with_derivative, escape_time_simd,
+                  escape_time_x4, in_cardioid_or_bulb, julia_escape_distance, julia_escape_time,
+                  julia_escape_time_smooth, julia_escape_time_x4, multibrot_escape_time, newton_convergence,
+                  tricorn_escape_time};
+pub use output::{ImageFormat, PngStream, write_image, write_image_as, write_image_with_metadata};
+pub use palette::{PALETTE_NAMES, Palette, escape_to_rgb};
+pub use strategy::{Strategy, default_threads, render_dynamic, render_parallel, render_rayon};
+pub use tile::{Tile, render_tiles};
+
+
+pub const LIMIT_TO_CALL_IT_OFF_TO_INFINITY: f64 = 4.0;
+pub const LIMIT_OF_ITERATION: usize = 255;
+/// The squared bailout radius `--smooth` uses unless told otherwise; the
+/// smooth count is only accurate once `|z|` has grown well past 2.
+pub const SMOOTH_BAILOUT: f64 = 1e6;
+/// How many pixels away from the set `Coloring::Distance` reaches the first
+/// shade unless `RenderParams::distance_scale` says otherwise.
+pub const DISTANCE_FALLOFF: f64 = 4.0;
+/// How much `Coloring::BinaryShaded` darkens orbits that escaped below the
+/// real axis.
+pub const BINARY_SHADE: f64 = 0.5;
+/// How far from the trap, on the complex plane, an orbit must stay for
+/// `Coloring::Trap` to give it the last shade.
+pub const TRAP_REACH: f64 = 1.0;
+/// How many stripes `Coloring::Stripe` draws for each turn of `z` around the
+/// origin unless `RenderParams::stripe_density` says otherwise.
+pub const STRIPE_DENSITY: f64 = 4.0;
+/// The display gamma `--gamma-correct` encodes images for, that of most
+/// screens.
+pub const DISPLAY_GAMMA: f64 = 2.2;
+/// How many units of the complex plane an image at zoom 1 is wide.
+pub const WIDTH_AT_ZOOM_ONE: f64 = 4.0;
+/// The highest iteration limit `auto_limit` picks unless `--auto-iter-max`
+/// says otherwise.
+pub const AUTO_LIMIT_MAX: usize = 50_000;
+/// How many rows of the image `--stream` has in memory at once unless
+/// `--strip-rows` says otherwise.
+pub const STREAM_BAND_ROWS: usize = 64;
+/// The colors `--fractal newton` gives the basins of `NEWTON_ROOTS`, in the
+/// same order.
+pub const NEWTON_COLORS: [[u8; 3]; 3] = [[220, 60, 50], [70, 180, 70], [60, 100, 220]];
+
+
+/// How escape counts are turned into pixel bytes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum ColorMode {
+    /// One byte per pixel, brighter for points that escape sooner.
+    #[default]
+    Gray,
+    /// Like `Gray`, but with sixteen bits per pixel, stored big-endian as
+    /// PNG expects, so high iteration limits do not collapse many counts
+    /// into the same shade.
+    Gray16,
+    /// Three bytes per pixel, looked up in the given palette.
+    Rgb(Palette),
+    /// Not an image: four bytes per pixel holding the integer escape count
+    /// as a little-endian `u32`, or `counts::NEVER_ESCAPED`. Used for
+    /// coloring passes that need every count before they can color any
+    /// pixel; `smooth` is ignored.
+    Counts,
+    /// Not an image either: four bytes per pixel holding the escape value as
+    /// a little-endian `f32`, NaN for points that never escape. The value is
+    /// the smooth count with `smooth`, the integer count without, and the
+    /// distance estimate with `Coloring::Distance`; no tone curve is applied.
+    /// Used for output that keeps the values themselves rather than shades.
+    Values,
+}
+
+impl ColorMode {
+    pub fn bytes_per_pixel(&self) -> usize
+    {
+        match self {
+            ColorMode::Gray => 1,
+            ColorMode::Gray16 => 2,
+            ColorMode::Rgb(_) => 3,
+            ColorMode::Counts | ColorMode::Values => 4,
+        }
+    }
+
+    /// Whether the pixels hold escape counts or values rather than colors.
+    pub fn is_raw(&self) -> bool
+    {
+        matches!(self, ColorMode::Counts | ColorMode::Values)
+    }
+
+    pub fn color_type(&self) -> ColorType
+    {
+        match self {
+            ColorMode::Gray => ColorType::Gray(8),
+            ColorMode::Gray16 => ColorType::Gray(16),
+            ColorMode::Rgb(_) => ColorType::RGB(8),
+            ColorMode::Counts => panic!("a buffer of escape counts is not an image"),
+            // image has no floating-point color type; only the EXR writer
+            // accepts these.
+            ColorMode::Values => ColorType::Gray(32),
+        }
+    }
+}
+
+/// How escape counts are spread over the shades of the `ColorMode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Coloring {
+    /// In proportion to the count, from the first shade for points that
+    /// escape at once to the last at the iteration limit.
+    #[default]
+    Linear,
+    /// By histogram equalization over the whole image (see
+    /// `counts::equalize`), so that every shade is used about equally often.
+    /// Works on integer counts, so `smooth` is ignored.
+    Histogram,
+    /// By the estimated distance to the set (see `escape_distance`) rather
+    /// than the escape count: points within a pixel or so of the set get
+    /// the last shades, and brightness rises to the first shade over
+    /// `RenderParams::distance_scale` pixels. Only for the Mandelbrot and
+    /// Julia sets; `smooth` is ignored.
+    Distance,
+    /// By how close the orbit comes to the shape `RenderParams::trap` (see
+    /// `escape_time_orbit_trap`): orbits that touch it get the first shade,
+    /// and those that stay `TRAP_REACH` or further away the last. Points
+    /// inside the set are shaded too. `smooth` is ignored.
+    Trap,
+    /// Binary decomposition: white where the orbit escaped with `Im z` at
+    /// least 0, black where it escaped below, which draws rings of cells
+    /// around the set. Points that never escape keep the interior color.
+    /// `smooth` is ignored.
+    Binary,
+    /// The linear shade of the escape count, darkened by `BINARY_SHADE`
+    /// where the orbit escaped below the real axis, laying the rings of
+    /// `Binary` over the usual image.
+    BinaryShaded,
+    /// Stripe average coloring (see `Fractal::stripe_average`): by the mean
+    /// over the orbit of `0.5 + 0.5 * sin(stripe_density * arg z)`, from the
+    /// first shade at 0 to the last at 1, which draws stripes flowing along
+    /// the field lines around the set. Points that never escape keep the
+    /// interior color. `smooth` is ignored.
+    Stripe,
+}
+
+impl Coloring {
+    /// Whether the coloring reads more of each orbit than its escape count,
+    /// so that it cannot work from a buffer of counts.
+    pub fn needs_orbits(&self) -> bool
+    {
+        matches!(self, Coloring::Distance | Coloring::Trap | Coloring::Binary | Coloring::BinaryShaded
+                       | Coloring::Stripe)
+    }
+}
+
+/// A rectangle of the complex plane, together with the size in pixels of the
+/// image it is drawn into.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub bounds: (usize, usize),
+    pub upper_left: Complex<f64>,
+    pub lower_right: Complex<f64>,
+}
+
+/// Which coordinates of a region's corners `Region::normalized` swapped:
+/// the real parts when the upper left corner lay to the right of the lower
+/// right one, the imaginary parts when it lay below it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CornerSwap {
+    pub re: bool,
+    pub im: bool,
+}
+
+impl CornerSwap {
+    /// Whether anything was swapped.
+    pub fn any(&self) -> bool
+    {
+        self.re || self.im
+    }
+}
+
+/// What to do with a view whose shape on the plane is not that of the
+/// image, which stretches it: its pixels are not square, and circles come
+/// out as ellipses. See `fit_viewport`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AspectPolicy {
+    /// Render the view as given.
+    #[default]
+    Ignore,
+    /// Render the view as given, but say how stretched it is.
+    Warn,
+    /// Grow the shorter side of the view about its center until its pixels
+    /// are square, so that all of it stays in the image.
+    Pad,
+    /// Shrink the longer side of the view about its center until its pixels
+    /// are square, so that it fills the image.
+    Crop,
+}
+
+impl Region {
+    pub fn new(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> Region
+    {
+        Region { bounds, upper_left, lower_right }
+    }
+
+    /// The point on the complex plane under the given pixel of this region.
+    pub fn pixel_to_point(&self, pixel: (usize, usize)) -> Complex<f64>
+    {
+        pixel_to_point(self.bounds, pixel, self.upper_left, self.lower_right)
+    }
+
+    /// The part of the plane the band of `rows` covers, full width. Bands
+    /// are still rendered through the whole region, as `render_rows` does,
+    /// since mapping pixels through the band's own corners can round them
+    /// differently; this is for describing a band, not for drawing it.
+    pub fn sub_region(&self, rows: Range<usize>) -> Region
+    {
+        assert!(rows.start <= rows.end && rows.end <= self.bounds.1);
+        Region::new((self.bounds.0, rows.len()), self.pixel_to_point((0, rows.start)),
+                    self.pixel_to_point((self.bounds.0, rows.end)))
+    }
+
+    /// This region with its corners the right way round, as `validate`
+    /// wants them: where the upper left corner lies to the right of the
+    /// lower right one their real parts are swapped, and where it lies below
+    /// it their imaginary parts, which draws the view the corners meant
+    /// rather than its mirror image. Also returns what was swapped.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::{CornerSwap, Region};
+    ///
+    /// let region = Region::new((4, 4), Complex { re: 1.0, im: 1.0 }, Complex { re: -2.0, im: -1.0 });
+    /// let (normalized, swapped) = region.normalized();
+    /// assert_eq!(normalized.upper_left, Complex { re: -2.0, im: 1.0 });
+    /// assert_eq!(normalized.lower_right, Complex { re: 1.0, im: -1.0 });
+    /// assert_eq!(swapped, CornerSwap { re: true, im: false });
+    /// ```
+    pub fn normalized(&self) -> (Region, CornerSwap)
+    {
+        let (mut upper_left, mut lower_right) = (self.upper_left, self.lower_right);
+        let swapped = CornerSwap { re: upper_left.re > lower_right.re, im: upper_left.im < lower_right.im };
+        if swapped.re {
+            std::mem::swap(&mut upper_left.re, &mut lower_right.re);
+        }
+        if swapped.im {
+            std::mem::swap(&mut upper_left.im, &mut lower_right.im);
+        }
+        (Region::new(self.bounds, upper_left, lower_right), swapped)
+    }
+
+    /// Check that the region spans some of the plane the right way round:
+    /// `lower_right` strictly to the right of and below `upper_left`.
+    /// Otherwise `pixel_to_point` would walk the plane backwards or not at
+    /// all. The number of pixels must also fit in a `usize`. Fails with
+    /// `MandelError::Dimensions`.
+    pub fn validate(&self) -> Result<(), MandelError>
+    {
+        if self.bounds.0.checked_mul(self.bounds.1).is_none() {
+            return Err(MandelError::Dimensions(format!("{}x{} pixels are too many to count", self.bounds.0,
+                                                       self.bounds.1)));
+        }
+        let (upper_left, lower_right) = (self.upper_left, self.lower_right);
+        if upper_left.re == lower_right.re || upper_left.im == lower_right.im {
+            return Err(MandelError::Dimensions(format!(
+                "the corners {},{} and {},{} enclose no area, which would render as one flat color; they must \
+                 differ in both the real and the imaginary part",
+                upper_left.re, upper_left.im, lower_right.re, lower_right.im)));
+        }
+        if lower_right.re.partial_cmp(&upper_left.re) != Some(Ordering::Greater) {
+            return Err(MandelError::Dimensions(format!(
+                "the lower right corner must be to the right of the upper left corner, got {},{} and {},{}",
+                lower_right.re, lower_right.im, upper_left.re, upper_left.im)));
+        }
+        if upper_left.im.partial_cmp(&lower_right.im) != Some(Ordering::Greater) {
+            return Err(MandelError::Dimensions(format!(
+                "the upper left corner must be above the lower right corner, got {},{} and {},{}",
+                upper_left.re, upper_left.im, lower_right.re, lower_right.im)));
+        }
+        Ok(())
+    }
+
+    /// This region grown about its center so that its pixels are square:
+    /// both axes take the larger of the two scales, so all of the region
+    /// stays in view and circles on the plane come out round.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::Region;
+    ///
+    /// let region = Region::new((200, 100), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    /// let square = region.with_square_pixels();
+    /// assert_eq!(square.upper_left, Complex { re: -2.0, im: 1.0 });
+    /// assert_eq!(square.lower_right, Complex { re: 2.0, im: -1.0 });
+    /// ```
+    pub fn with_square_pixels(&self) -> Region
+    {
+        let (upper_left, lower_right) = fit_viewport(self.bounds, self.upper_left, self.lower_right, AspectPolicy::Pad);
+        Region::new(self.bounds, upper_left, lower_right)
+    }
+
+    /// How many times as wide as it is tall each pixel is on the plane: 1
+    /// for square pixels, more for a view squashed sideways into the image.
+    pub fn pixel_aspect(&self) -> f64
+    {
+        self.pixel_width() / ((self.upper_left.im - self.lower_right.im) / self.bounds.1 as f64)
+    }
+
+    /// The region of the same size in pixels centered on the point under
+    /// `pixel`, magnified `factor` times: each side is `factor` times
+    /// shorter. A `factor` below 1 zooms out.
+    ///
+    /// ```
+    /// use num::Complex;
+    /// use mandelbrot_set::Region;
+    ///
+    /// let region = Region::new((400, 300), Complex { re: -2.0, im: 1.5 }, Complex { re: 2.0, im: -1.5 });
+    /// let zoomed = region.zoom_at((300, 150), 4.0);
+    /// assert_eq!(zoomed.upper_left, Complex { re: 0.5, im: 0.375 });
+    /// assert_eq!(zoomed.lower_right, Complex { re: 1.5, im: -0.375 });
+    /// ```
+    pub fn zoom_at(&self, pixel: (usize, usize), factor: f64) -> Region
+    {
+        let center = self.pixel_to_point(pixel);
+        let half = (self.lower_right - self.upper_left) / (2.0 * factor);
+        Region::new(self.bounds, center - half, center + half)
+    }
+
+    /// The width of one pixel on the complex plane.
+    pub fn pixel_width(&self) -> f64
+    {
+        (self.lower_right.re - self.upper_left.re) / self.bounds.0 as f64
+    }
+
+    /// The point under sample `sample` of an `per_side` by `per_side` grid
+    /// laid over `pixel`, sample (0, 0) being `pixel_to_point(pixel)`.
+    pub fn sample_to_point(&self, pixel: (usize, usize), sample: (usize, usize), per_side: usize) -> Complex<f64>
+    {
+        let (width, height) = (self.lower_right.re - self.upper_left.re, self.upper_left.im - self.lower_right.im);
+        let offset = |pixel: usize, sample: usize| pixel as f64 + sample as f64 / per_side as f64;
+        Complex {
+            re: self.upper_left.re + offset(pixel.0, sample.0) * (width  / self.bounds.0 as f64),
+            im: self.upper_left.im - offset(pixel.1, sample.1) * (height / self.bounds.1 as f64),
+        }
+    }
+}
+
+/// Everything `render` needs to know about the picture it is drawing: the
+/// region it covers, which fractal to draw, the iteration limit and squared
+/// bailout radius handed to the escape-time function, whether to use the
+/// smooth iteration count, and how to color the result.
+///
+/// `gamma` and `log_scale` reshape the position of each escaping pixel along
+/// the range of shades, after `smooth` and `coloring` have done their part;
+/// see `RenderParams::tone`.
+///
+/// `invert` flips grayscale output end for end, drawing the set white. It
+/// leaves palettes alone, which pick their own interior color.
+///
+/// `antialias` is the number of samples taken along each side of a pixel; the
+/// colors of all `antialias * antialias` samples are averaged, so rendering
+/// takes that many times longer. 1 samples each pixel once, as before.
+///
+/// `interior_check` lets the Mandelbrot set skip iterating points in its main
+/// cardioid and period-2 bulb. It never changes the image; turning it off is
+/// only useful for benchmarking. `periodicity_epsilon` is how close an orbit
+/// must come back to an earlier value to be taken for a cycle that will never
+/// escape; zero turns that check off, and lowering it trades speed for
+/// certainty in precision-sensitive renders. `mirror` lets views symmetric
+/// about the real axis copy rows from above it to below it instead of
+/// rendering them twice; like `interior_check` it never changes the image.
+///
+/// `trap` is the shape `Coloring::Trap` measures orbits against, and
+/// `distance_scale` how many pixels from the set `Coloring::Distance` takes
+/// to brighten to the first shade; smaller values sharpen the contrast.
+/// `stripe_density` is the `density` of `Fractal::stripe_average` for
+/// `Coloring::Stripe`. Other colorings ignore them.
+///
+/// `light_angle`, in degrees counterclockwise from the right of the image,
+/// lights the image as a surface whose height is the smooth escape count, or
+/// the distance estimate with `Coloring::Distance`; see `slope`. `None`
+/// leaves it unlit.
+///
+/// `perturbation` renders the Mandelbrot set by perturbation about a center
+/// held to more precision than `f64` has, for zooms too deep for `region`
+/// to tell its pixels apart; see `perturbation`. `region` then only gives
+/// the size of the image. It colors by escape count, or smooth count, and
+/// skips the interior and periodicity checks and `mirror`.
+///
+/// `precision` renders the Mandelbrot or Julia set with every point placed
+/// and iterated in `BigFloat`s of that many bits, from corners parsed to
+/// that precision; see `precision`. `region` then only gives the size of the
+/// image, and the coloring is by escape count or smooth count as with
+/// `perturbation`. It skips `mirror`, whose rows may no longer pair off,
+/// and its cycle check holds `periodicity_epsilon` to a fraction of a pixel.
+///
+/// `display_gamma` encodes the finished image for a display of that gamma;
+/// see `correct_gamma`. Unlike `gamma` it works on each channel of the
+/// colors, palettes included, rather than on the positions along the
+/// shades. 1 leaves the image as it is.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderParams {
+    pub region: Region,
+    pub fractal: Fractal,
+    pub limit: usize,
+    pub bailout: f64,
+    pub smooth: bool,
+    pub color: ColorMode,
+    pub coloring: Coloring,
+    pub gamma: f64,
+    pub log_scale: bool,
+    pub invert: bool,
+    pub antialias: usize,
+    pub interior_check: bool,
+    pub mirror: bool,
+    pub periodicity_epsilon: f64,
+    pub trap: Trap,
+    pub distance_scale: f64,
+    pub stripe_density: f64,
+    pub light_angle: Option<f64>,
+    pub display_gamma: f64,
+    pub perturbation: Option<Perturbation>,
+    pub precision: Option<Precision>,
+}
+
+impl RenderParams {
+    pub fn new(bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) -> RenderParams
+    {
+        RenderParams {
+            region: Region::new(bounds, upper_left, lower_right),
+            fractal: Fractal::Mandelbrot,
+            limit: LIMIT_OF_ITERATION,
+            bailout: LIMIT_TO_CALL_IT_OFF_TO_INFINITY,
+            smooth: false,
+            color: ColorMode::Gray,
+            coloring: Coloring::Linear,
+            gamma: 1.0,
+            log_scale: false,
+            invert: false,
+            antialias: 1,
+            interior_check: true,
+            mirror: true,
+            periodicity_epsilon: PERIODICITY_EPSILON,
+            trap: Trap::default(),
+            distance_scale: DISTANCE_FALLOFF,
+            stripe_density: STRIPE_DENSITY,
+            light_angle: None,
+            display_gamma: 1.0,
+            perturbation: None,
+            precision: None,
+        }
+    }
+
+    /// Reshape a position from 0 to 1 along the range of shades. With
+    /// `log_scale`, the position is taken as a count `t * limit` and replaced
+    /// by `ln(1 + count) / ln(1 + limit)`, which spends more of the range on
+    /// the low counts; then it is raised to the power `1 / gamma`, so a gamma
+    /// above 1 moves every position towards the slow end. Gammas from about
+    /// 0.2 to 5 are useful. Gamma 1 without `log_scale` changes nothing.
+    pub fn tone(&self, position: f64) -> f64
+    {
+        let position = if self.log_scale {
+            let limit = self.limit as f64;
+            (1.0 + position * limit).ln() / (1.0 + limit).ln()
+        } else {
+            position
+        };
+        if self.gamma == 1.0 { position } else { position.powf(1.0 / self.gamma) }
+    }
+
+    /// Whether `tone` does anything.
+    fn has_tone_curve(&self) -> bool
+    {
+        self.gamma != 1.0 || self.log_scale
+    }
+
+    /// Whether `point` can be skipped as lying in the Mandelbrot set's main
+    /// cardioid or period-2 bulb.
+    fn known_interior(&self, point: Complex<f64>) -> bool
+    {
+        self.interior_check && self.fractal == Fractal::Mandelbrot && in_cardioid_or_bulb(point)
+    }
+
+    /// Like `known_interior`, counting the point into `skipped` when it is
+    /// skipped.
+    fn skips_interior(&self, point: Complex<f64>, skipped: &mut Skipped) -> bool
+    {
+        let known = self.known_interior(point);
+        if known {
+            skipped.interior += 1;
+        }
+        known
+    }
+
+    /// Iterate the orbit of `point` with the periodicity check, counting it
+    /// into `skipped` when it is given up on as a cycle.
+    fn orbit(&self, point: Complex<f64>, skipped: &mut Skipped) -> EscapeResult
+    {
+        let (escape, cycle) = self.fractal.orbit_cycle(point, self.limit, self.bailout, self.periodicity_epsilon);
+        if let Some(steps) = cycle {
+            skipped.cycle(self.limit.saturating_sub(steps));
+        }
+        escape
+    }
+
+    /// The escape count of `point`, as `Fractal::escape_time` computes it.
+    /// What the checks skip is counted into `skipped`, here and in the
+    /// methods below.
+    fn escape_time(&self, point: Complex<f64>, skipped: &mut Skipped) -> Option<usize>
+    {
+        if self.skips_interior(point, skipped) {
+            return None;
+        }
+        self.orbit(point, skipped).iterations
+    }
+
+    /// How the orbit of `point` ended, as `Fractal::escape` finds it.
+    fn escape(&self, point: Complex<f64>, skipped: &mut Skipped) -> EscapeResult
+    {
+        if self.skips_interior(point, skipped) {
+            return EscapeResult { iterations: None, final_z: point };
+        }
+        self.orbit(point, skipped)
+    }
+
+    /// The estimated distance from `point` to the set, as
+    /// `Fractal::escape_distance` computes it.
+    fn escape_distance(&self, point: Complex<f64>, skipped: &mut Skipped) -> Option<f64>
+    {
+        if self.skips_interior(point, skipped) {
+            return None;
+        }
+        self.fractal.escape_distance(point, self.limit, self.bailout)
+    }
+
+    /// How close the orbit of `point` comes to `trap`, as
+    /// `Fractal::orbit_trap` computes it.
+    fn orbit_trap(&self, point: Complex<f64>) -> f64
+    {
+        self.fractal.orbit_trap(point, self.limit, self.bailout, &self.trap)
+    }
+
+    /// The stripe average of the orbit of `point`, as
+    /// `Fractal::stripe_average` computes it.
+    fn stripe_average(&self, point: Complex<f64>, skipped: &mut Skipped) -> Option<f64>
+    {
+        if self.skips_interior(point, skipped) {
+            return None;
+        }
+        self.fractal.stripe_average(point, self.limit, self.bailout, self.stripe_density)
+    }
+
+    /// The escape counts of four points, as `Fractal::escape_time_x4`
+    /// computes them.
+    fn escape_time_x4(&self, points: [Complex<f64>; 4], skipped: &mut Skipped) -> [Option<usize>; 4]
+    {
+        if points.iter().all(|&point| self.known_interior(point)) {
+            skipped.interior += 4;
+            return [None; 4];
+        }
+        self.fractal.orbit_x4(points, self.limit, self.bailout, self.periodicity_epsilon)
+    }
+
+    /// The bytes `render` stores for a pixel that never escapes.
+    pub fn interior_pixel(&self) -> Vec<u8>
+    {
+        let mut pixel = vec![0; self.color.bytes_per_pixel()];
+        match self.fractal {
+            Fractal::Newton(_) if !self.color.is_raw() => paint_root(&mut pixel, None, self),
+            _ => paint_count(&mut pixel, None, self),
+        }
+        pixel
+    }
+
+    /// The size of the pixel buffer `render` expects for these parameters.
+    pub fn buffer_len(&self) -> usize
+    {
+        self.region.bounds.0 * self.region.bounds.1 * self.color.bytes_per_pixel()
+    }
+}
+
+
+/// Given the row and column of a pixel in an image of size `bounds`, return
+/// the corresponding point on the complex plane spanned by `upper_left` and
+/// `lower_right`.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::pixel_to_point;
+///
+/// let point = pixel_to_point((100, 200), (25, 175),
+///                            Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+/// assert_eq!(point, Complex { re: -0.5, im: -0.75 });
+/// ```
+pub fn pixel_to_point(bounds: (usize, usize), pixel: (usize, usize),
+                    upper_left: Complex<f64>, lower_right: Complex<f64>) -> Complex<f64>
+{
+    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+    Complex {
+        re: upper_left.re + pixel.0 as f64 * (width  / bounds.0 as f64),
+        im: upper_left.im - pixel.1 as f64 * (height / bounds.1 as f64),
+    }
+}
+
+/// Return the upper left and lower right corners of the region centered on
+/// `center` at the given `zoom`, shaped to match an image of `pixel_bounds`
+/// pixels so that pixels come out square. At zoom 1 the region is
+/// `WIDTH_AT_ZOOM_ONE` units wide; each doubling of `zoom` halves it.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::bounds_from_center;
+///
+/// let (upper_left, lower_right) = bounds_from_center(Complex { re: 0.0, im: 0.0 }, 2.0, (400, 300));
+/// assert_eq!(upper_left, Complex { re: -1.0, im: 0.75 });
+/// assert_eq!(lower_right, Complex { re: 1.0, im: -0.75 });
+/// ```
+pub fn bounds_from_center(center: Complex<f64>, zoom: f64, pixel_bounds: (usize, usize)) -> (Complex<f64>, Complex<f64>)
+{
+    viewport_from_center(center, WIDTH_AT_ZOOM_ONE / zoom, pixel_bounds)
+}
+
+/// Like `bounds_from_center`, for a region `width` units wide along the
+/// real axis, its height following from the shape of `pixel_bounds`.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::viewport_from_center;
+///
+/// let (upper_left, lower_right) = viewport_from_center(Complex { re: -1.0, im: 0.5 }, 0.5, (200, 400));
+/// assert_eq!(upper_left, Complex { re: -1.25, im: 1.0 });
+/// assert_eq!(lower_right, Complex { re: -0.75, im: 0.0 });
+/// ```
+pub fn viewport_from_center(center: Complex<f64>, width: f64, pixel_bounds: (usize, usize))
+    -> (Complex<f64>, Complex<f64>)
+{
+    let height = width * pixel_bounds.1 as f64 / pixel_bounds.0 as f64;
+    let half = Complex { re: width / 2.0, im: -height / 2.0 };
+    (center - half, center + half)
+}
+
+/// The corners of the view from `upper_left` to `lower_right` fitted to an
+/// image of `pixel_bounds` as `policy` says: `Pad` or `Crop` change one side
+/// of it about its center to give square pixels, the others leave it be.
+/// Only the side that changes is worked out again, so a view whose pixels
+/// are already square comes back bit for bit.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{AspectPolicy, fit_viewport};
+///
+/// let (upper_left, lower_right) = (Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+/// let padded = fit_viewport((200, 100), upper_left, lower_right, AspectPolicy::Pad);
+/// assert_eq!(padded, (Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -1.0 }));
+/// let cropped = fit_viewport((200, 100), upper_left, lower_right, AspectPolicy::Crop);
+/// assert_eq!(cropped, (Complex { re: -1.0, im: 0.5 }, Complex { re: 1.0, im: -0.5 }));
+/// ```
+pub fn fit_viewport(pixel_bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>,
+                    policy: AspectPolicy) -> (Complex<f64>, Complex<f64>)
+{
+    let (columns, rows) = (pixel_bounds.0 as f64, pixel_bounds.1 as f64);
+    let (across, down) = ((lower_right.re - upper_left.re) / columns, (upper_left.im - lower_right.im) / rows);
+    let scale = match policy {
+        AspectPolicy::Ignore | AspectPolicy::Warn => return (upper_left, lower_right),
+        AspectPolicy::Pad => across.max(down),
+        AspectPolicy::Crop => across.min(down),
+    };
+    let center = (upper_left + lower_right) / 2.0;
+    let (mut upper_left, mut lower_right) = (upper_left, lower_right);
+    if scale != across {
+        upper_left.re = center.re - scale * columns / 2.0;
+        lower_right.re = center.re + scale * columns / 2.0;
+    }
+    if scale != down {
+        upper_left.im = center.im + scale * rows / 2.0;
+        lower_right.im = center.im - scale * rows / 2.0;
+    }
+    (upper_left, lower_right)
+}
+
+/// An iteration limit for a view `width` units wide, the deeper the higher:
+/// `50 * log10(WIDTH_AT_ZOOM_ONE / width)^1.5`, raised to
+/// `LIMIT_OF_ITERATION` if it falls short, but never above `max`.
+///
+/// ```
+/// use mandelbrot_set::{AUTO_LIMIT_MAX, LIMIT_OF_ITERATION, auto_limit};
+///
+/// assert_eq!(auto_limit(4.0, AUTO_LIMIT_MAX), LIMIT_OF_ITERATION);
+/// assert_eq!(auto_limit(4e-6, AUTO_LIMIT_MAX), 735);
+/// ```
+pub fn auto_limit(width: f64, max: usize) -> usize
+{
+    let depth = (WIDTH_AT_ZOOM_ONE / width).log10().max(0.0);
+    ((50.0 * depth.powf(1.5)).round() as usize).max(LIMIT_OF_ITERATION).min(max)
+}
+
+/// Render a rectangle of the chosen fractal into a buffer of pixels, row by
+/// row. `pixels` must hold exactly `params.buffer_len()` bytes: one per pixel
+/// in grayscale mode, three in RGB mode.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::{render, RenderParams};
+///
+/// let params = RenderParams::new((4, 4), Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 });
+/// let mut pixels = vec![0; 16];
+/// render(&mut pixels, &params);
+/// assert_eq!(pixels[0], 254);
+/// ```
+pub fn render(pixels: &mut [u8], params: &RenderParams)
+{
+    assert!(pixels.len() == params.buffer_len());
+    render_rows(pixels, params, 0);
+}
+
+/// Render a horizontal band of the image described by `params` into `band`,
+/// which holds a whole number of rows, the first of them being row `top` of
+/// the full image. Every pixel is mapped through the full image's region, so
+/// bands rendered separately match a single `render` exactly. Returns what
+/// the interior and periodicity checks skipped, as `render_rect` does.
+pub fn render_rows(band: &mut [u8], params: &RenderParams, top: usize) -> Skipped
+{
+    let width = params.region.bounds.0;
+    let row_len = width * params.color.bytes_per_pixel();
+    assert!(band.len().is_multiple_of(row_len));
+    render_rect(band, params, (0, top), (width, band.len() / row_len))
+}
+
+/// Render the rectangle of the image described by `params` whose upper left
+/// pixel is `origin` and whose size in pixels is `size` into `buffer`, row by
+/// row. Like `render_rows`, pixels are mapped through the full image's
+/// region.
+///
+/// When the crate is built with the `simd` feature, integer escape counts are
+/// computed four pixels at a time with `escape_time_x4`; the output is the
+/// same either way. Pixels with several samples (`params.antialias`) are
+/// always computed one sample at a time.
+///
+/// Returns what the interior and periodicity checks skipped on the way, for
+/// the strategies to add to their `Progress`; see `progress::Skipped`.
+pub fn render_rect(buffer: &mut [u8], params: &RenderParams, origin: (usize, usize), size: (usize, usize))
+    -> Skipped
+{
+    let bytes_per_pixel = params.color.bytes_per_pixel();
+    assert!(buffer.len() == size.0 * size.1 * bytes_per_pixel);
+    assert!(origin.0 + size.0 <= params.region.bounds.0 && origin.1 + size.1 <= params.region.bounds.1);
+
+    let mut skipped = Skipped::default();
+    if size.0 == 0 {
+        return skipped;
+    }
+    if let Some(perturbation) = &params.perturbation {
+        perturbation.render_rect(buffer, params, origin, size);
+        return skipped;
+    }
+    if let Some(precision) = &params.precision {
+        precision.render_rect(buffer, params, origin, size);
+        return skipped;
+    }
+    for (row, line) in buffer.chunks_mut(size.0 * bytes_per_pixel).enumerate() {
+        let start = (origin.0, origin.1 + row);
+        if params.antialias > 1 {
+            render_line_supersampled(line, params, start, &mut skipped);
+        } else if cfg!(feature = "simd") && !params.smooth && !matches!(params.fractal, Fractal::Newton(_))
+            && matches!(params.coloring, Coloring::Linear | Coloring::Histogram) {
+            render_line_x4(line, params, start, &mut skipped);
+        } else {
+            render_line(line, params, start, &mut skipped);
+        }
+    }
+    skipped
+}
+
+/// Render the pixels of `line`, a run of one row starting at pixel `start`,
+/// one at a time.
+fn render_line(line: &mut [u8], params: &RenderParams, start: (usize, usize), skipped: &mut Skipped)
+{
+    let bytes_per_pixel = params.color.bytes_per_pixel();
+    for (column, pixel) in line.chunks_mut(bytes_per_pixel).enumerate() {
+        paint(pixel, params.region.pixel_to_point((start.0 + column, start.1)), params, skipped);
+    }
+}
+
+/// Like `render_line`, but computes the escape counts of four pixels at a
+/// time. Any pixels left over at the end of the line are done one by one.
+/// Only suitable for integer escape counts.
+fn render_line_x4(line: &mut [u8], params: &RenderParams, start: (usize, usize), skipped: &mut Skipped)
+{
+    let bytes_per_pixel = params.color.bytes_per_pixel();
+    let done = (line.len() / bytes_per_pixel) / 4 * 4;
+    let mut quads = line.chunks_exact_mut(4 * bytes_per_pixel);
+    for (quad_index, quad) in quads.by_ref().enumerate() {
+        let column = start.0 + quad_index * 4;
+        let points = [0, 1, 2, 3].map(|lane| params.region.pixel_to_point((column + lane, start.1)));
+        let counts = params.escape_time_x4(points, skipped);
+        for (pixel, count) in quad.chunks_mut(bytes_per_pixel).zip(counts) {
+            paint_count(pixel, count, params);
+        }
+    }
+
+    render_line(quads.into_remainder(), params, (start.0 + done, start.1), skipped);
+}
+
+/// Like `render_line`, but paints `params.antialias` squared samples spread
+/// evenly over each pixel and stores their average.
+fn render_line_supersampled(line: &mut [u8], params: &RenderParams, start: (usize, usize), skipped: &mut Skipped)
+{
+    let bytes_per_pixel = params.color.bytes_per_pixel();
+    let per_side = params.antialias;
+    let mut samples = vec![0; per_side * per_side * bytes_per_pixel];
+    for (column, pixel) in line.chunks_mut(bytes_per_pixel).enumerate() {
+        for (index, sample) in samples.chunks_mut(bytes_per_pixel).enumerate() {
+            let sample_index = (index % per_side, index / per_side);
+            let point = params.region.sample_to_point((start.0 + column, start.1), sample_index, per_side);
+            paint(sample, point, params, skipped);
+        }
+        average_samples(pixel, &samples, &params.color);
+    }
+}
+
+/// Store the average of the colors in `samples`, one after another, in
+/// `pixel`, channel by channel and rounding to the nearest value. Escape
+/// counts cannot be averaged, so for `ColorMode::Counts` and
+/// `ColorMode::Values` the first sample is kept.
+fn average_samples(pixel: &mut [u8], samples: &[u8], color: &ColorMode)
+{
+    let width = pixel.len();
+    let count = (samples.len() / width) as u64;
+    match color {
+        ColorMode::Counts | ColorMode::Values => pixel.copy_from_slice(&samples[..width]),
+        ColorMode::Gray16 => {
+            let sum: u64 = samples.chunks_exact(2).map(|level| u16::from_be_bytes([level[0], level[1]]) as u64).sum();
+            pixel.copy_from_slice(&(((sum + count / 2) / count) as u16).to_be_bytes());
+        }
+        ColorMode::Gray | ColorMode::Rgb(_) => {
+            for (channel, value) in pixel.iter_mut().enumerate() {
+                let sum: u64 = samples.iter().skip(channel).step_by(width).map(|&level| level as u64).sum();
+                *value = ((sum + count / 2) / count) as u8;
+            }
+        }
+    }
+}
+
+/// Compute the color of the single pixel at `point` and store it in `pixel`,
+/// counting what the checks skip into `skipped`.
+fn paint(pixel: &mut [u8], point: Complex<f64>, params: &RenderParams, skipped: &mut Skipped)
+{
+    if params.coloring == Coloring::Trap && params.color != ColorMode::Counts {
+        let distance = params.orbit_trap(point);
+        if params.color == ColorMode::Values {
+            pixel.copy_from_slice(&(distance as f32).to_le_bytes());
+        } else {
+            paint_position(pixel, Some(params.tone((distance / TRAP_REACH).min(1.0))), params);
+        }
+    } else if let (Fractal::Newton(epsilon), false) = (params.fractal, params.color.is_raw()) {
+        paint_root(pixel, newton_convergence(point, params.limit, epsilon), params);
+    } else if matches!(params.coloring, Coloring::Binary | Coloring::BinaryShaded) && !params.color.is_raw() {
+        paint_binary(pixel, params.escape(point, skipped), params);
+    } else if params.coloring == Coloring::Stripe && params.color != ColorMode::Counts {
+        let average = params.stripe_average(point, skipped);
+        if params.color == ColorMode::Values {
+            pixel.copy_from_slice(&average.map_or(f32::NAN, |average| average as f32).to_le_bytes());
+        } else {
+            paint_position(pixel, average.map(|average| params.tone(average)), params);
+        }
+    } else if params.coloring == Coloring::Distance && params.color != ColorMode::Counts {
+        let distance = params.escape_distance(point, skipped);
+        if params.color == ColorMode::Values {
+            pixel.copy_from_slice(&distance.map_or(f32::NAN, |distance| distance as f32).to_le_bytes());
+        } else {
+            let falloff = params.distance_scale * params.region.pixel_width().abs();
+            let position = distance.map(|distance| params.tone(1.0 - (distance / falloff).min(1.0)));
+            paint_position(pixel, position, params);
+        }
+    } else if params.smooth && params.color != ColorMode::Counts {
+        paint_escape(pixel, params.escape(point, skipped), params);
+    } else {
+        paint_count(pixel, params.escape_time(point, skipped), params);
+    }
+}
+
+/// Store the color for the orbit that ended as `escape` in `pixel`: its
+/// smooth count with `params.smooth`, otherwise its escape count.
+fn paint_escape(pixel: &mut [u8], escape: EscapeResult, params: &RenderParams)
+{
+    let smooth = || escape.smooth_count(params.fractal.power());
+    if params.smooth && params.color == ColorMode::Values {
+        pixel.copy_from_slice(&smooth().map_or(f32::NAN, |value| value as f32).to_le_bytes());
+    } else if params.smooth && params.color != ColorMode::Counts {
+        let limit = params.limit as f64;
+        paint_position(pixel, smooth().map(|value| params.tone(value.clamp(0.0, limit) / limit)), params);
+    } else {
+        paint_count(pixel, escape.iterations, params);
+    }
+}
+
+/// Store the color for an integer escape count in `pixel`.
+pub(crate) fn paint_count(pixel: &mut [u8], count: Option<usize>, params: &RenderParams)
+{
+    if params.has_tone_curve() && !params.color.is_raw() {
+        let position = count.map(|count| params.tone(count as f64 / params.limit as f64));
+        return paint_position(pixel, position, params);
+    }
+
+    match &params.color {
+        ColorMode::Gray => pixel[0] = shade(count, params.limit, params.invert),
+        ColorMode::Gray16 => {
+            let level = gray_level(count, params.limit, 65535, params.invert) as u16;
+            pixel.copy_from_slice(&level.to_be_bytes());
+        }
+        ColorMode::Rgb(palette) => pixel.copy_from_slice(&palette.color_for(count, params.limit)),
+        ColorMode::Counts => pixel.copy_from_slice(&encode_count(count).to_le_bytes()),
+        ColorMode::Values => pixel.copy_from_slice(&count.map_or(f32::NAN, |count| count as f32).to_le_bytes()),
+    }
+}
+
+/// Store the binary decomposition color of the orbit that ended as `escape`
+/// in `pixel`, as `Coloring::Binary` or `Coloring::BinaryShaded` describes
+/// it. `invert` swaps black and white in grayscale, as it does the interior.
+fn paint_binary(pixel: &mut [u8], escape: EscapeResult, params: &RenderParams)
+{
+    let upper = match escape.upper_half() {
+        Some(upper) => upper,
+        None => return paint_count(pixel, None, params),
+    };
+    if params.coloring == Coloring::BinaryShaded {
+        paint_count(pixel, escape.iterations, params);
+        if !upper {
+            slope::shade_pixels(pixel, &[BINARY_SHADE], &params.color);
+        }
+    } else {
+        let inverted = params.invert && matches!(params.color, ColorMode::Gray | ColorMode::Gray16);
+        pixel.fill(if upper != inverted { 255 } else { 0 });
+    }
+}
+
+/// Store the color for where Newton's method took a pixel in `pixel`: that
+/// of its root, from full brightness for a pixel on the root to black at the
+/// iteration limit, shaped by `RenderParams::tone`; black if it never
+/// converged. In grayscale the roots are told apart by their brightest
+/// level, white for the first and two and one thirds of it for the others.
+fn paint_root(pixel: &mut [u8], found: Option<Convergence>, params: &RenderParams)
+{
+    let (brightness, root) = match found {
+        Some(found) => (1.0 - params.tone(found.iterations as f64 / params.limit as f64), found.root),
+        None => (0.0, 0),
+    };
+    let gray = |white: f64| {
+        let level = (brightness * white * (3 - root) as f64 / 3.0).round();
+        if params.invert { white - level } else { level }
+    };
+    match &params.color {
+        ColorMode::Gray => pixel[0] = gray(255.0) as u8,
+        ColorMode::Gray16 => pixel.copy_from_slice(&(gray(65535.0) as u16).to_be_bytes()),
+        ColorMode::Rgb(_) => {
+            for (channel, &full) in pixel.iter_mut().zip(&NEWTON_COLORS[root]) {
+                *channel = (brightness * full as f64).round() as u8;
+            }
+        }
+        ColorMode::Counts | ColorMode::Values => panic!("escape counts cannot be stored as a root"),
+    }
+}
+
+/// Store the color for a position from 0 to 1 along the range of shades in
+/// `pixel`, `None` standing for a point that never escaped.
+fn paint_position(pixel: &mut [u8], position: Option<f64>, params: &RenderParams)
+{
+    match &params.color {
+        ColorMode::Gray => pixel[0] = position_level(position, 255.0, params.invert) as u8,
+        ColorMode::Gray16 => {
+            let level = position_level(position, 65535.0, params.invert) as u16;
+            pixel.copy_from_slice(&level.to_be_bytes());
+        }
+        ColorMode::Rgb(palette) => pixel.copy_from_slice(&palette.color_for_position(position)),
+        ColorMode::Counts | ColorMode::Values => panic!("escape counts cannot be stored as a position"),
+    }
+}
+
+/// The 8-bit gray level for an escape count, as `gray_level` computes it.
+fn shade(count: Option<usize>, limit: usize, invert: bool) -> u8
+{
+    gray_level(count, limit, 255, invert) as u8
+}
+
+/// The gray level from 0 to `white` for an escape count: `white` for points
+/// that escape at once, darker for slower ones down to 1, and 0 for points
+/// that never escape, so that no escape shares its level with the interior.
+/// `invert` mirrors the scale, turning the interior white.
+fn gray_level(count: Option<usize>, limit: usize, white: usize, invert: bool) -> usize
+{
+    let level = match count {
+        None => 0,
+        Some(count) => white.saturating_sub(count * white / limit).max(1),
+    };
+    if invert { white - level } else { level }
+}
+
+/// Like `gray_level`, for a position from 0 to 1 such as a fractional escape
+/// count divided by the limit.
+fn position_level(position: Option<f64>, white: f64, invert: bool) -> f64
+{
+    let level = match position {
+        None => 0.0,
+        Some(fraction) => (white - fraction * white).round().max(1.0),
+    };
+    if invert { white - level } else { level }
+}
+
+/// Render the image described by `params` with the given strategy, and
+/// return its pixels together with the units of work each thread completed,
+/// as `Strategy::render` does. Unlike `Strategy::render`, this also applies
+/// `params.coloring`: with `Coloring::Histogram` the strategy renders raw
+/// escape counts, which are equalized and colored once every thread is done.
+pub fn render_image(params: &RenderParams, strategy: Strategy, threads: usize) -> (Vec<u8>, Vec<usize>)
+{
+    render_image_with_progress(params, strategy, threads, &Progress::hidden())
+}
+
+/// Like `render_image`, counting the pixels into `progress` as the strategy
+/// renders them.
+pub fn render_image_with_progress(params: &RenderParams, strategy: Strategy, threads: usize, progress: &Progress)
+    -> (Vec<u8>, Vec<usize>)
+{
+    let mut pixels = vec![0; params.buffer_len()];
+    let work_per_thread = match params.coloring {
+        Coloring::Linear | Coloring::Distance | Coloring::Trap | Coloring::Binary | Coloring::BinaryShaded
+        | Coloring::Stripe => {
+            strategy.render_with_progress(&mut pixels, params, threads, progress)
+        }
+        Coloring::Histogram => {
+            let (counts, work_per_thread) = render_counts_with_progress(params, strategy, threads, progress);
+            pixels = color_counts(&counts, params);
+            work_per_thread
+        }
+    };
+    slope::shade_slopes(&mut pixels, params, strategy, threads);
+    correct_gamma(&mut pixels, params);
+    (pixels, work_per_thread)
+}
+
+/// Render the image described by `params` a band of `strip_rows` rows at a
+/// time, writing each band to `output` as part of a PNG image with
+/// `metadata` before rendering the next, so that only one band is ever in
+/// memory and the image may be far larger than it. The image decodes to
+/// the pixels `render_image` returns, and the units of work each thread
+/// completed are added up over the bands. Panics
+/// unless `strategy.renders_bands()`, or with `Coloring::Histogram`, which
+/// needs every count before it can color the first pixel.
+pub fn stream_png<W: Write>(output: W, params: &RenderParams, strategy: Strategy, threads: usize, strip_rows: usize,
+                            progress: &Progress, metadata: &BTreeMap<String, String>) -> Result<Vec<usize>, MandelError>
+{
+    assert!(strategy.renders_bands() && params.coloring != Coloring::Histogram && strip_rows > 0);
+    let (width, height) = params.region.bounds;
+    let row_len = width * params.color.bytes_per_pixel();
+    let mut stream = PngStream::new(output, params.region.bounds, params.color.color_type(), metadata)?;
+    let mut band = vec![0; strip_rows.min(height) * row_len];
+    let mut work_per_thread: Vec<usize> = Vec::new();
+    for top in (0..height).step_by(strip_rows) {
+        let band = &mut band[..strip_rows.min(height - top) * row_len];
+        let work = strategy.render_band(band, params, top, threads, progress);
+        correct_gamma(band, params);
+        work_per_thread.resize(work_per_thread.len().max(work.len()), 0);
+        for (total, units) in work_per_thread.iter_mut().zip(work) {
+            *total += units;
+        }
+        stream.write_rows(band)?;
+    }
+    stream.finish()?;
+    Ok(work_per_thread)
+}
+
+/// The second pass: color escape counts from `render_counts`, or read back
+/// from a dump, the way `render_image` colors them for `params`.
+pub fn color_counts(counts: &[Option<usize>], params: &RenderParams) -> Vec<u8>
+{
+    assert!(counts.len() == params.region.bounds.0 * params.region.bounds.1);
+    let mut pixels = vec![0; params.buffer_len()];
+    let pixel_chunks = pixels.chunks_mut(params.color.bytes_per_pixel());
+    match params.coloring {
+        Coloring::Linear => {
+            for (pixel, &count) in pixel_chunks.zip(counts) {
+                paint_count(pixel, count, params);
+            }
+        }
+        Coloring::Histogram => {
+            for (pixel, position) in pixel_chunks.zip(counts::equalize(counts, params.limit)) {
+                paint_position(pixel, position.map(|position| params.tone(position)), params);
+            }
+        }
+        _ => panic!("{:?} coloring needs the orbits, not just the escape counts", params.coloring),
+    }
+    pixels
+}
+
+/// Encode `pixels`, all or part of an image of `params`, for a display of
+/// gamma `params.display_gamma`: each channel `v` of each pixel, out of a
+/// full brightness `max`, becomes `max * (v / max)^(1 / display_gamma)`,
+/// which lifts the midtones that a linear mapping of the counts leaves too
+/// dark. Escape counts and values are left alone, as is everything with a
+/// display gamma of 1.
+pub fn correct_gamma(pixels: &mut [u8], params: &RenderParams)
+{
+    let gamma = params.display_gamma;
+    let encode = |level: f64, max: f64| (max * (level / max).powf(1.0 / gamma)).round();
+    match params.color {
+        _ if gamma == 1.0 => {}
+        ColorMode::Gray | ColorMode::Rgb(_) => {
+            let table: Vec<u8> = (0..=255).map(|level| encode(level as f64, 255.0) as u8).collect();
+            for channel in pixels {
+                *channel = table[*channel as usize];
+            }
+        }
+        ColorMode::Gray16 => {
+            for pixel in pixels.chunks_exact_mut(2) {
+                let level = u16::from_be_bytes([pixel[0], pixel[1]]) as f64;
+                pixel.copy_from_slice(&(encode(level, 65535.0) as u16).to_be_bytes());
+            }
+        }
+        ColorMode::Counts | ColorMode::Values => {}
+    }
+}
+
+/// The first of two passes for colorings that need the whole image: render
+/// just the escape count of every pixel, row by row, with the given strategy.
+/// Returns the counts, `None` for points that never escaped, and the units of
+/// work each thread completed.
+pub fn render_counts(params: &RenderParams, strategy: Strategy, threads: usize) -> (Vec<Option<usize>>, Vec<usize>)
+{
+    render_counts_with_progress(params, strategy, threads, &Progress::hidden())
+}
+
+/// Like `render_counts`, counting the pixels into `progress`.
+pub fn render_counts_with_progress(params: &RenderParams, strategy: Strategy, threads: usize, progress: &Progress)
+    -> (Vec<Option<usize>>, Vec<usize>)
+{
+    let count_params = RenderParams { color: ColorMode::Counts, ..params.clone() };
+    let mut raw = vec![0; count_params.buffer_len()];
+    let work_per_thread = strategy.render_with_progress(&mut raw, &count_params, threads, progress);
+    (counts::decode_counts(&raw).into_iter().map(counts::decode_count).collect(), work_per_thread)
+}
+
+/// Render the whole image on the current thread and save it to `filename`.
+pub fn run_sequentially(filename: &str, params: &RenderParams) -> Result<(), MandelError>
+{
+    run_with_strategy(filename, params, Strategy::Sequential, 1).map(|_| ())
+}
+
+/// Render the image on `threads` threads, scheduling rows with rayon's work
+/// stealing, and save it to `filename`.
+pub fn run_parallelly(filename: &str, params: &RenderParams, threads: usize) -> Result<(), MandelError>
+{
+    run_with_strategy(filename, params, Strategy::Rayon, threads).map(|_| ())
+}
+
+/// Render the image with the given strategy and save it to `filename`, in
+/// the format its extension names, with `metadata::render_metadata` in
+/// PNG files.
+/// Returns the units of work each thread completed, as `Strategy::render`
+/// does.
+pub fn run_with_strategy(filename: &str, params: &RenderParams, strategy: Strategy, threads: usize)
+    -> Result<Vec<usize>, MandelError>
+{
+    let (pixels, work_per_thread) = render_image(params, strategy, threads);
+    let color_type = params.color.color_type();
+    write_image_with_metadata(filename, &pixels, params.region.bounds, color_type,
+                              ImageFormat::for_output(filename, color_type)?, &metadata::render_metadata(params))?;
+    Ok(work_per_thread)
+}
+
+
+#[test]
+fn test_region_validate() {
+    let corner = |re, im| Complex { re, im };
+    assert!(Region::new((10, 10), corner(-2.0, 1.0), corner(1.0, -1.0)).validate().is_ok());
+    for (upper_left, lower_right) in [(corner(1.0, -1.0), corner(-2.0, 1.0)), (corner(1.0, 1.0), corner(-2.0, -1.0)),
+                                      (corner(-2.0, -1.0), corner(1.0, 1.0)), (corner(0.5, 0.5), corner(0.5, 0.5)),
+                                      (corner(-2.0, 1.0), corner(1.0, 1.0)),
+                                      (corner(f64::NAN, 1.0), corner(1.0, -1.0))] {
+        let region = Region::new((10, 10), upper_left, lower_right);
+        assert!(matches!(region.validate(), Err(MandelError::Dimensions(_))), "{:?}", region);
+    }
+    let huge = Region::new((usize::MAX / 2, 3), corner(-2.0, 1.0), corner(1.0, -1.0));
+    assert!(matches!(huge.validate(), Err(MandelError::Dimensions(_))));
+}
+
+#[test]
+fn test_region_normalized() {
+    let corner = |re, im| Complex { re, im };
+    let expected = (corner(-2.0, 1.0), corner(1.0, -1.0));
+    for (upper_left, lower_right, swapped) in [(corner(-2.0, 1.0), corner(1.0, -1.0), (false, false)),
+                                               (corner(1.0, 1.0), corner(-2.0, -1.0), (true, false)),
+                                               (corner(-2.0, -1.0), corner(1.0, 1.0), (false, true)),
+                                               (corner(1.0, -1.0), corner(-2.0, 1.0), (true, true))] {
+        let (region, swap) = Region::new((10, 10), upper_left, lower_right).normalized();
+        assert_eq!(((region.upper_left, region.lower_right), (swap.re, swap.im)), (expected, swapped));
+        assert_eq!(swap.any(), swapped != (false, false));
+        assert!(region.validate().is_ok());
+    }
+
+    // Nothing to swap makes a region of no area any better.
+    for (upper_left, lower_right) in [(corner(0.5, 0.5), corner(0.5, 0.5)), (corner(-2.0, 1.0), corner(1.0, 1.0)),
+                                      (corner(1.0, 1.0), corner(1.0, -1.0))] {
+        let (region, swap) = Region::new((10, 10), upper_left, lower_right).normalized();
+        assert!(!swap.any());
+        match region.validate() {
+            Err(MandelError::Dimensions(message)) => assert!(message.contains("no area"), "{}", message),
+            other => panic!("expected a dimensions error, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_auto_limit() {
+    // 50 * log10(4 / width)^1.5 at whole decades of depth, and the default
+    // limit for views too wide to need more.
+    for (width, limit) in [(4e-3, 260), (4e-6, 735), (4e-9, 1350), (4e-16, 3200), (4e-36, 10800)] {
+        assert_eq!(auto_limit(width, usize::MAX), limit, "{}", width);
+    }
+    for width in [40.0, 4.0, 1.0, 0.01] {
+        assert_eq!(auto_limit(width, AUTO_LIMIT_MAX), LIMIT_OF_ITERATION, "{}", width);
+    }
+    assert_eq!(auto_limit(1e-100, AUTO_LIMIT_MAX), AUTO_LIMIT_MAX);
+    assert_eq!(auto_limit(1e-100, 100), 100);
+}
+
+#[test]
+fn test_pixel_to_point() {
+    assert_eq!(pixel_to_point(
+                                (100, 200), (25, 175),
+                                Complex {re: -1.0, im: 1.0},
+                                Complex {re: 1.0, im: -1.0},
+                            ),  Complex {re: -0.5, im: -0.75});
+}
+
+#[test]
+fn test_region_pixel_to_point() {
+    let region = Region::new((100, 200), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(region.pixel_to_point((25, 175)), Complex { re: -0.5, im: -0.75 });
+    assert_eq!(region.pixel_to_point((0, 0)), region.upper_left);
+    assert_eq!(region.pixel_to_point((100, 200)), region.lower_right);
+}
+
+#[test]
+fn test_sub_region() {
+    let region = Region::new((100, 200), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let band = region.sub_region(50..150);
+    assert_eq!(band, Region::new((100, 100), Complex { re: -1.0, im: 0.5 }, Complex { re: 1.0, im: -0.5 }));
+    assert_eq!(band.pixel_to_point((25, 25)), region.pixel_to_point((25, 75)));
+    assert_eq!(region.sub_region(0..200), region);
+    assert_eq!(region.sub_region(10..10).bounds, (100, 0));
+}
+
+#[test]
+fn test_bounds_from_center_keeps_pixels_square() {
+    let center = Complex { re: -0.75, im: 0.1 };
+    for bounds in [(1000, 750), (300, 900), (64, 64)] {
+        let (upper_left, lower_right) = bounds_from_center(center, 8.0, bounds);
+        let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
+        assert!((width - WIDTH_AT_ZOOM_ONE / 8.0).abs() < 1e-12);
+        assert!((width / bounds.0 as f64 - height / bounds.1 as f64).abs() < 1e-12);
+        assert!(((upper_left + lower_right) / 2.0 - center).norm() < 1e-12);
+    }
+}
+
+#[test]
+fn test_viewport_from_center() {
+    let center = Complex { re: -0.75, im: 0.1 };
+    for (bounds, width, height) in [((400, 200), 2.0, 1.0), ((200, 400), 2.0, 4.0), ((1000, 1), 1e-3, 1e-6),
+                                     ((3, 7), 0.375, 0.875)] {
+        let (upper_left, lower_right) = viewport_from_center(center, width, bounds);
+        assert_eq!((upper_left.re, lower_right.re), (center.re - width / 2.0, center.re + width / 2.0));
+        assert_eq!((upper_left.im, lower_right.im), (center.im + height / 2.0, center.im - height / 2.0));
+        // Square pixels, and the first and last pixel centers as far from
+        // the middle as each other.
+        let region = Region::new(bounds, upper_left, lower_right);
+        let step = region.pixel_to_point((1, 1)) - region.pixel_to_point((0, 0));
+        assert!((step.re + step.im).abs() < 1e-9 * step.re, "{:?} {:?}", bounds, step);
+        assert_eq!(viewport_from_center(center, width, bounds), bounds_from_center(center, 4.0 / width, bounds));
+    }
+}
+
+#[test]
+fn test_fit_viewport() {
+    let corner = |re, im| Complex { re, im };
+    let policies = [AspectPolicy::Ignore, AspectPolicy::Warn, AspectPolicy::Pad, AspectPolicy::Crop];
+    // Views whose pixels are already square come back bit for bit, even
+    // deep ones whose corners their center and half size would not give
+    // back exactly.
+    let deep = corner(-0.743643887037151, 0.131825904205330);
+    let side = 2f64.powi(-40);
+    for (bounds, upper_left, lower_right) in [((400, 300), corner(-2.0, 1.5), corner(2.0, -1.5)),
+                                              ((3, 7), corner(-0.25, 0.875), corner(0.125, 0.0)),
+                                              ((1, 1), deep, deep + corner(side, -side))] {
+        assert_eq!(Region::new(bounds, upper_left, lower_right).pixel_aspect(), 1.0, "{:?}", bounds);
+        for policy in policies {
+            let (from, to) = fit_viewport(bounds, upper_left, lower_right, policy);
+            assert_eq!((from.re.to_bits(), from.im.to_bits()), (upper_left.re.to_bits(), upper_left.im.to_bits()));
+            assert_eq!((to.re.to_bits(), to.im.to_bits()), (lower_right.re.to_bits(), lower_right.im.to_bits()));
+        }
+    }
+
+    // A square view in a wide image and in a tall one, and a wide view in a
+    // square image.
+    for (bounds, upper_left, lower_right) in [((1000, 200), corner(-2.0, 1.5), corner(1.0, -1.5)),
+                                              ((200, 1000), corner(-2.0, 1.5), corner(1.0, -1.5)),
+                                              ((64, 64), corner(-0.8, 0.2), corner(-0.7, 0.19))] {
+        let stretched = Region::new(bounds, upper_left, lower_right);
+        assert!((stretched.pixel_aspect() - 1.0).abs() > 0.5, "{:?}", bounds);
+        for policy in [AspectPolicy::Ignore, AspectPolicy::Warn] {
+            assert_eq!(fit_viewport(bounds, upper_left, lower_right, policy), (upper_left, lower_right));
+        }
+        let middle = (upper_left + lower_right) / 2.0;
+        let [padded, cropped] = [AspectPolicy::Pad, AspectPolicy::Crop].map(|policy| {
+            let (from, to) = fit_viewport(bounds, upper_left, lower_right, policy);
+            let fitted = Region::new(bounds, from, to);
+            assert!((fitted.pixel_aspect() - 1.0).abs() < 1e-12, "{:?} {:?}", policy, fitted);
+            assert!(((from + to) / 2.0 - middle).norm() < 1e-15, "{:?} {:?}", policy, fitted);
+            // One side is left exactly as it was.
+            assert!((from.re, to.re) == (upper_left.re, lower_right.re)
+                    || (from.im, to.im) == (upper_left.im, lower_right.im));
+            fitted
+        });
+        // Padding keeps all of the view, cropping keeps only what is in it.
+        assert!(padded.upper_left.re <= upper_left.re && padded.upper_left.im >= upper_left.im);
+        assert!(padded.lower_right.re >= lower_right.re && padded.lower_right.im <= lower_right.im);
+        assert!(cropped.upper_left.re >= upper_left.re && cropped.upper_left.im <= upper_left.im);
+        assert!(cropped.lower_right.re <= lower_right.re && cropped.lower_right.im >= lower_right.im);
+        assert_eq!(padded, stretched.with_square_pixels());
+    }
+}
+
+#[test]
+fn test_with_square_pixels() {
+    let corner = |re, im| Complex { re, im };
+    for (bounds, upper_left, lower_right) in [((300, 100), corner(-2.0, 1.5), corner(1.0, -1.5)),
+                                              ((100, 300), corner(-2.0, 1.5), corner(1.0, -1.5)),
+                                              ((640, 480), corner(-0.8, 0.2), corner(-0.7, 0.19))] {
+        let region = Region::new(bounds, upper_left, lower_right);
+        let square = region.with_square_pixels();
+        let height = square.upper_left.im - square.lower_right.im;
+        assert!((square.pixel_width() - height / bounds.1 as f64).abs() < 1e-15, "{:?}", square);
+        assert!(((square.upper_left + square.lower_right) / 2.0 - (upper_left + lower_right) / 2.0).norm() < 1e-15);
+        // Nothing of the region asked for is cut off.
+        assert!(square.upper_left.re <= upper_left.re && square.upper_left.im >= upper_left.im);
+        assert!(square.lower_right.re >= lower_right.re && square.lower_right.im <= lower_right.im);
+    }
+    let already = Region::new((400, 300), corner(-2.0, 1.5), corner(2.0, -1.5));
+    assert_eq!(already.with_square_pixels(), already);
+}
+
+#[test]
+fn test_zoom_at() {
+    let region = Region::new((200, 100), Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -1.0 });
+    // Clicking the middle only magnifies.
+    let middle = region.zoom_at((100, 50), 2.0);
+    assert_eq!((middle.upper_left, middle.lower_right), (Complex { re: -1.0, im: 0.5 }, Complex { re: 1.0, im: -0.5 }));
+    // The clicked point becomes the middle of the new view.
+    let corner = region.zoom_at((20, 90), 8.0);
+    assert_eq!(corner.pixel_to_point((100, 50)), region.pixel_to_point((20, 90)));
+    assert_eq!(corner.bounds, region.bounds);
+    assert!((corner.pixel_width() - region.pixel_width() / 8.0).abs() < 1e-15);
+    // Zooming back out at the middle undoes it.
+    let back = corner.zoom_at((100, 50), 1.0 / 8.0);
+    assert!((back.upper_left - Complex { re: -3.6, im: 0.2 }).norm() < 1e-12, "{}", back.upper_left);
+    assert!(corner.validate().is_ok() && back.validate().is_ok());
+}
+
+#[test]
+fn test_render_counts_skips() {
+    // Smooth, so that the `simd` feature, which counts no cycles, leaves the
+    // render alone.
+    let params = RenderParams {
+        smooth: true,
+        bailout: SMOOTH_BAILOUT,
+        mirror: false,
+        ..RenderParams::new((60, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let skipped = |params: &RenderParams| {
+        let progress = Progress::hidden();
+        render_image_with_progress(params, Strategy::Rayon, 3, &progress);
+        progress.skipped()
+    };
+    let checked = skipped(&params);
+    let (counts, _) = render_counts(&params, Strategy::Sequential, 1);
+    // Every skipped point is inside the set, but not every point inside it
+    // is in the cardioid or the bulb.
+    let inside = counts.iter().filter(|count| count.is_none()).count();
+    assert!(checked.interior > 0 && checked.cycles > 0 && checked.interior + checked.cycles <= inside, "{:?}", checked);
+    assert!(checked.saved > 0);
+
+    let antialiased = skipped(&RenderParams { antialias: 2, ..params.clone() }).interior;
+    assert!(3 * checked.interior < antialiased && antialiased < 5 * checked.interior);
+    // Mirrored rows are copied rather than checked.
+    assert!(skipped(&RenderParams { mirror: true, ..params.clone() }).interior < checked.interior);
+    for params in [RenderParams { interior_check: false, ..params.clone() },
+                   RenderParams { fractal: Fractal::Julia(Complex { re: -0.8, im: 0.156 }), ..params.clone() },
+                   RenderParams { coloring: Coloring::Trap, ..params.clone() }] {
+        assert_eq!(skipped(&params).interior, 0, "{:?}", params);
+    }
+    let unchecked = skipped(&RenderParams { periodicity_epsilon: 0.0, ..params.clone() });
+    assert_eq!(unchecked, Skipped { interior: checked.interior, ..Default::default() });
+
+    // The counts come from the render alone, whatever ran before it.
+    estimate::estimate(&params, 1);
+    assert_eq!(render_rows(&mut vec![0; params.buffer_len()], &params, 0), checked);
+    assert_eq!(skipped(&params), checked);
+}
+
+#[test]
+fn test_tone() {
+    let params = RenderParams::new((1, 1), Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: -1.0 });
+    for position in [0.0, 0.1, 0.5, 1.0] {
+        assert_eq!(params.tone(position), position);
+    }
+
+    let gamma = RenderParams { gamma: 2.0, ..params.clone() };
+    assert_eq!(gamma.tone(0.25), 0.5);
+    assert_eq!(gamma.tone(1.0), 1.0);
+
+    let log = RenderParams { log_scale: true, limit: 99, ..params.clone() };
+    assert!((log.tone(9.0 / 99.0) - 0.5).abs() < 1e-12);
+    assert_eq!(log.tone(0.0), 0.0);
+    assert!((log.tone(1.0) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn test_sample_to_point() {
+    let region = Region::new((100, 200), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(region.sample_to_point((25, 175), (0, 0), 1), region.pixel_to_point((25, 175)));
+    assert_eq!(region.sample_to_point((25, 175), (0, 0), 3), region.pixel_to_point((25, 175)));
+
+    let region = Region::new((8, 8), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(region.sample_to_point((2, 6), (1, 1), 2), Complex { re: -0.375, im: -0.625 });
+}
+
+#[test]
+fn test_average_samples() {
+    let mut gray = [0];
+    average_samples(&mut gray, &[0, 255, 255, 255], &ColorMode::Gray);
+    assert_eq!(gray, [191]);
+
+    let mut rgb = [0; 3];
+    average_samples(&mut rgb, &[10, 20, 30, 20, 40, 61], &ColorMode::Rgb(Palette::default()));
+    assert_eq!(rgb, [15, 30, 46]);
+
+    let mut gray16 = [0; 2];
+    average_samples(&mut gray16, &[0x01, 0xff, 0x02, 0x01], &ColorMode::Gray16);
+    assert_eq!(gray16, 0x0200u16.to_be_bytes());
+}
+
+#[test]
+fn test_shade() {
+    assert_eq!(shade(None, 255, false), 0);
+    assert_eq!(shade(Some(0), 255, false), 255);
+    assert_eq!(shade(Some(128), 255, false), 127);
+    assert_eq!(shade(Some(254), 255, false), 1);
+    // An escape on the last iteration still differs from the interior.
+    assert_eq!(shade(Some(255), 255, false), 1);
+
+    assert_eq!(shade(None, 255, true), 255);
+    assert_eq!(shade(Some(0), 255, true), 0);
+    assert_eq!(shade(Some(128), 255, true), 128);
+    assert_eq!(shade(Some(255), 255, true), 254);
+
+    for count in 0..1000 {
+        assert_ne!(shade(Some(count), 1000, false), shade(None, 1000, false));
+        assert_eq!(shade(Some(count), 1000, true), 255 - shade(Some(count), 1000, false));
+    }
+}
+
+#[test]
+fn test_gray16() {
+    let params = RenderParams {
+        color: ColorMode::Gray16,
+        limit: 1000,
+        ..RenderParams::new((3, 1), Complex { re: 0.0, im: 0.0 }, Complex { re: 3.0, im: -1.0 })
+    };
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &params);
+    // 0 never escapes, 1 escapes after three iterations, 2 after two.
+    let expected = [0u16, 65339, 65404];
+    assert_eq!(pixels, expected.iter().flat_map(|value| value.to_be_bytes()).collect::<Vec<u8>>());
+    // Eight bits cannot tell those two counts apart.
+    assert_eq!(shade(Some(3), 1000, false), shade(Some(2), 1000, false));
+}
+
+#[test]
+fn test_correct_gamma() {
+    let params = RenderParams::new((4, 1), Complex { re: 0.0, im: 0.0 }, Complex { re: 4.0, im: -1.0 });
+    let levels = [0, 64, 128, 255];
+    let mut pixels = levels.to_vec();
+    correct_gamma(&mut pixels, &params);
+    assert_eq!(pixels, levels);
+
+    // 128 is about a fifth of full brightness on a display of gamma 2.2;
+    // encoding for it lifts the midtones and keeps black and white.
+    let display = RenderParams { display_gamma: 2.2, ..params.clone() };
+    correct_gamma(&mut pixels, &display);
+    assert_eq!(pixels, [0, 136, 186, 255]);
+    let mut deep = [0u16, 32768, 65535].iter().flat_map(|level| level.to_be_bytes()).collect::<Vec<u8>>();
+    correct_gamma(&mut deep, &RenderParams { color: ColorMode::Gray16, ..display.clone() });
+    assert_eq!(deep, [0u16, 47824, 65535].iter().flat_map(|level| level.to_be_bytes()).collect::<Vec<u8>>());
+    let mut counts = vec![7, 0, 0, 0];
+    correct_gamma(&mut counts, &RenderParams { color: ColorMode::Counts, ..display });
+    assert_eq!(counts, [7, 0, 0, 0]);
+}
+
+#[test]
+fn test_render_line_x4_matches_scalar() {
+    for color in [ColorMode::Gray, ColorMode::Gray16, ColorMode::Rgb(Palette::default())] {
+        let params = RenderParams {
+            color,
+            limit: 500,
+            ..RenderParams::new((64, 64), Complex { re: -2.0, im: 1.2 }, Complex { re: 0.6, im: -1.2 })
+        };
+        let row_len = 64 * params.color.bytes_per_pixel();
+        let (mut scalar, mut lanes) = (vec![0; params.buffer_len()], vec![0; params.buffer_len()]);
+        for row in 0..64 {
+            let range = row * row_len..(row + 1) * row_len;
+            render_line(&mut scalar[range.clone()], &params, (0, row), &mut Skipped::default());
+            render_line_x4(&mut lanes[range], &params, (0, row), &mut Skipped::default());
+        }
+        assert_eq!(scalar, lanes);
+
+        // A run whose length is not a multiple of four.
+        let bytes_per_pixel = params.color.bytes_per_pixel();
+        let (mut scalar, mut lanes) = (vec![0; 7 * bytes_per_pixel], vec![0; 7 * bytes_per_pixel]);
+        render_line(&mut scalar, &params, (30, 31), &mut Skipped::default());
+        render_line_x4(&mut lanes, &params, (30, 31), &mut Skipped::default());
+        assert_eq!(scalar, lanes);
+    }
+}
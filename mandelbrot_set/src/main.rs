@@ -1,172 +1,476 @@
-use std::str::FromStr;
-use std::fs::File;
 use std::env;
-use num::Complex;
-use crossbeam;
-use image::ColorType;
-use image::png::PNGEncoder;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use mandelbrot_set::{AspectPolicy, IterDump, MandelError, RenderParams, Strategy, color_counts, correct_gamma,
+                     render_counts_with_progress, render_image_with_progress, stream_png, write_image_with_metadata};
+use mandelbrot_set::animation::run_animation;
+use mandelbrot_set::checkpoint::{CHECKPOINT_BAND_ROWS, Checkpoint, render_image_with_checkpoint};
+use mandelbrot_set::counts::CountStats;
+use mandelbrot_set::deepzoom::write_deep_zoom;
+use mandelbrot_set::estimate::estimate;
+use mandelbrot_set::metadata::{read_png_text, render_args, render_metadata};
+use mandelbrot_set::output::create_output;
+use mandelbrot_set::poster::{render_tile, run_poster};
+use mandelbrot_set::pyramid::run_pyramid;
+use mandelbrot_set::raw::write_raw;
+use mandelbrot_set::slope::shade_slopes;
+use mandelbrot_set::strategy::band_heights;
+use mandelbrot_set::symmetry::mirror_sources;
+use mandelbrot_set::progress::Progress;
+use mandelbrot_set::progressive::render_previews;
 
+mod cli;
 
-const LIMIT_TO_CALL_IT_OFF_TO_INFINITY: f64 = 4.0;
-const LIMIT_OF_ITERATION: usize = 255;
-const CMD_ARG_COMPLEX_NUMBER_SEPARATOR: char = ',';
+use cli::{Command, Verbosity};
 
 
-fn escape_time(c: Complex<f64>, limit: usize) -> Option<usize>
+fn run(args: &[String]) -> Result<(), MandelError>
 {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-
-    for i in 0..limit {
-        if z.norm_sqr() > LIMIT_TO_CALL_IT_OFF_TO_INFINITY {
-            return Some(i);
+    match cli::parse_args(args)? {
+        Command::Help => {
+            println!("{}", cli::usage(&args[0]));
+            Ok(())
+        }
+        Command::Render(options) => {
+            announce_seed(&options);
+            warn_of_swapped_corners(&options);
+            warn_of_aspect(&options);
+            warn_of_precision(&options);
+            if options.auto_iter && !options.quiet {
+                eprintln!("--auto-iter picked --max-iter {}", options.params.limit);
+            }
+            render(&options)
+        }
+        Command::Recolor(recolor) => {
+            let dump = IterDump::read(&mut BufReader::new(File::open(&recolor.input)?))?;
+            let region = &dump.region;
+            eprintln!("{}: {}x{} pixels from {},{} to {},{}, at most {} iterations", recolor.input, region.bounds.0,
+                      region.bounds.1, region.upper_left.re, region.upper_left.im, region.lower_right.re,
+                      region.lower_right.im, dump.limit);
+            let options = &recolor.options;
+            announce_seed(options);
+            let params = RenderParams {
+                region: dump.region,
+                limit: dump.limit,
+                bailout: dump.bailout,
+                ..options.params.clone()
+            };
+            let mut pixels = color_counts(&dump.counts, &params);
+            correct_gamma(&mut pixels, &params);
+            write_output(options, &pixels, &params)
+        }
+        Command::Bench(bench) => {
+            let options = &bench.options;
+            let fastest = |strategy: Strategy| -> Duration {
+                (0..bench.runs).map(|_| {
+                    let start = Instant::now();
+                    render_image_with_progress(&options.params, strategy, options.threads, &Progress::hidden());
+                    start.elapsed()
+                }).min().unwrap()
+            };
+            let (width, height) = options.params.region.bounds;
+            let sequential = fastest(Strategy::Sequential);
+            let parallel = fastest(options.strategy);
+            let plural = |n: usize| if n == 1 { "" } else { "s" };
+            println!("{}x{} pixels, best of {} run{}", width, height, bench.runs, plural(bench.runs));
+            println!("sequential: {:.3} s", sequential.as_secs_f64());
+            println!("{}, {} thread{}: {:.3} s", options.strategy.name(), options.threads, plural(options.threads),
+                     parallel.as_secs_f64());
+            println!("speedup: {:.2}x", sequential.as_secs_f64() / parallel.as_secs_f64());
+            Ok(())
+        }
+        Command::Batch(batch) => {
+            let text = std::fs::read_to_string(&batch.input)?;
+            let (mut rendered, mut failed) = (0, 0);
+            // Built once, so the jobs do not each start threads of their own:
+            // every strategy runs its threads on this pool.
+            let pool = match rayon::ThreadPoolBuilder::new().num_threads(batch.threads).build() {
+                Ok(pool) => Some(pool),
+                Err(error) => {
+                    eprintln!("warning: cannot start {} threads to share between the jobs: {}; each job starts its \
+                               own", batch.threads, error);
+                    None
+                }
+            };
+            for (index, line) in text.lines().enumerate() {
+                let options = match cli::parse_job(line, batch.threads) {
+                    None => continue,
+                    Some(Ok(options)) => options,
+                    Some(Err(error)) => {
+                        eprintln!("warning: {}:{}: {}; skipping this job", batch.input, index + 1, error);
+                        continue;
+                    }
+                };
+                let start = Instant::now();
+                let result = match &pool {
+                    Some(pool) => pool.install(|| render(&options)),
+                    None => render(&options),
+                };
+                match result {
+                    Ok(()) => {
+                        rendered += 1;
+                        if !batch.quiet {
+                            println!("{}: {:.3} s", options.filename, start.elapsed().as_secs_f64());
+                        }
+                    }
+                    Err(error) => {
+                        failed += 1;
+                        eprintln!("{}:{}: {}", batch.input, index + 1, error);
+                    }
+                }
+            }
+            if failed > 0 {
+                return Err(MandelError::Batch { failed, jobs: rendered + failed });
+            }
+            Ok(())
+        }
+        Command::Tiles(tiles) => {
+            let options = &tiles.options;
+            announce_seed(options);
+            warn_of_swapped_corners(options);
+            warn_of_aspect(options);
+            let counts = run_pyramid(&tiles.out_dir, &options.params, &tiles.pyramid, options.threads)?;
+            if !options.quiet {
+                eprintln!("wrote {} tiles to {}, skipped {} empty ones", counts.written, tiles.out_dir.display(),
+                          counts.skipped);
+            }
+            Ok(())
+        }
+        Command::Explore(mut options) => {
+            announce_seed(&options);
+            warn_of_swapped_corners(&options);
+            warn_of_aspect(&options);
+            let mut line = String::new();
+            loop {
+                render(&options)?;
+                let region = options.params.region;
+                eprintln!("{}: {},{} to {},{}", options.filename, region.upper_left.re, region.upper_left.im,
+                          region.lower_right.re, region.lower_right.im);
+                loop {
+                    eprint!("X Y ZOOM, or q to quit: ");
+                    line.clear();
+                    if io::stdin().lock().read_line(&mut line)? == 0 {
+                        return Ok(());
+                    }
+                    match cli::parse_click(&line, region.bounds) {
+                        Ok(None) => return Ok(()),
+                        Ok(Some(click)) => {
+                            options.params.region = region.zoom_at(click.pixel, click.zoom);
+                            break;
+                        }
+                        Err(error) => eprintln!("{}", error),
+                    }
+                }
+            }
+        }
+        Command::ReadMetadata(filename) => {
+            for (keyword, text) in read_png_text(&std::fs::read(filename)?)? {
+                println!("{}: {}", keyword, text);
+            }
+            Ok(())
+        }
+        Command::Describe(filename) => {
+            let flags = render_args(&read_png_text(&std::fs::read(&filename)?)?)?;
+            let words: Vec<String> = [args[0].clone(), "--output".to_string(), filename].into_iter().chain(flags)
+                .map(|word| shell_quote(&word)).collect();
+            println!("{}", words.join(" "));
+            Ok(())
+        }
+        Command::Animate(animation) => {
+            let options = &animation.options;
+            announce_seed(options);
+            warn_of_swapped_corners(options);
+            warn_of_aspect(options);
+            run_animation(&animation.out_dir, &options.params, &animation.zoom, options.threads, |filename| {
+                log(options, Verbosity::Info, || format!("wrote {}", filename.display()));
+            })
         }
-        z = z * z + c;
-    }
-
-    None
-}
-
-fn parse_bool(s: &str) -> Option<bool> {
-    match s {
-        "0" => Some(false),
-        "1" => Some(true),
-        _ => None,
     }
 }
-
-fn parse_pair<T: FromStr>(s: &str, separator: char) -> Option<(T, T)>
+/// Render and write the image `options` describes, or with `--estimate`
+/// only say what that would cost.
+fn render(options: &cli::Options) -> Result<(), MandelError>
 {
-    match s.find(separator) {
-        None => None,
-        Some(index) => {
-            match (T::from_str(&s[..index]), T::from_str(&s[index + 1..])) {
-                (Ok(l), Ok(r)) => Some((l, r)),
-                _ => None,
+    let params = &options.params;
+    let (width, height) = params.region.bounds;
+    let region = &params.region;
+    log(options, Verbosity::Info, || {
+        format!("{}x{} pixels of {} from {},{} to {},{}, at most {} iterations", width, height, params.fractal.name(),
+                region.upper_left.re, region.upper_left.im, region.lower_right.re, region.lower_right.im, params.limit)
+    });
+    log(options, Verbosity::Debug, || format!("{:?}", params));
+    if options.estimate {
+        let threads = if options.strategy == Strategy::Sequential { 1 } else { options.threads };
+        let guess = estimate(params, threads);
+        println!("{}x{} pixels, estimated from {} samples", width, height, guess.samples);
+        println!("about {:.3e} iterations", guess.iterations);
+        println!("about {:.1} s on {} thread{}", guess.seconds, threads, if threads == 1 { "" } else { "s" });
+        return Ok(());
+    }
+    log(options, Verbosity::Info, || describe_threads(options));
+    log(options, Verbosity::Debug, || describe_split(options));
+    let start = Instant::now();
+    if let Some(roi) = &options.roi {
+        let progress = Progress::new(roi.size.0 * roi.size.1, !options.quiet && io::stderr().is_terminal());
+        let pixels = render_tile(params, roi, options.threads, &progress);
+        log_time(options, "rendering", start);
+        let start = Instant::now();
+        write_output(options, &pixels, &RenderParams { region: roi.region(&params.region), ..params.clone() })?;
+        log_time(options, "writing", start);
+        return Ok(());
+    }
+    let progress = Progress::new(width * height, !options.quiet && io::stderr().is_terminal());
+    if let Some(grid) = &options.grid {
+        run_poster(Path::new(&options.filename), params, grid, options.format, options.threads, &progress,
+                   |filename| log(options, Verbosity::Info, || format!("wrote {}", filename.display())))?;
+        log_time(options, "rendering and writing", start);
+        return Ok(());
+    }
+    let work_per_thread = if options.stream {
+        let mut output = create_output(&options.filename)?;
+        let work_per_thread = stream_png(&mut output, params, options.strategy, options.threads,
+                                         options.strip_rows, &progress, &render_metadata(params))?;
+        output.flush()?;
+        log_time(options, "rendering and writing", start);
+        work_per_thread
+    } else {
+        if options.progressive {
+            render_previews(params, options.strategy, options.threads, |scale, pixels| {
+                write_output(options, pixels, params)?;
+                log(options, Verbosity::Info, || format!("wrote the 1/{} preview to {}", scale, options.filename));
+                Ok(())
+            })?;
+            log_time(options, "previewing", start);
+        }
+        let start = Instant::now();
+        let (pixels, work_per_thread) = if let Some(filename) = &options.checkpoint {
+            let mut checkpoint = if options.resume {
+                Checkpoint::open(Path::new(filename), params)?
+            } else {
+                Checkpoint::create(Path::new(filename), params, CHECKPOINT_BAND_ROWS)?
+            };
+            if options.resume {
+                let (done, bands) = checkpoint.progress();
+                log(options, Verbosity::Info, || format!("resuming {}: {} of {} bands already done", filename, done,
+                                                         bands));
+            }
+            render_image_with_checkpoint(params, options.strategy, options.threads, &progress, &mut checkpoint)?
+        } else if options.dump.is_none() && options.raw.is_none() && !options.stats {
+            render_image_with_progress(params, options.strategy, options.threads, &progress)
+        } else {
+            let (counts, work_per_thread) = render_counts_with_progress(params, options.strategy, options.threads,
+                                                                        &progress);
+            if options.stats {
+                eprintln!("{}", describe_stats(&CountStats::new(&counts), params.limit));
+            }
+            if let Some((filename, format)) = &options.raw {
+                let mut output = BufWriter::new(File::create(filename)?);
+                write_raw(&mut output, &counts, params.region.bounds, *format)?;
+                output.flush()?;
             }
+            let mut pixels = color_counts(&counts, params);
+            shade_slopes(&mut pixels, params, options.strategy, options.threads);
+            correct_gamma(&mut pixels, params);
+            if let Some(filename) = &options.dump {
+                let mut output = BufWriter::new(File::create(filename)?);
+                IterDump::new(params, counts).write(&mut output)?;
+                output.flush()?;
+            }
+            (pixels, work_per_thread)
+        };
+        log_time(options, "rendering", start);
+        let start = Instant::now();
+        write_output(options, &pixels, params)?;
+        log_time(options, "writing", start);
+        work_per_thread
+    };
+    if options.verbosity >= Verbosity::Info {
+        for (thread, count) in work_per_thread.iter().enumerate() {
+            eprintln!("thread {}: {} {}", thread, count, options.strategy.unit());
+        }
+        let skipped = progress.skipped();
+        let points = width * height * params.antialias * params.antialias;
+        if skipped.interior > 0 {
+            eprintln!("interior check: {} of {} points skipped ({:.1}%)", skipped.interior, points,
+                      100.0 * skipped.interior as f64 / points as f64);
+        }
+        if skipped.cycles > 0 {
+            eprintln!("periodicity check: {} of {} points caught in a cycle, sparing {} iterations", skipped.cycles,
+                      points, skipped.saved);
         }
     }
+    Ok(())
 }
 
-fn parse_complex(s: &str) -> Option<Complex<f64>>
+/// Print `message` on standard error if `options` asked for `level` or more.
+fn log(options: &cli::Options, level: Verbosity, message: impl FnOnce() -> String)
 {
-    match parse_pair(s, CMD_ARG_COMPLEX_NUMBER_SEPARATOR) {
-        None => None,
-        Some((re, im)) => Some(Complex {re, im}),
+    if options.verbosity >= level {
+        eprintln!("{}", message());
     }
 }
 
-fn pixel_to_point(bounds: (usize, usize), pixel: (usize, usize),
-                    upper_left: Complex<f64>, lower_right: Complex<f64>) -> Complex<f64>
+/// Report at `Verbosity::Info` how long `step` has taken since `start`.
+fn log_time(options: &cli::Options, step: &str, start: Instant)
+{
+    log(options, Verbosity::Info, || format!("{} took {:.3} s", step, start.elapsed().as_secs_f64()));
+}
+
+/// Which threads the render `options` describes runs on.
+fn describe_threads(options: &cli::Options) -> String
 {
-    let (width, height) = (lower_right.re - upper_left.re, upper_left.im - lower_right.im);
-    Complex {
-        re: upper_left.re + pixel.0 as f64 * (width  / bounds.0 as f64),
-        im: upper_left.im - pixel.1 as f64 * (height / bounds.1 as f64),
+    let threads = |threads: usize| format!("{} thread{}", threads, if threads == 1 { "" } else { "s" });
+    match options.strategy {
+        _ if options.grid.is_some() || options.roi.is_some() => format!("rows split between {}",
+                                                                         threads(options.threads)),
+        Strategy::Sequential => format!("strategy sequential, on {}", threads(1)),
+        strategy => format!("strategy {}, on {}", strategy.name(), threads(options.threads)),
     }
 }
 
-fn render(pixels: &mut [u8], bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>)
+/// How the render `options` describes splits the rows of the image up.
+fn describe_split(options: &cli::Options) -> String
 {
-    assert!(pixels.len() == bounds.0 * bounds.1);
-
-    for row in 0..bounds.1 {
-        for column in 0..bounds.0 {
-            let pixel = (column, row);
-            let point = pixel_to_point(bounds, pixel, upper_left, lower_right);
-            pixels[(row * bounds.0) + column] = 
-                match  escape_time(point, LIMIT_OF_ITERATION) {
-                    None => 0,
-                    Some(count) => (LIMIT_OF_ITERATION - count) as u8,
-                };
+    let (width, height) = options.params.region.bounds;
+    let bands = |height: usize| {
+        let heights: Vec<String> = band_heights(height, options.threads).iter().map(usize::to_string).collect();
+        format!("bands of {} rows", heights.join(", "))
+    };
+    if let Some(roi) = &options.roi {
+        return format!("{}x{} pixels from {},{}, in {}", roi.size.0, roi.size.1, roi.origin.0, roi.origin.1,
+                       bands(roi.size.1));
+    }
+    if let Some(grid) = &options.grid {
+        let tile = grid.tiles((width, height)).unwrap()[0];
+        return format!("{} tiles of up to {}x{} pixels, each in {}", grid.columns * grid.rows, tile.size.0,
+                       tile.size.1, bands(tile.size.1));
+    }
+    if options.strategy.mirrors() && !options.stream {
+        if let Some(sources) = mirror_sources(&options.params) {
+            let copies = sources.iter().filter(|source| source.is_some()).count();
+            return format!("{} rows, {} of them copied from their mirror images above the real axis", height, copies);
         }
     }
+    match options.strategy {
+        Strategy::Sequential if options.stream => format!("strips of {} rows", options.strip_rows),
+        Strategy::Sequential => format!("one band of {} rows", height),
+        Strategy::Bands if options.stream => format!("strips of {} rows, each in {}", options.strip_rows,
+                                                     bands(options.strip_rows.min(height))),
+        Strategy::Bands => bands(height),
+        Strategy::Rayon | Strategy::Dynamic => format!("{} rows, handed out one at a time", height),
+        Strategy::Tiles(size) => format!("{} tiles of up to {}x{} pixels", width.div_ceil(size) * height.div_ceil(size),
+                                         size, size),
+    }
 }
 
-fn write_image(filename: &str, pixels: &[u8], bounds: (usize, usize)) -> Result<(), std::io::Error>
+/// The line `--stats` prints for `stats` of a render at most `limit`
+/// iterations deep.
+fn describe_stats(stats: &CountStats, limit: usize) -> String
 {
-    let output = File::create(filename)?;
-
-    let encoder = PNGEncoder::new(output);
-    let (width, height) = (bounds.0 as u32, bounds.1 as u32);
-    encoder.encode(&pixels, width, height, ColorType::Gray(8))?;
-    Ok(())
+    let interior = format!("{:.1}% of {} pixels never escaped", 100.0 * stats.interior_fraction(),
+                           stats.escaped + stats.interior);
+    match (stats.min, stats.max) {
+        (Some(min), Some(max)) => format!("escape counts: min {}, max {} of --max-iter {}, mean {:.1}; {}", min, max,
+                                          limit, stats.mean, interior),
+        _ => format!("escape counts: none escaped within --max-iter {}; {}", limit, interior),
+    }
 }
 
-
-#[test]
-fn test_parse_pair() {
-    assert_eq!(parse_pair::<u64>("     ", ','), None);
-    assert_eq!(parse_pair::<u64>("45*50", '*'), Some((45, 50)));
+/// Say which seed `--random-palette` picked, if it had to pick one, so that
+/// the palette can be had again.
+fn announce_seed(options: &cli::Options)
+{
+    if let (Some(seed), false) = (options.palette_seed, options.quiet) {
+        eprintln!("random palette seed: {} (--random-palette {} colors the same)", seed, seed);
+    }
 }
 
-#[test]
-fn test_parse_complex() {
-    assert_eq!(parse_complex("1.25,-0.0625"), Some(Complex {re: 1.25, im: -0.0625}));
-    assert_eq!(parse_complex(",-0.0625"), None);
+/// Warn that corners given the wrong way round were swapped, and say which
+/// view is rendered instead.
+fn warn_of_swapped_corners(options: &cli::Options)
+{
+    let swapped = options.swapped;
+    if !swapped.any() || options.quiet {
+        return;
+    }
+    let parts = match (swapped.re, swapped.im) {
+        (true, true) => "real and imaginary parts",
+        (true, false) => "real parts",
+        _ => "imaginary parts",
+    };
+    let region = &options.params.region;
+    eprintln!("warning: swapped the {} of the corners, which were given the wrong way round; rendering {},{} to {},{} \
+               (--strict refuses them instead)", parts, region.upper_left.re, region.upper_left.im,
+              region.lower_right.re, region.lower_right.im);
 }
 
-#[test]
-fn test_pixel_to_point() {
-    assert_eq!(pixel_to_point(
-                                (100, 200), (25, 175),
-                                Complex {re: -1.0, im: 1.0},
-                                Complex {re: 1.0, im: -1.0},
-                            ),  Complex {re: -0.5, im: -0.75});
+/// Warn, under `--aspect warn`, by how much the view is stretched to the
+/// shape of the image.
+fn warn_of_aspect(options: &cli::Options)
+{
+    if options.aspect != AspectPolicy::Warn || options.quiet {
+        return;
+    }
+    // Views given by their center are square to within rounding.
+    let aspect = options.params.region.pixel_aspect();
+    if (aspect - 1.0).abs() > 1e-9 {
+        let (factor, way) = if aspect > 1.0 { (aspect, "sideways") } else { (1.0 / aspect, "from top to bottom") };
+        eprintln!("warning: the view is squashed {:.3} times {} to fit --size; --aspect pad or --aspect crop keeps \
+                   its shape", factor, way);
+    }
 }
 
-fn run_sequentially(filename: &str, bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) {
-    let (width, height) = bounds;
-    let mut pixels = vec![0; width * height];
-    render(&mut pixels, bounds, upper_left, lower_right);
-    write_image(filename, &pixels, bounds).expect("error writing PNG file");
+/// Warn when `--precision` asks for far more bits than the pixels of the
+/// view need, since every extra bit only slows the render down.
+fn warn_of_precision(options: &cli::Options)
+{
+    let params = &options.params;
+    if let (Some(precision), false) = (&params.precision, options.quiet) {
+        let needed = precision.suggested_bits(params.region.bounds);
+        if precision.bits > 2 * needed {
+            eprintln!("warning: --precision {} is more than twice the {} bits this view needs; it will only render \
+                       slower", precision.bits, needed);
+        }
+    }
 }
 
-fn run_parallelly(filename: &str, bounds: (usize, usize), upper_left: Complex<f64>, lower_right: Complex<f64>) {
-    let (width, height) = bounds;
-
-    let mut pixels = vec![0; width * height];
-    
-    let threads: usize = 8;
-    let rows_per_band = (height / threads) + 1;
-    {
-        let bands: Vec<&mut [u8]> = pixels.chunks_mut(rows_per_band * width).collect();
-        crossbeam::scope(|spanner| {
-            for (i, band) in bands.into_iter().enumerate() {
-                let top = rows_per_band * i;
-                let height = band.len() / width;
-                let band_bounds = (width, height);
-                let band_upper_left = pixel_to_point(bounds, (0usize, top), upper_left, lower_right);
-                let band_lower_right = pixel_to_point(bounds, (width, top + height), upper_left, lower_right);
-        
-                spanner.spawn(
-                    move |_| {
-                        render(band, band_bounds, band_upper_left, band_lower_right);
-                    }
-                );
-            }
-        }).unwrap();        
+/// Write the image `pixels` of `params` to the output `options` name: one
+/// image file, or a Deep Zoom descriptor and its tiles.
+fn write_output(options: &cli::Options, pixels: &[u8], params: &RenderParams) -> Result<(), MandelError>
+{
+    let color_type = params.color.color_type();
+    match &options.deep_zoom {
+        Some(deep_zoom) => write_deep_zoom(Path::new(&options.filename), pixels, params.region.bounds, color_type,
+                                           options.format, deep_zoom, |filename| {
+            log(options, Verbosity::Info, || format!("wrote {}", filename.display()));
+        }),
+        None => write_image_with_metadata(&options.filename, pixels, params.region.bounds, color_type, options.format,
+                                          &render_metadata(params)),
     }
+}
 
-    write_image(filename, &pixels, bounds).expect("error writing PNG file");
+/// `word` as a POSIX shell reads it back: unchanged if it is safe as it
+/// stands, otherwise in single quotes.
+fn shell_quote(word: &str) -> String
+{
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_.,:/=+@%".contains(c);
+    if !word.is_empty() && word.chars().all(safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
 
-    if args.len() != 6 {
-        eprintln!("Usage: {} FILE PIXELS UPPERLEFT LOWERRIGHT <SEQUENTIAL:0|PARALLEL:1>", args[0]);
-        eprintln!("Example: {} mandel.png 1000x750 -1.20,0.35 -1,0.20 1", args[0]);
-        std::process::exit(1);
-    }
-
-    let bounds: (usize, usize) = parse_pair::<usize>(&args[2], 'x').expect("error parsing image dimensions");
-    let upper_left: Complex<f64> = parse_complex(&args[3]).expect("error parsing upper left corner point");
-    let lower_right: Complex<f64> = parse_complex(&args[4]).expect("error parsing lower right corner point");
-    let heuristics: bool = parse_bool(&args[5]).expect("error parsing <SEQUENTIAL:0|PARALLEL:1>");
-
-    if heuristics {
-        run_parallelly(&args[1], bounds, upper_left, lower_right);
-    } else {
-        run_sequentially(&args[1], bounds, upper_left, lower_right);
+    if let Err(error) = run(&args) {
+        eprintln!("{}: {}", args[0], error);
+        if let MandelError::Usage(_) = error {
+            eprintln!("{}", cli::usage(&args[0]));
+        }
+        std::process::exit(error.exit_code());
     }
 }
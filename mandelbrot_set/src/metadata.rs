@@ -0,0 +1,156 @@
+//! Text that travels with a PNG image and says how it was rendered, so the
+//! view it shows can be recovered later.
+
+use std::collections::BTreeMap;
+use crate::{Fractal, MandelError, RenderParams};
+
+
+/// The eight bytes every PNG file starts with.
+pub const PNG_SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// What every keyword `render_metadata` makes but `Software` starts with, so
+/// that ours stand apart from the text other programs add.
+pub const KEYWORD_PREFIX: &str = "mandelbrot:";
+
+/// The flags, without their `--`, whose values `render_metadata` records, in
+/// the order `render_args` gives them.
+const FLAGS: [&str; 8] =
+    ["size", "upper-left", "lower-right", "max-iter", "fractal", "julia-c", "power", "newton-epsilon"];
+
+
+/// The keywords and text describing the render `params`. Past the
+/// `KEYWORD_PREFIX`, the keywords are the names of the flags that would
+/// render the view again, and the text is in the syntax those flags take:
+/// `fractal`, `size`, `upper-left`, `lower-right`, `max-iter`, `julia-c`
+/// for Julia sets, `power` for Multibrot sets and `newton-epsilon` for
+/// Newton's method. `Software` and `mandelbrot:version` name the program.
+///
+/// ```
+/// use num::Complex;
+/// use mandelbrot_set::RenderParams;
+/// use mandelbrot_set::metadata::render_metadata;
+///
+/// let params = RenderParams::new((300, 200), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+/// let metadata = render_metadata(&params);
+/// assert_eq!(metadata["mandelbrot:size"], "300x200");
+/// assert_eq!(metadata["mandelbrot:upper-left"], "-2,1");
+/// ```
+pub fn render_metadata(params: &RenderParams) -> BTreeMap<String, String>
+{
+    let region = &params.region;
+    let complex = |z: num::Complex<f64>| format!("{},{}", z.re, z.im);
+    let mut metadata = BTreeMap::new();
+    let mut insert = |name: &str, text: String| metadata.insert(format!("{}{}", KEYWORD_PREFIX, name), text);
+    insert("version", env!("CARGO_PKG_VERSION").to_string());
+    insert("fractal", params.fractal.name().to_string());
+    if let Fractal::Julia(c) = params.fractal {
+        insert("julia-c", complex(c));
+    }
+    if let Fractal::Multibrot(power) = params.fractal {
+        insert("power", power.to_string());
+    }
+    if let Fractal::Newton(epsilon) = params.fractal {
+        insert("newton-epsilon", epsilon.to_string());
+    }
+    insert("size", format!("{}x{}", region.bounds.0, region.bounds.1));
+    insert("upper-left", complex(region.upper_left));
+    insert("lower-right", complex(region.lower_right));
+    insert("max-iter", params.limit.to_string());
+    metadata.insert("Software".to_string(), env!("CARGO_PKG_NAME").to_string());
+    metadata
+}
+
+/// The flags and values that render the view `metadata` describes again,
+/// e.g. `["--size", "300x200", "--upper-left", "-2,1", ...]`, for metadata
+/// that `render_metadata` made. Fails with `MandelError::Parse` if the size,
+/// a corner or the iteration limit is missing.
+pub fn render_args(metadata: &BTreeMap<String, String>) -> Result<Vec<String>, MandelError>
+{
+    let mut args = Vec::new();
+    for (index, flag) in FLAGS.iter().enumerate() {
+        match metadata.get(&format!("{}{}", KEYWORD_PREFIX, flag)) {
+            Some(text) => args.extend([format!("--{}", flag), text.clone()]),
+            None if index < 4 => {
+                return Err(MandelError::Parse(format!("no {}{} in the metadata", KEYWORD_PREFIX, flag)));
+            }
+            None => {}
+        }
+    }
+    Ok(args)
+}
+
+/// The keywords and text of every `tEXt` chunk in the PNG file `bytes`.
+/// Fails with `MandelError::Parse` if `bytes` is not a PNG file or a chunk
+/// runs past its end. The text is Latin-1, as PNG specifies.
+pub fn read_png_text(bytes: &[u8]) -> Result<BTreeMap<String, String>, MandelError>
+{
+    if !bytes.starts_with(PNG_SIGNATURE) {
+        return Err(MandelError::Parse("not a PNG file".to_string()));
+    }
+    let latin1 = |bytes: &[u8]| bytes.iter().map(|&byte| byte as char).collect::<String>();
+
+    let mut metadata = BTreeMap::new();
+    let mut at = PNG_SIGNATURE.len();
+    while at < bytes.len() {
+        // Each chunk is its length, its type, its data and a checksum.
+        let header = bytes.get(at..at + 8).ok_or_else(|| MandelError::Parse("truncated PNG chunk".to_string()))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let data = bytes.get(at + 8..at + 8 + length + 4)
+            .ok_or_else(|| MandelError::Parse("truncated PNG chunk".to_string()))?;
+        let data = &data[..length];
+        match &header[4..8] {
+            b"tEXt" => {
+                let (keyword, text) = data.split_at(data.iter().position(|&byte| byte == 0).unwrap_or(length));
+                metadata.insert(latin1(keyword), latin1(text.get(1..).unwrap_or_default()));
+            }
+            b"IEND" => break,
+            _ => {}
+        }
+        at += 8 + length + 4;
+    }
+    Ok(metadata)
+}
+
+
+#[test]
+fn test_render_metadata() {
+    use num::Complex;
+
+    let params = RenderParams {
+        fractal: Fractal::Julia(Complex { re: -0.8, im: 0.156 }),
+        limit: 5000,
+        ..RenderParams::new((1000, 750), Complex { re: -1.2, im: 0.35 }, Complex { re: -1.0, im: 0.2 })
+    };
+    let metadata = render_metadata(&params);
+    assert_eq!(metadata["mandelbrot:fractal"], "julia");
+    assert_eq!(metadata["mandelbrot:julia-c"], "-0.8,0.156");
+    assert_eq!(metadata["mandelbrot:lower-right"], "-1,0.2");
+    assert_eq!(metadata["mandelbrot:max-iter"], "5000");
+    assert_eq!(metadata["mandelbrot:version"], env!("CARGO_PKG_VERSION"));
+    let tricorn = render_metadata(&RenderParams { fractal: Fractal::Tricorn, ..params.clone() });
+    assert!(!tricorn.contains_key("mandelbrot:julia-c"));
+    let cubic = render_metadata(&RenderParams { fractal: Fractal::Multibrot(3), ..params });
+    assert_eq!((cubic["mandelbrot:fractal"].as_str(), cubic["mandelbrot:power"].as_str()), ("mandelbrot", "3"));
+}
+
+#[test]
+fn test_render_args() {
+    use num::Complex;
+
+    let params = RenderParams { limit: 300, ..RenderParams::new((30, 20), Complex { re: -2.0, im: 1.0 },
+                                                                Complex { re: 1.0, im: -1.0 }) };
+    let mut metadata = render_metadata(&params);
+    assert_eq!(render_args(&metadata).unwrap(),
+               ["--size", "30x20", "--upper-left", "-2,1", "--lower-right", "1,-1", "--max-iter", "300",
+                "--fractal", "mandelbrot"]);
+    metadata.remove("mandelbrot:max-iter");
+    assert!(matches!(render_args(&metadata), Err(MandelError::Parse(message)) if message.contains("max-iter")));
+}
+
+#[test]
+fn test_read_png_text_errors() {
+    assert!(matches!(read_png_text(b"GIF89a"), Err(MandelError::Parse(_))));
+    let truncated = [&PNG_SIGNATURE[..], b"\0\0\0\x10tEXtab"].concat();
+    assert!(matches!(read_png_text(&truncated), Err(MandelError::Parse(_))));
+    assert!(read_png_text(PNG_SIGNATURE).unwrap().is_empty());
+}
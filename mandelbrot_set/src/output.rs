@@ -0,0 +1,569 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::Path;
+use image::ColorType;
+use image::jpeg::JPEGEncoder;
+use deflate::Compression;
+use deflate::write::ZlibEncoder;
+use png::HasParameters;
+use crate::MandelError;
+
+
+/// The JPEG quality used unless another is asked for.
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// The filename that stands for standard output.
+pub const STDOUT: &str = "-";
+
+/// Why EXR output fails in a build without the `exr` feature.
+pub const EXR_UNAVAILABLE: &str = "EXR output needs mandelbrot_set built with --features exr";
+
+/// The most compressed bytes `PngStream` holds before writing them out as an
+/// `IDAT` chunk.
+const IDAT_CHUNK_LEN: usize = 1 << 16;
+
+
+/// The file formats `write_image` can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// Binary Netpbm, `P6`.
+    Ppm,
+    /// Binary Netpbm grayscale, `P5`, in 8 or 16 bits.
+    Pgm,
+    /// Uncompressed 24-bit Windows bitmap.
+    Bmp,
+    /// Uncompressed baseline TIFF, grayscale in 8 or 16 bits or RGB.
+    Tiff,
+    /// Lossy, with a quality from 1 (worst) to 100 (best).
+    Jpeg(u8),
+    /// Uncompressed OpenEXR with a single 32-bit float channel, for
+    /// `ColorMode::Values`. Writing it needs the `exr` feature.
+    Exr,
+}
+
+impl ImageFormat {
+    /// Pick the format from the extension of `filename`: `.png`, `.ppm`,
+    /// `.pgm`, `.bmp`, `.tif`, `.tiff`, `.jpg`, `.jpeg` or `.exr`, in any
+    /// case. JPEG gets `DEFAULT_JPEG_QUALITY`.
+    pub fn from_filename(filename: &str) -> Result<ImageFormat, MandelError>
+    {
+        let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        ImageFormat::from_name(extension).ok_or_else(|| {
+            MandelError::Parse(format!("cannot tell the image format of '{}'; expected a .png, .ppm, .pgm, .bmp, \
+                                        .tif, .tiff, .jpg, .jpeg or .exr file", filename))
+        })
+    }
+
+    /// The format called `name`, one of the extensions `from_filename`
+    /// knows, in any case.
+    pub fn from_name(name: &str) -> Option<ImageFormat>
+    {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(ImageFormat::Png),
+            "ppm" => Some(ImageFormat::Ppm),
+            "pgm" => Some(ImageFormat::Pgm),
+            "bmp" => Some(ImageFormat::Bmp),
+            "tif" | "tiff" => Some(ImageFormat::Tiff),
+            "jpg" | "jpeg" => Some(ImageFormat::Jpeg(DEFAULT_JPEG_QUALITY)),
+            "exr" => Some(ImageFormat::Exr),
+            _ => None,
+        }
+    }
+
+    /// The Netpbm format for pixels of `color_type`: PGM for grayscale, PPM
+    /// otherwise. This is what goes to `STDOUT`, which needs a format that
+    /// can be written front to back.
+    pub fn netpbm_for(color_type: ColorType) -> ImageFormat
+    {
+        match color_type {
+            ColorType::Gray(_) => ImageFormat::Pgm,
+            _ => ImageFormat::Ppm,
+        }
+    }
+
+    /// The format `write_image` uses for `filename`: Netpbm for `STDOUT`,
+    /// otherwise whatever the extension names.
+    pub fn for_output(filename: &str, color_type: ColorType) -> Result<ImageFormat, MandelError>
+    {
+        if filename == STDOUT { Ok(ImageFormat::netpbm_for(color_type)) } else { ImageFormat::from_filename(filename) }
+    }
+}
+
+
+/// Write the buffer `pixels`, whose dimensions are given by `bounds` and
+/// whose layout is described by `color_type`, to the file named `filename`,
+/// in the format its extension names. A `filename` of `STDOUT` writes
+/// Netpbm to standard output.
+pub fn write_image(filename: &str, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    write_image_as(filename, pixels, bounds, color_type, ImageFormat::for_output(filename, color_type)?)
+}
+
+/// Like `write_image`, but in the given format whatever the file is called.
+/// Every format is written front to back without seeking, so any of them
+/// can go to `STDOUT`.
+pub fn write_image_as(filename: &str, pixels: &[u8], bounds: (usize, usize), color_type: ColorType,
+                      format: ImageFormat) -> Result<(), MandelError>
+{
+    write_image_with_metadata(filename, pixels, bounds, color_type, format, &BTreeMap::new())
+}
+
+/// Like `write_image_as`, storing each keyword and text of `metadata`, such
+/// as `metadata::render_metadata` builds, in a `tEXt` chunk when the format
+/// is PNG. Other formats leave it out. Writing to `STDOUT` fails with
+/// `MandelError::Usage` when standard output is a terminal rather than a
+/// file or a pipe.
+pub fn write_image_with_metadata(filename: &str, pixels: &[u8], bounds: (usize, usize), color_type: ColorType,
+                                 format: ImageFormat, metadata: &BTreeMap<String, String>) -> Result<(), MandelError>
+{
+    let mut output = create_output(filename)?;
+    let (width, height) = (bounds.0 as u32, bounds.1 as u32);
+
+    match format {
+        ImageFormat::Png => write_png(&mut output, pixels, bounds, color_type, metadata)?,
+        ImageFormat::Ppm => write_ppm(&mut output, pixels, bounds, color_type)?,
+        ImageFormat::Pgm => write_pgm(&mut output, pixels, bounds, color_type)?,
+        ImageFormat::Bmp => write_bmp(&mut output, pixels, bounds, color_type)?,
+        ImageFormat::Tiff => write_tiff(&mut output, pixels, bounds, color_type)?,
+        ImageFormat::Jpeg(quality) => JPEGEncoder::new_with_quality(&mut output, quality)
+            .encode(pixels, width, height, color_type)?,
+        #[cfg(feature = "exr")]
+        ImageFormat::Exr => write_exr(&mut output, pixels, bounds, color_type)?,
+        #[cfg(not(feature = "exr"))]
+        ImageFormat::Exr => return Err(MandelError::Usage(EXR_UNAVAILABLE.to_string())),
+    }
+    output.flush()?;
+    Ok(())
+}
+
+/// Open `filename`, or standard output for `STDOUT`, for writing an image.
+/// Fails with `MandelError::Usage` when standard output is a terminal
+/// rather than a file or a pipe.
+pub fn create_output(filename: &str) -> Result<BufWriter<Box<dyn Write>>, MandelError>
+{
+    // Rust's standard output does no newline translation, even on Windows,
+    // so the bytes reach a pipe unchanged; a console is another matter.
+    let output: Box<dyn Write> = if filename == STDOUT {
+        if io::stdout().is_terminal() {
+            return Err(MandelError::Usage("refusing to write an image to a terminal; \
+                                           redirect standard output to a file or a pipe".to_string()));
+        }
+        Box::new(io::stdout().lock())
+    } else {
+        Box::new(File::create(filename)?)
+    };
+    Ok(BufWriter::new(output))
+}
+
+/// Write `pixels` as a PNG image, with a `tEXt` chunk before the pixels for
+/// each entry of `metadata`. This is what image's `PNGEncoder` does, plus
+/// the text. The bytes are the same as a `PngStream` fed the rows in any
+/// number of pieces.
+pub fn write_png<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType,
+                           metadata: &BTreeMap<String, String>) -> Result<(), MandelError>
+{
+    let mut stream = PngStream::new(output, bounds, color_type, metadata)?;
+    if pixels.len() != stream.row.len() * bounds.1 || pixels.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                  format!("the pixels do not fill a {}x{} image", bounds.0, bounds.1)).into());
+    }
+    stream.write_rows(pixels)?;
+    stream.finish()
+}
+
+/// Start a PNG image on `output` with `metadata` in `tEXt` chunks, ready for
+/// the image data. Fails with `MandelError::Dimensions` if either side is
+/// longer than PNG allows.
+fn png_header<W: Write>(output: W, bounds: (usize, usize), color_type: ColorType,
+                        metadata: &BTreeMap<String, String>) -> Result<png::Writer<W>, MandelError>
+{
+    const PNG_MAX_SIDE: usize = i32::MAX as usize;
+    if bounds.0 > PNG_MAX_SIDE || bounds.1 > PNG_MAX_SIDE {
+        return Err(MandelError::Dimensions(format!("PNG images are at most {} pixels on a side, got {}x{}",
+                                                   PNG_MAX_SIDE, bounds.0, bounds.1)));
+    }
+    let (color, depth) = color_type.into();
+    let mut encoder = png::Encoder::new(output, bounds.0 as u32, bounds.1 as u32);
+    encoder.set(color).set(depth);
+    let mut writer = encoder.write_header().map_err(io::Error::from)?;
+    for (keyword, text) in metadata {
+        let chunk = [keyword.as_bytes(), &[0], text.as_bytes()].concat();
+        writer.write_chunk(*b"tEXt", &chunk).map_err(io::Error::from)?;
+    }
+    Ok(writer)
+}
+
+/// A PNG image written a few rows at a time, so that the whole image never
+/// has to be in memory. The rows are compressed as they come and written out
+/// in `IDAT` chunks; decoded, the image is the same as `write_png` makes.
+pub struct PngStream<W: Write> {
+    zlib: ZlibEncoder<IdatChunks<W>>,
+    row: Vec<u8>,
+    bytes_per_pixel: usize,
+    rows_left: usize,
+}
+
+/// Compressed image data on its way into `IDAT` chunks.
+struct IdatChunks<W: Write> {
+    writer: png::Writer<W>,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> IdatChunks<W> {
+    fn write_chunk(&mut self, len: usize) -> io::Result<()>
+    {
+        self.writer.write_chunk(*b"IDAT", &self.buffer[..len]).map_err(io::Error::from)?;
+        self.buffer.drain(..len);
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for IdatChunks<W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize>
+    {
+        self.buffer.extend_from_slice(data);
+        while self.buffer.len() >= IDAT_CHUNK_LEN {
+            self.write_chunk(IDAT_CHUNK_LEN)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()>
+    {
+        Ok(())
+    }
+}
+
+impl<W: Write> PngStream<W> {
+    /// Start a PNG image of `bounds` pixels of `color_type` on `output`, with
+    /// `metadata` in `tEXt` chunks as `write_png` stores it. Fails with
+    /// `MandelError::Dimensions` if either side is longer than PNG allows.
+    pub fn new(output: W, bounds: (usize, usize), color_type: ColorType, metadata: &BTreeMap<String, String>)
+        -> Result<PngStream<W>, MandelError>
+    {
+        let writer = png_header(output, bounds, color_type, metadata)?;
+        let (color, depth): (png::ColorType, png::BitDepth) = color_type.into();
+        let bytes_per_pixel = color.samples() * (depth as usize).div_ceil(8);
+        Ok(PngStream {
+            zlib: ZlibEncoder::new(IdatChunks { writer, buffer: Vec::new() }, Compression::Fast),
+            row: vec![0; bounds.0 * bytes_per_pixel],
+            bytes_per_pixel,
+            rows_left: bounds.1,
+        })
+    }
+
+    /// Add the next whole rows of the image, top to bottom.
+    pub fn write_rows(&mut self, rows: &[u8]) -> Result<(), MandelError>
+    {
+        let row_len = self.row.len();
+        assert!(row_len > 0 && rows.len().is_multiple_of(row_len) && rows.len() / row_len <= self.rows_left);
+        // Each row is stored with the Sub filter, each byte less the same
+        // byte of the pixel on its left, as png's own encoder does.
+        for line in rows.chunks(row_len) {
+            for (i, filtered) in self.row.iter_mut().enumerate() {
+                let left = if i < self.bytes_per_pixel { 0 } else { line[i - self.bytes_per_pixel] };
+                *filtered = line[i].wrapping_sub(left);
+            }
+            self.zlib.write_all(&[1])?;
+            self.zlib.write_all(&self.row)?;
+        }
+        self.rows_left -= rows.len() / row_len;
+        Ok(())
+    }
+
+    /// End the image once every row has been written.
+    pub fn finish(self) -> Result<(), MandelError>
+    {
+        assert!(self.rows_left == 0, "{} rows of the PNG image were never written", self.rows_left);
+        let mut chunks = self.zlib.finish()?;
+        if !chunks.buffer.is_empty() {
+            chunks.write_chunk(chunks.buffer.len())?;
+        }
+        // Dropping the png writer adds the closing IEND chunk.
+        Ok(())
+    }
+}
+
+/// Write `pixels` as a binary `P6` Netpbm image. Grayscale pixels are
+/// written as three equal channels; 16-bit grayscale is not supported.
+pub fn write_ppm<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    write!(output, "P6\n{} {}\n255\n", bounds.0, bounds.1)?;
+    match color_type {
+        ColorType::RGB(8) => output.write_all(pixels)?,
+        ColorType::Gray(8) => {
+            let rgb: Vec<u8> = pixels.iter().flat_map(|&value| [value; 3]).collect();
+            output.write_all(&rgb)?;
+        }
+        _ => return Err(MandelError::Parse(format!("cannot write {:?} pixels as PPM", color_type))),
+    }
+    Ok(())
+}
+
+/// Write grayscale `pixels` as a binary `P5` Netpbm image, with a maxval of
+/// 255 for 8-bit and 65535 for 16-bit pixels, which are already stored most
+/// significant byte first as the format wants.
+pub fn write_pgm<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    let maxval = match color_type {
+        ColorType::Gray(8) => 255,
+        ColorType::Gray(16) => 65535,
+        _ => return Err(MandelError::Parse(format!("cannot write {:?} pixels as PGM", color_type))),
+    };
+    write!(output, "P5\n{} {}\n{}\n", bounds.0, bounds.1, maxval)?;
+    output.write_all(pixels)?;
+    Ok(())
+}
+
+/// Write `pixels` as an uncompressed 24-bit BMP: a file header, a
+/// `BITMAPINFOHEADER`, and the rows from the bottom up, each as blue, green
+/// and red bytes padded to a multiple of four. Grayscale pixels are written
+/// as three equal channels; 16-bit grayscale is not supported.
+pub fn write_bmp<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    let bytes_per_pixel = match color_type {
+        ColorType::RGB(8) => 3,
+        ColorType::Gray(8) => 1,
+        _ => return Err(MandelError::Parse(format!("cannot write {:?} pixels as BMP", color_type))),
+    };
+    let (width, height) = bounds;
+    let row_size = (width * 3).div_ceil(4) * 4;
+    let image_size = row_size * height;
+    const HEADERS: usize = 14 + 40;
+
+    output.write_all(b"BM")?;
+    for field in [(HEADERS + image_size) as u32, 0, HEADERS as u32, 40, width as u32, height as u32] {
+        output.write_all(&field.to_le_bytes())?;
+    }
+    output.write_all(&1u16.to_le_bytes())?;
+    output.write_all(&24u16.to_le_bytes())?;
+    // No compression, the image size, 72 dpi in pixels per meter, and no
+    // color table.
+    for field in [0u32, image_size as u32, 2835, 2835, 0, 0] {
+        output.write_all(&field.to_le_bytes())?;
+    }
+
+    let mut line = vec![0; row_size];
+    for row in pixels.chunks(width * bytes_per_pixel).rev() {
+        for (bgr, pixel) in line.chunks_mut(3).zip(row.chunks(bytes_per_pixel)) {
+            match pixel {
+                [r, g, b] => bgr.copy_from_slice(&[*b, *g, *r]),
+                gray => bgr.fill(gray[0]),
+            }
+        }
+        output.write_all(&line)?;
+    }
+    Ok(())
+}
+
+/// Write `pixels` as a big-endian baseline TIFF with the whole image in one
+/// uncompressed strip. Big-endian byte order lets 16-bit grayscale pixels be
+/// written as they are stored.
+pub fn write_tiff<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    const ENTRIES: u16 = 9;
+
+    let (bits, samples, photometric) = match color_type {
+        ColorType::Gray(8) => (8, 1, 1),
+        ColorType::Gray(16) => (16, 1, 1),
+        ColorType::RGB(8) => (8, 3, 2),
+        _ => return Err(MandelError::Parse(format!("cannot write {:?} pixels as TIFF", color_type))),
+    };
+    // The header, then the strip, then the bits per sample if they do not
+    // fit in their entry, then the directory on a word boundary.
+    let strip = 8;
+    let bits_offset = strip + pixels.len() + pixels.len() % 2;
+    let directory = bits_offset + if samples == 3 { 6 } else { 0 };
+
+    output.write_all(b"MM\0\x2a")?;
+    output.write_all(&(directory as u32).to_be_bytes())?;
+    output.write_all(pixels)?;
+    output.write_all(&vec![0; bits_offset - strip - pixels.len()])?;
+    if samples == 3 {
+        for _ in 0..3 {
+            output.write_all(&(bits as u16).to_be_bytes())?;
+        }
+    }
+
+    let bits_value = if samples == 3 { bits_offset as u32 } else { (bits as u32) << 16 };
+    let short = |value: u32| value << 16;
+    let entries: [(u16, u16, u32, u32); ENTRIES as usize] = [
+        (256, LONG, 1, bounds.0 as u32),           // ImageWidth
+        (257, LONG, 1, bounds.1 as u32),           // ImageLength
+        (258, SHORT, samples, bits_value),         // BitsPerSample
+        (259, SHORT, 1, short(1)),                 // Compression: none
+        (262, SHORT, 1, short(photometric)),       // PhotometricInterpretation
+        (273, LONG, 1, strip as u32),              // StripOffsets
+        (277, SHORT, 1, short(samples)),           // SamplesPerPixel
+        (278, LONG, 1, bounds.1 as u32),           // RowsPerStrip
+        (279, LONG, 1, pixels.len() as u32),       // StripByteCounts
+    ];
+    output.write_all(&ENTRIES.to_be_bytes())?;
+    for (tag, field_type, count, value) in entries {
+        output.write_all(&tag.to_be_bytes())?;
+        output.write_all(&field_type.to_be_bytes())?;
+        output.write_all(&count.to_be_bytes())?;
+        output.write_all(&value.to_be_bytes())?;
+    }
+    output.write_all(&0u32.to_be_bytes())?;
+    Ok(())
+}
+
+/// Write `ColorMode::Values` pixels, 32-bit floats described as
+/// `ColorType::Gray(32)`, as a scanline OpenEXR image with one `FLOAT`
+/// channel named `Y` and no compression. The float bits are stored as they
+/// are, both being little-endian.
+#[cfg(feature = "exr")]
+pub fn write_exr<W: Write>(output: &mut W, pixels: &[u8], bounds: (usize, usize), color_type: ColorType)
+    -> Result<(), MandelError>
+{
+    if color_type != ColorType::Gray(32) {
+        return Err(MandelError::Parse(format!("cannot write {:?} pixels as EXR", color_type)));
+    }
+    let (width, height) = bounds;
+    let attribute = |header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]| {
+        for text in [name, kind] {
+            header.extend_from_slice(text.as_bytes());
+            header.push(0);
+        }
+        header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+        header.extend_from_slice(value);
+    };
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1].iter().flat_map(|n| n.to_le_bytes()).collect();
+
+    // The magic number and version 2 with single-part scanline flags.
+    let mut header = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    // A channel list entry: the name, pixel type 2 (FLOAT), linear and
+    // reserved bytes, the x and y sampling, then an empty name to end it.
+    let mut channels = b"Y\0".to_vec();
+    for field in [2i32, 0, 1, 1] {
+        channels.extend_from_slice(&field.to_le_bytes());
+    }
+    channels.push(0);
+    attribute(&mut header, "channels", "chlist", &channels);
+    attribute(&mut header, "compression", "compression", &[0]);
+    attribute(&mut header, "dataWindow", "box2i", &window);
+    attribute(&mut header, "displayWindow", "box2i", &window);
+    attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut header, "pixelAspectRatio", "float", &1.0f32.to_le_bytes());
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut header, "screenWindowWidth", "float", &1.0f32.to_le_bytes());
+    header.push(0);
+    output.write_all(&header)?;
+
+    // Uncompressed files hold one scanline per block, each block being the
+    // row number, the size of its data, and the data; the offset table in
+    // front of them says where each starts.
+    let row_size = width * 4;
+    let first_block = header.len() + height * 8;
+    for row in 0..height {
+        output.write_all(&((first_block + row * (8 + row_size)) as u64).to_le_bytes())?;
+    }
+    for (row, line) in pixels.chunks(row_size).enumerate() {
+        output.write_all(&(row as i32).to_le_bytes())?;
+        output.write_all(&(row_size as i32).to_le_bytes())?;
+        output.write_all(line)?;
+    }
+    Ok(())
+}
+
+
+#[test]
+fn test_format_from_filename() {
+    assert_eq!(ImageFormat::from_filename("mandel.png").unwrap(), ImageFormat::Png);
+    assert_eq!(ImageFormat::from_filename("out/mandel.PPM").unwrap(), ImageFormat::Ppm);
+    assert_eq!(ImageFormat::from_filename("mandel.jpg").unwrap(), ImageFormat::Jpeg(DEFAULT_JPEG_QUALITY));
+    assert_eq!(ImageFormat::from_filename("mandel.jpeg").unwrap(), ImageFormat::Jpeg(DEFAULT_JPEG_QUALITY));
+    assert!(matches!(ImageFormat::from_filename("mandel.gifv"), Err(MandelError::Parse(_))));
+    assert!(matches!(ImageFormat::from_filename("mandel"), Err(MandelError::Parse(_))));
+    assert_eq!(ImageFormat::from_filename("mandel.pgm").unwrap(), ImageFormat::Pgm);
+    assert_eq!(ImageFormat::from_filename("mandel.bmp").unwrap(), ImageFormat::Bmp);
+    assert_eq!(ImageFormat::from_filename("mandel.tif").unwrap(), ImageFormat::Tiff);
+    assert_eq!(ImageFormat::from_filename("mandel.TIFF").unwrap(), ImageFormat::Tiff);
+    assert_eq!(ImageFormat::from_filename("mandel.exr").unwrap(), ImageFormat::Exr);
+    assert_eq!(ImageFormat::from_name("EXR"), Some(ImageFormat::Exr));
+    assert_eq!(ImageFormat::from_name("gif"), None);
+
+    assert_eq!(ImageFormat::for_output(STDOUT, ColorType::Gray(16)).unwrap(), ImageFormat::Pgm);
+    assert_eq!(ImageFormat::for_output(STDOUT, ColorType::RGB(8)).unwrap(), ImageFormat::Ppm);
+    assert_eq!(ImageFormat::for_output("mandel.png", ColorType::RGB(8)).unwrap(), ImageFormat::Png);
+}
+
+#[test]
+fn test_write_ppm() {
+    let mut gray = vec![];
+    write_ppm(&mut gray, &[0, 128, 255, 7], (2, 2), ColorType::Gray(8)).unwrap();
+    assert_eq!(&gray[..11], b"P6\n2 2\n255\n");
+    assert_eq!(&gray[11..], &[0, 0, 0, 128, 128, 128, 255, 255, 255, 7, 7, 7]);
+
+    let mut rgb = vec![];
+    write_ppm(&mut rgb, &[1, 2, 3], (1, 1), ColorType::RGB(8)).unwrap();
+    assert_eq!(rgb, b"P6\n1 1\n255\n\x01\x02\x03");
+}
+
+#[test]
+fn test_write_pgm() {
+    let mut gray = vec![];
+    write_pgm(&mut gray, &[0, 128, 255, 7], (2, 2), ColorType::Gray(8)).unwrap();
+    assert_eq!(gray, b"P5\n2 2\n255\n\x00\x80\xff\x07");
+
+    let mut deep = vec![];
+    write_pgm(&mut deep, &[1, 2], (1, 1), ColorType::Gray(16)).unwrap();
+    assert_eq!(deep, b"P5\n1 1\n65535\n\x01\x02");
+
+    assert!(matches!(write_pgm(&mut vec![], &[1, 2, 3], (1, 1), ColorType::RGB(8)), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_write_bmp() {
+    let mut gray = vec![];
+    write_bmp(&mut gray, &[10, 20, 30, 40], (2, 2), ColorType::Gray(8)).unwrap();
+    // Two 8-byte rows (six bytes of pixels, two of padding), bottom row first.
+    assert_eq!(gray.len(), 54 + 16);
+    assert_eq!(&gray[..2], b"BM");
+    assert_eq!(&gray[2..6], &70u32.to_le_bytes());
+    assert_eq!(&gray[54..], &[30, 30, 30, 40, 40, 40, 0, 0, 10, 10, 10, 20, 20, 20, 0, 0]);
+
+    let mut rgb = vec![];
+    write_bmp(&mut rgb, &[1, 2, 3], (1, 1), ColorType::RGB(8)).unwrap();
+    assert_eq!(&rgb[54..], &[3, 2, 1, 0]);
+
+    assert!(matches!(write_bmp(&mut vec![], &[0, 0], (1, 1), ColorType::Gray(16)), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_write_tiff() {
+    let mut deep = vec![];
+    write_tiff(&mut deep, &[0x12, 0x34, 0x56, 0x78], (2, 1), ColorType::Gray(16)).unwrap();
+    assert_eq!(&deep[..8], b"MM\0\x2a\0\0\0\x0c");
+    assert_eq!(&deep[8..12], &[0x12, 0x34, 0x56, 0x78]);
+    assert_eq!(deep.len(), 12 + 2 + 9 * 12 + 4);
+    // The BitsPerSample entry holds 16 in place.
+    assert_eq!(&deep[14 + 2 * 12..14 + 3 * 12], &[1, 2, 0, 3, 0, 0, 0, 1, 0, 16, 0, 0]);
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn test_write_exr() {
+    let pixels: Vec<u8> = [1.5f32, f32::NAN, 3.0, 4.25].iter().flat_map(|value| value.to_le_bytes()).collect();
+    let mut exr = vec![];
+    write_exr(&mut exr, &pixels, (2, 2), ColorType::Gray(32)).unwrap();
+    assert_eq!(&exr[..8], &[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0]);
+    // Two offsets, then two blocks of eight bytes of framing and eight of
+    // pixels, the second row last.
+    assert_eq!(&exr[exr.len() - 16..exr.len() - 8], &[1, 0, 0, 0, 8, 0, 0, 0]);
+    assert_eq!(&exr[exr.len() - 8..], &pixels[8..]);
+    let offsets = exr.len() - 2 * 16 - 2 * 8;
+    assert_eq!(&exr[offsets..offsets + 8], &((offsets + 16) as u64).to_le_bytes());
+
+    assert!(matches!(write_exr(&mut vec![], &[0], (1, 1), ColorType::Gray(8)), Err(MandelError::Parse(_))));
+}
@@ -0,0 +1,280 @@
+use std::fs;
+use std::path::Path;
+use crate::MandelError;
+
+
+/// The names accepted by `Palette::named`.
+pub const PALETTE_NAMES: [&str; 5] = ["classic", "grayscale", "fire", "ocean", "rainbow"];
+
+
+/// A color gradient defined by evenly spaced RGB control points, together
+/// with the solid color used for points that never escaped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Palette {
+    stops: Vec<[u8; 3]>,
+    interior: [u8; 3],
+}
+
+impl Palette {
+    /// Build a palette from its control points, which must not be empty.
+    /// The interior is painted black.
+    pub fn new(stops: Vec<[u8; 3]>) -> Palette
+    {
+        assert!(!stops.is_empty(), "a palette needs at least one color");
+        Palette { stops, interior: [0, 0, 0] }
+    }
+
+    /// Look up one of the built-in palettes listed in `PALETTE_NAMES`.
+    pub fn named(name: &str) -> Option<Palette>
+    {
+        let stops = match name {
+            "classic" => vec![[0, 0, 255], [0, 255, 255], [255, 255, 0], [255, 0, 0]],
+            "grayscale" => vec![[255, 255, 255], [0, 0, 0]],
+            "fire" => vec![[0, 0, 0], [128, 0, 0], [255, 64, 0], [255, 192, 0], [255, 255, 255]],
+            "ocean" => vec![[0, 0, 64], [0, 64, 128], [0, 160, 192], [192, 240, 255]],
+            "rainbow" => vec![[255, 0, 0], [255, 255, 0], [0, 255, 0], [0, 255, 255], [0, 0, 255], [255, 0, 255]],
+            _ => return None,
+        };
+        Some(Palette::new(stops))
+    }
+
+    /// A gradient through three to six random colors, the same every time
+    /// for the same `seed`.
+    pub fn random(seed: u64) -> Palette
+    {
+        let mut rng = SplitMix64(seed);
+        let count = 3 + (rng.next() % 4) as usize;
+        let stops = (0..count).map(|_| {
+            let bits = rng.next().to_le_bytes();
+            [bits[0], bits[1], bits[2]]
+        }).collect();
+        Palette::new(stops)
+    }
+
+    /// Read a palette from a file, in either of the formats `parse` accepts.
+    pub fn from_file(path: &Path) -> Result<Palette, MandelError>
+    {
+        let text = fs::read_to_string(path)?;
+        Palette::parse(&text).map_err(|error| match error {
+            MandelError::Parse(message) => MandelError::Parse(format!("{}:{}", path.display(), message)),
+            error => error,
+        })
+    }
+
+    /// Parse a palette from the text of a palette file. Two formats are
+    /// understood, told apart by the first line holding a color:
+    ///
+    /// - Fractint `.map` files, with one color per line as three numbers
+    ///   separated by whitespace (`R G B`), optionally followed by a comment;
+    /// - gradient files, with one color per line as `r,g,b`.
+    ///
+    /// In both, blank lines and lines starting with `#` or `;` are ignored,
+    /// and each value must be in `0..=255`. The colors become the control
+    /// points of the gradient, so a file with fewer than 256 entries is
+    /// interpolated. Errors name the offending line.
+    pub fn parse(text: &str) -> Result<Palette, MandelError>
+    {
+        let mut stops = vec![];
+        let mut gradient = None;
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let gradient = *gradient.get_or_insert_with(|| line.split_whitespace().next().unwrap().contains(','));
+            let color = if gradient { parse_rgb(line) } else { parse_map_line(line) };
+            stops.push(color.map_err(|message| MandelError::Parse(format!("{}: {}", index + 1, message)))?);
+        }
+
+        if stops.is_empty() {
+            return Err(MandelError::Parse("0: the palette file holds no colors".to_string()));
+        }
+        Ok(Palette::new(stops))
+    }
+
+    /// This palette with points that never escaped painted `color`.
+    pub fn with_interior(self, color: [u8; 3]) -> Palette
+    {
+        Palette { interior: color, ..self }
+    }
+
+    /// The control points and the interior color, in that order.
+    pub(crate) fn colors(&self) -> (&[[u8; 3]], [u8; 3])
+    {
+        (&self.stops, self.interior)
+    }
+
+    /// Return the color at position `t` along the gradient, where `0.0` is the
+    /// first control point and `1.0` the last. Values outside that range are
+    /// clamped.
+    pub fn sample(&self, t: f64) -> [u8; 3]
+    {
+        let last = self.stops.len() - 1;
+        let position = t.clamp(0.0, 1.0) * last as f64;
+        let index = (position.floor() as usize).min(last);
+        if index == last {
+            return self.stops[last];
+        }
+
+        let fraction = position - index as f64;
+        let (from, to) = (self.stops[index], self.stops[index + 1]);
+        let mut color = [0; 3];
+        for channel in 0..3 {
+            let value = from[channel] as f64 + (to[channel] as f64 - from[channel] as f64) * fraction;
+            color[channel] = value.round() as u8;
+        }
+        color
+    }
+
+    /// Map an escape count from `escape_time` onto this palette. Points that
+    /// never escaped get the interior color.
+    pub fn color_for(&self, count: Option<usize>, limit: usize) -> [u8; 3]
+    {
+        self.color_for_value(count.map(|count| count as f64), limit)
+    }
+
+    /// Like `color_for`, but for a fractional escape count such as the one
+    /// returned by `escape_time_smooth`.
+    pub fn color_for_value(&self, value: Option<f64>, limit: usize) -> [u8; 3]
+    {
+        self.color_for_position(value.map(|value| value / limit as f64))
+    }
+
+    /// Like `color_for`, but for a position from 0 to 1 along the gradient
+    /// as passed to `sample`, `None` standing for the interior.
+    pub fn color_for_position(&self, position: Option<f64>) -> [u8; 3]
+    {
+        match position {
+            None => self.interior,
+            Some(position) => self.sample(position),
+        }
+    }
+}
+
+impl Default for Palette {
+    /// The `classic` palette: blue through cyan and yellow to red.
+    fn default() -> Palette
+    {
+        Palette::named("classic").unwrap()
+    }
+}
+
+/// The SplitMix64 generator: small, fast, and random enough for colors.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64
+    {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Parse one `R G B [comment]` line of a Fractint map.
+fn parse_map_line(line: &str) -> Result<[u8; 3], String>
+{
+    let values: Vec<&str> = line.split_whitespace().take(3).collect();
+    if values.len() < 3 {
+        return Err(format!("expected three numbers R G B, got '{}'", line));
+    }
+    parse_channels(&values, line)
+}
+
+/// Parse a color written `r,g,b`, as on each line of a gradient file.
+pub fn parse_rgb(line: &str) -> Result<[u8; 3], String>
+{
+    let values: Vec<&str> = line.split(',').map(str::trim).collect();
+    if values.len() != 3 {
+        return Err(format!("expected a color as r,g,b, got '{}'", line));
+    }
+    parse_channels(&values, line)
+}
+
+fn parse_channels(values: &[&str], line: &str) -> Result<[u8; 3], String>
+{
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().zip(values) {
+        *channel = value.parse().map_err(|_| format!("expected values from 0 to 255, got '{}'", line))?;
+    }
+    Ok(color)
+}
+
+/// Map an escape count onto the default blue→cyan→yellow→red palette.
+///
+/// ```
+/// use mandelbrot_set::escape_to_rgb;
+///
+/// assert_eq!(escape_to_rgb(None, 255), [0, 0, 0]);
+/// assert_eq!(escape_to_rgb(Some(0), 255), [0, 0, 255]);
+/// ```
+pub fn escape_to_rgb(count: Option<usize>, limit: usize) -> [u8; 3]
+{
+    Palette::default().color_for(count, limit)
+}
+
+
+#[test]
+fn test_palette_sample() {
+    let palette = Palette::new(vec![[0, 0, 0], [255, 255, 255]]);
+    assert_eq!(palette.sample(0.0), [0, 0, 0]);
+    assert_eq!(palette.sample(0.5), [128, 128, 128]);
+    assert_eq!(palette.sample(1.0), [255, 255, 255]);
+    assert_eq!(palette.sample(7.0), [255, 255, 255]);
+}
+
+#[test]
+fn test_escape_to_rgb() {
+    assert_eq!(escape_to_rgb(Some(85), 255), [0, 255, 255]);
+    assert_eq!(escape_to_rgb(Some(170), 255), [255, 255, 0]);
+    assert_eq!(escape_to_rgb(Some(255), 255), [255, 0, 0]);
+}
+
+#[test]
+fn test_named_palettes() {
+    // Each palette's first and last stop, and the color a third or half of
+    // the way along, scaled to an iteration limit of 300.
+    let expected = [
+        ("classic", [0, 0, 255], 100, [0, 255, 255], [255, 0, 0]),
+        ("grayscale", [255, 255, 255], 150, [128, 128, 128], [0, 0, 0]),
+        ("fire", [0, 0, 0], 150, [255, 64, 0], [255, 255, 255]),
+        ("ocean", [0, 0, 64], 100, [0, 64, 128], [192, 240, 255]),
+        ("rainbow", [255, 0, 0], 150, [0, 255, 128], [255, 0, 255]),
+    ];
+    for (name, first, count, middle, last) in expected {
+        let palette = Palette::named(name).unwrap();
+        assert_eq!(palette.color_for(Some(0), 300), first, "{}", name);
+        assert_eq!(palette.color_for(Some(count), 300), middle, "{}", name);
+        assert_eq!(palette.color_for(Some(300), 300), last, "{}", name);
+        assert_eq!(palette.color_for(None, 300), [0, 0, 0], "{}", name);
+    }
+    assert_eq!(PALETTE_NAMES.len(), expected.len());
+    assert_eq!(Palette::named("sepia"), None);
+}
+
+#[test]
+fn test_interior_color() {
+    let palette = Palette::named("fire").unwrap().with_interior([255, 255, 255]);
+    assert_eq!(palette.color_for(None, 255), [255, 255, 255]);
+    assert_eq!(palette.color_for_value(None, 255), [255, 255, 255]);
+    assert_eq!(palette.color_for(Some(0), 255), [0, 0, 0]);
+}
+
+#[test]
+fn test_random_palette() {
+    // The first outputs of SplitMix64 from 0, as every implementation of it
+    // gives them.
+    let mut rng = SplitMix64(0);
+    assert_eq!([rng.next(), rng.next()], [0xe220_a839_7b1d_cdaf, 0x6e78_9e6a_a1b9_65f4]);
+
+    assert_eq!(Palette::random(42), Palette::random(42));
+    assert_ne!(Palette::random(42), Palette::random(43));
+    for seed in 0..100 {
+        let palette = Palette::random(seed);
+        assert!((3..=6).contains(&palette.stops.len()), "{}: {:?}", seed, palette);
+        assert_eq!(palette.interior, [0, 0, 0]);
+    }
+}
@@ -0,0 +1,315 @@
+//! Perturbation rendering, for zooms too deep for `f64` coordinates. Past a
+//! width of about 1e-13 neighbouring pixels round to the same point and the
+//! image breaks up into blocks. Instead, one reference orbit is iterated at
+//! high precision, and each pixel follows only its small difference from
+//! it, which `f64` holds well: with `Z` the reference orbit and `Z + dz`
+//! the pixel's, `dz` steps to `2 Z dz + dz^2 + dc`, `dc` being how far the
+//! pixel lies from the reference point.
+
+use std::sync::{Arc, OnceLock};
+use num::{BigInt, Complex, FromPrimitive, ToPrimitive, Zero};
+use crate::{EscapeResult, MandelError, RenderParams, average_samples, paint_escape};
+use crate::bigfloat::parse_decimal;
+
+
+/// How many bits of precision the reference orbits carry beyond what it
+/// takes to tell neighbouring pixels apart.
+pub const GUARD_BITS: u64 = 64;
+/// How close to zero a pixel's orbit may come, relative to the reference
+/// orbit at the same step, before its delta is taken to have cancelled the
+/// reference and lost its precision: the pixel is then a glitch.
+pub const GLITCH_TOLERANCE: f64 = 1e-3;
+/// How many more references a rectangle of pixels may compute, each at one
+/// of its glitched pixels, to render those pixels again. Pixels still
+/// glitched against the last one keep what it gives them.
+pub const MAX_REFERENCES: usize = 8;
+
+/// A point of the complex plane in fixed point, both parts scaled by
+/// `2^bits` for some number of bits fixed by the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BigComplex {
+    pub re: BigInt,
+    pub im: BigInt,
+}
+
+/// Parse a decimal number such as `-0.7436438870371587047521915`, or with
+/// an exponent such as `1.5e-3`, into fixed point with `bits` fractional
+/// bits, rounding to the nearest. `None` unless `parse_decimal` takes `s`.
+pub fn parse_fixed(s: &str, bits: u64) -> Option<BigInt>
+{
+    let (negative, digits, exponent) = parse_decimal(s)?;
+    let scaled = BigInt::from(digits) << bits;
+    let ten = BigInt::from(10);
+    let value = if exponent >= 0 {
+        scaled * ten.pow(exponent as u32)
+    } else {
+        let divisor = ten.pow(exponent.unsigned_abs() as u32);
+        (scaled + &divisor / 2) / divisor
+    };
+    Some(if negative { -value } else { value })
+}
+
+/// The bits of precision a view whose pixels are `pixel` apart needs:
+/// enough to tell them apart, and `GUARD_BITS` more.
+pub fn bits_for_pixel(pixel: f64) -> u64
+{
+    GUARD_BITS + (-pixel.log2()).ceil().max(0.0) as u64
+}
+
+/// The fixed point number `x`, with `bits` fractional bits, as an `f64`.
+fn fixed_to_f64(x: &BigInt, bits: u64) -> f64
+{
+    // Only the top 64 bits are converted, which keeps the conversion in
+    // range for any precision and leaves 11 over for rounding.
+    let spare = x.bits().saturating_sub(64);
+    (x >> spare).to_f64().unwrap() * 2f64.powi(spare as i32 - bits as i32)
+}
+
+/// `x` in fixed point with `bits` fractional bits, exactly if `bits` leaves
+/// room for its lowest bit.
+fn f64_to_fixed(x: f64, bits: u64) -> BigInt
+{
+    // Scaling by a power of two is exact; only bits below the precision of
+    // the fixed point are lost.
+    let (high, low) = (bits.min(900), bits.saturating_sub(900));
+    BigInt::from_f64(x * 2f64.powi(high as i32)).unwrap() << low
+}
+
+/// The orbit of `c`, with `bits` fractional bits, under `z = z^2 + c` from
+/// 0, iterated at full precision and rounded to `f64` at each step: `z_0`
+/// to `z_limit`, or fewer if the orbit leaves the circle whose squared
+/// radius is `bailout`, the last value being the first outside it.
+pub fn reference_orbit(c: &BigComplex, bits: u64, limit: usize, bailout: f64) -> Vec<Complex<f64>>
+{
+    let (mut re, mut im) = (BigInt::zero(), BigInt::zero());
+    let mut orbit = Vec::new();
+    loop {
+        let z = Complex { re: fixed_to_f64(&re, bits), im: fixed_to_f64(&im, bits) };
+        orbit.push(z);
+        if orbit.len() > limit || z.norm_sqr() > bailout {
+            return orbit;
+        }
+        let (re_squared, im_squared, cross) = ((&re * &re) >> bits, (&im * &im) >> bits, (&re * &im) >> (bits - 1));
+        re = re_squared - im_squared + &c.re;
+        im = cross + &c.im;
+    }
+}
+
+/// Follow the orbit of the point `dc` away from the start of `reference`,
+/// a `reference_orbit` for the same `limit` and `bailout`, as `julia_orbit`
+/// follows the orbit of a point. With `glitches`, `None` if the pixel
+/// glitches: its orbit comes within `GLITCH_TOLERANCE` of zero relative to
+/// the reference, or outlasts a reference that escaped. Orbits that never
+/// escape are followed to the limit; there is no periodicity check.
+pub fn perturbed_orbit(reference: &[Complex<f64>], dc: Complex<f64>, limit: usize, bailout: f64, glitches: bool)
+    -> Option<EscapeResult>
+{
+    let mut dz = Complex::zero();
+    let mut z = reference[0];
+    for i in 0..limit {
+        let reference = match reference.get(i) {
+            Some(&reference) => reference,
+            None if glitches => return None,
+            None => break,
+        };
+        z = reference + dz;
+        if z.norm_sqr() > bailout {
+            return Some(EscapeResult { iterations: Some(i), final_z: z });
+        }
+        if glitches && z.norm_sqr() < GLITCH_TOLERANCE * GLITCH_TOLERANCE * reference.norm_sqr() {
+            return None;
+        }
+        dz = (reference * 2.0 + dz) * dz + dc;
+    }
+    Some(EscapeResult { iterations: None, final_z: z })
+}
+
+/// A reference orbit and the limit and bailout, as bits, it was iterated
+/// with.
+#[derive(Debug)]
+struct Reference {
+    limit: usize,
+    bailout: u64,
+    orbit: Vec<Complex<f64>>,
+}
+
+/// A view of the Mandelbrot set rendered by perturbation: its center, in
+/// fixed point with `bits` fractional bits, and its width and height on the
+/// plane. The primary reference orbit, at the center, is iterated the first
+/// time a render needs it and shared by every copy.
+#[derive(Clone, Debug)]
+pub struct Perturbation {
+    pub center: BigComplex,
+    pub bits: u64,
+    pub span: (f64, f64),
+    primary: Arc<OnceLock<Reference>>,
+}
+
+impl PartialEq for Perturbation {
+    fn eq(&self, other: &Perturbation) -> bool
+    {
+        (&self.center, self.bits, self.span) == (&other.center, other.bits, other.span)
+    }
+}
+
+impl Perturbation {
+    pub fn new(center: BigComplex, bits: u64, span: (f64, f64)) -> Perturbation
+    {
+        Perturbation { center, bits, span, primary: Arc::new(OnceLock::new()) }
+    }
+
+    /// The view centered on `center`, written `RE,IM` in decimal to any
+    /// number of digits, `span` wide and high on the plane and `bounds`
+    /// pixels large, with enough bits to tell its pixels apart and
+    /// `GUARD_BITS` more. Fails with `MandelError::Parse` for a bad center.
+    pub fn parse(center: &str, span: (f64, f64), bounds: (usize, usize)) -> Result<Perturbation, MandelError>
+    {
+        let bits = bits_for_pixel((span.0 / bounds.0 as f64).min(span.1 / bounds.1 as f64));
+        let error = || MandelError::Parse(format!("expected the center as RE,IM in decimal, got '{}'", center));
+        let (re, im) = center.split_once(',').ok_or_else(error)?;
+        let part = |s: &str| parse_fixed(s, bits).ok_or_else(error);
+        Ok(Perturbation::new(BigComplex { re: part(re)?, im: part(im)? }, bits, span))
+    }
+
+    /// How far sample `sample` of a `per_side` by `per_side` grid over
+    /// `pixel` lies from the center, in an image of size `bounds`, laid out
+    /// as `Region::sample_to_point` lays out its samples.
+    pub fn delta(&self, bounds: (usize, usize), pixel: (usize, usize), sample: (usize, usize), per_side: usize)
+        -> Complex<f64>
+    {
+        let offset = |pixel: usize, sample: usize, size: usize| {
+            (pixel as f64 + sample as f64 / per_side as f64) / size as f64 - 0.5
+        };
+        Complex { re: offset(pixel.0, sample.0, bounds.0) * self.span.0,
+                  im: -offset(pixel.1, sample.1, bounds.1) * self.span.1 }
+    }
+
+    /// The point `delta` away from the center, in fixed point.
+    fn offset_center(&self, delta: Complex<f64>) -> BigComplex
+    {
+        BigComplex { re: &self.center.re + f64_to_fixed(delta.re, self.bits),
+                     im: &self.center.im + f64_to_fixed(delta.im, self.bits) }
+    }
+
+    /// How the orbits of the points `deltas` away from the center end, for
+    /// the limit and bailout of `params`. Glitched points are followed again
+    /// against a new reference at one of them, up to `MAX_REFERENCES` times.
+    pub fn escapes(&self, params: &RenderParams, deltas: &[Complex<f64>]) -> Vec<EscapeResult>
+    {
+        let (limit, bailout) = (params.limit, params.bailout);
+        let primary = self.primary.get_or_init(|| {
+            let orbit = reference_orbit(&self.center, self.bits, limit, bailout);
+            Reference { limit, bailout: bailout.to_bits(), orbit }
+        });
+        // Another limit or bailout than the shared orbit was made for needs
+        // its own.
+        let local;
+        let reference = if (primary.limit, primary.bailout) == (limit, bailout.to_bits()) {
+            &primary.orbit
+        } else {
+            local = reference_orbit(&self.center, self.bits, limit, bailout);
+            &local
+        };
+        let mut escapes: Vec<Option<EscapeResult>> = deltas.iter()
+            .map(|&dc| perturbed_orbit(reference, dc, limit, bailout, true))
+            .collect();
+        for round in 1..=MAX_REFERENCES {
+            let glitched: Vec<usize> = (0..deltas.len()).filter(|&index| escapes[index].is_none()).collect();
+            if glitched.is_empty() {
+                break;
+            }
+            let at = deltas[glitched[glitched.len() / 2]];
+            let reference = reference_orbit(&self.offset_center(at), self.bits, limit, bailout);
+            for index in glitched {
+                let glitches = round < MAX_REFERENCES;
+                escapes[index] = perturbed_orbit(&reference, deltas[index] - at, limit, bailout, glitches);
+            }
+        }
+        escapes.into_iter().map(Option::unwrap).collect()
+    }
+
+    /// Render the rectangle of the image of `params` that `render_rect`
+    /// describes into `buffer`, coloring each sample by its escape count,
+    /// or smooth count with `params.smooth`.
+    pub fn render_rect(&self, buffer: &mut [u8], params: &RenderParams, origin: (usize, usize), size: (usize, usize))
+    {
+        let (bounds, per_side) = (params.region.bounds, params.antialias);
+        let mut deltas = Vec::with_capacity(size.0 * size.1 * per_side * per_side);
+        for y in origin.1..origin.1 + size.1 {
+            for x in origin.0..origin.0 + size.0 {
+                for index in 0..per_side * per_side {
+                    deltas.push(self.delta(bounds, (x, y), (index % per_side, index / per_side), per_side));
+                }
+            }
+        }
+        let escapes = self.escapes(params, &deltas);
+        let bytes_per_pixel = params.color.bytes_per_pixel();
+        let mut samples = vec![0; per_side * per_side * bytes_per_pixel];
+        for (pixel, escapes) in buffer.chunks_mut(bytes_per_pixel).zip(escapes.chunks(per_side * per_side)) {
+            for (sample, &escape) in samples.chunks_mut(bytes_per_pixel).zip(escapes) {
+                paint_escape(sample, escape, params);
+            }
+            average_samples(pixel, &samples, &params.color);
+        }
+    }
+}
+
+
+#[test]
+fn test_parse_fixed() {
+    assert_eq!(parse_fixed("1", 4), Some(BigInt::from(16)));
+    assert_eq!(parse_fixed(" -0.75 ", 4), Some(BigInt::from(-12)));
+    assert_eq!(parse_fixed("+2.5e-1", 8), Some(BigInt::from(64)));
+    assert_eq!(parse_fixed("1.5E2", 0), Some(BigInt::from(150)));
+    assert_eq!(parse_fixed(".5", 1), Some(BigInt::from(1)));
+    // 0.1 is not a whole number of sixteenths; it rounds to the nearest.
+    assert_eq!(parse_fixed("0.1", 4), Some(BigInt::from(2)));
+    for bad in ["", "-", "1.2.3", "1e", "0x10", "1,5", "--1", "e5"] {
+        assert_eq!(parse_fixed(bad, 8), None, "{:?}", bad);
+    }
+    // Far more digits than an f64 holds are kept.
+    let deep = parse_fixed("0.10000000000000000000000000000001", 200).unwrap();
+    assert!(deep > parse_fixed("0.1", 200).unwrap());
+}
+
+#[test]
+fn test_fixed_round_trip() {
+    for x in [0.0, 1.0, -0.75, 1e-30, -3.25e-21, 0.1] {
+        for bits in [200, 1200] {
+            assert_eq!(fixed_to_f64(&f64_to_fixed(x, bits), bits), x, "{} at {} bits", x, bits);
+        }
+    }
+}
+
+#[test]
+fn test_reference_orbit_matches_f64() {
+    use crate::{julia_escape_time, PERIODICITY_EPSILON};
+
+    // Where f64 is precise enough the two agree on when orbits escape.
+    for c in [Complex { re: -0.75, im: 0.1 }, Complex { re: 0.3, im: 0.5 }, Complex { re: -1.5, im: 0.0 }] {
+        let fixed = BigComplex { re: f64_to_fixed(c.re, 128), im: f64_to_fixed(c.im, 128) };
+        let orbit = reference_orbit(&fixed, 128, 500, 4.0);
+        let escaped = (orbit.len() <= 500).then(|| orbit.len() - 1);
+        assert_eq!(escaped, julia_escape_time(Complex::zero(), c, 500, 4.0, PERIODICITY_EPSILON), "{}", c);
+    }
+}
+
+#[test]
+fn test_perturbed_orbit_tracks_the_reference() {
+    use crate::fractal::julia_orbit;
+
+    let c = Complex { re: -0.75, im: 0.1 };
+    let fixed = BigComplex { re: f64_to_fixed(c.re, 128), im: f64_to_fixed(c.im, 128) };
+    let reference = reference_orbit(&fixed, 128, 1000, 4.0);
+    // The reference point itself, and points near it with deltas f64 can
+    // still add to it, escape when their own orbits do.
+    for dc in [Complex::zero(), Complex { re: 1e-9, im: 0.0 }, Complex { re: -2e-10, im: 5e-10 }] {
+        let perturbed = perturbed_orbit(&reference, dc, 1000, 4.0, true).unwrap();
+        let direct = julia_orbit(Complex::zero(), c + dc, 1000, 4.0, 0.0);
+        assert_eq!(perturbed.iterations, direct.iterations, "{}", dc);
+    }
+    // A reference that escapes early cannot vouch for a point that does not.
+    let escaping = reference_orbit(&BigComplex { re: f64_to_fixed(0.3, 64), im: f64_to_fixed(0.5, 64) }, 64, 1000, 4.0);
+    assert_eq!(perturbed_orbit(&escaping, Complex { re: -0.3, im: -0.5 }, 1000, 4.0, true), None);
+    assert_eq!(perturbed_orbit(&escaping, Complex { re: -0.3, im: -0.5 }, 1000, 4.0, false).unwrap().iterations, None);
+}
@@ -0,0 +1,157 @@
+//! One view cut into a grid of separate image files, for printing services
+//! that want a large poster in pieces.
+
+use std::path::{Path, PathBuf};
+use crate::{ImageFormat, MandelError, RenderParams, correct_gamma, render_rect, write_image_with_metadata};
+use crate::metadata::render_metadata;
+use crate::progress::Progress;
+use crate::strategy::band_heights;
+use crate::tile::Tile;
+
+
+/// A grid of `columns` by `rows` tiles covering the whole image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Grid {
+    pub columns: usize,
+    pub rows: usize,
+}
+
+impl Grid {
+    /// The tiles of an image of size `bounds`, row by row. All are the same
+    /// size but those along the right and bottom edges, which are smaller
+    /// when the image does not divide evenly. Fails with
+    /// `MandelError::Dimensions` if that would leave an edge tile empty.
+    pub fn tiles(&self, bounds: (usize, usize)) -> Result<Vec<Tile>, MandelError>
+    {
+        assert!(self.columns > 0 && self.rows > 0);
+        let size = (bounds.0.div_ceil(self.columns), bounds.1.div_ceil(self.rows));
+        if size.0 * (self.columns - 1) >= bounds.0 || size.1 * (self.rows - 1) >= bounds.1 {
+            return Err(MandelError::Dimensions(format!("a {}x{} image cannot be cut into a {}x{} grid",
+                                                       bounds.0, bounds.1, self.columns, self.rows)));
+        }
+        let mut tiles = vec![];
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let origin = (size.0 * column, size.1 * row);
+                tiles.push(Tile { origin, size: (size.0.min(bounds.0 - origin.0), size.1.min(bounds.1 - origin.1)) });
+            }
+        }
+        Ok(tiles)
+    }
+
+    /// Where the tile in `row` and `column`, counting from 0, goes when the
+    /// whole image would have been `output`: `out.png` becomes
+    /// `out_r0_c0.png`, `out_r0_c1.png` and so on.
+    pub fn tile_filename(&self, output: &Path, row: usize, column: usize) -> PathBuf
+    {
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match output.extension() {
+            Some(extension) => format!("{}_r{}_c{}.{}", stem, row, column, extension.to_string_lossy()),
+            None => format!("{}_r{}_c{}", stem, row, column),
+        };
+        output.with_file_name(name)
+    }
+}
+
+/// Render the pixels of `tile` of the image described by `params`, its rows
+/// split between `threads` threads. Pixels are mapped through the full
+/// image's region, so tiles put back together match `render` exactly.
+pub fn render_tile(params: &RenderParams, tile: &Tile, threads: usize, progress: &Progress) -> Vec<u8>
+{
+    let row_len = tile.size.0 * params.color.bytes_per_pixel();
+    let mut pixels = vec![0; row_len * tile.size.1];
+    crossbeam::scope(|spanner| {
+        let (mut rest, mut top) = (&mut pixels[..], tile.origin.1);
+        for rows in band_heights(tile.size.1, threads) {
+            let (band, after) = rest.split_at_mut(rows * row_len);
+            spanner.spawn(move |_| {
+                for (row, line) in band.chunks_mut(row_len).enumerate() {
+                    progress.add_skipped(render_rect(line, params, (tile.origin.0, top + row), (tile.size.0, 1)));
+                    progress.add(tile.size.0);
+                }
+            });
+            (rest, top) = (after, top + rows);
+        }
+    }).unwrap();
+    correct_gamma(&mut pixels, params);
+    pixels
+}
+
+/// Render the image described by `params` as the tiles of `grid`, writing
+/// each to the file `Grid::tile_filename` names for `output` in `format`,
+/// with the metadata of the part of the plane that tile covers. `on_tile` is
+/// called with the name of each file once it is written.
+pub fn run_poster(output: &Path, params: &RenderParams, grid: &Grid, format: ImageFormat, threads: usize,
+                  progress: &Progress, mut on_tile: impl FnMut(&Path)) -> Result<(), MandelError>
+{
+    let color_type = params.color.color_type();
+    for (index, tile) in grid.tiles(params.region.bounds)?.iter().enumerate() {
+        let pixels = render_tile(params, tile, threads, progress);
+        let piece = RenderParams { region: tile.region(&params.region), ..params.clone() };
+        let filename = grid.tile_filename(output, index / grid.columns, index % grid.columns);
+        write_image_with_metadata(&filename.to_string_lossy(), &pixels, tile.size, color_type, format,
+                                  &render_metadata(&piece))?;
+        on_tile(&filename);
+    }
+    Ok(())
+}
+
+
+/// Put the tiles of `grid`, rendered by `render_tile` and in the order
+/// `Grid::tiles` gives them, back together into one image.
+#[cfg(test)]
+fn stitch(params: &RenderParams, grid: &Grid, pieces: &[Vec<u8>]) -> Vec<u8> {
+    use crate::tile::blit;
+
+    let tiles = grid.tiles(params.region.bounds).unwrap();
+    assert_eq!(tiles.len(), pieces.len());
+    let mut pixels = vec![0; params.buffer_len()];
+    for (tile, piece) in tiles.iter().zip(pieces) {
+        blit(&mut pixels, params.region.bounds.0, tile, piece, params.color.bytes_per_pixel());
+    }
+    pixels
+}
+
+#[test]
+fn test_grid_tiles() {
+    let grid = Grid { columns: 4, rows: 3 };
+    let tiles = grid.tiles((30, 20)).unwrap();
+    assert_eq!(tiles.len(), 12);
+    assert_eq!(tiles[0], Tile { origin: (0, 0), size: (8, 7) });
+    assert_eq!(tiles[3], Tile { origin: (24, 0), size: (6, 7) });
+    assert_eq!(tiles[11], Tile { origin: (24, 14), size: (6, 6) });
+    assert_eq!(tiles.iter().map(|tile| tile.size.0 * tile.size.1).sum::<usize>(), 30 * 20);
+
+    assert_eq!(Grid { columns: 1, rows: 1 }.tiles((5, 5)).unwrap(), [Tile { origin: (0, 0), size: (5, 5) }]);
+    // Columns of two pixels would leave nothing for the sixth.
+    assert!(matches!(Grid { columns: 6, rows: 1 }.tiles((10, 5)), Err(MandelError::Dimensions(_))));
+    assert!(matches!(Grid { columns: 1, rows: 6 }.tiles((5, 5)), Err(MandelError::Dimensions(_))));
+}
+
+#[test]
+fn test_tile_filename() {
+    let grid = Grid { columns: 4, rows: 3 };
+    assert_eq!(grid.tile_filename(Path::new("prints/out.png"), 2, 3), Path::new("prints/out_r2_c3.png"));
+    assert_eq!(grid.tile_filename(Path::new("out"), 0, 1), Path::new("out_r0_c1"));
+}
+
+#[test]
+fn test_stitched_tiles_match_render() {
+    use num::Complex;
+    use crate::{ColorMode, Palette, render};
+
+    let params = RenderParams {
+        color: ColorMode::Rgb(Palette::default()),
+        ..RenderParams::new((45, 31), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let mut expected = vec![0; params.buffer_len()];
+    render(&mut expected, &params);
+
+    for grid in [Grid { columns: 4, rows: 3 }, Grid { columns: 1, rows: 5 }, Grid { columns: 7, rows: 1 }] {
+        let progress = Progress::new(45 * 31, false);
+        let pieces: Vec<Vec<u8>> = grid.tiles(params.region.bounds).unwrap().iter()
+            .map(|tile| render_tile(&params, tile, 3, &progress)).collect();
+        assert_eq!(stitch(&params, &grid, &pieces), expected, "{:?}", grid);
+        assert_eq!(progress.done(), 45 * 31);
+    }
+}
@@ -0,0 +1,253 @@
+//! Rendering in arbitrary precision, for `--precision`: the corners are
+//! parsed straight from their decimal text into `BigFloat`s, and each
+//! pixel's point is placed and its orbit iterated at that many bits. Far
+//! slower than `f64` or perturbation, but every pixel is computed directly,
+//! without reference orbits or glitches. Each operation is the one the
+//! `f64` path performs, in the same order, so at 53 bits the image is the
+//! `f64` one byte for byte.
+
+use num::Complex;
+use crate::{EscapeResult, Fractal, MandelError, RenderParams, average_samples, paint_escape, viewport_from_center};
+use crate::bigfloat::BigFloat;
+use crate::perturbation::bits_for_pixel;
+
+
+/// The most bits of mantissa `--precision` accepts.
+pub const MAX_PRECISION: u64 = 1 << 16;
+/// The largest fraction of a pixel an orbit may come back within and still
+/// be taken for a cycle, however large `RenderParams::periodicity_epsilon`.
+pub const CYCLE_PIXEL_FRACTION: f64 = 1e-3;
+
+
+/// A view held to `bits` bits of mantissa: its corners, and for
+/// `Fractal::Julia` its constant, which otherwise comes from the fractal
+/// rounded to `f64`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Precision {
+    pub bits: u64,
+    pub upper_left: Complex<BigFloat>,
+    pub lower_right: Complex<BigFloat>,
+    pub julia_c: Option<Complex<BigFloat>>,
+}
+
+impl Precision {
+    pub fn new(bits: u64, upper_left: Complex<BigFloat>, lower_right: Complex<BigFloat>) -> Precision
+    {
+        Precision { bits, upper_left, lower_right, julia_c: None }
+    }
+
+    /// The view `viewport_from_center` fits around `center`, `width`
+    /// units wide.
+    pub fn from_center(bits: u64, center: &Complex<BigFloat>, width: f64, bounds: (usize, usize)) -> Precision
+    {
+        // About the origin the f64 corners are just the half extents, so
+        // adding them to the center rounds as `center - half` and
+        // `center + half` would.
+        let (from, to) = viewport_from_center(Complex { re: 0.0, im: 0.0 }, width, bounds);
+        let offset = |corner: Complex<f64>| Complex {
+            re: &center.re + &BigFloat::from_f64(corner.re, bits),
+            im: &center.im + &BigFloat::from_f64(corner.im, bits),
+        };
+        Precision::new(bits, offset(from), offset(to))
+    }
+
+    /// Check the view as `Region::validate` checks a region of `bounds`.
+    pub fn validate(&self, bounds: (usize, usize)) -> Result<(), MandelError>
+    {
+        if bounds.0.checked_mul(bounds.1).is_none() {
+            return Err(MandelError::Dimensions(format!("{}x{} pixels are too many to count", bounds.0, bounds.1)));
+        }
+        if self.lower_right.re <= self.upper_left.re {
+            return Err(MandelError::Dimensions("the lower right corner must be to the right of the upper left corner"
+                                               .to_string()));
+        }
+        if self.upper_left.im <= self.lower_right.im {
+            return Err(MandelError::Dimensions("the upper left corner must be above the lower right corner"
+                                               .to_string()));
+        }
+        Ok(())
+    }
+
+    /// This view grown about its center to square pixels, as
+    /// `Region::with_square_pixels` grows a region of `bounds`.
+    pub fn with_square_pixels(&self, bounds: (usize, usize)) -> Precision
+    {
+        let number = |x: f64| BigFloat::from_f64(x, self.bits);
+        let (width, height) = self.span();
+        let (columns, rows) = (number(bounds.0 as f64), number(bounds.1 as f64));
+        let (across, down) = (&width / &columns, &height / &rows);
+        let scale = if across >= down { across } else { down };
+        let two = number(2.0);
+        let center = Complex {
+            re: &(&self.upper_left.re + &self.lower_right.re) / &two,
+            im: &(&self.upper_left.im + &self.lower_right.im) / &two,
+        };
+        let half = Complex { re: &(&scale * &columns) / &two, im: &(&-&scale * &rows) / &two };
+        Precision {
+            upper_left: Complex { re: &center.re - &half.re, im: &center.im - &half.im },
+            lower_right: Complex { re: &center.re + &half.re, im: &center.im + &half.im },
+            ..self.clone()
+        }
+    }
+
+    /// The width of a pixel of an image of `bounds`, as `Region::pixel_width`.
+    pub fn pixel_width(&self, bounds: (usize, usize)) -> f64
+    {
+        (&self.span().0 / &BigFloat::from_usize(bounds.0, self.bits)).to_f64()
+    }
+
+    /// The bits an image of `bounds` needs: enough to tell its pixels apart,
+    /// and as many to spare as perturbation keeps.
+    pub fn suggested_bits(&self, bounds: (usize, usize)) -> u64
+    {
+        let (width, height) = self.span();
+        bits_for_pixel((width.to_f64() / bounds.0 as f64).min(height.to_f64() / bounds.1 as f64))
+    }
+
+    /// The width and height of the view.
+    fn span(&self) -> (BigFloat, BigFloat)
+    {
+        (&self.lower_right.re - &self.upper_left.re, &self.upper_left.im - &self.lower_right.im)
+    }
+
+    /// The point `offset` pixels of size `step`, across and down, from the
+    /// upper left corner, placed as `Region::sample_to_point` places it:
+    /// `offset` is the `f64` sum that computes.
+    fn point(&self, step: &Complex<BigFloat>, offset: (f64, f64)) -> Complex<BigFloat>
+    {
+        Complex {
+            re: &self.upper_left.re + &(&BigFloat::from_f64(offset.0, self.bits) * &step.re),
+            im: &self.upper_left.im - &(&BigFloat::from_f64(offset.1, self.bits) * &step.im),
+        }
+    }
+
+    /// How the orbit of `point` ends, as `RenderParams::escape` finds it,
+    /// for a `bailout` and a cycle `epsilon` already held to `bits`.
+    fn escape(&self, point: Complex<BigFloat>, params: &RenderParams, bailout: &BigFloat, epsilon: &BigFloat)
+        -> EscapeResult
+    {
+        let to_f64 = |z: &Complex<BigFloat>| Complex { re: z.re.to_f64(), im: z.im.to_f64() };
+        if params.interior_check && params.fractal == Fractal::Mandelbrot && in_cardioid_or_bulb(&point) {
+            return EscapeResult { iterations: None, final_z: to_f64(&point) };
+        }
+        let (mut z, c) = match params.fractal {
+            Fractal::Julia(c) => {
+                let c = self.julia_c.clone().unwrap_or_else(|| Complex {
+                    re: BigFloat::from_f64(c.re, self.bits),
+                    im: BigFloat::from_f64(c.im, self.bits),
+                });
+                (point, c)
+            }
+            _ => (Complex { re: BigFloat::zero(self.bits), im: BigFloat::zero(self.bits) }, point),
+        };
+        // Brent's cycle check, as `Fractal::orbit` makes it.
+        let (mut saved, mut steps, mut period) = (z.clone(), 0, 1);
+        for i in 0..params.limit {
+            let (re_squared, im_squared) = (&z.re * &z.re, &z.im * &z.im);
+            if &re_squared + &im_squared > *bailout {
+                return EscapeResult { iterations: Some(i), final_z: to_f64(&z) };
+            }
+            // `re * im` and `im * re` round alike, so their sum is exactly
+            // twice one of them.
+            let cross = &z.re * &z.im;
+            z = Complex { re: &(&re_squared - &im_squared) + &c.re, im: &(&cross + &cross) + &c.im };
+            if !epsilon.is_zero() {
+                if (&z.re - &saved.re).abs() <= *epsilon && (&z.im - &saved.im).abs() <= *epsilon {
+                    return EscapeResult { iterations: None, final_z: to_f64(&z) };
+                }
+                steps += 1;
+                if steps == period {
+                    saved = z.clone();
+                    steps = 0;
+                    period *= 2;
+                }
+            }
+        }
+        EscapeResult { iterations: None, final_z: to_f64(&z) }
+    }
+
+    /// Render the rectangle of the image of `params` that `render_rect`
+    /// describes into `buffer`, coloring each sample by its escape count,
+    /// or smooth count with `params.smooth`.
+    pub fn render_rect(&self, buffer: &mut [u8], params: &RenderParams, origin: (usize, usize), size: (usize, usize))
+    {
+        let (bounds, per_side) = (params.region.bounds, params.antialias);
+        let (width, height) = self.span();
+        let step = Complex {
+            re: &width / &BigFloat::from_f64(bounds.0 as f64, self.bits),
+            im: &height / &BigFloat::from_f64(bounds.1 as f64, self.bits),
+        };
+        let bailout = BigFloat::from_f64(params.bailout, self.bits);
+        let epsilon = params.periodicity_epsilon.min(self.pixel_width(bounds) * CYCLE_PIXEL_FRACTION);
+        let epsilon = BigFloat::from_f64(epsilon, self.bits);
+        let offset = |pixel: usize, sample: usize| pixel as f64 + sample as f64 / per_side as f64;
+
+        let bytes_per_pixel = params.color.bytes_per_pixel();
+        let mut samples = vec![0; per_side * per_side * bytes_per_pixel];
+        for (index, pixel) in buffer.chunks_mut(bytes_per_pixel).enumerate() {
+            let (x, y) = (origin.0 + index % size.0, origin.1 + index / size.0);
+            for (index, sample) in samples.chunks_mut(bytes_per_pixel).enumerate() {
+                let point = self.point(&step, (offset(x, index % per_side), offset(y, index / per_side)));
+                paint_escape(sample, self.escape(point, params, &bailout, &epsilon), params);
+            }
+            average_samples(pixel, &samples, &params.color);
+        }
+    }
+}
+
+/// `fractal::in_cardioid_or_bulb`, in the same operations at the precision
+/// of `c`.
+fn in_cardioid_or_bulb(c: &Complex<BigFloat>) -> bool
+{
+    let number = |x: f64| BigFloat::from_f64(x, c.re.precision());
+    let x = &c.re - &number(0.25);
+    let q = &(&x * &x) + &(&c.im * &c.im);
+    let in_cardioid = &q * &(&q + &x) <= &(&number(0.25) * &c.im) * &c.im;
+    let shifted = &c.re + &number(1.0);
+    let in_bulb = &(&shifted * &shifted) + &(&c.im * &c.im) <= number(0.0625);
+    in_cardioid || in_bulb
+}
+
+
+#[test]
+fn test_corners() {
+    let number = |x: f64| BigFloat::from_f64(x, 53);
+    let center = Complex { re: number(-0.5), im: number(0.25) };
+    let precision = Precision::from_center(53, &center, 2.0, (400, 300));
+    let (from, to) = viewport_from_center(Complex { re: -0.5, im: 0.25 }, 2.0, (400, 300));
+    assert_eq!(precision.upper_left, Complex { re: number(from.re), im: number(from.im) });
+    assert_eq!(precision.lower_right, Complex { re: number(to.re), im: number(to.im) });
+    assert_eq!(precision.pixel_width((400, 300)), 2.0 / 400.0);
+    assert!(precision.validate((400, 300)).is_ok());
+    assert!(matches!(Precision::new(53, precision.lower_right.clone(), precision.upper_left.clone()).validate((4, 3)),
+                     Err(MandelError::Dimensions(_))));
+
+    let region = crate::Region::new((200, 100), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.0, im: -0.5 });
+    let square = region.with_square_pixels();
+    let precise = Precision::new(53, Complex { re: number(-1.0), im: number(1.0) },
+                                 Complex { re: number(1.0), im: number(-0.5) })
+        .with_square_pixels((200, 100));
+    assert_eq!(precise.upper_left, Complex { re: number(square.upper_left.re), im: number(square.upper_left.im) });
+    assert_eq!(precise.lower_right, Complex { re: number(square.lower_right.re), im: number(square.lower_right.im) });
+}
+
+#[test]
+fn test_suggested_bits() {
+    let number = |x: &str| BigFloat::parse(x, 200).unwrap();
+    let shallow = Precision::new(200, Complex { re: number("-2"), im: number("1") },
+                                 Complex { re: number("1"), im: number("-1") });
+    assert_eq!(shallow.suggested_bits((300, 200)), bits_for_pixel(0.01));
+    let deep = Precision::new(200, Complex { re: number("-0.75"), im: number("0.1") },
+                              Complex { re: number("-0.7499999999999999999999"),
+                                        im: number("0.0999999999999999999999") });
+    assert_eq!(deep.suggested_bits((100, 100)), bits_for_pixel(1e-24));
+}
+
+#[test]
+fn test_in_cardioid_or_bulb() {
+    for (re, im) in [(0.0, 0.0), (-1.0, 0.0), (0.25, 0.0), (0.3, 0.0), (-0.75, 0.1), (-1.3, 0.0), (0.2, 0.5),
+                     (-0.1, 0.6)] {
+        let c = Complex { re: BigFloat::from_f64(re, 53), im: BigFloat::from_f64(im, 53) };
+        assert_eq!(in_cardioid_or_bulb(&c), crate::in_cardioid_or_bulb(Complex { re, im }), "{},{}", re, im);
+    }
+}
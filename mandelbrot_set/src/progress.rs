@@ -0,0 +1,145 @@
+//! Reporting how much of an image is done while the threads are still at
+//! work on it.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+
+/// What the interior and periodicity checks spared a render: the points the
+/// interior check answered without iterating, the orbits given up on as
+/// cycles, and the iterations those orbits would have run on for. Neither
+/// check runs on rows copied by `RenderParams::mirror`, or with
+/// `perturbation` or `precision`, and with the `simd` feature orbits
+/// iterated four at a time are not counted as cycles.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Skipped {
+    pub interior: usize,
+    pub cycles: usize,
+    pub saved: u64,
+}
+
+impl Skipped {
+    /// Count an orbit given up on as a cycle `saved` iterations short of
+    /// the limit.
+    pub(crate) fn cycle(&mut self, saved: usize)
+    {
+        self.cycles += 1;
+        self.saved += saved as u64;
+    }
+}
+
+
+/// A count of rendered pixels shared by every thread of a render. When
+/// `visible`, each new whole percentage is written to stderr, overwriting
+/// the last one, with a newline once the image is complete.
+///
+/// The threads also add what the interior and periodicity checks skipped in
+/// the pixels they render, so `skipped` tells how the checks fared over the
+/// render itself.
+#[derive(Debug)]
+pub struct Progress {
+    total: usize,
+    done: AtomicUsize,
+    shown: AtomicUsize,
+    visible: bool,
+    interior: AtomicUsize,
+    cycles: AtomicUsize,
+    saved: AtomicU64,
+}
+
+impl Progress {
+    /// Track the rendering of `total` pixels.
+    pub fn new(total: usize, visible: bool) -> Progress
+    {
+        Progress {
+            total,
+            done: AtomicUsize::new(0),
+            shown: AtomicUsize::new(0),
+            visible,
+            interior: AtomicUsize::new(0),
+            cycles: AtomicUsize::new(0),
+            saved: AtomicU64::new(0),
+        }
+    }
+
+    /// A progress that nobody is watching.
+    pub fn hidden() -> Progress
+    {
+        Progress::new(0, false)
+    }
+
+    /// Count `pixels` more pixels as rendered. Safe to call from any thread.
+    pub fn add(&self, pixels: usize)
+    {
+        let done = self.done.fetch_add(pixels, Ordering::Relaxed) + pixels;
+        if !self.visible || self.total == 0 {
+            return;
+        }
+        // Only the thread that moves the percentage on prints it, so a
+        // number never goes backwards on screen.
+        let percent = (done * 100 / self.total).min(100);
+        if self.shown.fetch_max(percent, Ordering::Relaxed) < percent {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\rrendering: {:3}%", percent);
+            let _ = if percent == 100 { writeln!(stderr) } else { stderr.flush() };
+        }
+    }
+
+    /// Count what the checks skipped in some rendered pixels, as
+    /// `render_rect` returns it. Safe to call from any thread.
+    pub fn add_skipped(&self, skipped: Skipped)
+    {
+        self.interior.fetch_add(skipped.interior, Ordering::Relaxed);
+        self.cycles.fetch_add(skipped.cycles, Ordering::Relaxed);
+        self.saved.fetch_add(skipped.saved, Ordering::Relaxed);
+    }
+
+    /// The number of pixels counted so far.
+    pub fn done(&self) -> usize
+    {
+        self.done.load(Ordering::Relaxed)
+    }
+
+    /// What the checks skipped in the pixels counted so far.
+    pub fn skipped(&self) -> Skipped
+    {
+        Skipped {
+            interior: self.interior.load(Ordering::Relaxed),
+            cycles: self.cycles.load(Ordering::Relaxed),
+            saved: self.saved.load(Ordering::Relaxed),
+        }
+    }
+}
+
+
+#[test]
+fn test_progress_counts_across_threads() {
+    let progress = Progress::new(4000, false);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                for _ in 0..100 {
+                    progress.add(10);
+                }
+            });
+        }
+    });
+    assert_eq!(progress.done(), 4000);
+}
+
+#[test]
+fn test_progress_collects_skips() {
+    let progress = Progress::hidden();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let mut skipped = Skipped { interior: 3, ..Skipped::default() };
+                skipped.cycle(10);
+                skipped.cycle(5);
+                progress.add_skipped(skipped);
+                progress.add_skipped(Skipped::default());
+            });
+        }
+    });
+    assert_eq!(progress.skipped(), Skipped { interior: 12, cycles: 8, saved: 60 });
+}
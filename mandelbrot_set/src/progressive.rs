@@ -0,0 +1,87 @@
+//! Coarse previews written before the full render, so that something shows
+//! almost at once and the detail fills in as each pass overwrites the last.
+
+use crate::{MandelError, RenderParams, Strategy, render_image};
+
+
+/// The fractions of the full size along each side that the previews are
+/// rendered at, coarsest first.
+pub const PREVIEW_SCALES: [usize; 2] = [8, 4];
+
+/// The parameters of the preview of `params` at 1/`scale` of its width and
+/// height, rounded up, over the same part of the plane.
+pub fn preview_params(params: &RenderParams, scale: usize) -> RenderParams
+{
+    let (width, height) = params.region.bounds;
+    let mut preview = params.clone();
+    preview.region.bounds = (width.div_ceil(scale), height.div_ceil(scale));
+    preview
+}
+
+/// Enlarge `pixels`, an image of size `small`, to size `bounds` by repeating
+/// each pixel over the block it covers.
+pub fn upsample(pixels: &[u8], small: (usize, usize), bounds: (usize, usize), bytes_per_pixel: usize) -> Vec<u8>
+{
+    assert_eq!(pixels.len(), small.0 * small.1 * bytes_per_pixel);
+    let mut large = Vec::with_capacity(bounds.0 * bounds.1 * bytes_per_pixel);
+    for row in 0..bounds.1 {
+        let line = row * small.1 / bounds.1 * small.0;
+        for column in 0..bounds.0 {
+            let start = (line + column * small.0 / bounds.0) * bytes_per_pixel;
+            large.extend_from_slice(&pixels[start..start + bytes_per_pixel]);
+        }
+    }
+    large
+}
+
+/// Render a preview of `params` at each of `PREVIEW_SCALES` with `strategy`
+/// on `threads` threads, and pass it to `on_preview` with its scale,
+/// enlarged to the full size. The full render is left to the caller.
+pub fn render_previews(params: &RenderParams, strategy: Strategy, threads: usize,
+                       mut on_preview: impl FnMut(usize, &[u8]) -> Result<(), MandelError>)
+    -> Result<(), MandelError>
+{
+    for scale in PREVIEW_SCALES {
+        let preview = preview_params(params, scale);
+        let (pixels, _) = render_image(&preview, strategy, threads);
+        let bytes_per_pixel = params.color.bytes_per_pixel();
+        on_preview(scale, &upsample(&pixels, preview.region.bounds, params.region.bounds, bytes_per_pixel))?;
+    }
+    Ok(())
+}
+
+
+#[test]
+fn test_preview_params() {
+    use num::Complex;
+
+    let params = RenderParams::new((100, 61), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let preview = preview_params(&params, 8);
+    assert_eq!(preview.region.bounds, (13, 8));
+    assert_eq!((preview.region.upper_left, preview.region.lower_right),
+               (params.region.upper_left, params.region.lower_right));
+    assert_eq!(preview_params(&params, 1), params);
+}
+
+#[test]
+fn test_upsample() {
+    assert_eq!(upsample(&[1, 2, 3, 4], (2, 2), (4, 3), 1), [1, 1, 2, 2,
+                                                           1, 1, 2, 2,
+                                                           3, 3, 4, 4]);
+    assert_eq!(upsample(&[1, 2, 3, 4, 5, 6], (2, 1), (3, 1), 3), [1, 2, 3, 1, 2, 3, 4, 5, 6]);
+    assert_eq!(upsample(&[7], (1, 1), (2, 2), 1), [7; 4]);
+}
+
+#[test]
+fn test_render_previews() {
+    use num::Complex;
+
+    let params = RenderParams::new((40, 24), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut scales = vec![];
+    render_previews(&params, Strategy::Rayon, 2, |scale, pixels| {
+        assert_eq!(pixels.len(), params.buffer_len());
+        scales.push(scale);
+        Ok(())
+    }).unwrap();
+    assert_eq!(scales, PREVIEW_SCALES);
+}
@@ -0,0 +1,124 @@
+//! A pyramid of square tiles in the `{z}/{x}/{y}.png` layout that slippy-map
+//! viewers such as Leaflet load as the user pans and zooms.
+
+use std::path::{Path, PathBuf};
+use rayon::prelude::*;
+use crate::{MandelError, Region, RenderParams, correct_gamma, render_rect, write_image_with_metadata};
+use crate::metadata::render_metadata;
+use crate::output::ImageFormat;
+use crate::tile::Tile;
+
+
+/// The side length of a map tile in pixels, as slippy-map viewers expect.
+pub const MAP_TILE_SIZE: usize = 256;
+/// The deepest zoom level there is room to address.
+pub const MAX_ZOOM_LEVEL: u32 = 30;
+
+
+/// Zoom levels 0 to `max_zoom` of tiles `tile_size` pixels square. Level `z`
+/// covers the view with 2^z by 2^z tiles, each rendered with
+/// `iterations_per_level` more iterations than on the level above.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pyramid {
+    pub max_zoom: u32,
+    pub tile_size: usize,
+    pub iterations_per_level: usize,
+    /// Whether to leave out tiles in which every pixel has the interior
+    /// color.
+    pub skip_empty: bool,
+}
+
+/// How many tiles `run_pyramid` wrote, and how many it left out as empty.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PyramidCounts {
+    pub written: usize,
+    pub skipped: usize,
+}
+
+impl Pyramid {
+    /// The whole image at level `zoom`, over the region of `params`, that
+    /// the tiles of that level are cut from.
+    pub fn level_params(&self, params: &RenderParams, zoom: u32) -> RenderParams
+    {
+        assert!(zoom <= MAX_ZOOM_LEVEL);
+        let side = self.tile_size << zoom;
+        let region = &params.region;
+        RenderParams {
+            region: Region::new((side, side), region.upper_left, region.lower_right),
+            limit: params.limit + self.iterations_per_level * zoom as usize,
+            ..params.clone()
+        }
+    }
+
+    /// Where the tile in column `x` and row `y` of level `zoom` goes in `dir`.
+    pub fn tile_filename(dir: &Path, zoom: u32, x: usize, y: usize) -> PathBuf
+    {
+        dir.join(zoom.to_string()).join(x.to_string()).join(format!("{}.png", y))
+    }
+
+    /// The tile in column `x` and row `y` of a level.
+    pub fn tile(&self, x: usize, y: usize) -> Tile
+    {
+        Tile { origin: (x * self.tile_size, y * self.tile_size), size: (self.tile_size, self.tile_size) }
+    }
+}
+
+/// Render every level of `pyramid` over the region of `params` into `dir`,
+/// the tiles of each level spread over `threads` threads. Each tile is
+/// rendered through its level's region, so neighbouring tiles meet exactly,
+/// and records the part of the plane it covers in its metadata.
+pub fn run_pyramid(dir: &Path, params: &RenderParams, pyramid: &Pyramid, threads: usize)
+    -> Result<PyramidCounts, MandelError>
+{
+    let mut counts = PyramidCounts::default();
+    let render_level = |zoom: u32| -> Result<Vec<bool>, MandelError> {
+        let level = pyramid.level_params(params, zoom);
+        let side = 1usize << zoom;
+        let interior = level.interior_pixel();
+        (0..side * side).into_par_iter().map(|index| {
+            let (x, y) = (index % side, index / side);
+            let tile = pyramid.tile(x, y);
+            let mut pixels = vec![0; pyramid.tile_size * pyramid.tile_size * level.color.bytes_per_pixel()];
+            render_rect(&mut pixels, &level, tile.origin, tile.size);
+            if pyramid.skip_empty && pixels.chunks(interior.len()).all(|pixel| pixel == interior) {
+                return Ok(false);
+            }
+            correct_gamma(&mut pixels, &level);
+            let filename = Pyramid::tile_filename(dir, zoom, x, y);
+            std::fs::create_dir_all(filename.parent().unwrap())?;
+            let piece = RenderParams { region: tile.region(&level.region), ..level.clone() };
+            write_image_with_metadata(&filename.to_string_lossy(), &pixels, tile.size, level.color.color_type(),
+                                      ImageFormat::Png, &render_metadata(&piece))?;
+            Ok(true)
+        }).collect()
+    };
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(threads.max(1)).build().ok();
+    for zoom in 0..=pyramid.max_zoom {
+        let written = match &pool {
+            Some(pool) => pool.install(|| render_level(zoom))?,
+            None => render_level(zoom)?,
+        };
+        let level_written = written.iter().filter(|&&written| written).count();
+        counts.written += level_written;
+        counts.skipped += written.len() - level_written;
+    }
+    Ok(counts)
+}
+
+
+#[test]
+fn test_level_params() {
+    use num::Complex;
+
+    let params = RenderParams { limit: 100, ..RenderParams::new((1, 1), Complex { re: -2.0, im: 1.5 },
+                                                                Complex { re: 1.0, im: -1.5 }) };
+    let pyramid = Pyramid { max_zoom: 3, tile_size: MAP_TILE_SIZE, iterations_per_level: 50, skip_empty: false };
+    let level = pyramid.level_params(&params, 2);
+    assert_eq!(level.region.bounds, (1024, 1024));
+    assert_eq!((level.region.upper_left, level.region.lower_right),
+               (params.region.upper_left, params.region.lower_right));
+    assert_eq!(level.limit, 200);
+    assert_eq!(pyramid.level_params(&params, 0).limit, 100);
+    assert_eq!(pyramid.tile(3, 1), Tile { origin: (768, 256), size: (256, 256) });
+    assert_eq!(Pyramid::tile_filename(Path::new("out"), 2, 3, 1), Path::new("out/2/3/1.png"));
+}
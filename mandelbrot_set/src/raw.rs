@@ -0,0 +1,89 @@
+//! Escape counts written out bare for other tools to analyze, without the
+//! corners and limit a dump records for coloring them again.
+//!
+//! CSV holds one line of comma-separated counts per row of pixels, -1 for
+//! points that never escaped. The binary form is the width and height as
+//! little-endian `u32`s, then the counts row by row, each a little-endian
+//! `u32`, `counts::NEVER_ESCAPED` for points that never escaped.
+
+use std::io::{self, Write};
+use std::path::Path;
+use crate::MandelError;
+use crate::counts::encode_count;
+
+
+/// The size of the header in front of the counts of the binary form.
+pub const RAW_HEADER_LEN: usize = 2 * 4;
+
+/// How `write_raw` lays out the counts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RawFormat {
+    Csv,
+    Binary,
+}
+
+impl RawFormat {
+    /// Pick the format from the extension of `filename`: `.csv` for CSV,
+    /// `.bin` or `.raw` for the binary form, in any case.
+    pub fn from_filename(filename: &str) -> Result<RawFormat, MandelError>
+    {
+        let extension = Path::new(filename).extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        match extension.to_ascii_lowercase().as_str() {
+            "csv" => Ok(RawFormat::Csv),
+            "bin" | "raw" => Ok(RawFormat::Binary),
+            _ => Err(MandelError::Parse(format!("cannot tell the raw format of '{}'; expected a .csv, .bin or .raw \
+                                                 file", filename))),
+        }
+    }
+}
+
+/// Write `counts`, an image of size `bounds` as `render_counts` returns it,
+/// in `format`.
+pub fn write_raw<W: Write>(output: &mut W, counts: &[Option<usize>], bounds: (usize, usize), format: RawFormat)
+    -> io::Result<()>
+{
+    assert_eq!(counts.len(), bounds.0 * bounds.1);
+    match format {
+        RawFormat::Csv => {
+            for row in counts.chunks(bounds.0.max(1)) {
+                let fields: Vec<String> = row.iter().map(|count| match count {
+                    None => "-1".to_string(),
+                    Some(count) => count.to_string(),
+                }).collect();
+                writeln!(output, "{}", fields.join(","))?;
+            }
+        }
+        RawFormat::Binary => {
+            output.write_all(&(bounds.0 as u32).to_le_bytes())?;
+            output.write_all(&(bounds.1 as u32).to_le_bytes())?;
+            let counts: Vec<u8> = counts.iter().flat_map(|&count| encode_count(count).to_le_bytes()).collect();
+            output.write_all(&counts)?;
+        }
+    }
+    Ok(())
+}
+
+
+#[test]
+fn test_raw_format_from_filename() {
+    assert_eq!(RawFormat::from_filename("view.csv").unwrap(), RawFormat::Csv);
+    assert_eq!(RawFormat::from_filename("out/view.BIN").unwrap(), RawFormat::Binary);
+    assert_eq!(RawFormat::from_filename("view.raw").unwrap(), RawFormat::Binary);
+    assert!(matches!(RawFormat::from_filename("view.iter"), Err(MandelError::Parse(_))));
+    assert!(matches!(RawFormat::from_filename("view"), Err(MandelError::Parse(_))));
+}
+
+#[test]
+fn test_write_raw() {
+    let counts = [Some(0), Some(12), None, Some(499), None, Some(7)];
+    let mut csv = vec![];
+    write_raw(&mut csv, &counts, (3, 2), RawFormat::Csv).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "0,12,-1\n499,-1,7\n");
+
+    let mut binary = vec![];
+    write_raw(&mut binary, &counts, (3, 2), RawFormat::Binary).unwrap();
+    assert_eq!(binary.len(), RAW_HEADER_LEN + 6 * 4);
+    assert_eq!(&binary[..8], &[3, 0, 0, 0, 2, 0, 0, 0]);
+    assert_eq!(&binary[12..16], &12u32.to_le_bytes());
+    assert_eq!(&binary[16..20], &[0xff; 4]);
+}
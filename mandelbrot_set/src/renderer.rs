@@ -0,0 +1,91 @@
+//! A rendering context that lives across many renders, such as the frames
+//! of an animation, so that each one does not start from scratch.
+
+use rayon::ThreadPool;
+use crate::{Coloring, RenderParams, Strategy, color_counts, correct_gamma, render_counts_with_progress};
+use crate::progress::Progress;
+use crate::slope::shade_slopes;
+
+
+/// A pixel buffer and a pool of `threads` threads kept from one render to
+/// the next. The buffer only grows, so renders of the same size after the
+/// first allocate nothing for their pixels, and `Strategy::Rayon` runs on the
+/// pool instead of building one of its own each time.
+pub struct Renderer {
+    /// The pixels of the last render.
+    pub buffer: Vec<u8>,
+    pub threads: usize,
+    pool: Option<ThreadPool>,
+}
+
+impl Renderer {
+    /// A renderer with an empty buffer and a pool of `threads` threads, or
+    /// none if the pool cannot be built, in which case each render builds
+    /// its own as before.
+    pub fn new(threads: usize) -> Renderer
+    {
+        let threads = threads.max(1);
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().ok();
+        Renderer { buffer: Vec::new(), threads, pool }
+    }
+
+    /// Render `params` with `strategy` into `buffer`, with the coloring
+    /// `render_image` applies, and return the units of work each thread
+    /// completed. `buffer` ends up holding exactly the image's pixels.
+    pub fn render_into(&mut self, params: &RenderParams, strategy: Strategy) -> Vec<usize>
+    {
+        let Renderer { buffer, threads, pool } = self;
+        let mut render = || {
+            let work_per_thread = match params.coloring {
+                Coloring::Histogram => {
+                    let (counts, work_per_thread) = render_counts_with_progress(params, strategy, *threads,
+                                                                                &Progress::hidden());
+                    buffer.clear();
+                    buffer.extend(color_counts(&counts, params));
+                    work_per_thread
+                }
+                _ => {
+                    buffer.resize(params.buffer_len(), 0);
+                    strategy.render(buffer, params, *threads)
+                }
+            };
+            shade_slopes(buffer, params, strategy, *threads);
+            correct_gamma(buffer, params);
+            work_per_thread
+        };
+        match pool {
+            Some(pool) => pool.install(render),
+            None => render(),
+        }
+    }
+}
+
+
+#[test]
+fn test_render_into_reuses_the_buffer() {
+    use num::Complex;
+    use crate::render_image;
+
+    let mut renderer = Renderer::new(3);
+    let base = RenderParams::new((40, 30), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for strategy in [Strategy::Rayon, Strategy::Bands, Strategy::Sequential] {
+        renderer.render_into(&base, strategy);
+        assert_eq!(renderer.buffer, render_image(&base, strategy, 3).0, "{:?}", strategy);
+    }
+
+    let start = renderer.buffer.as_ptr();
+    let zoomed = RenderParams::new((40, 30), Complex { re: -1.0, im: 0.5 }, Complex { re: 0.0, im: -0.25 });
+    let work_per_thread = renderer.render_into(&zoomed, Strategy::Rayon);
+    assert_eq!(renderer.buffer.as_ptr(), start);
+    assert_eq!(work_per_thread.iter().sum::<usize>(), 30);
+    assert_eq!(renderer.buffer, render_image(&zoomed, Strategy::Rayon, 3).0);
+
+    let equalized = RenderParams { coloring: Coloring::Histogram, ..zoomed.clone() };
+    renderer.render_into(&equalized, Strategy::Rayon);
+    assert_eq!(renderer.buffer, render_image(&equalized, Strategy::Rayon, 3).0);
+
+    // A smaller image leaves the buffer its own size.
+    let small = RenderParams::new((8, 6), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    renderer.render_into(&small, Strategy::Rayon);
+    assert_eq!(renderer.buffer.len(), small.buffer_len());
+}
@@ -0,0 +1,150 @@
+//! Slope shading: lighting the image as if the smooth escape count, or the
+//! distance estimate, were the height of a surface, for an embossed look.
+//! The slope at a pixel needs the heights of its neighbours, so the pass
+//! runs over the whole image once every band of it has been rendered.
+
+use crate::{ColorMode, Coloring, RenderParams, Strategy, render_image};
+
+
+/// How many degrees above the image the light of `RenderParams::light_angle`
+/// stands.
+pub const LIGHT_ELEVATION: f64 = 45.0;
+
+/// The height of every pixel of `params`, row by row, rendered with
+/// `strategy` on `threads` threads: with `Coloring::Distance` the distance
+/// estimate in pixels, otherwise the smooth escape count. `None` for points
+/// that never escape.
+pub fn height_field(params: &RenderParams, strategy: Strategy, threads: usize) -> Vec<Option<f64>>
+{
+    let distance = params.coloring == Coloring::Distance;
+    let field = RenderParams {
+        color: ColorMode::Values,
+        coloring: if distance { Coloring::Distance } else { Coloring::Linear },
+        smooth: !distance,
+        antialias: 1,
+        light_angle: None,
+        ..params.clone()
+    };
+    let (values, _) = render_image(&field, strategy, threads);
+    let unit = if distance { params.region.pixel_width().abs() } else { 1.0 };
+    values.chunks_exact(4).map(|chunk| {
+        let value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64;
+        (!value.is_nan()).then_some(value / unit)
+    }).collect()
+}
+
+/// The Lambertian shading, from 0 to 1, of each pixel of `field`, the
+/// heights of an image of size `bounds`, lit from `light_angle` degrees
+/// counterclockwise from the right of the image and `LIGHT_ELEVATION`
+/// degrees above it. Slopes are central differences, one-sided at the
+/// borders and beside points that never escape; those points themselves
+/// are shaded as flat ground.
+pub fn lambert_shading(field: &[Option<f64>], bounds: (usize, usize), light_angle: f64) -> Vec<f64>
+{
+    assert_eq!(field.len(), bounds.0 * bounds.1);
+    let (azimuth, elevation) = (light_angle.to_radians(), LIGHT_ELEVATION.to_radians());
+    // Rows run down the image, so a light towards the top has a negative y.
+    let light = [elevation.cos() * azimuth.cos(), -elevation.cos() * azimuth.sin(), elevation.sin()];
+    let height = |x: usize, y: usize| field[y * bounds.0 + x];
+    let slope = |before: Option<f64>, here: f64, after: Option<f64>| match (before, after) {
+        (Some(before), Some(after)) => (after - before) / 2.0,
+        (Some(before), None) => here - before,
+        (None, Some(after)) => after - here,
+        (None, None) => 0.0,
+    };
+    let mut shading = Vec::with_capacity(field.len());
+    for y in 0..bounds.1 {
+        for x in 0..bounds.0 {
+            let here = match height(x, y) {
+                Some(here) => here,
+                None => {
+                    shading.push(light[2]);
+                    continue;
+                }
+            };
+            let dx = slope(x.checked_sub(1).and_then(|left| height(left, y)), here,
+                           (x + 1 < bounds.0).then(|| height(x + 1, y)).flatten());
+            let dy = slope(y.checked_sub(1).and_then(|up| height(x, up)), here,
+                           (y + 1 < bounds.1).then(|| height(x, y + 1)).flatten());
+            // The surface rising by dx and dy per pixel faces (-dx, -dy, 1).
+            let facing = -dx * light[0] - dy * light[1] + light[2];
+            shading.push((facing / (dx * dx + dy * dy + 1.0).sqrt()).max(0.0));
+        }
+    }
+    shading
+}
+
+/// Darken each pixel of `pixels`, in `color`, by its shade in `shading`.
+pub fn shade_pixels(pixels: &mut [u8], shading: &[f64], color: &ColorMode)
+{
+    assert_eq!(pixels.len(), shading.len() * color.bytes_per_pixel());
+    for (pixel, &shade) in pixels.chunks_mut(color.bytes_per_pixel()).zip(shading) {
+        match color {
+            ColorMode::Gray | ColorMode::Rgb(_) => {
+                for channel in pixel {
+                    *channel = (*channel as f64 * shade).round() as u8;
+                }
+            }
+            ColorMode::Gray16 => {
+                let level = u16::from_be_bytes([pixel[0], pixel[1]]) as f64;
+                pixel.copy_from_slice(&((level * shade).round() as u16).to_be_bytes());
+            }
+            ColorMode::Counts | ColorMode::Values => panic!("escape counts cannot be shaded"),
+        }
+    }
+}
+
+/// Light `pixels`, the image of `params`, from `params.light_angle`, rendering
+/// the heights again with `strategy` on `threads` threads. Without a light
+/// angle the image is left as it is.
+pub fn shade_slopes(pixels: &mut [u8], params: &RenderParams, strategy: Strategy, threads: usize)
+{
+    if let Some(light_angle) = params.light_angle {
+        let field = height_field(params, strategy, threads);
+        shade_pixels(pixels, &lambert_shading(&field, params.region.bounds, light_angle), &params.color);
+    }
+}
+
+
+#[test]
+fn test_linear_ramp_shades_evenly() {
+    // A height of 0.5 x - 0.25 y leans the same way everywhere, borders
+    // included.
+    let bounds = (7, 5);
+    let field: Vec<Option<f64>> = (0..bounds.1)
+        .flat_map(|y| (0..bounds.0).map(move |x| Some(0.5 * x as f64 - 0.25 * y as f64)))
+        .collect();
+    for light_angle in [0.0, 45.0, 200.0] {
+        let (azimuth, elevation) = (f64::to_radians(light_angle), LIGHT_ELEVATION.to_radians());
+        let light = [elevation.cos() * azimuth.cos(), -elevation.cos() * azimuth.sin(), elevation.sin()];
+        let normal_length = (0.5f64.powi(2) + 0.25f64.powi(2) + 1.0).sqrt();
+        let expected = ((-0.5 * light[0] + 0.25 * light[1] + light[2]) / normal_length).max(0.0);
+        for shade in lambert_shading(&field, bounds, light_angle) {
+            assert!((shade - expected).abs() < 1e-12, "{}: {} vs {}", light_angle, shade, expected);
+        }
+    }
+}
+
+#[test]
+fn test_interior_is_flat() {
+    let flat = LIGHT_ELEVATION.to_radians().sin();
+    // A peak in the middle of interior points: its slopes are all zero.
+    let field = [None, None, None, None, Some(3.0), None, None, None, None];
+    assert!(lambert_shading(&field, (3, 3), 90.0).iter().all(|&shade| (shade - flat).abs() < 1e-12));
+    // Beside the interior the difference is one-sided.
+    let row = [Some(0.0), Some(1.0), None];
+    let shading = lambert_shading(&row, (3, 1), 180.0);
+    assert_eq!(shading[0], shading[1]);
+    assert!(shading[0] > flat);
+    assert!((shading[2] - flat).abs() < 1e-12);
+}
+
+#[test]
+fn test_shade_pixels() {
+    let mut gray = vec![200, 100];
+    shade_pixels(&mut gray, &[0.5, 1.0], &ColorMode::Gray);
+    assert_eq!(gray, [100, 100]);
+    let mut deep = 1000u16.to_be_bytes().to_vec();
+    shade_pixels(&mut deep, &[0.25], &ColorMode::Gray16);
+    assert_eq!(deep, 250u16.to_be_bytes());
+}
@@ -0,0 +1,473 @@
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use rayon::prelude::*;
+use crate::{RenderParams, render_rows};
+use crate::progress::Progress;
+use crate::symmetry::{copy_mirrored_rows, mirror_sources};
+use crate::tile::render_tiles;
+
+
+/// The ways the renderer can divide an image between threads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Everything on the calling thread.
+    #[default]
+    Sequential,
+    /// One contiguous band of rows per thread.
+    Bands,
+    /// One task per row, balanced by rayon's work stealing.
+    Rayon,
+    /// Threads repeatedly claim the next unrendered row from a shared
+    /// counter.
+    Dynamic,
+    /// Square tiles of the given side length, each rendered into its own
+    /// buffer by a pool of threads and copied into place afterwards.
+    Tiles(usize),
+}
+
+impl Strategy {
+    /// Render the image described by `params` into `pixels` using this
+    /// strategy, and return how many units of work (rows, or tiles for
+    /// `Tiles`) each thread completed. `threads` is ignored by `Sequential`.
+    /// Every strategy produces exactly the same bytes.
+    ///
+    /// When `symmetry::mirror_sources` finds rows below the real axis that
+    /// mirror rows above it, the strategies that `mirrors` render only the
+    /// other rows and copy those. The others always render every pixel, so
+    /// that each keeps its own way of splitting the image.
+    pub fn render(&self, pixels: &mut [u8], params: &RenderParams, threads: usize) -> Vec<usize>
+    {
+        self.render_with_progress(pixels, params, threads, &Progress::hidden())
+    }
+
+    /// Like `render`, adding each row or tile to `progress` as soon as it is
+    /// done.
+    pub fn render_with_progress(&self, pixels: &mut [u8], params: &RenderParams, threads: usize, progress: &Progress)
+        -> Vec<usize>
+    {
+        if self.mirrors() {
+            if let Some(sources) = mirror_sources(params) {
+                return render_mirrored(pixels, params, &sources, *self == Strategy::Sequential, threads, progress);
+            }
+        }
+        match self {
+            Strategy::Sequential => {
+                render_sequential(pixels, params, progress);
+                vec![params.region.bounds.1]
+            }
+            Strategy::Bands => render_parallel(pixels, params, threads, progress),
+            Strategy::Rayon => render_rayon(pixels, params, threads, progress),
+            Strategy::Dynamic => render_dynamic(pixels, params, threads, progress),
+            Strategy::Tiles(tile_size) => render_tiles(pixels, params, threads, *tile_size, progress),
+        }
+    }
+
+    /// Whether `render` copies mirrored rows rather than render them:
+    /// `Sequential` one after another, and `Rayon` with its work stealing,
+    /// which cares nothing for the rows left being scattered over the image.
+    pub fn mirrors(&self) -> bool
+    {
+        matches!(self, Strategy::Sequential | Strategy::Rayon)
+    }
+
+    /// Whether `render_band` can render a band of the image with this
+    /// strategy: `Sequential` and `Bands` can, since both fill in rows where
+    /// they belong. The others work on the whole image at once.
+    pub fn renders_bands(&self) -> bool
+    {
+        matches!(self, Strategy::Sequential | Strategy::Bands)
+    }
+
+    /// Like `render_with_progress`, for the rows of `band` only, the first of
+    /// them being row `top` of the full image. Panics unless `renders_bands`.
+    pub fn render_band(&self, band: &mut [u8], params: &RenderParams, top: usize, threads: usize, progress: &Progress)
+        -> Vec<usize>
+    {
+        let row_len = params.region.bounds.0 * params.color.bytes_per_pixel();
+        assert!(band.len().is_multiple_of(row_len) && top + band.len() / row_len <= params.region.bounds.1);
+        match self {
+            Strategy::Sequential => {
+                render_sequential_rows(band, params, top, progress);
+                vec![band.len() / row_len]
+            }
+            Strategy::Bands => render_parallel_rows(band, params, top, threads, progress),
+            _ => panic!("{} cannot render a band of the image", self.name()),
+        }
+    }
+
+    /// The name `--strategy` knows this strategy by.
+    pub fn name(&self) -> &'static str
+    {
+        match self {
+            Strategy::Sequential => "sequential",
+            Strategy::Bands => "bands",
+            Strategy::Rayon => "rayon",
+            Strategy::Dynamic => "dynamic",
+            Strategy::Tiles(_) => "tiles",
+        }
+    }
+
+    /// What the counts returned by `render` are counting.
+    pub fn unit(&self) -> &'static str
+    {
+        match self {
+            Strategy::Tiles(_) => "tiles",
+            _ => "rows",
+        }
+    }
+}
+
+
+/// The number of threads to render with when the user does not say:
+/// one per available core, or a single thread if that cannot be determined.
+pub fn default_threads() -> usize
+{
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Render the image row by row on the calling thread, as `render` does.
+fn render_sequential(pixels: &mut [u8], params: &RenderParams, progress: &Progress)
+{
+    assert!(pixels.len() == params.buffer_len());
+    render_sequential_rows(pixels, params, 0, progress);
+}
+
+/// Render the rows of `band`, the first of them being row `top` of the full
+/// image, one after another on the calling thread.
+fn render_sequential_rows(band: &mut [u8], params: &RenderParams, top: usize, progress: &Progress)
+{
+    let width = params.region.bounds.0;
+    for (row, line) in band.chunks_mut(width * params.color.bytes_per_pixel()).enumerate() {
+        progress.add_skipped(render_rows(line, params, top + row));
+        progress.add(width);
+    }
+}
+
+/// Split the image into horizontal bands of `band_heights` rows and render
+/// each band on its own thread. Returns the height of each band.
+pub fn render_parallel(pixels: &mut [u8], params: &RenderParams, threads: usize, progress: &Progress) -> Vec<usize>
+{
+    assert!(pixels.len() == params.buffer_len());
+    render_parallel_rows(pixels, params, 0, threads, progress)
+}
+
+/// Like `render_parallel`, for only those rows of the image in `pixels`, the
+/// first of them being row `first_row`.
+fn render_parallel_rows(pixels: &mut [u8], params: &RenderParams, first_row: usize, threads: usize,
+                        progress: &Progress) -> Vec<usize>
+{
+    let width = params.region.bounds.0;
+    let row_len = width * params.color.bytes_per_pixel();
+    let height = pixels.len() / row_len;
+
+    let heights = band_heights(height, threads);
+    let mut bands = Vec::with_capacity(heights.len());
+    let (mut rest, mut top) = (pixels, first_row);
+    for &rows in &heights {
+        let (band, after) = rest.split_at_mut(rows * row_len);
+        bands.push((top, band));
+        (rest, top) = (after, top + rows);
+    }
+    run_workers(bands, |(top, band)| {
+        for (row, line) in band.chunks_mut(row_len).enumerate() {
+            progress.add_skipped(render_rows(line, params, top + row));
+            progress.add(width);
+        }
+    });
+
+    heights
+}
+
+/// Call `work` on each of `inputs` at once, one thread apiece, and return
+/// what the calls returned in the same order. Called from within a rayon
+/// pool of at least that many threads, as a batch runs its jobs, the calls
+/// run on that pool's threads; otherwise they get threads of their own.
+pub(crate) fn run_workers<I: Send, T: Send>(inputs: Vec<I>, work: impl Fn(I) -> T + Sync) -> Vec<T>
+{
+    let work = &work;
+    if rayon::current_thread_index().is_some() && rayon::current_num_threads() >= inputs.len() {
+        let mut results: Vec<Option<T>> = inputs.iter().map(|_| None).collect();
+        rayon::scope(|scope| {
+            for (input, result) in inputs.into_iter().zip(&mut results) {
+                scope.spawn(move |_| *result = Some(work(input)));
+            }
+        });
+        return results.into_iter().map(Option::unwrap).collect();
+    }
+    crossbeam::scope(|spanner| {
+        let workers: Vec<_> = inputs.into_iter().map(|input| spanner.spawn(move |_| work(input))).collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).collect()
+    }).unwrap()
+}
+
+/// Split `height` rows between `threads` threads as evenly as possible: one
+/// band per thread, but never more bands than rows, the first bands taking
+/// one row more than the rest when the rows do not divide evenly.
+///
+/// ```
+/// use mandelbrot_set::strategy::band_heights;
+///
+/// assert_eq!(band_heights(10, 4), [3, 3, 2, 2]);
+/// assert_eq!(band_heights(3, 8), [1, 1, 1]);
+/// ```
+pub fn band_heights(height: usize, threads: usize) -> Vec<usize>
+{
+    let bands = threads.clamp(1, height.max(1));
+    (0..bands).map(|band| height / bands + usize::from(band < height % bands)).collect()
+}
+
+/// Render each row of the image as a separate rayon task on a pool of
+/// `threads` threads, so that threads which finish the cheap rows early
+/// steal the expensive ones. Called from within a pool of that many
+/// threads, it uses that pool rather than building a new one. Returns the
+/// number of rows each pool thread rendered.
+pub fn render_rayon(pixels: &mut [u8], params: &RenderParams, threads: usize, progress: &Progress) -> Vec<usize>
+{
+    render_rayon_rows(pixels, params, threads, progress, |_| true)
+}
+
+/// Like `render_rayon`, for only the rows of the image for which `wanted`
+/// holds; the others are left as they are.
+fn render_rayon_rows(pixels: &mut [u8], params: &RenderParams, threads: usize, progress: &Progress,
+                     wanted: impl Fn(usize) -> bool + Sync) -> Vec<usize>
+{
+    assert!(pixels.len() == params.buffer_len());
+    let width = params.region.bounds.0;
+    let row_len = width * params.color.bytes_per_pixel();
+    let threads = threads.max(1);
+    let counts: Vec<AtomicUsize> = (0..threads).map(|_| AtomicUsize::new(0)).collect();
+
+    let render_all = |pixels: &mut [u8]| {
+        pixels.par_chunks_mut(row_len).enumerate().filter(|(row, _)| wanted(*row)).for_each(|(row, line)| {
+            progress.add_skipped(render_rows(line, params, row));
+            progress.add(width);
+            let index = rayon::current_thread_index().unwrap_or(0) % threads;
+            counts[index].fetch_add(1, Ordering::Relaxed);
+        });
+    };
+    if rayon::current_thread_index().is_some() && rayon::current_num_threads() == threads {
+        render_all(pixels);
+    } else {
+        match rayon::ThreadPoolBuilder::new().num_threads(threads).build() {
+            Ok(pool) => pool.install(|| render_all(pixels)),
+            Err(_) => render_all(pixels),
+        }
+    }
+
+    counts.into_iter().map(AtomicUsize::into_inner).collect()
+}
+
+/// Render the rows of the image that `sources` gives no source for, on the
+/// calling thread if `sequential` or else with `render_rayon`, then copy the
+/// others from their sources. Returns the rows each thread rendered.
+fn render_mirrored(pixels: &mut [u8], params: &RenderParams, sources: &[Option<usize>], sequential: bool,
+                   threads: usize, progress: &Progress) -> Vec<usize>
+{
+    assert!(pixels.len() == params.buffer_len());
+    let width = params.region.bounds.0;
+    let row_len = width * params.color.bytes_per_pixel();
+    let work_per_thread = if sequential {
+        let mut rendered = 0;
+        for (row, line) in pixels.chunks_mut(row_len).enumerate().filter(|(row, _)| sources[*row].is_none()) {
+            progress.add_skipped(render_rows(line, params, row));
+            progress.add(width);
+            rendered += 1;
+        }
+        vec![rendered]
+    } else {
+        render_rayon_rows(pixels, params, threads, progress, |row| sources[row].is_none())
+    };
+    copy_mirrored_rows(pixels, row_len, sources);
+    progress.add(width * sources.iter().filter(|source| source.is_some()).count());
+    work_per_thread
+}
+
+/// Render the image on `threads` threads that each repeatedly take the next
+/// row index from a shared atomic counter and render that row, until none
+/// are left. Rows near the set cost far more than rows far from it, and this
+/// keeps every thread busy until the very end. Returns the number of rows
+/// each thread rendered.
+pub fn render_dynamic(pixels: &mut [u8], params: &RenderParams, threads: usize, progress: &Progress) -> Vec<usize>
+{
+    assert!(pixels.len() == params.buffer_len());
+    let (width, height) = params.region.bounds;
+    let row_len = width * params.color.bytes_per_pixel();
+    let threads = threads.clamp(1, height.max(1));
+
+    let rows = RowClaims::new(pixels, row_len);
+
+    run_workers(vec![(); threads], |()| {
+        let mut rendered = 0;
+        while let Some((row, line)) = rows.claim() {
+            progress.add_skipped(render_rows(line, params, row));
+            progress.add(width);
+            rendered += 1;
+        }
+        rendered
+    })
+}
+
+/// The rows of an image, each handed out once, to whichever thread claims
+/// it next from a shared atomic counter, as a slice no other thread can
+/// reach.
+struct RowClaims<'a> {
+    start: *mut u8,
+    row_len: usize,
+    rows: usize,
+    next_row: AtomicUsize,
+    pixels: PhantomData<&'a mut [u8]>,
+}
+
+// Each row is only ever reachable through the one claim that returned it.
+unsafe impl Sync for RowClaims<'_> {}
+
+impl<'a> RowClaims<'a> {
+    /// Hand out `pixels` in rows of `row_len` bytes.
+    fn new(pixels: &'a mut [u8], row_len: usize) -> RowClaims<'a>
+    {
+        assert!(row_len > 0 && pixels.len().is_multiple_of(row_len));
+        RowClaims {
+            start: pixels.as_mut_ptr(),
+            row_len,
+            rows: pixels.len() / row_len,
+            next_row: AtomicUsize::new(0),
+            pixels: PhantomData,
+        }
+    }
+
+    /// The next row no thread has claimed yet, and its index, or `None`
+    /// once every row has been.
+    fn claim(&self) -> Option<(usize, &'a mut [u8])>
+    {
+        let row = self.next_row.fetch_add(1, Ordering::Relaxed);
+        if row >= self.rows {
+            return None;
+        }
+        // SAFETY: the counter gives out each index below `rows` exactly once,
+        // so the rows returned never overlap, and all of them lie within the
+        // `pixels` borrowed for `'a`.
+        Some((row, unsafe { std::slice::from_raw_parts_mut(self.start.add(row * self.row_len), self.row_len) }))
+    }
+}
+
+
+#[test]
+fn test_band_count_follows_threads() {
+    use num::Complex;
+
+    assert!(default_threads() >= 1);
+
+    let params = RenderParams::new((8, 60), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut pixels = vec![0; params.buffer_len()];
+    for threads in [1, 2, 6, 12, 60] {
+        assert_eq!(render_parallel(&mut pixels, &params, threads, &Progress::hidden()).len(), threads);
+    }
+    assert_eq!(render_parallel(&mut pixels, &params, 0, &Progress::hidden()).len(), 1);
+    assert_eq!(render_parallel(&mut pixels, &params, 7, &Progress::hidden()), [9, 9, 9, 9, 8, 8, 8]);
+}
+
+#[test]
+fn test_band_heights_are_even() {
+    for height in [1, 2, 7, 10, 60, 61, 1000] {
+        for threads in [1, 2, 3, 4, 6, 7, 12, 64, 2000] {
+            let heights = band_heights(height, threads);
+            assert_eq!(heights.iter().sum::<usize>(), height, "{} rows on {} threads", height, threads);
+            assert_eq!(heights.len(), threads.min(height), "{} rows on {} threads", height, threads);
+            let (least, most) = (heights.iter().min().unwrap(), heights.iter().max().unwrap());
+            assert!(*least >= 1 && most - least <= 1, "{} rows on {} threads: {:?}", height, threads, heights);
+        }
+    }
+    assert_eq!(band_heights(0, 4), [0]);
+}
+
+#[test]
+fn test_rayon_uses_the_pool_it_runs_in() {
+    use num::Complex;
+
+    let params = RenderParams::new((8, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut expected = vec![0; params.buffer_len()];
+    crate::render(&mut expected, &params);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    for threads in [3, 2] {
+        let mut pixels = vec![0; params.buffer_len()];
+        let rows = pool.install(|| render_rayon(&mut pixels, &params, threads, &Progress::hidden()));
+        assert_eq!((rows.len(), rows.iter().sum::<usize>()), (threads, 40));
+        assert_eq!(pixels, expected);
+    }
+}
+
+#[test]
+fn test_workers_use_the_pool_they_run_in() {
+    use num::Complex;
+
+    assert_eq!(run_workers(vec![1, 2, 3], |n| (n * 10, rayon::current_thread_index())),
+               [(10, None), (20, None), (30, None)]);
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(3).build().unwrap();
+    let on_pool = pool.install(|| run_workers(vec![(); 3], |()| rayon::current_thread_index()));
+    assert!(on_pool.iter().all(Option::is_some), "{:?}", on_pool);
+    // One worker more than the pool has threads: they get threads of their own.
+    assert!(pool.install(|| run_workers(vec![(); 4], |()| rayon::current_thread_index())).iter().all(Option::is_none));
+
+    let params = RenderParams::new((8, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut expected = vec![0; params.buffer_len()];
+    crate::render(&mut expected, &params);
+    for strategy in [Strategy::Bands, Strategy::Dynamic, Strategy::Tiles(4)] {
+        let mut pixels = vec![0; params.buffer_len()];
+        pool.install(|| strategy.render(&mut pixels, &params, 3));
+        assert_eq!(pixels, expected, "{:?}", strategy);
+    }
+}
+
+#[test]
+fn test_bands_and_dynamic_ignore_symmetry() {
+    use num::Complex;
+
+    // The default view is symmetric, but every thread still gets its rows.
+    let params = RenderParams::new((30, 20), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert!(mirror_sources(&params).is_some());
+    let mut expected = vec![0; params.buffer_len()];
+    crate::render(&mut expected, &params);
+    for strategy in [Strategy::Bands, Strategy::Dynamic] {
+        for threads in [1, 3, 4] {
+            let mut pixels = vec![0; params.buffer_len()];
+            let work = strategy.render(&mut pixels, &params, threads);
+            assert_eq!((work.len(), work.iter().sum::<usize>()), (threads, 20), "{:?} on {}", strategy, threads);
+            assert_eq!(pixels, expected, "{:?} on {}", strategy, threads);
+        }
+    }
+    assert_eq!(Strategy::Bands.render(&mut expected, &params, 4), [5, 5, 5, 5]);
+}
+
+#[test]
+fn test_row_claims_cover_every_row_once() {
+    let mut pixels = vec![0u8; 6 * 50];
+    let claims = RowClaims::new(&mut pixels, 6);
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                while let Some((row, line)) = claims.claim() {
+                    for byte in line.iter_mut() {
+                        *byte += row as u8 + 1;
+                    }
+                }
+            });
+        }
+    });
+    assert!(claims.claim().is_none());
+    let expected: Vec<u8> = (0..50).flat_map(|row| [row as u8 + 1; 6]).collect();
+    assert_eq!(pixels, expected);
+}
+
+#[test]
+fn test_every_strategy_completes_progress() {
+    use num::Complex;
+
+    let params = RenderParams::new((23, 17), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for strategy in [Strategy::Sequential, Strategy::Bands, Strategy::Rayon, Strategy::Dynamic, Strategy::Tiles(5)] {
+        let progress = Progress::new(23 * 17, false);
+        let mut pixels = vec![0; params.buffer_len()];
+        strategy.render_with_progress(&mut pixels, &params, 3, &progress);
+        assert_eq!(progress.done(), 23 * 17, "{:?}", strategy);
+    }
+}
@@ -0,0 +1,84 @@
+//! Mirroring views that are symmetric about the real axis. The Mandelbrot
+//! set is its own reflection in that axis, so a row below it can be copied
+//! from the row above it with the conjugate points instead of being
+//! rendered again.
+
+use crate::{Coloring, Fractal, RenderParams};
+
+
+/// Whether the image of `params` reads the same flipped top to bottom: a
+/// view whose top is as far above the real axis as its bottom is below it,
+/// of a fractal and coloring that treat a point and its conjugate alike.
+/// Off with `params.mirror` false, with antialiasing, whose samples do not
+/// pair off between rows, and with `params.perturbation` or
+/// `params.precision`, whose rows are not placed by `params.region`.
+pub fn is_mirror_symmetric(params: &RenderParams) -> bool
+{
+    params.mirror && params.antialias == 1 && params.perturbation.is_none() && params.precision.is_none()
+        && matches!(params.fractal, Fractal::Mandelbrot | Fractal::Tricorn | Fractal::Multibrot(_))
+        && matches!(params.coloring, Coloring::Linear | Coloring::Histogram | Coloring::Distance)
+        && params.region.upper_left.im == -params.region.lower_right.im
+}
+
+/// For each row of the image of `params`, the row above the axis it can be
+/// copied from. A row is only a copy when its points are exactly the
+/// conjugates of the other row's; rounding leaves some rows of most views
+/// to be rendered anyway, but the image always matches a full render byte
+/// for byte. `None` unless `is_mirror_symmetric`, or if no row is a copy.
+pub fn mirror_sources(params: &RenderParams) -> Option<Vec<Option<usize>>>
+{
+    if !is_mirror_symmetric(params) {
+        return None;
+    }
+    let height = params.region.bounds.1;
+    let im = |row: usize| params.region.pixel_to_point((0, row)).im;
+    let sources: Vec<Option<usize>> = (0..height).map(|row| {
+        let source = height - row;
+        (source < row && im(row) == -im(source)).then_some(source)
+    }).collect();
+    sources.iter().any(Option::is_some).then_some(sources)
+}
+
+/// Copy into each row of `pixels`, rows of `row_len` bytes, that has a
+/// source in `sources` the row it names.
+pub fn copy_mirrored_rows(pixels: &mut [u8], row_len: usize, sources: &[Option<usize>])
+{
+    assert_eq!(pixels.len(), sources.len() * row_len);
+    for (row, source) in sources.iter().enumerate() {
+        if let Some(source) = *source {
+            let (above, below) = pixels.split_at_mut(row * row_len);
+            below[..row_len].copy_from_slice(&above[source * row_len..(source + 1) * row_len]);
+        }
+    }
+}
+
+
+#[test]
+fn test_mirror_sources() {
+    use num::Complex;
+
+    // Eighths of two units land exactly, so every row below the axis pairs
+    // off; row 4 is the axis itself and row 0 has no partner.
+    let params = RenderParams::new((6, 8), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(mirror_sources(&params).unwrap(), [None, None, None, None, None, Some(3), Some(2), Some(1)]);
+    // Ninths do not, and only rows 4 and 5 come out exact conjugates.
+    let ninths = RenderParams::new((6, 9), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert_eq!(mirror_sources(&ninths).unwrap(), [None, None, None, None, None, Some(4), None, None, None]);
+    let fifths = RenderParams::new((6, 5), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    assert!(is_mirror_symmetric(&fifths) && mirror_sources(&fifths).is_none());
+
+    for params in [RenderParams { mirror: false, ..params.clone() },
+                   RenderParams { fractal: Fractal::BurningShip, ..params.clone() },
+                   RenderParams { coloring: Coloring::Binary, ..params.clone() },
+                   RenderParams { antialias: 2, ..params.clone() },
+                   RenderParams::new((6, 8), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -0.5 })] {
+        assert!(!is_mirror_symmetric(&params) && mirror_sources(&params).is_none(), "{:?}", params);
+    }
+}
+
+#[test]
+fn test_copy_mirrored_rows() {
+    let mut pixels = vec![1, 2, 3, 4, 0, 0, 0, 0];
+    copy_mirrored_rows(&mut pixels, 2, &[None, None, Some(1), Some(0)]);
+    assert_eq!(pixels, [1, 2, 3, 4, 3, 4, 1, 2]);
+}
@@ -0,0 +1,151 @@
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{RenderParams, Region, render_rect};
+use crate::progress::Progress;
+use crate::strategy::run_workers;
+
+
+pub const DEFAULT_TILE_SIZE: usize = 256;
+
+
+/// A rectangle of pixels within a larger image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub origin: (usize, usize),
+    pub size: (usize, usize),
+}
+
+impl Tile {
+    /// The part of the complex plane this tile covers, when the whole image
+    /// covers `region`.
+    pub fn region(&self, region: &Region) -> Region
+    {
+        let lower_right = (self.origin.0 + self.size.0, self.origin.1 + self.size.1);
+        Region::new(self.size, region.pixel_to_point(self.origin), region.pixel_to_point(lower_right))
+    }
+}
+
+/// Cut an image of size `bounds` into tiles of `tile_size` pixels square, row
+/// by row. Tiles along the right and bottom edges are smaller when the image
+/// size is not a multiple of `tile_size`.
+pub fn tiles(bounds: (usize, usize), tile_size: usize) -> Vec<Tile>
+{
+    assert!(tile_size > 0);
+    let mut tiles = vec![];
+    for top in (0..bounds.1).step_by(tile_size) {
+        for left in (0..bounds.0).step_by(tile_size) {
+            tiles.push(Tile {
+                origin: (left, top),
+                size: (tile_size.min(bounds.0 - left), tile_size.min(bounds.1 - top)),
+            });
+        }
+    }
+    tiles
+}
+
+/// Copy the pixels of `tile`, stored row by row in `source`, into their place
+/// in `image`, which is `width` pixels wide.
+pub fn blit(image: &mut [u8], width: usize, tile: &Tile, source: &[u8], bytes_per_pixel: usize)
+{
+    let row_len = tile.size.0 * bytes_per_pixel;
+    assert!(source.len() == row_len * tile.size.1);
+
+    for (row, line) in source.chunks(row_len).enumerate() {
+        let start = ((tile.origin.1 + row) * width + tile.origin.0) * bytes_per_pixel;
+        image[start..start + row_len].copy_from_slice(line);
+    }
+}
+
+/// Render the image in tiles of `tile_size` pixels square. A pool of
+/// `threads` threads takes tiles from a shared counter and renders each into
+/// its own buffer; the buffers are then copied into `pixels`. Tiles are
+/// rendered through the full image's region, so the result matches `render`
+/// exactly. Returns the number of tiles each thread rendered.
+pub fn render_tiles(pixels: &mut [u8], params: &RenderParams, threads: usize, tile_size: usize, progress: &Progress)
+    -> Vec<usize>
+{
+    assert!(pixels.len() == params.buffer_len());
+    let bytes_per_pixel = params.color.bytes_per_pixel();
+    let tiles = tiles(params.region.bounds, tile_size);
+    let threads = threads.clamp(1, tiles.len().max(1));
+
+    let next_tile = AtomicUsize::new(0);
+    let finished = Mutex::new(vec![]);
+
+    let counts = run_workers(vec![(); threads], |()| {
+        let mut rendered = 0;
+        loop {
+            let index = next_tile.fetch_add(1, Ordering::Relaxed);
+            let Some(tile) = tiles.get(index) else {
+                break rendered;
+            };
+            let mut buffer = vec![0; tile.size.0 * tile.size.1 * bytes_per_pixel];
+            progress.add_skipped(render_rect(&mut buffer, params, tile.origin, tile.size));
+            progress.add(tile.size.0 * tile.size.1);
+            finished.lock().unwrap().push((*tile, buffer));
+            rendered += 1;
+        }
+    });
+
+    for (tile, buffer) in finished.into_inner().unwrap() {
+        blit(pixels, params.region.bounds.0, &tile, &buffer, bytes_per_pixel);
+    }
+
+    counts
+}
+
+
+#[test]
+fn test_tiles_cover_edges() {
+    let tiles = tiles((10, 7), 4);
+    assert_eq!(tiles.len(), 6);
+    assert_eq!(tiles[0], Tile { origin: (0, 0), size: (4, 4) });
+    assert_eq!(tiles[2], Tile { origin: (8, 0), size: (2, 4) });
+    assert_eq!(tiles[5], Tile { origin: (8, 4), size: (2, 3) });
+    assert_eq!(tiles.iter().map(|tile| tile.size.0 * tile.size.1).sum::<usize>(), 70);
+}
+
+#[test]
+fn test_blit_edge_tile() {
+    let mut image = vec![0u8; 5 * 3];
+    let tile = Tile { origin: (3, 1), size: (2, 2) };
+    blit(&mut image, 5, &tile, &[1, 2, 3, 4], 1);
+    assert_eq!(image, vec![
+        0, 0, 0, 0, 0,
+        0, 0, 0, 1, 2,
+        0, 0, 0, 3, 4,
+    ]);
+
+    let mut rgb = vec![0u8; 2 * 2 * 3];
+    blit(&mut rgb, 2, &Tile { origin: (1, 1), size: (1, 1) }, &[7, 8, 9], 3);
+    assert_eq!(&rgb[9..], &[7, 8, 9]);
+}
+
+#[test]
+fn test_tile_region() {
+    use num::Complex;
+
+    let region = Region::new((100, 50), Complex { re: -2.0, im: 1.0 }, Complex { re: 2.0, im: -1.0 });
+    let tile = Tile { origin: (50, 25), size: (50, 25) };
+    let sub = tile.region(&region);
+    assert_eq!(sub.bounds, (50, 25));
+    assert_eq!(sub.upper_left, Complex { re: 0.0, im: 0.0 });
+    assert_eq!(sub.lower_right, region.lower_right);
+}
+
+#[test]
+fn test_render_tiles_matches_render() {
+    use num::Complex;
+    use crate::render;
+
+    let params = RenderParams::new((45, 31), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut expected = vec![0; params.buffer_len()];
+    render(&mut expected, &params);
+
+    for tile_size in [1, 7, 16, 256] {
+        let mut pixels = vec![0; params.buffer_len()];
+        let counts = render_tiles(&mut pixels, &params, 3, tile_size, &Progress::hidden());
+        assert_eq!(counts.iter().sum::<usize>(), tiles((45, 31), tile_size).len());
+        assert_eq!(pixels, expected, "tile size {}", tile_size);
+    }
+}
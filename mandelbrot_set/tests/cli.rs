@@ -0,0 +1,555 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn mandelbrot_set(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mandelbrot_set")).args(args).output().unwrap()
+}
+
+#[test]
+fn test_bad_arguments_exit_with_2() {
+    for args in [&["--output", "x.png"][..], &["--bogus"], &["x.png", "wide", "-1,1", "1,-1", "0"],
+                 &["x.png", "4x4", "1,1", "1,-1", "0"]] {
+        let output = mandelbrot_set(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+    }
+}
+
+#[test]
+fn test_unwritable_output_exits_with_1() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_missing_dir").join("x.png");
+    let output = mandelbrot_set(&["--output", path.to_str().unwrap(), "--size", "4x4", "--upper-left", "-2,1",
+                                  "--lower-right", "1,-1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("I/O error"));
+}
+
+#[test]
+fn test_help_exits_with_0() {
+    let output = mandelbrot_set(&["--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage:"));
+}
+
+#[test]
+fn test_output_to_stdout() {
+    let output = mandelbrot_set(&["--output", "-", "--size", "6x4", "--upper-left", "-2,1", "--lower-right", "1,-1"]);
+    assert!(output.status.success());
+    assert_eq!(&output.stdout[..11], b"P5\n6 4\n255\n");
+    assert_eq!(output.stdout.len(), 11 + 6 * 4);
+}
+
+#[test]
+fn test_read_metadata_recovers_the_view() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_metadata.png");
+    let filename = path.to_str().unwrap();
+    let render = mandelbrot_set(&["--output", filename, "--size", "12x8", "--upper-left", "-0.75,0.125",
+                                  "--lower-right", "-0.5,-0.0625", "--max-iter", "600", "--fractal", "tricorn"]);
+    assert!(render.status.success());
+    assert!(image::open(&path).is_ok());
+
+    let output = mandelbrot_set(&["--read-metadata", filename]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    for line in ["mandelbrot:fractal: tricorn", "mandelbrot:size: 12x8", "mandelbrot:upper-left: -0.75,0.125",
+                 "mandelbrot:lower-right: -0.5,-0.0625", "mandelbrot:max-iter: 600"] {
+        assert!(stdout.lines().any(|found| found == line), "{:?} not in {:?}", line, stdout);
+    }
+
+    let none = env::temp_dir().join("mandelbrot_cli_test_none.png");
+    let missing = mandelbrot_set(&["--read-metadata", none.to_str().unwrap()]);
+    assert_eq!(missing.status.code(), Some(1));
+}
+
+#[test]
+fn test_recolor_matches_a_direct_render() {
+    let path = |name: &str| {
+        env::temp_dir().join(format!("mandelbrot_cli_test_recolor_{}", name)).to_str().unwrap().to_string()
+    };
+    let view = ["--size", "24x16", "--upper-left", "-2,1", "--lower-right", "1,-1", "--max-iter", "300"];
+    let rendered =
+        mandelbrot_set(&[&view[..], &["--output", &path("direct.png"), "--dump", &path("view.iter")]].concat());
+    assert!(rendered.status.success());
+
+    let recolored = mandelbrot_set(&["recolor", &path("view.iter"), "--output", &path("default.png")]);
+    assert!(recolored.status.success());
+    assert!(String::from_utf8_lossy(&recolored.stderr)
+        .contains("24x16 pixels from -2,1 to 1,-1, at most 300 iterations"));
+    let decode = |name: &str| image::open(path(name)).unwrap().raw_pixels();
+    assert_eq!(decode("default.png"), decode("direct.png"));
+
+    let fire = ["--palette", "fire", "--coloring", "histogram"];
+    let recolor = ["recolor", &path("view.iter"), "--output", &path("fire.png")];
+    assert!(mandelbrot_set(&[&recolor[..], &fire].concat()).status.success());
+    assert!(mandelbrot_set(&[&view[..], &["--output", &path("fire_direct.png")], &fire].concat()).status.success());
+    assert_eq!(decode("fire.png"), decode("fire_direct.png"));
+    assert_eq!(decode("fire.png").len(), 24 * 16 * 3);
+
+    std::fs::write(path("bogus.iter"), b"MANDITER\x09\0\0\0").unwrap();
+    let bogus = mandelbrot_set(&["recolor", &path("bogus.iter"), "--output", &path("bogus.png")]);
+    assert_eq!(bogus.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&bogus.stderr).contains("version"));
+}
+
+#[test]
+fn test_output_raw_agrees_with_the_dump() {
+    let path = |name: &str| {
+        env::temp_dir().join(format!("mandelbrot_cli_test_raw_{}", name)).to_str().unwrap().to_string()
+    };
+    let view = ["--size", "12x8", "--upper-left", "-2,1", "--lower-right", "1,-1", "--max-iter", "50",
+                "--output", &path("view.png")];
+    assert!(mandelbrot_set(&[&view[..], &["--output-raw", &path("view.csv"), "--dump", &path("view.iter")]].concat())
+        .status.success());
+    assert!(mandelbrot_set(&[&view[..], &["--output-raw", &path("view.bin")]].concat()).status.success());
+
+    let csv = std::fs::read_to_string(path("view.csv")).unwrap();
+    let rows: Vec<Vec<i64>> = csv.lines().map(|line| line.split(',').map(|field| field.parse().unwrap()).collect())
+        .collect();
+    assert_eq!(rows.len(), 8);
+    assert!(rows.iter().all(|row| row.len() == 12));
+    assert!(rows.iter().flatten().any(|&count| count == -1));
+
+    let binary = std::fs::read(path("view.bin")).unwrap();
+    assert_eq!(&binary[..8], &[12, 0, 0, 0, 8, 0, 0, 0]);
+    let dump = std::fs::read(path("view.iter")).unwrap();
+    assert_eq!(binary[8..], dump[dump.len() - 12 * 8 * 4..]);
+    let first = u32::from_le_bytes(binary[8..12].try_into().unwrap());
+    assert_eq!(first as i64, rows[0][0]);
+
+    let unknown = mandelbrot_set(&[&view[..], &["--output-raw", &path("view.txt")]].concat());
+    assert_eq!(unknown.status.code(), Some(2));
+}
+
+#[test]
+fn test_progressive_ends_with_the_full_render() {
+    let path = |name: &str| {
+        env::temp_dir().join(format!("mandelbrot_cli_test_progressive_{}", name)).to_str().unwrap().to_string()
+    };
+    let view = ["--size", "50x30", "--upper-left", "-2,1", "--lower-right", "1,-1", "--color", "color"];
+    assert!(mandelbrot_set(&[&view[..], &["--output", &path("plain.png")]].concat()).status.success());
+    let progressive = mandelbrot_set(&[&view[..], &["--output", &path("progressive.png"), "--progressive", "--verbose"]]
+        .concat());
+    assert!(progressive.status.success());
+    let stderr = String::from_utf8_lossy(&progressive.stderr);
+    assert!(stderr.contains("1/8 preview") && stderr.contains("1/4 preview"), "{}", stderr);
+    assert_eq!(std::fs::read(path("progressive.png")).unwrap(), std::fs::read(path("plain.png")).unwrap());
+}
+
+#[test]
+fn test_png_to_stdout_keeps_messages_on_stderr() {
+    let output = mandelbrot_set(&["--output", "-", "--format", "png", "--size", "6x4", "--upper-left", "-2,1",
+                                  "--lower-right", "1,-1", "--verbose", "--strategy", "bands", "--threads", "2"]);
+    assert!(output.status.success());
+    let decoded = image::load_from_memory(&output.stdout).unwrap();
+    assert_eq!(decoded.raw_pixels().len(), 6 * 4);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("thread 1"));
+
+    let legacy = mandelbrot_set(&["-", "6x4", "-2,1", "1,-1", "1"]);
+    assert!(legacy.status.success());
+    assert_eq!(&legacy.stdout[..11], b"P5\n6 4\n255\n");
+}
+
+#[test]
+fn test_stdout_matches_the_file() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_stdout.bmp");
+    let view = ["--size", "7x5", "--upper-left", "-2,1", "--lower-right", "1,-1", "--palette", "fire"];
+    let file = [&["--output", path.to_str().unwrap()][..], &view].concat();
+    assert!(mandelbrot_set(&file).status.success());
+    let piped = mandelbrot_set(&[&["--output", "-", "--format", "bmp"][..], &view].concat());
+    assert!(piped.status.success());
+    assert_eq!(piped.stdout, std::fs::read(&path).unwrap());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_describe_renders_the_image_again() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_describe.png");
+    let filename = path.to_str().unwrap();
+    let render = mandelbrot_set(&["--output", filename, "--size", "12x8", "--upper-left", "-0.2,1.1",
+                                  "--lower-right", "0.1,0.9", "--max-iter", "400", "--fractal", "julia",
+                                  "--julia-c", "-0.8,0.156"]);
+    assert!(render.status.success());
+    let original = image::open(&path).unwrap().raw_pixels();
+
+    let output = mandelbrot_set(&["--describe", filename]);
+    assert!(output.status.success());
+    let line = String::from_utf8(output.stdout).unwrap();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    assert_eq!(words[1..3], ["--output", filename]);
+    assert!(line.contains(" --julia-c -0.8,0.156"), "{}", line);
+    std::fs::remove_file(&path).unwrap();
+    assert!(mandelbrot_set(&words[1..]).status.success());
+    assert_eq!(image::open(&path).unwrap().raw_pixels(), original);
+
+    let plain = env::temp_dir().join("mandelbrot_cli_test_describe.bmp");
+    assert!(mandelbrot_set(&["--output", plain.to_str().unwrap(), "--size", "4x4", "--upper-left", "-2,1",
+                             "--lower-right", "1,-1"]).status.success());
+    assert_eq!(mandelbrot_set(&["--describe", plain.to_str().unwrap()]).status.code(), Some(2));
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&plain).unwrap();
+}
+
+#[test]
+fn test_bench_prints_both_timings() {
+    let output = mandelbrot_set(&["bench", "--size", "40x30", "--upper-left", "-2,1", "--lower-right", "1,-1",
+                                  "--threads", "2", "--runs", "2"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "40x30 pixels, best of 2 runs");
+    assert!(lines[1].starts_with("sequential: ") && lines[1].ends_with(" s"), "{}", stdout);
+    assert!(lines[2].starts_with("bands, 2 threads: "), "{}", stdout);
+    assert!(lines[3].starts_with("speedup: ") && lines[3].ends_with('x'), "{}", stdout);
+}
+
+#[test]
+fn test_stream_writes_the_same_image() {
+    let view = ["--size", "50x140", "--upper-left", "-2,1.2", "--lower-right", "1,-1.2", "--palette", "fire"];
+    let buffered = mandelbrot_set(&[&["--output", "-", "--format", "png"][..], &view].concat());
+    let streamed = mandelbrot_set(&[&["--output", "-", "--format", "png", "--stream", "--strategy", "bands",
+                                      "--threads", "3"][..], &view].concat());
+    assert!(buffered.status.success() && streamed.status.success());
+    let decode = |bytes: &[u8]| image::load_from_memory(bytes).unwrap().raw_pixels();
+    assert_eq!(decode(&streamed.stdout), decode(&buffered.stdout));
+}
+
+#[test]
+fn test_grid_writes_one_file_per_tile() {
+    let dir = env::temp_dir().join("mandelbrot_cli_test_grid");
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("poster.png");
+    let render = mandelbrot_set(&["--output", output.to_str().unwrap(), "--size", "32x16", "--upper-left", "-2,1",
+                                  "--lower-right", "2,-1", "--grid", "4x3", "--verbose"]);
+    assert!(render.status.success());
+    assert_eq!(String::from_utf8_lossy(&render.stderr).lines().filter(|line| line.starts_with("wrote ")).count(), 12);
+
+    let corner = dir.join("poster_r2_c3.png");
+    assert_eq!(image::open(&corner).unwrap().raw_pixels().len(), 8 * 4);
+    let metadata = mandelbrot_set(&["--read-metadata", corner.to_str().unwrap()]);
+    let stdout = String::from_utf8(metadata.stdout).unwrap();
+    for line in ["mandelbrot:size: 8x4", "mandelbrot:upper-left: 1,-0.5", "mandelbrot:lower-right: 2,-1"] {
+        assert!(stdout.lines().any(|found| found == line), "{:?} not in {:?}", line, stdout);
+    }
+    assert!(!output.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_tiles_writes_a_pyramid() {
+    let dir = env::temp_dir().join("mandelbrot_cli_test_tiles");
+    let _ = std::fs::remove_dir_all(&dir);
+    let output = mandelbrot_set(&["tiles", "--upper-left", "-2,1.5", "--lower-right", "1,-1.5", "--max-zoom", "1",
+                                  "--out-dir", dir.to_str().unwrap(), "--iterations-per-level", "20"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("wrote 5 tiles"));
+    for tile in ["0/0/0.png", "1/0/0.png", "1/0/1.png", "1/1/0.png", "1/1/1.png"] {
+        assert_eq!(image::open(dir.join(tile)).unwrap().raw_pixels().len(), 256 * 256, "{}", tile);
+    }
+    let metadata = mandelbrot_set(&["--read-metadata", dir.join("1/1/0.png").to_str().unwrap()]);
+    let stdout = String::from_utf8(metadata.stdout).unwrap();
+    for line in ["mandelbrot:size: 256x256", "mandelbrot:upper-left: -0.5,1.5", "mandelbrot:max-iter: 275"] {
+        assert!(stdout.lines().any(|found| found == line), "{:?} not in {:?}", line, stdout);
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_estimate_writes_nothing() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_estimate.png");
+    let _ = std::fs::remove_file(&path);
+    let output = mandelbrot_set(&["--estimate", "--output", path.to_str().unwrap(), "--size", "4000x3000",
+                                  "--upper-left", "-2,1.5", "--lower-right", "2,-1.5", "--parallel", "--threads", "4"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines[0], "4000x3000 pixels, estimated from 4096 samples");
+    assert!(lines[1].starts_with("about ") && lines[1].ends_with(" iterations"), "{}", stdout);
+    assert!(lines[2].ends_with(" s on 4 threads"), "{}", stdout);
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_deep_zoom_writes_every_level() {
+    let dir = env::temp_dir().join("mandelbrot_cli_test_dzi");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("view.dzi");
+    let render = mandelbrot_set(&["--output", output.to_str().unwrap(), "--size", "50x30", "--upper-left", "-2,1",
+                                  "--lower-right", "1,-1", "--tile-size", "16", "--overlap", "1", "--palette", "fire"]);
+    assert!(render.status.success(), "{}", String::from_utf8_lossy(&render.stderr));
+    let descriptor = std::fs::read_to_string(&output).unwrap();
+    assert!(descriptor.contains("Format=\"png\" Overlap=\"1\" TileSize=\"16\""), "{}", descriptor);
+    assert!(descriptor.contains("<Size Width=\"50\" Height=\"30\"/>"), "{}", descriptor);
+
+    // 50x30 halves down to 1x1 in six steps, and is 4x2 tiles of 16 at the top.
+    let files = dir.join("view_files");
+    let levels: Vec<_> =
+        (0..7).map(|level| std::fs::read_dir(files.join(level.to_string())).unwrap().count()).collect();
+    assert_eq!(levels, [1, 1, 1, 1, 1, 2, 8]);
+    assert!(!files.join("7").exists());
+    let size = |name: &str| image::open(files.join(name)).unwrap().raw_pixels().len() / 3;
+    assert_eq!(size("6/0_0.png"), 17 * 17);
+    assert_eq!(size("6/1_0.png"), 18 * 17);
+    assert_eq!(size("6/3_1.png"), 3 * 15);
+    assert_eq!(size("5/1_0.png"), 10 * 15);
+    assert_eq!(size("0/0_0.png"), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_batch_renders_every_good_line() {
+    let dir = env::temp_dir().join("mandelbrot_cli_test_batch");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let jobs = format!("# two views\n{} 12x8 -2,1 1,-1 0\n\n{} 6x4 -2,1 1,-1 1 color\n{} 6x4 -2,1\n",
+                       file("a.png"), file("b.bmp"), file("c.png"));
+    std::fs::write(dir.join("jobs.txt"), jobs).unwrap();
+
+    let output = mandelbrot_set(&["batch", &file("jobs.txt"), "--threads", "2"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2, "{}", stdout);
+    assert!(lines[0].starts_with(&format!("{}: ", file("a.png"))) && lines[0].ends_with(" s"), "{}", stdout);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("jobs.txt:5: "));
+    assert_eq!(image::open(dir.join("a.png")).unwrap().raw_pixels().len(), 12 * 8);
+    assert_eq!(image::open(dir.join("b.bmp")).unwrap().raw_pixels().len(), 6 * 4 * 3);
+    assert!(!dir.join("c.png").exists());
+
+    let matching = mandelbrot_set(&["--output", &file("direct.png"), "--size", "12x8", "--upper-left", "-2,1",
+                                    "--lower-right", "1,-1"]);
+    assert!(matching.status.success());
+    assert_eq!(image::open(dir.join("a.png")).unwrap().raw_pixels(),
+               image::open(dir.join("direct.png")).unwrap().raw_pixels());
+
+    std::fs::write(dir.join("unwritable.txt"), format!("{} 4x4 -2,1 1,-1 0\n", file("missing/x.png"))).unwrap();
+    assert_eq!(mandelbrot_set(&["batch", &file("unwritable.txt")]).status.code(), Some(1));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_explore_zooms_where_told() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_explore.png");
+    let filename = path.to_str().unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mandelbrot_set"))
+        .args(["explore", "--output", filename, "--size", "40x20", "--upper-left", "-2,1", "--lower-right", "2,-1",
+               "--quiet"])
+        .stdin(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+    child.stdin.take().unwrap().write_all(b"30 5 2\nnowhere\n50 5 2\n10 10 4\nq\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("expected X Y ZOOM or q") && stderr.contains("inside the 40x20 image"), "{}", stderr);
+
+    // Pixel 30,5 lies on 1+0.5i, and in the view 2 units wide around it
+    // pixel 10,10 lies on 0.5+0.5i.
+    let metadata = String::from_utf8(mandelbrot_set(&["--read-metadata", filename]).stdout).unwrap();
+    assert!(metadata.contains("mandelbrot:upper-left: 0.25,0.625\n"), "{}", metadata);
+    assert!(metadata.contains("mandelbrot:lower-right: 0.75,0.375\n"), "{}", metadata);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_random_palette_prints_a_seed_that_repeats_it() {
+    let view = ["--output", "-", "--format", "bmp", "--size", "7x5", "--upper-left", "-2,1", "--lower-right", "1,-1"];
+    let picked = mandelbrot_set(&[&view[..], &["--random-palette"]].concat());
+    assert!(picked.status.success());
+    let stderr = String::from_utf8_lossy(&picked.stderr);
+    let seed = stderr.strip_prefix("random palette seed: ").and_then(|rest| rest.split(' ').next())
+        .unwrap_or_else(|| panic!("no seed in {:?}", stderr));
+    let again = mandelbrot_set(&[&view[..], &["--random-palette", seed]].concat());
+    assert!(again.status.success() && again.stderr.is_empty());
+    assert_eq!(again.stdout, picked.stdout);
+}
+
+#[test]
+fn test_auto_iter_prints_and_records_the_limit() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_auto_iter.png");
+    let filename = path.to_str().unwrap();
+    // 4e-6 units wide, six decades in from zoom 1.
+    let view = ["--output", filename, "--size", "16x8", "--preserve-aspect", "--center", "-0.75,0.1", "--zoom", "1e6",
+                "--auto-iter"];
+    let output = mandelbrot_set(&view);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "--auto-iter picked --max-iter 735\n");
+    let metadata = String::from_utf8(mandelbrot_set(&["--read-metadata", filename]).stdout).unwrap();
+    assert!(metadata.contains("mandelbrot:max-iter: 735\n"), "{}", metadata);
+
+    let explicit = mandelbrot_set(&[&view[..], &["--max-iter", "300"]].concat());
+    assert!(explicit.status.success() && explicit.stderr.is_empty());
+    let metadata = String::from_utf8(mandelbrot_set(&["--read-metadata", filename]).stdout).unwrap();
+    assert!(metadata.contains("mandelbrot:max-iter: 300\n"), "{}", metadata);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_roi_is_a_slice_of_the_full_render() {
+    let view = ["--output", "-", "--format", "ppm", "--size", "33x21", "--upper-left", "-2,1.1", "--lower-right",
+                "0.7,-1.1", "--palette", "fire", "--smooth", "--aa", "2", "--threads", "3"];
+    let pixels = |extra: &[&str]| {
+        let output = mandelbrot_set(&[&view[..], extra].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        output.stdout
+    };
+    let full = pixels(&[]);
+    let header = b"P6\n33 21\n255\n".len();
+    for (x0, y0, x1, y1) in [(5, 3, 17, 12), (0, 0, 33, 21), (32, 20, 33, 21), (0, 10, 33, 11)] {
+        let crop = pixels(&["--roi", &format!("{},{},{},{}", x0, y0, x1, y1)]);
+        let crop_header = format!("P6\n{} {}\n255\n", x1 - x0, y1 - y0);
+        assert_eq!(&crop[..crop_header.len()], crop_header.as_bytes());
+        let expected: Vec<u8> = (y0..y1).flat_map(|y| {
+            full[header + (y * 33 + x0) * 3..header + (y * 33 + x1) * 3].to_vec()
+        }).collect();
+        assert!(crop[crop_header.len()..] == expected[..], "{},{},{},{}", x0, y0, x1, y1);
+    }
+    for roi in ["5,3,17", "5,3,3,12", "0,0,34,21", "0,0,33,22", "4,4,4,5"] {
+        assert_eq!(mandelbrot_set(&[&view[..], &["--roi", roi]].concat()).status.code(), Some(2), "{}", roi);
+    }
+}
+
+#[test]
+fn test_verbosity_levels() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_verbosity.png");
+    let view = ["--output", path.to_str().unwrap(), "--size", "24x16", "--upper-left", "-2,1", "--lower-right",
+                "1,-0.5", "--strategy", "bands", "--threads", "2"];
+    let stderr = |extra: &[&str]| {
+        let output = mandelbrot_set(&[&view[..], extra].concat());
+        assert!(output.status.success());
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert_eq!(stderr(&[]), "");
+    let info = stderr(&["-v"]);
+    for line in ["24x16 pixels of mandelbrot from -2,1 to 1,-0.5, at most 255 iterations",
+                 "strategy bands, on 2 threads"] {
+        assert!(info.lines().any(|found| found == line), "{:?} not in {:?}", line, info);
+    }
+    for start in ["rendering took ", "writing took ", "thread 1: "] {
+        assert!(info.lines().any(|found| found.starts_with(start)), "{:?} not in {:?}", start, info);
+    }
+    assert!(!info.contains("bands of") && !info.contains("RenderParams"), "{}", info);
+    let debug = stderr(&["-vv"]);
+    assert!(debug.lines().any(|found| found == "bands of 8, 8 rows"), "{}", debug);
+    assert!(debug.contains("RenderParams {") && debug.contains("rendering took "), "{}", debug);
+
+    // Errors are reported whatever the verbosity.
+    let failed = mandelbrot_set(&[&view[..], &["--max-iter", "0"]].concat());
+    assert_eq!(failed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("iteration limit"));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_stats_reports_the_escape_counts() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_stats.png");
+    let view = ["--output", path.to_str().unwrap(), "--size", "24x16", "--upper-left", "-2,1", "--lower-right",
+                "1,-0.5"];
+    let output = mandelbrot_set(&[&view[..], &["--stats", "--quiet"]].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("escape counts: min 1, max ") && stderr.contains(" of --max-iter 255, mean "),
+            "{}", stderr);
+    assert!(stderr.trim_end().ends_with("% of 384 pixels never escaped"), "{}", stderr);
+    let image = std::fs::read(&path).unwrap();
+
+    // The image is the same with or without the statistics.
+    assert!(mandelbrot_set(&view).status.success());
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_swapped_corners_are_put_right_with_a_warning() {
+    let paths = ["swapped.png", "ordered.png"]
+        .map(|name| env::temp_dir().join(format!("mandelbrot_cli_test_{}", name)));
+    let [swapped, ordered] = paths.each_ref().map(|path| path.to_str().unwrap());
+    let output = mandelbrot_set(&["--output", swapped, "--size", "30x20", "--upper-left", "1,-1", "--lower-right",
+                                  "-2,1"]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("warning: swapped the real and imaginary parts of the corners") &&
+            stderr.contains("rendering -2,1 to 1,-1"), "{}", stderr);
+
+    let output = mandelbrot_set(&["--output", ordered, "--size", "30x20", "--upper-left", "-2,1", "--lower-right",
+                                  "1,-1"]);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(std::fs::read(swapped).unwrap(), std::fs::read(ordered).unwrap());
+
+    let output = mandelbrot_set(&["--output", swapped, "--size", "30x20", "--upper-left", "1,-1", "--lower-right",
+                                  "-2,1", "--strict"]);
+    assert!(!output.status.success());
+    for path in &paths {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn test_aspect_warn_says_how_stretched_the_view_is() {
+    let path = env::temp_dir().join("mandelbrot_cli_test_aspect.png");
+    let view = ["--output", path.to_str().unwrap(), "--size", "40x10", "--upper-left", "-2,1", "--lower-right",
+                "1,-1"];
+    let output = mandelbrot_set(&[&view[..], &["--aspect", "warn"]].concat());
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("warning: the view is squashed 2.667 times from top to bottom"), "{}", stderr);
+    let image = std::fs::read(&path).unwrap();
+
+    // Warning changes nothing of the image, and padding leaves alone a view
+    // already the shape of the image.
+    assert!(mandelbrot_set(&view).status.success());
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+    let square = ["--output", path.to_str().unwrap(), "--size", "30x20", "--upper-left", "-2,1", "--lower-right",
+                  "1,-1"];
+    assert!(mandelbrot_set(&square).status.success());
+    let image = std::fs::read(&path).unwrap();
+    for policy in ["pad", "crop", "warn"] {
+        let output = mandelbrot_set(&[&square[..], &["--aspect", policy]].concat());
+        assert!(output.status.success() && output.stderr.is_empty(), "{}", policy);
+        assert_eq!(std::fs::read(&path).unwrap(), image, "{}", policy);
+    }
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_resume_matches_an_uninterrupted_render() {
+    use mandelbrot_set::checkpoint::{CHECKPOINT_BAND_ROWS, HEADER_LEN};
+
+    let paths = ["resume.png", "resume_expected.png", "resume.ckpt"]
+        .map(|name| env::temp_dir().join(format!("mandelbrot_cli_test_{}", name)));
+    let [image, expected, checkpoint] = paths.each_ref().map(|path| path.to_str().unwrap());
+    let height = 2 * CHECKPOINT_BAND_ROWS + 5;
+    let size = format!("20x{}", height);
+    let view = ["--size", &size, "--upper-left", "-2,1.2", "--lower-right", "1,-1.2", "--palette", "fire"];
+    let run = |args: &[&str]| {
+        let output = mandelbrot_set(&[&view[..], args].concat());
+        assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+        String::from_utf8(output.stderr).unwrap()
+    };
+    run(&["--output", expected]);
+    run(&["--output", image, "--checkpoint", checkpoint]);
+    assert_eq!(std::fs::read(image).unwrap(), std::fs::read(expected).unwrap());
+
+    // Cut the render short after its first band: the others were never
+    // marked done, and what they hold is rendered again.
+    let mut bytes = std::fs::read(checkpoint).unwrap();
+    let bands = height.div_ceil(CHECKPOINT_BAND_ROWS);
+    bytes[HEADER_LEN + 1..HEADER_LEN + bands].fill(0);
+    bytes[HEADER_LEN + bands + CHECKPOINT_BAND_ROWS * 20 * 3..].fill(0x55);
+    std::fs::write(checkpoint, &bytes).unwrap();
+    std::fs::remove_file(image).unwrap();
+    let stderr = run(&["--output", image, "--resume", checkpoint, "-v"]);
+    assert!(stderr.contains("1 of 3 bands already done"), "{}", stderr);
+    assert_eq!(std::fs::read(image).unwrap(), std::fs::read(expected).unwrap());
+
+    // A checkpoint only resumes the render it was started for.
+    let other = ["--output", image, "--resume", checkpoint, "--max-iter", "100"];
+    let failed = mandelbrot_set(&[&view[..], &other].concat());
+    assert_eq!(failed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&failed.stderr).contains("other parameters"));
+    for path in &paths {
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,409 @@
+use std::env;
+use num::Complex;
+use image::GenericImage;
+use png::HasParameters;
+use mandelbrot_set::metadata::{read_png_text, render_metadata};
+use mandelbrot_set::output::write_png;
+use mandelbrot_set::progress::Progress;
+use mandelbrot_set::{render, render_image, stream_png, write_image, PngStream, STREAM_BAND_ROWS, write_image_as,
+                     write_image_with_metadata, ColorMode, DISPLAY_GAMMA, ImageFormat, MandelError, Palette,
+                     RenderParams, Strategy};
+
+fn rendered(color: ColorMode) -> RenderParams {
+    RenderParams { color, ..RenderParams::new((24, 16), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 }) }
+}
+
+#[test]
+fn test_formats_round_trip() {
+    for color in [ColorMode::Gray, ColorMode::Rgb(Palette::default())] {
+        let params = rendered(color);
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, &params);
+        let rgb: Vec<u8> = match params.color {
+            ColorMode::Rgb(_) => pixels.clone(),
+            _ => pixels.iter().flat_map(|&value| [value; 3]).collect(),
+        };
+
+        for extension in ["png", "ppm", "bmp", "tiff", "jpg"] {
+            let path = env::temp_dir()
+                .join(format!("mandelbrot_output_test_{}.{}", params.color.bytes_per_pixel(), extension));
+            let filename = path.to_str().unwrap();
+            write_image(filename, &pixels, params.region.bounds, params.color.color_type()).unwrap();
+
+            let decoded = image::open(&path).unwrap();
+            assert_eq!(decoded.dimensions(), (24, 16), "{}", filename);
+            if extension != "jpg" {
+                assert_eq!(decoded.to_rgb().into_raw(), rgb, "{}", filename);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_jpeg_is_close_to_png() {
+    for color in [ColorMode::Gray, ColorMode::Rgb(Palette::default())] {
+        let params = RenderParams {
+            color,
+            ..RenderParams::new((64, 48), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+        };
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, &params);
+
+        let decode = |format: ImageFormat, extension: &str| {
+            let path = env::temp_dir()
+                .join(format!("mandelbrot_output_test_lossy_{}.{}", params.color.bytes_per_pixel(), extension));
+            write_image_as(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type(), format)
+                .unwrap();
+            let decoded = image::open(&path).unwrap();
+            assert_eq!(decoded.dimensions(), (64, 48));
+            decoded.to_rgb().into_raw()
+        };
+        // Mean absolute difference per channel from the lossless PNG.
+        let error = |lossy: &[u8], exact: &[u8]| {
+            lossy.iter().zip(exact).map(|(a, b)| a.abs_diff(*b) as f64).sum::<f64>() / exact.len() as f64
+        };
+
+        let png = decode(ImageFormat::Png, "png");
+        let fine = error(&decode(ImageFormat::Jpeg(95), "jpg"), &png);
+        let coarse = error(&decode(ImageFormat::Jpeg(10), "jpeg"), &png);
+        // The encoder is rougher on grayscale than on RGB, where it comes
+        // within a couple of levels.
+        assert!(fine < 16.0, "quality 95 is off by {} on average", fine);
+        assert!(coarse > fine, "quality 10 is off by {}, quality 95 by {}", coarse, fine);
+    }
+}
+
+#[test]
+fn test_png_metadata_round_trip() {
+    let params = RenderParams { limit: 1234, ..rendered(ColorMode::Rgb(Palette::default())) };
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &params);
+
+    let path = env::temp_dir().join("mandelbrot_output_test_metadata.png");
+    let filename = path.to_str().unwrap();
+    let metadata = render_metadata(&params);
+    write_image_with_metadata(filename, &pixels, params.region.bounds, params.color.color_type(), ImageFormat::Png,
+                              &metadata).unwrap();
+    assert_eq!(read_png_text(&std::fs::read(&path).unwrap()).unwrap(), metadata);
+    assert_eq!(metadata["mandelbrot:max-iter"], "1234");
+    assert_eq!(image::open(&path).unwrap().to_rgb().into_raw(), pixels);
+
+    // Plain write_image adds no text.
+    write_image(filename, &pixels, params.region.bounds, params.color.color_type()).unwrap();
+    assert!(read_png_text(&std::fs::read(&path).unwrap()).unwrap().is_empty());
+}
+
+#[test]
+fn test_png_to_a_vec() {
+    let params = rendered(ColorMode::Gray);
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &params);
+
+    // A Vec cannot seek, like a pipe.
+    let mut encoded = Vec::new();
+    write_png(&mut encoded, &pixels, params.region.bounds, params.color.color_type(), &render_metadata(&params))
+        .unwrap();
+    let decoded = image::load_from_memory(&encoded).unwrap();
+    assert_eq!(decoded.dimensions(), (24, 16));
+    assert_eq!(decoded.raw_pixels(), pixels);
+}
+
+/// The size, bit depth and color type of a PNG image, and its pixels as
+/// stored, 16-bit samples included.
+fn decode_png(bytes: &[u8]) -> ((u32, u32, u8, u8), Vec<u8>) {
+    let mut decoder = png::Decoder::new(bytes);
+    decoder.set(png::TRANSFORM_IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    let mut decoded = vec![0; info.buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    ((info.width, info.height, info.bit_depth as u8, info.color_type as u8), decoded)
+}
+
+#[test]
+fn test_streamed_png_matches_buffered() {
+    let rgb = || ColorMode::Rgb(Palette::default());
+    for (color, display_gamma) in [(ColorMode::Gray, 1.0), (ColorMode::Gray16, 1.0), (rgb(), 1.0),
+                                   (ColorMode::Gray16, DISPLAY_GAMMA), (rgb(), DISPLAY_GAMMA)] {
+        let params = RenderParams {
+            color,
+            display_gamma,
+            ..RenderParams::new((70, STREAM_BAND_ROWS * 2 + 9), Complex { re: -2.0, im: 1.0 },
+                                Complex { re: 1.0, im: -1.0 })
+        };
+        let (pixels, _) = render_image(&params, Strategy::Sequential, 1);
+        let metadata = render_metadata(&params);
+        let mut buffered = Vec::new();
+        write_png(&mut buffered, &pixels, params.region.bounds, params.color.color_type(), &metadata).unwrap();
+
+        for (strategy, threads, strip_rows) in [(Strategy::Sequential, 1, STREAM_BAND_ROWS),
+                                                (Strategy::Bands, 3, STREAM_BAND_ROWS),
+                                                (Strategy::Sequential, 1, 1), (Strategy::Bands, 4, 7)] {
+            let progress = Progress::new(params.region.bounds.0 * params.region.bounds.1, false);
+            let mut streamed = Vec::new();
+            let work = stream_png(&mut streamed, &params, strategy, threads, strip_rows, &progress, &metadata).unwrap();
+            assert_eq!(work.iter().sum::<usize>(), params.region.bounds.1, "{:?}", strategy);
+            assert_eq!(progress.done(), params.region.bounds.0 * params.region.bounds.1);
+            assert_eq!(decode_png(&streamed), decode_png(&buffered), "{:?} {:?}", params.color, strategy);
+            assert!(streamed == buffered, "{:?} {:?} in strips of {}", params.color, strategy, strip_rows);
+            assert_eq!(read_png_text(&streamed).unwrap(), metadata);
+        }
+    }
+}
+
+#[test]
+fn test_png_stream_splits_idat_chunks() {
+    // Noise hardly compresses, so it takes several chunks.
+    let mut state = 12345u32;
+    let pixels: Vec<u8> = (0..200 * 200 * 3).map(|_| {
+        state = state.wrapping_mul(1103515245).wrapping_add(12345);
+        (state >> 24) as u8
+    }).collect();
+    let mut encoded = Vec::new();
+    let mut stream = PngStream::new(&mut encoded, (200, 200), image::ColorType::RGB(8), &Default::default()).unwrap();
+    for rows in pixels.chunks(200 * 3 * 7) {
+        stream.write_rows(rows).unwrap();
+    }
+    stream.finish().unwrap();
+
+    let (info, decoded) = decode_png(&encoded);
+    assert_eq!(info, (200, 200, 8, 2));
+    assert_eq!(decoded, pixels);
+    assert!(encoded.windows(4).filter(|window| window == b"IDAT").count() > 1);
+    let mut buffered = Vec::new();
+    write_png(&mut buffered, &pixels, (200, 200), image::ColorType::RGB(8), &Default::default()).unwrap();
+    assert!(buffered == encoded);
+    assert!(encoded.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+}
+
+#[test]
+fn test_unknown_extension() {
+    let params = rendered(ColorMode::Gray);
+    let pixels = vec![0; params.buffer_len()];
+    for extension in ["tga", "xyz"] {
+        let path = env::temp_dir().join(format!("mandelbrot_output_test.{}", extension));
+        match write_image(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type()) {
+            Err(MandelError::Parse(message)) => assert!(message.contains(".bmp, .tif, .tiff"), "{}", message),
+            _ => panic!("expected a parse error for .{}", extension),
+        }
+        assert!(!path.exists());
+    }
+}
+
+#[test]
+fn test_gray16_png() {
+    let params = rendered(ColorMode::Gray16);
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &params);
+
+    let path = env::temp_dir().join("mandelbrot_output_test_16.png");
+    write_image(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type()).unwrap();
+    assert_eq!(image::open(&path).unwrap().dimensions(), (24, 16));
+    // The IHDR chunk: bit depth 16, color type 0 (grayscale).
+    let bytes = std::fs::read(&path).unwrap();
+    assert_eq!(&bytes[12..16], b"IHDR");
+    assert_eq!((bytes[24], bytes[25]), (16, 0));
+}
+
+#[test]
+fn test_gray16_png_keeps_deep_counts() {
+    let params = RenderParams {
+        color: ColorMode::Gray16,
+        limit: 1000,
+        ..RenderParams::new((60, 40), Complex { re: -0.80, im: 0.20 }, Complex { re: -0.70, im: 0.10 })
+    };
+    let (pixels, _) = render_image(&params, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Rayon, Strategy::Dynamic, Strategy::Tiles(16)] {
+        assert_eq!(render_image(&params, strategy, 3).0, pixels, "{:?}", strategy);
+    }
+
+    let path = env::temp_dir().join("mandelbrot_output_test_deep_16.png");
+    write_image(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type()).unwrap();
+    let mut decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+    decoder.set(png::TRANSFORM_IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height, info.bit_depth), (60, 40, png::BitDepth::Sixteen));
+    let mut decoded = vec![0; info.buffer_size()];
+    reader.next_frame(&mut decoded).unwrap();
+    assert_eq!(decoded, pixels);
+
+    // Some neighbouring counts only differ below the top eight bits, which
+    // an 8-bit image would have merged.
+    let levels: Vec<u16> = decoded.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+    assert!(levels.iter().any(|&a| levels.iter().any(|&b| a != b && a >> 8 == b >> 8)));
+}
+
+/// Split a binary Netpbm image into its magic number, width, height and
+/// maxval, and the payload after the header.
+fn parse_netpbm(bytes: &[u8]) -> (String, usize, usize, usize, &[u8]) {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    while fields.len() < 4 {
+        let end = start + bytes[start..].iter().position(|byte| byte.is_ascii_whitespace()).unwrap();
+        fields.push(String::from_utf8(bytes[start..end].to_vec()).unwrap());
+        start = end + 1;
+    }
+    let number = |index: usize| fields[index].parse().unwrap();
+    (fields[0].clone(), number(1), number(2), number(3), &bytes[start..])
+}
+
+#[test]
+fn test_netpbm_headers() {
+    for (color, extension, magic, maxval) in [(ColorMode::Gray, "pgm", "P5", 255),
+                                              (ColorMode::Gray16, "pgm", "P5", 65535),
+                                              (ColorMode::Rgb(Palette::default()), "ppm", "P6", 255)] {
+        let params = rendered(color);
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, &params);
+        let path = env::temp_dir().join(format!("mandelbrot_output_test_netpbm_{}_{}.{}",
+                                                params.color.bytes_per_pixel(), maxval, extension));
+        write_image(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type()).unwrap();
+
+        let bytes = std::fs::read(&path).unwrap();
+        let (found, width, height, found_maxval, payload) = parse_netpbm(&bytes);
+        assert_eq!((found.as_str(), width, height, found_maxval), (magic, 24, 16, maxval));
+        assert_eq!(payload, &pixels[..]);
+
+        // image 0.13 only decodes P6.
+        if magic == "P6" {
+            assert_eq!(image::open(&path).unwrap().to_rgb().into_raw(), pixels);
+        }
+    }
+}
+
+/// Read a single-part, uncompressed scanline EXR image with one `FLOAT`
+/// channel: its width, height, channel name and pixels.
+#[cfg(feature = "exr")]
+fn parse_exr(bytes: &[u8]) -> (usize, usize, String, Vec<f32>) {
+    assert_eq!(&bytes[..4], &[0x76, 0x2f, 0x31, 0x01]);
+    let word = |at: usize| i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let text = |at: usize| {
+        let end = at + bytes[at..].iter().position(|&byte| byte == 0).unwrap();
+        (String::from_utf8(bytes[at..end].to_vec()).unwrap(), end + 1)
+    };
+    let (mut at, mut window, mut channel) = (8, None, None);
+    while bytes[at] != 0 {
+        let (name, after_name) = text(at);
+        let (kind, after_kind) = text(after_name);
+        let (size, value) = (word(after_kind) as usize, after_kind + 4);
+        match (name.as_str(), kind.as_str()) {
+            ("dataWindow", "box2i") => {
+                window = Some((word(value + 8) - word(value) + 1, word(value + 12) - word(value + 4) + 1));
+            }
+            ("channels", "chlist") => {
+                let (name, after) = text(value);
+                assert_eq!((word(after), bytes[after + 16]), (2, 0), "one FLOAT channel");
+                channel = Some(name);
+            }
+            ("compression", _) => assert_eq!(bytes[value], 0, "no compression"),
+            _ => {}
+        }
+        at = value + size;
+    }
+    let (width, height) = window.unwrap();
+    let (width, height) = (width as usize, height as usize);
+
+    let offsets = at + 1;
+    let mut pixels = vec![];
+    for row in 0..height {
+        let block = u64::from_le_bytes(bytes[offsets + row * 8..offsets + row * 8 + 8].try_into().unwrap()) as usize;
+        assert_eq!((word(block), word(block + 4)), (row as i32, width as i32 * 4));
+        let row_values = &bytes[block + 8..block + 8 + width * 4];
+        pixels.extend(row_values.chunks(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())));
+    }
+    (width, height, channel.unwrap(), pixels)
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn test_exr_round_trip() {
+    // Every float comes back bit for bit, including the ones a tone curve
+    // never produces.
+    let values = [0.0f32, -0.0, 1.0, 2.5, 1e-42, -7.25, f32::MAX, f32::INFINITY, f32::NEG_INFINITY, f32::NAN,
+                  f32::from_bits(0x7fc0_0001), 1e9];
+    let pixels: Vec<u8> = values.iter().flat_map(|value| value.to_le_bytes()).collect();
+    let path = env::temp_dir().join("mandelbrot_output_test_round_trip.exr");
+    write_image_as(path.to_str().unwrap(), &pixels, (4, 3), image::ColorType::Gray(32), ImageFormat::Exr).unwrap();
+
+    let (width, height, channel, decoded) = parse_exr(&std::fs::read(&path).unwrap());
+    assert_eq!((width, height, channel.as_str()), (4, 3, "Y"));
+    let bits = |values: &[f32]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+    assert_eq!(bits(&decoded), bits(&values));
+}
+
+#[cfg(feature = "exr")]
+#[test]
+fn test_exr_keeps_escape_values() {
+    let params = RenderParams { smooth: true, bailout: mandelbrot_set::SMOOTH_BAILOUT, ..rendered(ColorMode::Values) };
+    let (sequential, _) = render_image(&params, Strategy::Sequential, 1);
+    let (pixels, _) = render_image(&params, Strategy::Rayon, 4);
+    assert_eq!(pixels, sequential);
+
+    let path = env::temp_dir().join("mandelbrot_output_test_values.exr");
+    write_image(path.to_str().unwrap(), &pixels, params.region.bounds, params.color.color_type()).unwrap();
+    let (width, height, channel, values) = parse_exr(&std::fs::read(&path).unwrap());
+    assert_eq!((width, height, channel.as_str()), (24, 16, "Y"));
+    let bits = |values: &[f32]| values.iter().map(|value| value.to_bits()).collect::<Vec<_>>();
+    let written: Vec<f32> = pixels.chunks(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())).collect();
+    assert_eq!(bits(&values), bits(&written));
+
+    for (column, row) in [(0, 0), (3, 5), (11, 8), (23, 15), (17, 2)] {
+        let value = values[row * width + column];
+        let point = params.region.pixel_to_point((column, row));
+        match params.fractal.escape_time_smooth(point, params.limit, params.bailout, params.periodicity_epsilon) {
+            Some(expected) => assert_eq!(value.to_bits(), (expected as f32).to_bits(), "({}, {})", column, row),
+            None => assert!(value.is_nan(), "({}, {})", column, row),
+        }
+    }
+    assert!(values.iter().any(|value| value.is_nan()));
+    assert!(values.iter().any(|value| value.fract() != 0.0 && value.is_finite()));
+
+    // Without --smooth the values are the integer counts.
+    let counts = RenderParams { smooth: false, ..params.clone() };
+    let (pixels, _) = render_image(&counts, Strategy::Tiles(5), 3);
+    let values: Vec<f32> = pixels.chunks(4).map(|value| f32::from_le_bytes(value.try_into().unwrap())).collect();
+    let corner = params.fractal.escape_time(params.region.pixel_to_point((0, 0)), params.limit, params.bailout,
+                                            params.periodicity_epsilon);
+    assert_eq!(values[0], corner.unwrap() as f32);
+    assert!(values.iter().all(|value| value.is_nan() || value.fract() == 0.0));
+}
+
+#[test]
+fn test_pyramid_levels_agree() {
+    use mandelbrot_set::pyramid::{run_pyramid, Pyramid};
+
+    let dir = env::temp_dir().join("mandelbrot_output_test_pyramid");
+    let _ = std::fs::remove_dir_all(&dir);
+    let params = RenderParams::new((1, 1), Complex { re: -2.0, im: 1.5 }, Complex { re: 1.0, im: -1.5 });
+    let pyramid = Pyramid { max_zoom: 2, tile_size: 16, iterations_per_level: 0, skip_empty: false };
+    let counts = run_pyramid(&dir, &params, &pyramid, 3).unwrap();
+    assert_eq!((counts.written, counts.skipped), (1 + 4 + 16, 0));
+
+    let tile = |zoom: u32, x: usize, y: usize| {
+        let decoded = image::open(Pyramid::tile_filename(&dir, zoom, x, y)).unwrap();
+        assert_eq!(decoded.dimensions(), (16, 16));
+        decoded.raw_pixels()
+    };
+    // Each pixel of level 0 should be about the average of the four level 1
+    // pixels it covers; they sample the plane at different points, so only
+    // on average.
+    let top = tile(0, 0, 0);
+    let mut difference = 0;
+    for y in 0..16 {
+        for x in 0..16 {
+            let quarter = tile(1, x / 8, y / 8);
+            let (qx, qy) = (x % 8 * 2, y % 8 * 2);
+            let sum: usize = [(0, 0), (1, 0), (0, 1), (1, 1)].iter()
+                .map(|&(dx, dy)| quarter[(qy + dy) * 16 + qx + dx] as usize).sum();
+            difference += (sum / 4).abs_diff(top[y * 16 + x] as usize);
+        }
+    }
+    assert!(difference / 256 < 16, "mean difference {}", difference / 256);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // The middle of the main cardioid is interior at every level.
+    let inside = RenderParams::new((1, 1), Complex { re: -0.4, im: 0.2 }, Complex { re: 0.0, im: -0.2 });
+    let skipping = Pyramid { skip_empty: true, ..pyramid };
+    let counts = run_pyramid(&dir, &inside, &skipping, 2).unwrap();
+    assert_eq!(counts, mandelbrot_set::pyramid::PyramidCounts { written: 0, skipped: 21 });
+    assert!(!dir.exists());
+}
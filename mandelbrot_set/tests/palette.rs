@@ -0,0 +1,40 @@
+use std::path::{Path, PathBuf};
+use mandelbrot_set::{MandelError, Palette};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+#[test]
+fn test_fractint_map() {
+    let palette = Palette::from_file(&fixture("fire.map")).unwrap();
+    assert_eq!(palette.sample(0.0), [0, 0, 0]);
+    assert_eq!(palette.sample(100.0 / 255.0), [200, 0, 0]);
+    assert_eq!(palette.sample(1.0), [255, 255, 0]);
+}
+
+#[test]
+fn test_short_map_is_interpolated() {
+    let palette = Palette::from_file(&fixture("short.map")).unwrap();
+    assert_eq!(palette.sample(1.0 / 3.0), [255, 0, 0]);
+    assert_eq!(palette.sample(0.5), [255, 128, 0]);
+    assert_eq!(palette.sample(1.0), [255, 255, 255]);
+}
+
+#[test]
+fn test_gradient_file() {
+    let palette = Palette::from_file(&fixture("sunset.gradient")).unwrap();
+    assert_eq!(palette, Palette::new(vec![[32, 0, 64], [255, 64, 0], [255, 224, 128]]));
+}
+
+#[test]
+fn test_broken_files_name_the_line() {
+    for (name, line) in [("broken.gradient", ":4: "), ("broken.map", ":3: ")] {
+        match Palette::from_file(&fixture(name)) {
+            Err(MandelError::Parse(message)) => assert!(message.contains(line), "{}", message),
+            other => panic!("expected a parse error for {}, got {:?}", name, other),
+        }
+    }
+    assert!(matches!(Palette::from_file(&fixture("missing.map")), Err(MandelError::Io(_))));
+    assert!(matches!(Palette::parse("# nothing here\n"), Err(MandelError::Parse(_))));
+}
@@ -0,0 +1,717 @@
+use num::Complex;
+use mandelbrot_set::{color_counts, correct_gamma, render, render_counts, render_image, render_parallel, ColorMode,
+                     Coloring, DISPLAY_GAMMA, Fractal, IterDump, Palette, PERIODICITY_EPSILON, RenderParams,
+                     SMOOTH_BAILOUT, Strategy};
+use mandelbrot_set::bigfloat::BigFloat;
+use mandelbrot_set::perturbation::Perturbation;
+use mandelbrot_set::precision::Precision;
+use mandelbrot_set::progress::Progress;
+use mandelbrot_set::renderer::Renderer;
+use mandelbrot_set::symmetry::mirror_sources;
+
+#[test]
+fn test_render_tiny_image() {
+    let params = RenderParams::new((8, 8), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut pixels = vec![0; 8 * 8];
+    render(&mut pixels, &params);
+    assert_eq!(pixels, vec![
+        254, 253, 252, 252, 251, 242, 251, 253,
+        254, 252, 252, 251, 249,   0, 250, 252,
+        254, 252, 251, 250,   0,   0,   0, 252,
+        254, 251, 246, 231,   0,   0,   0, 251,
+          0,   0,   0,   0,   0,   0,   0, 251,
+        254, 251, 246, 231,   0,   0,   0, 251,
+        254, 252, 251, 250,   0,   0,   0, 252,
+        254, 252, 252, 251, 249,   0, 250, 252,
+    ]);
+}
+
+#[test]
+fn test_higher_limit_shows_more_detail() {
+    let viewport = RenderParams::new((64, 48), Complex { re: -0.80, im: 0.20 }, Complex { re: -0.70, im: 0.10 });
+    let nonzero = |limit: usize| {
+        let params = RenderParams { limit, ..viewport.clone() };
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, &params);
+        pixels.iter().filter(|&&p| p != 0).count()
+    };
+
+    assert!(nonzero(5000) > nonzero(100));
+}
+
+#[test]
+fn test_more_threads_than_rows() {
+    let params = RenderParams::new((16, 10), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut sequential = vec![0; params.buffer_len()];
+    render(&mut sequential, &params);
+
+    for threads in [1, 3, 7, 10, 64] {
+        let mut parallel = vec![0; params.buffer_len()];
+        render_parallel(&mut parallel, &params, threads, &Progress::hidden());
+        assert_eq!(parallel, sequential, "{} threads", threads);
+    }
+}
+
+#[test]
+fn test_strategies_agree() {
+    // Without mirroring, so that every strategy renders every row.
+    let params = RenderParams {
+        mirror: false,
+        ..RenderParams::new((37, 23), Complex { re: -1.6, im: 1.0 }, Complex { re: 0.6, im: -1.0 })
+    };
+    let strategies = [Strategy::Sequential, Strategy::Bands, Strategy::Rayon, Strategy::Dynamic];
+    let images: Vec<Vec<u8>> = strategies.iter().map(|strategy| {
+        let mut pixels = vec![0; params.buffer_len()];
+        let rows_per_thread = strategy.render(&mut pixels, &params, 4);
+        assert_eq!(rows_per_thread.iter().sum::<usize>(), 23);
+        pixels
+    }).collect();
+
+    assert_eq!(images[0], images[1]);
+    assert_eq!(images[0], images[2]);
+    assert_eq!(images[0], images[3]);
+}
+
+#[test]
+fn test_tiles_match_sequential() {
+    let params = RenderParams::new((37, 23), Complex { re: -1.6, im: 1.0 }, Complex { re: 0.6, im: -1.0 });
+    let mut sequential = vec![0; params.buffer_len()];
+    Strategy::Sequential.render(&mut sequential, &params, 1);
+
+    let mut tiled = vec![0; params.buffer_len()];
+    let tiles_per_thread = Strategy::Tiles(8).render(&mut tiled, &params, 4);
+    assert_eq!(tiles_per_thread.iter().sum::<usize>(), 5 * 3);
+    assert_eq!(tiled, sequential);
+}
+
+#[test]
+fn test_interior_checks_do_not_change_the_image() {
+    let params = RenderParams::new((60, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut checked = vec![0; params.buffer_len()];
+    let progress = Progress::hidden();
+    Strategy::Sequential.render_with_progress(&mut checked, &params, 1, &progress);
+
+    for unchecked_params in [RenderParams { interior_check: false, ..params.clone() },
+                             RenderParams { periodicity_epsilon: 0.0, ..params.clone() }] {
+        let mut unchecked = vec![0; params.buffer_len()];
+        render(&mut unchecked, &unchecked_params);
+        assert_eq!(checked, unchecked);
+    }
+    assert!(progress.skipped().interior > 0);
+
+    // Every coloring that takes the shortcut, over the same full view.
+    for checked_params in [RenderParams { smooth: true, bailout: SMOOTH_BAILOUT, ..params.clone() },
+                           RenderParams { coloring: Coloring::Distance, bailout: SMOOTH_BAILOUT, ..params.clone() },
+                           RenderParams { coloring: Coloring::Stripe, bailout: SMOOTH_BAILOUT, ..params.clone() },
+                           RenderParams { coloring: Coloring::Binary, ..params.clone() },
+                           RenderParams { color: ColorMode::Counts, ..params.clone() }] {
+        let unchecked_params = RenderParams { interior_check: false, ..checked_params.clone() };
+        assert!(render_image(&checked_params, Strategy::Rayon, 2).0
+                    == render_image(&unchecked_params, Strategy::Rayon, 2).0, "{:?}", checked_params);
+    }
+}
+
+#[test]
+fn test_mirrored_views_match_a_full_render() {
+    // All of 64 rows pair off exactly across the axis; of 60 only some do.
+    for height in [64, 60] {
+        let base = RenderParams::new((50, height), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+        let sources = mirror_sources(&base).unwrap();
+        assert!(sources.iter().filter(|source| source.is_some()).count() > 1);
+        for params in [base.clone(),
+                       RenderParams { smooth: true, bailout: SMOOTH_BAILOUT, color: ColorMode::Gray16, ..base.clone() },
+                       RenderParams { coloring: Coloring::Histogram, ..base.clone() },
+                       RenderParams { coloring: Coloring::Distance, bailout: SMOOTH_BAILOUT, ..base.clone() },
+                       RenderParams { fractal: Fractal::Tricorn, ..base.clone() },
+                       RenderParams { fractal: Fractal::Multibrot(3), color: ColorMode::Counts, ..base.clone() }] {
+            let full = render_image(&RenderParams { mirror: false, ..params.clone() }, Strategy::Sequential, 1).0;
+            for strategy in [Strategy::Sequential, Strategy::Bands, Strategy::Rayon, Strategy::Dynamic] {
+                assert!(render_image(&params, strategy, 3).0 == full, "{:?} with {:?}", params, strategy);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_interior_color() {
+    let interior = [12, 200, 34];
+    let base = RenderParams {
+        color: ColorMode::Rgb(Palette::named("fire").unwrap().with_interior(interior)),
+        ..RenderParams::new((40, 30), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let (counts, _) = render_counts(&base, Strategy::Sequential, 1);
+    assert!(counts.iter().any(Option::is_none));
+    for params in [base.clone(),
+                   RenderParams { smooth: true, bailout: SMOOTH_BAILOUT, ..base.clone() },
+                   RenderParams { coloring: Coloring::Histogram, ..base.clone() }] {
+        let (pixels, _) = render_image(&params, Strategy::Rayon, 2);
+        for (pixel, count) in pixels.chunks(3).zip(&counts) {
+            assert_eq!(count.is_none(), pixel == interior, "{:?}", params);
+        }
+    }
+}
+
+#[test]
+fn test_display_gamma() {
+    let base = RenderParams::new((40, 30), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for params in [base.clone(),
+                   RenderParams { color: ColorMode::Rgb(Palette::named("fire").unwrap()), ..base.clone() },
+                   RenderParams { coloring: Coloring::Histogram, ..base.clone() },
+                   RenderParams { light_angle: Some(45.0), bailout: SMOOTH_BAILOUT, ..base.clone() }] {
+        let (linear, _) = render_image(&params, Strategy::Rayon, 2);
+        let mut corrected = linear.clone();
+        correct_gamma(&mut corrected, &params);
+        assert_eq!(corrected, linear, "{:?}", params);
+
+        let display = RenderParams { display_gamma: DISPLAY_GAMMA, ..params.clone() };
+        correct_gamma(&mut corrected, &display);
+        assert_eq!(render_image(&display, Strategy::Rayon, 2).0, corrected, "{:?}", params);
+        let mut renderer = Renderer::new(2);
+        renderer.render_into(&display, Strategy::Bands);
+        assert_eq!(renderer.buffer, corrected, "{:?}", params);
+        // No channel darkens, and black stays black.
+        assert!(linear.iter().zip(&corrected)
+            .all(|(&before, &after)| after >= before && (after == 0) == (before == 0)));
+        assert!(linear.iter().zip(&corrected).any(|(&before, &after)| after > before));
+    }
+}
+
+#[test]
+fn test_stripe_average_image() {
+    // The fixture was written with
+    //   mandelbrot_set --output tests/fixtures/stripe-64x64.png --size 64x64 --upper-left -2,1.25
+    //                  --lower-right 0.5,-1.25 --coloring stripe --palette fire
+    // and only needs writing again if the coloring is meant to change.
+    let params = RenderParams {
+        coloring: Coloring::Stripe,
+        bailout: SMOOTH_BAILOUT,
+        color: ColorMode::Rgb(Palette::named("fire").unwrap()),
+        ..RenderParams::new((64, 64), Complex { re: -2.0, im: 1.25 }, Complex { re: 0.5, im: -1.25 })
+    };
+    let fixture = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/stripe-64x64.png");
+    let expected = image::open(fixture).unwrap().raw_pixels();
+    for strategy in [Strategy::Sequential, Strategy::Rayon] {
+        assert!(render_image(&params, strategy, 3).0 == expected, "{:?}", strategy);
+    }
+}
+
+#[test]
+fn test_render_tiny_smooth_image() {
+    let params = RenderParams {
+        smooth: true,
+        bailout: SMOOTH_BAILOUT,
+        ..RenderParams::new((8, 8), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let mut pixels = vec![0; 8 * 8];
+    render(&mut pixels, &params);
+    assert_eq!(pixels, vec![
+        252, 252, 252, 251, 250, 241, 251, 252,
+        252, 252, 251, 250, 248,   0, 249, 251,
+        252, 251, 249, 249,   0,   0,   0, 251,
+        251, 250, 245, 230,   0,   0,   0, 250,
+          0,   0,   0,   0,   0,   0,   0, 250,
+        251, 250, 245, 230,   0,   0,   0, 250,
+        252, 251, 249, 249,   0,   0,   0, 251,
+        252, 252, 251, 250, 248,   0, 249, 251,
+    ]);
+}
+
+#[test]
+fn test_histogram_coloring() {
+    let params = RenderParams {
+        coloring: Coloring::Histogram,
+        limit: 1000,
+        ..RenderParams::new((48, 32), Complex { re: -0.75, im: 0.12 }, Complex { re: -0.73, im: 0.1 })
+    };
+    let (sequential, _) = render_image(&params, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Rayon, Strategy::Dynamic, Strategy::Tiles(8)] {
+        assert_eq!(render_image(&params, strategy, 4).0, sequential, "{:?}", strategy);
+    }
+
+    // The linear mapping squeezes this deep view into a narrow band of
+    // shades; equalization spreads it over nearly all of them.
+    let (linear, _) =
+        render_image(&RenderParams { coloring: Coloring::Linear, ..params.clone() }, Strategy::Sequential, 1);
+    // Measure the band as the shades between the 10th and 90th percentile.
+    let spread = |pixels: &[u8]| {
+        let mut escaped: Vec<u8> = pixels.iter().copied().filter(|&shade| shade > 0).collect();
+        escaped.sort();
+        (escaped[escaped.len() * 9 / 10] - escaped[escaped.len() / 10]) as usize
+    };
+    assert!(spread(&sequential) > 2 * spread(&linear), "{} vs {}", spread(&sequential), spread(&linear));
+}
+
+#[test]
+fn test_neutral_tone_changes_nothing() {
+    let base = RenderParams::new((40, 30), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    for params in [base.clone(),
+                   RenderParams { smooth: true, bailout: SMOOTH_BAILOUT, ..base.clone() },
+                   RenderParams { color: ColorMode::Rgb(Palette::default()), ..base.clone() }] {
+        let mut expected = vec![0; params.buffer_len()];
+        render(&mut expected, &params);
+
+        let neutral = RenderParams { gamma: 1.0, log_scale: false, ..params.clone() };
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, &neutral);
+        assert_eq!(pixels, expected);
+
+        // A gamma above 1 moves escaping pixels towards the slow end, so
+        // grayscale gets darker and never brighter.
+        if params.color == ColorMode::Gray {
+            let mut darker = vec![0; params.buffer_len()];
+            render(&mut darker, &RenderParams { gamma: 2.0, ..params.clone() });
+            assert!(darker.iter().zip(&expected).all(|(dark, light)| dark <= light));
+            assert!(darker.iter().zip(&expected).any(|(dark, light)| dark < light));
+        }
+    }
+}
+
+#[test]
+fn test_antialiasing() {
+    let params = RenderParams::new((30, 20), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut aliased = vec![0; params.buffer_len()];
+    render(&mut aliased, &params);
+
+    let smoothed = RenderParams { antialias: 3, ..params.clone() };
+    assert_eq!(smoothed.buffer_len(), params.buffer_len());
+    let (sequential, _) = render_image(&smoothed, Strategy::Sequential, 1);
+    for strategy in [Strategy::Rayon, Strategy::Tiles(8)] {
+        assert_eq!(render_image(&smoothed, strategy, 4).0, sequential, "{:?}", strategy);
+    }
+
+    // Pixels straddling the edge of the set come out between black and the
+    // shade of the escaping samples, so fewer pixels are exactly black.
+    let black = |pixels: &[u8]| pixels.iter().filter(|&&shade| shade == 0).count();
+    assert!(black(&sequential) < black(&aliased));
+    assert!(sequential.iter().zip(&aliased).any(|(a, b)| a != b));
+}
+
+#[test]
+fn test_histogram_on_the_classic_view() {
+    let params = RenderParams {
+        coloring: Coloring::Histogram,
+        ..RenderParams::new((90, 60), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let (counts, _) = render_counts(&params, Strategy::Rayon, 4);
+    assert_eq!(counts.len(), 90 * 60);
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &RenderParams { coloring: Coloring::Linear, ..params.clone() });
+    for (count, pixel) in counts.iter().zip(&pixels) {
+        assert_eq!(count.is_none(), *pixel == 0);
+    }
+
+    // Most of the classic view escapes within a few iterations, which the
+    // linear mapping draws in a handful of nearly white shades.
+    // Equalizing spreads those over the whole range instead.
+    let (equalized, _) = render_image(&params, Strategy::Sequential, 1);
+    let light = |pixels: &[u8]| pixels.iter().filter(|&&shade| shade >= 240).count();
+    assert!(light(&equalized) * 2 < light(&pixels), "{} vs {}", light(&equalized), light(&pixels));
+}
+
+#[test]
+fn test_dump_colors_like_a_fresh_render() {
+    let params = RenderParams::new((40, 30), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let (counts, _) = render_counts(&params, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Dynamic, Strategy::Tiles(7)] {
+        assert_eq!(render_counts(&params, strategy, 3).0, counts, "{:?}", strategy);
+    }
+
+    let mut bytes = vec![];
+    IterDump::new(&params, counts).write(&mut bytes).unwrap();
+    let dump = IterDump::read(&mut &bytes[..]).unwrap();
+    for coloring in [Coloring::Linear, Coloring::Histogram] {
+        let recolored = RenderParams { coloring, color: ColorMode::Rgb(Palette::default()), ..dump.params() };
+        assert_eq!(color_counts(&dump.counts, &recolored), render_image(&recolored, Strategy::Rayon, 4).0, "{:?}",
+                   coloring);
+    }
+}
+
+#[test]
+fn test_distance_coloring() {
+    let params = RenderParams {
+        coloring: Coloring::Distance,
+        bailout: SMOOTH_BAILOUT,
+        limit: 500,
+        ..RenderParams::new((60, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let (sequential, _) = render_image(&params, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Tiles(8)] {
+        assert_eq!(render_image(&params, strategy, 3).0, sequential, "{:?}", strategy);
+    }
+
+    // The interior is the same as with counts, but far from the set every
+    // pixel is white, and darkness tracks closeness rather than the count.
+    let (counts, _) =
+        render_image(&RenderParams { coloring: Coloring::Linear, ..params.clone() }, Strategy::Sequential, 1);
+    for (distance, count) in sequential.iter().zip(&counts) {
+        assert_eq!(*distance == 0, *count == 0);
+    }
+    assert_eq!(sequential[0], 255);
+    let dark = |pixels: &[u8]| pixels.iter().filter(|&&shade| shade > 0 && shade < 128).count();
+    assert!(dark(&sequential) > dark(&counts), "{} vs {}", dark(&sequential), dark(&counts));
+
+    // A smaller scale turns white closer to the set.
+    let (sharp, _) = render_image(&RenderParams { distance_scale: 1.0, ..params.clone() }, Strategy::Sequential, 1);
+    let white = |pixels: &[u8]| pixels.iter().filter(|&&shade| shade == 255).count();
+    assert!(white(&sharp) > white(&sequential), "{} vs {}", white(&sharp), white(&sequential));
+    assert!(sharp.iter().zip(&sequential).all(|(sharp, wide)| sharp >= wide));
+}
+
+#[test]
+fn test_slope_shading() {
+    let params = RenderParams {
+        color: ColorMode::Rgb(Palette::default()),
+        bailout: SMOOTH_BAILOUT,
+        limit: 300,
+        ..RenderParams::new((60, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let lit = RenderParams { light_angle: Some(45.0), ..params.clone() };
+    let (shaded, _) = render_image(&lit, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Rayon, Strategy::Tiles(8)] {
+        assert_eq!(render_image(&lit, strategy, 3).0, shaded, "{:?}", strategy);
+    }
+    let mut renderer = Renderer::new(3);
+    renderer.render_into(&lit, Strategy::Bands);
+    assert_eq!(renderer.buffer, shaded);
+
+    // The light only ever darkens, and not by the same amount everywhere.
+    let (plain, _) = render_image(&params, Strategy::Sequential, 1);
+    assert!(shaded.iter().zip(&plain).all(|(shaded, plain)| shaded <= plain));
+    let ratios: Vec<f64> = shaded.iter().zip(&plain).filter(|&(_, &plain)| plain > 50)
+        .map(|(&shaded, &plain)| shaded as f64 / plain as f64).collect();
+    let (lowest, highest) =
+        ratios.iter().fold((1.0f64, 0.0f64), |(low, high), &ratio| (low.min(ratio), high.max(ratio)));
+    assert!(highest - lowest > 0.3, "{} to {}", lowest, highest);
+}
+
+#[test]
+fn test_invert_is_exact() {
+    // Every shade is mirrored exactly, the interior included: v becomes
+    // white - v, whichever way the counts were turned into shades.
+    let base = RenderParams::new((48, 32), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let views = [
+        base.clone(),
+        RenderParams { smooth: true, bailout: SMOOTH_BAILOUT, ..base.clone() },
+        RenderParams { coloring: Coloring::Histogram, ..base.clone() },
+        RenderParams { gamma: 2.0, log_scale: true, ..base.clone() },
+        RenderParams { antialias: 3, ..base.clone() },
+    ];
+    for params in views {
+        let plain = render_image(&params, Strategy::Sequential, 1).0;
+        let inverted = render_image(&RenderParams { invert: true, ..params.clone() }, Strategy::Sequential, 1).0;
+        assert!(plain.iter().zip(&inverted).all(|(&v, &w)| w == 255 - v), "{:?}", params.coloring);
+        assert!(plain.contains(&0) && inverted.contains(&255));
+
+        let deep = RenderParams { color: ColorMode::Gray16, ..params };
+        let plain = render_image(&deep, Strategy::Sequential, 1).0;
+        let inverted = render_image(&RenderParams { invert: true, ..deep.clone() }, Strategy::Sequential, 1).0;
+        let levels = |pixels: &[u8]| {
+            pixels.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect::<Vec<_>>()
+        };
+        assert!(levels(&plain).iter().zip(&levels(&inverted)).all(|(&v, &w)| w == 65535 - v));
+    }
+}
+
+#[test]
+fn test_binary_decomposition() {
+    // The column at Re 1: 1 + i escapes at 1 + 3i, 1 at 5 and 1 - i at
+    // 1 - 3i. The second image is a single pixel at 0, which never escapes.
+    let column = RenderParams {
+        coloring: Coloring::Binary,
+        ..RenderParams::new((1, 3), Complex { re: 1.0, im: 1.0 }, Complex { re: 2.0, im: -2.0 })
+    };
+    let origin = RenderParams {
+        limit: 50,
+        ..RenderParams::new((1, 1), Complex { re: 0.0, im: 0.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    assert_eq!(render_image(&column, Strategy::Sequential, 1).0, [255, 255, 0]);
+    assert_eq!(render_image(&RenderParams { invert: true, ..column.clone() }, Strategy::Sequential, 1).0, [0, 0, 255]);
+    let binary_origin = RenderParams { coloring: Coloring::Binary, ..origin.clone() };
+    assert_eq!(render_image(&binary_origin, Strategy::Sequential, 1).0, [0]);
+
+    let palette = Palette::default().with_interior([10, 20, 30]);
+    let colored = RenderParams { color: ColorMode::Rgb(palette.clone()), ..column.clone() };
+    assert_eq!(render_image(&colored, Strategy::Rayon, 2).0, [255, 255, 255, 255, 255, 255, 0, 0, 0]);
+    let inside = RenderParams { coloring: Coloring::Binary, color: ColorMode::Rgb(palette), ..origin.clone() };
+    assert_eq!(render_image(&inside, Strategy::Sequential, 1).0, [10, 20, 30]);
+
+    // Shaded, the upper half keeps the linear shade and the lower half
+    // gets half of it.
+    let (linear, _) =
+        render_image(&RenderParams { coloring: Coloring::Linear, ..column.clone() }, Strategy::Sequential, 1);
+    let shaded = RenderParams { coloring: Coloring::BinaryShaded, ..column.clone() };
+    let (pixels, _) = render_image(&shaded, Strategy::Sequential, 1);
+    assert_eq!(pixels, [linear[0], linear[1], (linear[2] as f64 * 0.5).round() as u8]);
+
+    let wide = RenderParams {
+        coloring: Coloring::Binary,
+        ..RenderParams::new((60, 40), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 })
+    };
+    let (sequential, _) = render_image(&wide, Strategy::Sequential, 1);
+    for strategy in [Strategy::Bands, Strategy::Tiles(8)] {
+        assert_eq!(render_image(&wide, strategy, 3).0, sequential, "{:?}", strategy);
+    }
+}
+
+#[test]
+fn test_burning_ship_points() {
+    // Pixel (x, y) of this 4x4 view lies on -2 + x + (2 - y)i.
+    let view = RenderParams::new((4, 4), Complex { re: -2.0, im: 2.0 }, Complex { re: 2.0, im: -2.0 });
+    let ship = RenderParams { fractal: Fractal::BurningShip, ..view.clone() };
+    let mut pixels = vec![0; ship.buffer_len()];
+    render(&mut pixels, &ship);
+    let at = |pixels: &[u8], x: usize, y: usize| pixels[y * 4 + x];
+
+    // 0 and -1 are fixed or period-2 points, as in the Mandelbrot set. -i
+    // folds into the cycle i, -1 - i, i, ..., but i runs i, -1 + i, 3i and
+    // escapes: the ship is not symmetric about the real axis.
+    for (x, y) in [(2, 2), (1, 2), (2, 3)] {
+        assert_eq!(at(&pixels, x, y), 0, "({}, {})", x, y);
+    }
+    for (x, y) in [(2, 1), (3, 2), (0, 0)] {
+        assert!(at(&pixels, x, y) > 0, "({}, {})", x, y);
+    }
+
+    // The Mandelbrot orbit of i is bounded: 0, i, -1 + i, -i, -1 + i, ...
+    let mut mandelbrot = vec![0; view.buffer_len()];
+    render(&mut mandelbrot, &view);
+    assert_eq!(at(&mandelbrot, 2, 1), 0);
+}
+
+#[test]
+fn test_trap_coloring_shades_the_interior() {
+    use mandelbrot_set::Trap;
+
+    let view = RenderParams::new((48, 32), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let mut linear = vec![0; view.buffer_len()];
+    render(&mut linear, &view);
+    let trap = Trap::Circle(Complex { re: 0.0, im: 0.0 }, 0.5);
+    let trapped = RenderParams { coloring: Coloring::Trap, trap, ..view.clone() };
+    let (pixels, _) = render_image(&trapped, Strategy::Bands, 3);
+
+    // Escape counts leave the whole interior black; the trap does not.
+    let interior: Vec<u8> =
+        linear.iter().zip(&pixels).filter(|&(&count, _)| count == 0).map(|(_, &trap)| trap).collect();
+    assert!(interior.len() > 100);
+    assert!(interior.iter().any(|&shade| shade != interior[0]));
+    let mut sequential = vec![0; trapped.buffer_len()];
+    render(&mut sequential, &trapped);
+    assert_eq!(pixels, sequential);
+
+    let point = RenderParams { trap: Trap::Point(Complex { re: 0.0, im: 0.0 }), ..trapped.clone() };
+    assert_ne!(render_image(&point, Strategy::Sequential, 1).0, pixels);
+}
+
+#[test]
+fn test_multibrot_power_two_is_the_mandelbrot_set() {
+    use mandelbrot_set::{escape_time, multibrot_escape_time};
+
+    let view = RenderParams { color: ColorMode::Counts, ..RenderParams::new((48, 32), Complex { re: -2.0, im: 1.0 },
+                                                                             Complex { re: 1.0, im: -1.0 }) };
+    for smooth in [false, true] {
+        let mandelbrot = RenderParams { smooth, bailout: SMOOTH_BAILOUT, ..view.clone() };
+        let squared = RenderParams { fractal: Fractal::Multibrot(2), ..mandelbrot.clone() };
+        assert_eq!(render_image(&squared, Strategy::Sequential, 1).0,
+                   render_image(&mandelbrot, Strategy::Sequential, 1).0);
+    }
+    for c in [Complex { re: -0.75, im: 0.1 }, Complex { re: 0.3, im: 0.5 }, Complex { re: -1.5, im: 0.0 }] {
+        assert_eq!(multibrot_escape_time(c, 2, 1000, 4.0, PERIODICITY_EPSILON),
+                   escape_time(c, 1000, 4.0, PERIODICITY_EPSILON));
+    }
+}
+
+#[test]
+fn test_multibrot_symmetry() {
+    // z^3 + c is odd in z, so the orbit of -c is that of c negated: the
+    // cubic set has 2-fold symmetry, besides the mirror in the real axis.
+    let cubic = Fractal::Multibrot(3);
+    let mut escaped = 0;
+    for y in -8..=8 {
+        for x in -8..=8 {
+            let c = Complex { re: x as f64 / 8.0, im: y as f64 / 8.0 };
+            let count = cubic.escape_time(c, 500, 4.0, PERIODICITY_EPSILON);
+            assert_eq!(cubic.escape_time(-c, 500, 4.0, PERIODICITY_EPSILON), count, "{}", c);
+            assert_eq!(cubic.escape_time(c.conj(), 500, 4.0, PERIODICITY_EPSILON), count, "{}", c);
+            escaped += count.is_some() as usize;
+        }
+    }
+    assert!(escaped > 0 && escaped < 17 * 17);
+
+    // The orbit of i is 0, i, 0, ... in the cubic set, which unlike the
+    // Mandelbrot set does not reach out to -1.5 on the real axis.
+    assert_eq!(cubic.escape_time(Complex { re: 0.0, im: 1.0 }, 500, 4.0, PERIODICITY_EPSILON), None);
+    let c = Complex { re: -1.5, im: 0.0 };
+    assert!(cubic.escape_time(c, 500, 4.0, PERIODICITY_EPSILON).is_some());
+    assert_eq!(Fractal::Mandelbrot.escape_time(c, 500, 4.0, PERIODICITY_EPSILON), None);
+}
+
+#[test]
+fn test_newton_colors_by_root() {
+    use mandelbrot_set::{NEWTON_COLORS, NEWTON_EPSILON};
+
+    // Pixel (x, y) of this 5x5 view lies on -1 + x/2 + (1 - y/2)i.
+    let view = RenderParams {
+        fractal: Fractal::Newton(NEWTON_EPSILON),
+        limit: 40,
+        color: ColorMode::Rgb(Palette::default()),
+        ..RenderParams::new((5, 5), Complex { re: -1.0, im: 1.0 }, Complex { re: 1.5, im: -1.5 })
+    };
+    let (pixels, _) = render_image(&view, Strategy::Rayon, 2);
+    let at = |x: usize, y: usize| &pixels[(y * 5 + x) * 3..][..3];
+    // 1 is a root, 0 is where the step is undefined, and -1 ends up on the
+    // first root too, by way of -1/3 and 2.
+    assert_eq!(at(4, 2), NEWTON_COLORS[0]);
+    assert_eq!(at(2, 2), [0, 0, 0]);
+    let darker = |pixel: &[u8], root: usize| pixel.iter().zip(NEWTON_COLORS[root]).all(|(&value, full)| value < full);
+    assert!(darker(at(0, 2), 0));
+    assert!(darker(at(1, 0), 1) && darker(at(1, 4), 2));
+
+    let gray = RenderParams { color: ColorMode::Gray, ..view.clone() };
+    let mut levels = vec![0; gray.buffer_len()];
+    render(&mut levels, &gray);
+    assert_eq!((levels[14], levels[12]), (255, 0));
+    assert_eq!(gray.interior_pixel(), [0]);
+}
+
+#[test]
+fn test_deep_zoom_shows_structure() {
+    // A view 1e-20 wide in the seahorse valley: f64 corners all round to
+    // the same few points, but every escape count comes out of perturbation.
+    let center = "-0.743643887037158704752191506114774,0.131825904205311970493132056385139";
+    let (bounds, span) = ((48, 32), (1e-20, 1e-20 * 32.0 / 48.0));
+    let flat = RenderParams {
+        limit: 10000,
+        ..RenderParams::new(bounds, Complex { re: -0.7436438870371587, im: 0.13182590420531198 },
+                            Complex { re: -0.7436438870371587, im: 0.13182590420531198 })
+    };
+    let deep = RenderParams { perturbation: Some(Perturbation::parse(center, span, bounds).unwrap()), ..flat.clone() };
+    let distinct = |params: &RenderParams| {
+        let (mut counts, _) = render_counts(params, Strategy::Sequential, 1);
+        counts.sort();
+        counts.dedup();
+        counts.len()
+    };
+    assert!(distinct(&flat) <= 2);
+    assert!(distinct(&deep) > 100, "{}", distinct(&deep));
+
+    // Strategies split the image differently but agree away from glitches.
+    let (sequential, _) = render_counts(&deep, Strategy::Sequential, 1);
+    for strategy in [Strategy::Rayon, Strategy::Tiles(16)] {
+        let (counts, _) = render_counts(&deep, strategy, 4);
+        let same = counts.iter().zip(&sequential).filter(|(a, b)| a == b).count();
+        assert!(same * 100 >= counts.len() * 99, "{:?}: {} of {}", strategy, same, counts.len());
+    }
+}
+
+#[test]
+fn test_deep_matches_f64_at_shallow_zoom() {
+    // 1e-3 across, where f64 is still exact enough: perturbation agrees with
+    // the direct render on nearly every pixel.
+    let (bounds, span) = ((40, 30), (1e-3, 0.75e-3));
+    let center = Complex { re: -1.75, im: 0.0 };
+    let direct = RenderParams {
+        limit: 2000,
+        ..RenderParams::new(bounds, Complex { re: center.re - span.0 / 2.0, im: center.im + span.1 / 2.0 },
+                            Complex { re: center.re + span.0 / 2.0, im: center.im - span.1 / 2.0 })
+    };
+    let perturbation = Some(Perturbation::parse("-1.75,0", span, bounds).unwrap());
+    let deep = RenderParams { perturbation, ..direct.clone() };
+    let (expected, _) = render_counts(&direct, Strategy::Sequential, 1);
+    let (counts, _) = render_counts(&deep, Strategy::Sequential, 1);
+    let same = counts.iter().zip(&expected).filter(|(a, b)| a == b).count();
+    assert!(same * 100 >= counts.len() * 95, "{} of {}", same, counts.len());
+    assert!(expected.iter().any(Option::is_some) && expected.iter().any(Option::is_none));
+}
+
+#[test]
+fn test_precision_53_matches_f64() {
+    let (upper_left, lower_right) = (Complex { re: -0.8, im: 0.3 }, Complex { re: -0.6, im: 0.05 });
+    let number = |z: Complex<f64>| Complex { re: BigFloat::from_f64(z.re, 53), im: BigFloat::from_f64(z.im, 53) };
+    let precision = Precision::new(53, number(upper_left), number(lower_right));
+    let base = RenderParams { limit: 300, ..RenderParams::new((37, 23), upper_left, lower_right) };
+    let variants = [
+        base.clone(),
+        RenderParams {
+            smooth: true, bailout: SMOOTH_BAILOUT, color: ColorMode::Rgb(Palette::named("fire").unwrap()),
+            ..base.clone()
+        },
+        RenderParams { antialias: 2, color: ColorMode::Gray16, ..base.clone() },
+        RenderParams { coloring: Coloring::Histogram, interior_check: false, ..base.clone() },
+        RenderParams { smooth: true, color: ColorMode::Values, ..base.clone() },
+        RenderParams { fractal: Fractal::Julia(Complex { re: -0.8, im: 0.156 }), ..base.clone() },
+    ];
+    for params in variants {
+        let precise = RenderParams { precision: Some(precision.clone()), ..params.clone() };
+        let (expected, _) = render_image(&params, Strategy::Sequential, 1);
+        for strategy in [Strategy::Sequential, Strategy::Rayon, Strategy::Tiles(8)] {
+            assert!(render_image(&precise, strategy, 3).0 == expected, "{:?} {:?}", strategy, params);
+        }
+    }
+}
+
+#[test]
+fn test_precision_128_resolves_a_deep_view() {
+    // 1e-20 across about i, which f64 rounds to a single point.
+    let center = Complex { re: BigFloat::parse("0", 128).unwrap(), im: BigFloat::parse("1", 128).unwrap() };
+    let bounds = (32, 24);
+    let flat = RenderParams {
+        limit: 1000,
+        ..RenderParams::new(bounds, Complex { re: -5e-21, im: 1.0 }, Complex { re: 5e-21, im: 1.0 })
+    };
+    let precise = RenderParams { precision: Some(Precision::from_center(128, &center, 1e-20, bounds)), ..flat.clone() };
+    let distinct = |params: &RenderParams| {
+        let (mut counts, _) = render_counts(params, Strategy::Rayon, 4);
+        counts.sort();
+        counts.dedup();
+        counts.len()
+    };
+    assert_eq!(distinct(&flat), 1);
+    assert!(distinct(&precise) > 10, "{}", distinct(&precise));
+}
+
+#[test]
+fn test_square_pixels_keep_a_symmetric_view_symmetric() {
+    // 3 by 2 units over 64x32 pixels is 1/16 of a unit a pixel up and down
+    // but less across, so the view widens to 4 units about -0.5.
+    let stretched = RenderParams::new((64, 32), Complex { re: -2.0, im: 1.0 }, Complex { re: 1.0, im: -1.0 });
+    let params = RenderParams { region: stretched.region.with_square_pixels(), ..stretched.clone() };
+    assert_eq!((params.region.upper_left, params.region.lower_right),
+               (Complex { re: -2.5, im: 1.0 }, Complex { re: 1.5, im: -1.0 }));
+    let mut pixels = vec![0; params.buffer_len()];
+    render(&mut pixels, &params);
+
+    // Row y lies on Im = 1 - y/16, so rows y and 32 - y mirror each other
+    // across the real axis, as the set does.
+    let row = |y: usize| &pixels[y * 64..(y + 1) * 64];
+    for y in 1..16 {
+        assert_eq!(row(y), row(32 - y), "row {}", y);
+    }
+    assert!(row(16).contains(&0) && row(16).iter().any(|&shade| shade > 0));
+}
+
+#[test]
+fn test_orbits_through_the_trap_get_the_first_shade() {
+    use mandelbrot_set::Trap;
+
+    // Pixel (x, y) of this 4x4 view lies on -2 + x + (2 - y)i.
+    let view = RenderParams { coloring: Coloring::Trap, ..RenderParams::new((4, 4), Complex { re: -2.0, im: 2.0 },
+                                                                             Complex { re: 2.0, im: -2.0 }) };
+    let at = |params: &RenderParams, x: usize, y: usize| {
+        let mut pixels = vec![0; params.buffer_len()];
+        render(&mut pixels, params);
+        pixels[(y * 4 + x) * params.color.bytes_per_pixel()..][..params.color.bytes_per_pixel()].to_vec()
+    };
+
+    // -1 runs -1, 0, -1, ... right through the origin, while i runs i,
+    // -1 + i, -i, ... and stays 1 away from it.
+    let origin = RenderParams { trap: Trap::Point(Complex { re: 0.0, im: 0.0 }), ..view.clone() };
+    assert_eq!(at(&origin, 1, 2), [255]);
+    assert_eq!(at(&origin, 2, 1), [1]);
+    // i starts on the vertical line of a cross there.
+    let cross = RenderParams { trap: Trap::Cross(Complex { re: 0.0, im: 0.0 }), ..view.clone() };
+    assert_eq!(at(&cross, 2, 1), [255]);
+    // In color the extreme is the palette's first color.
+    let palette = Palette::default();
+    let colored = RenderParams { color: ColorMode::Rgb(palette.clone()), ..origin.clone() };
+    assert_eq!(at(&colored, 1, 2), palette.color_for_position(Some(0.0)));
+}